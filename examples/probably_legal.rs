@@ -0,0 +1,51 @@
+//! This example compares the fast [`is_probably_legal`] against the complete
+//! [`is_legal`] on a small corpus of positions, reporting the time taken by
+//! each function and the positions where their verdicts differ (illegal
+//! positions that escape the fast path).
+//!
+//! Run it in release mode for meaningful timings:
+//! `cargo run --release --example probably_legal`.
+
+use std::{str::FromStr, time::Instant};
+
+use chess::Board;
+use sherlock::{is_legal, is_probably_legal};
+
+const CORPUS: [&str; 10] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq -",
+    "rnbqkbnr/pppppp1p/8/3b4/8/6P1/PPPPPP2/RNBQK1NR w K -",
+    "r3k3/ppp1p1pp/8/8/8/8/8/R1R1K2R b q -",
+    "r1bqkb1r/1ppppppp/8/2P5/8/8/PPPPP1PP/R1BQKB1R w Qq -",
+    "Nrq1kb1r/pppppppp/1N6/8/1P6/4n1n1/1PPPPPPP/R1BQKB1R b KQk -",
+    "r1b1kb1r/pppppppp/2N5/5n2/6N1/2n5/PPPPPPPP/1RBK1B1R w - -",
+    "r1b1kbr1/pppppppp/5N2/1n6/2N5/5n2/PPPPPPPP/R1BK1B1R b - -",
+    "2nR3K/pk1Rp1p1/p2p4/P1p5/1Pp4B/2PP2P1/4P2P/n7 b - -",
+    "r1b1k2r/p1p1p1pp/1p3p2/8/8/P7/1PPPPPPP/2BQKB2 b k -",
+];
+
+fn main() {
+    let boards: Vec<_> = CORPUS
+        .iter()
+        .map(|fen| Board::from_str(fen).unwrap())
+        .collect();
+
+    let start = Instant::now();
+    let fast: Vec<_> = boards.iter().map(is_probably_legal).collect();
+    let fast_time = start.elapsed();
+
+    let start = Instant::now();
+    let complete: Vec<_> = boards.iter().map(is_legal).collect();
+    let complete_time = start.elapsed();
+
+    println!("is_probably_legal: {:?}", fast_time);
+    println!("is_legal:          {:?}", complete_time);
+
+    for ((fen, fast), complete) in CORPUS.iter().zip(fast).zip(complete) {
+        // the fast path never declares a legal position illegal
+        assert!(fast || !complete);
+        if fast != complete {
+            println!("only proven illegal by is_legal: {}", fen);
+        }
+    }
+}
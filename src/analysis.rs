@@ -1,17 +1,25 @@
-use std::fmt;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
 
 use chess::{
-    get_bishop_rays, get_rank, get_rook_rays, BitBoard, Color, File, Piece, Square, ALL_COLORS,
-    ALL_FILES, ALL_PIECES, ALL_SQUARES, EMPTY, NUM_COLORS, NUM_FILES, NUM_PIECES,
+    get_bishop_rays, get_rank, get_rook_rays, BitBoard, Color, File, Piece, Rank, Square,
+    ALL_COLORS, ALL_FILES, ALL_PIECES, ALL_SQUARES, EMPTY, NUM_COLORS, NUM_FILES, NUM_PIECES,
     NUM_PROMOTION_PIECES, NUM_SQUARES, PROMOTION_PIECES,
 };
 
 use crate::{
-    rules::ALL_ORIGINS,
-    utils::{prom_index, MobilityGraph, UncertainSet},
-    RetractableBoard,
+    cache::AnalysisCache,
+    rules::{RuleFactory, ALL_ORIGINS},
+    utils::{
+        initial_piece_on, origin_color, prom_index, ByOrigin, BySquare, Geometry, MobilityGraph,
+        Origin, Orthodox, UncertainSet,
+    },
+    RetractableBoard, RetroConvention,
 };
 
+#[derive(Clone)]
 pub(crate) struct Counter<T> {
     pub(crate) value: T,
     counter: usize,
@@ -27,8 +35,53 @@ impl<T> Counter<T> {
     }
 }
 
+/// The progress counters of every field of an [`Analysis`].
+///
+/// Every counter is increased whenever the corresponding information is
+/// refined, so a rule needs to be re-applied only if any of the counters it
+/// depends on has changed since its last application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AnalysisCounters {
+    /// The counter of the set of steady pieces.
+    pub steady: usize,
+    /// The counter of the candidate origins of the pieces on the board.
+    pub origins: usize,
+    /// The counter of the candidate destinies of every origin.
+    pub destinies: usize,
+    /// The counter of the candidate death squares of every origin.
+    pub death_squares: usize,
+    /// The counter of the squares that may have been reached by every origin.
+    pub reachable: usize,
+    /// The counter of the squares that may have been reached by pawns before
+    /// promoting.
+    pub reachable_as_pawn: usize,
+    /// The counter of the squares that may have been reached by officers.
+    pub reachable_from_origin: usize,
+    /// The counter of the squares that may have been reached by promoted
+    /// pieces.
+    pub reachable_from_promotion: usize,
+    /// The counter of the capture distances of pawns.
+    pub pawn_capture_distances: usize,
+    /// The counter of the forced captures of pawns.
+    pub pawn_forced_captures: usize,
+    /// The counter of the capture distances of officers.
+    pub officer_capture_distances: usize,
+    /// The counter of the missing pieces of both colors.
+    pub missing: usize,
+    /// The counter of the squares where every origin captured.
+    pub captures: usize,
+    /// The counter of the bounds on the number of captures of every origin.
+    pub nb_captures: usize,
+    /// The counter of the mobility graphs.
+    pub mobility: usize,
+    /// The counter of the parity of the knight moves.
+    pub knight_parity: usize,
+    /// The counter of the pieces that may have performed the last move.
+    pub last_movers: usize,
+}
+
 /// The result of a legality analysis.
-#[derive(PartialOrd, PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(PartialOrd, PartialEq, Eq, Copy, Clone, Debug, Hash)]
 pub enum Legality {
     /// A position is legal if it is reachable from the starting position via a
     /// sequence of legal moves.
@@ -38,16 +91,256 @@ pub enum Legality {
     Illegal,
 }
 
+/// The mechanism that proved a position illegal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IllegalityReason {
+    /// The rule of the static analysis with the given name, which declared
+    /// the position illegal with the given class (see
+    /// [`Analysis::set_illegal`]).
+    Rule(String, IllegalityClass),
+    /// The set of candidate origins of the piece on the given square became
+    /// empty.
+    EmptyOrigins(Square),
+    /// The set of candidate destinies of the piece that started on the given
+    /// square became empty.
+    EmptyDestinies(Square),
+    /// The static analysis was inconclusive, but every retraction sequence led
+    /// to an illegal position.
+    RetractionSearchExhausted,
+    /// The static analysis was inconclusive, but an external solver proved
+    /// its constraints unsatisfiable (see [`crate::analyze_with_solver`]).
+    #[cfg(feature = "sat")]
+    ExternalSolver,
+    /// The position was discarded before the analysis for a reason of the
+    /// given class that can be read directly from the diagram (see
+    /// [`crate::static_screen`]).
+    StaticScreen(IllegalityClass),
+}
+
+/// A coarse classification of the mechanisms that prove positions illegal
+/// (see [`IllegalityReason::class`]), e.g. for filtering corpora of illegal
+/// positions by the kind of argument that refutes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IllegalityClass {
+    /// The material on the board cannot be reached in a game (e.g. 9 pawns).
+    Material,
+    /// The pieces cannot be assigned origins, or the missing pieces cannot
+    /// account for the captures that the pieces on the board performed.
+    Counting,
+    /// Some pieces cannot have reached their squares (or left their origins).
+    Route,
+    /// The number of moves performed by the pieces does not agree with the
+    /// side to move.
+    Parity,
+    /// The checks on the board cannot have been given by a single move.
+    Check,
+    /// The static analysis was inconclusive, but every retraction sequence led
+    /// to an illegal position.
+    RetractionExhausted,
+    /// Any other mechanism, e.g. a user-defined rule.
+    Other,
+}
+
+impl fmt::Display for IllegalityClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IllegalityClass::Material => "material",
+            IllegalityClass::Counting => "counting",
+            IllegalityClass::Route => "route",
+            IllegalityClass::Parity => "parity",
+            IllegalityClass::Check => "check",
+            IllegalityClass::RetractionExhausted => "retraction-exhausted",
+            IllegalityClass::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A piece of information derived by a legality analysis.
+///
+/// Facts are a stable description of the knowledge of an [`Analysis`],
+/// independent of its internal representation. Only non-trivial facts are
+/// reported, e.g. an origins set is not reported if it includes all squares.
+#[non_exhaustive]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Fact {
+    /// The piece on the given square has never moved.
+    Steady(Square),
+    /// The piece on the given square started the game on one of the squares of
+    /// the given set.
+    OriginsSubset(Square, BitBoard),
+    /// The piece that started on the given square ended the game (it was
+    /// captured or is still on the board) on one of the squares of the set.
+    DestiniesSubset(Square, BitBoard),
+    /// The piece that started on the given square, if it was captured, was
+    /// captured on one of the squares of the given set.
+    DeathSquaresSubset(Square, BitBoard),
+    /// The piece that started on the given square may have only visited the
+    /// squares of the given set.
+    ReachableSubset(Square, BitBoard),
+    /// The piece that started on the given square has captured opponent
+    /// pieces on all the squares of the given set.
+    CapturedOn(Square, BitBoard),
+    /// The piece that started on the given square performed at least the given
+    /// number of captures.
+    CapturesAtLeast(Square, u8),
+    /// The piece that started on the given square performed at most the given
+    /// number of captures.
+    CapturesAtMost(Square, u8),
+    /// The pieces that started on the squares of the given set (of the given
+    /// color) were all captured.
+    Missing(Color, BitBoard),
+    /// The parity of the total number of moves performed by the original
+    /// knights of the given color.
+    KnightsParity(Color, u8),
+    /// The legality of the position has been determined.
+    Result(Legality),
+}
+
+/// A route skeleton of a piece: the sequence of squares where it captured
+/// along its route (see [`Analysis::journeys`]).
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Journey {
+    /// The squares where the piece captured, in order.
+    pub captures: Vec<Square>,
+}
+
 /// Errors that may result from the interaction with our API.
 #[derive(PartialOrd, PartialEq, Eq, Copy, Clone, Debug)]
 pub enum Error {
     /// The given square was expected to belong in the 1st, 2nd, 7th or 8th
     /// ranks.
     NotOriginSquare,
+    /// The given search checkpoint could not be parsed.
+    InvalidCheckpoint,
+    /// The given stipulation could not be parsed.
+    InvalidStipulation,
+    /// The given stipulation is not supported by our solver (yet).
+    UnsupportedStipulation,
+    /// The given board with holdings could not be parsed.
+    InvalidHoldings,
+    /// The given position was expected to be a checkmate or a stalemate.
+    NotMateOrStalemate,
+    /// The given twin could not be applied on the diagram or led to an
+    /// invalid board.
+    InvalidTwin,
+    /// The given game contains a move that is illegal in its position.
+    IllegalGameMove,
+    /// The external solver could not be run or did not report a verdict.
+    #[cfg(feature = "sat")]
+    SolverFailure,
+}
+
+/// Options that alter the assumptions of a legality analysis.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct AnalysisOptions {
+    /// Assume that none of the pieces on the board is the result of a
+    /// promotion, following the convention of orthodox directmate problems.
+    /// Under this assumption, every officer on the board must have started the
+    /// game on their relative 1st rank.
+    pub assume_no_promoted_pieces: bool,
+
+    /// A hypothesis on whether each player castled during the game, indexed by
+    /// `color.to_index()`. The analysis result is only meaningful under the
+    /// given hypotheses.
+    pub castling: [Option<CastlingHypothesis>; NUM_COLORS],
+
+    /// The maximum number of promotions of each player during the game,
+    /// indexed by `color.to_index()` (unlimited if `None`), as in stipulations
+    /// such as "no more than one promotion occurred". It applies to the whole
+    /// game, so the retraction search discounts the unpromotions it retracts.
+    pub max_promotions: [Option<u8>; NUM_COLORS],
+
+    /// The chess variant under whose rules the position is analyzed.
+    pub variant: Variant,
+
+    /// Allow the players to pass ("null moves"), as in some fairy genres such
+    /// as series-movers or help-play with null moves. The turn is then not
+    /// determined by the moves of the pieces, so no parity argument applies.
+    pub allow_null_moves: bool,
+
+    /// Record every edge removed from the mobility graphs together with the
+    /// rule that removed it (see [`Analysis::removed_edges`]).
+    pub trace_edge_removals: bool,
+
+    /// The policy that determines the order in which rules are applied during
+    /// the analysis. It is not meant to affect the derived information, only
+    /// how fast it is derived (see [`Analysis::nb_rule_applications`]).
+    pub rule_schedule: RuleSchedule,
+
+    /// User-defined rules applied during the analysis, besides the built-in
+    /// ones (see [`Rule`](crate::Rule) for their soundness requirements).
+    pub extra_rules: &'static [RuleFactory],
+
+    /// The retro convention under which the en passant square of the position
+    /// is judged by the retraction search (see [`RetroConvention`]). If `None`,
+    /// the en passant square is taken literally: the last move was a double
+    /// pawn push if and only if it is given.
+    pub convention: Option<RetroConvention>,
+}
+
+/// The policies for ordering the rules applied during an analysis.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum RuleSchedule {
+    /// Apply the rules in a fixed order, the same for every position.
+    #[default]
+    Fixed,
+    /// Order the rules based on cheap features of the position, e.g., apply
+    /// the rules about captures first if many pieces are missing, or the rules
+    /// about mobility first if the pawn structure is locked.
+    Adaptive,
+}
+
+/// The chess variants supported by the analysis.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum Variant {
+    /// Orthodox chess.
+    #[default]
+    Orthodox,
+    /// Atomic chess, where captures explode: the capturing piece, the captured
+    /// piece and all the non-pawn pieces adjacent to the capture square are
+    /// removed from the board.
+    ///
+    /// Only the static analysis follows these rules: the rules that count
+    /// captures are disabled and the retraction search is skipped.
+    #[cfg(feature = "atomic")]
+    Atomic,
+}
+
+/// A hypothesis on whether a player castled during the game.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum CastlingHypothesis {
+    /// The player never castled.
+    DidNotCastle,
+    /// The player castled kingside.
+    CastledKingside,
+    /// The player castled queenside.
+    CastledQueenside,
+}
+
+/// What is known about whether a player castled during the game.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum CastlingStatus {
+    /// The player must have castled kingside.
+    MustHaveCastledKingside,
+    /// The player must have castled queenside.
+    MustHaveCastledQueenside,
+    /// The player cannot have castled.
+    CannotHaveCastled,
+    /// Nothing could be determined.
+    Unknown,
 }
 
+/// All the castling hypotheses, which are exhaustive.
+pub const ALL_CASTLING_HYPOTHESES: [CastlingHypothesis; 3] = [
+    CastlingHypothesis::DidNotCastle,
+    CastlingHypothesis::CastledKingside,
+    CastlingHypothesis::CastledQueenside,
+];
+
 /// This type contains all the information that has been derived about the
 /// legality of the position of interest.
+#[derive(Clone)]
 pub struct Analysis {
     /// The position being analyzed.
     pub(crate) board: RetractableBoard,
@@ -58,32 +351,47 @@ pub struct Analysis {
 
     /// The candidate origins of the pieces that are still on the board.
     ///
-    /// For `s : Square`, `origins[s.to_index()]` is a `BitBoard` encoding the
-    /// squares where the piece currently on `s` may have started the game.
+    /// For `s : Square`, `origins[s]` is a `BitBoard` encoding the squares
+    /// where the piece currently on `s` may have started the game.
     ///
-    /// If `BitBoard::from_square(t) & origins[s.to_index()] == EMPTY`, then
-    /// the piece on `s` has definitely not started the game on square `t`.
-    pub(crate) origins: Counter<[BitBoard; NUM_SQUARES]>,
+    /// If `BitBoard::from_square(t) & origins[s] == EMPTY`, then the piece on
+    /// `s` has definitely not started the game on square `t`.
+    pub(crate) origins: Counter<BySquare<BitBoard>>,
 
     /// The candidate locations where pieces may have ended the game, i.e.,
     /// where they were captured or where they are currently standing.
     ///
-    /// For `s : Square`, `destinies[s.to_index()]` is a `BitBoard` encoding the
-    /// squares where the piece that started on `s` may have ended the game.
+    /// For `s : Square`, `destinies[s]` is a `BitBoard` encoding the squares
+    /// where the piece that started on `s` may have ended the game.
+    ///
+    /// If `BitBoard::from_square(t) & destinies[s] == EMPTY`, then the piece
+    /// which started on `s` has definitely not ended the game on `t`.
+    pub(crate) destinies: Counter<ByOrigin<BitBoard>>,
+
+    /// The candidate locations where pieces may have been captured.
     ///
-    /// If `BitBoard::from_square(t) & destinies[s.to_index()] == EMPTY`, then
-    /// the piece which started on `s` has definitely not ended the game on `t`.
-    pub(crate) destinies: Counter<[BitBoard; NUM_SQUARES]>,
+    /// For `s : Square`, `death_squares[s]` is a `BitBoard` encoding the
+    /// squares where the piece that started on `s` may have been captured. It
+    /// is always a subset of `destinies[s]`, which also includes the squares
+    /// where the piece may be standing.
+    pub(crate) death_squares: Counter<ByOrigin<BitBoard>>,
 
     /// The candidate squares that may have been reached by a certain piece.
     ///
-    /// For `s : Square`, `reachable[s.to_index()]` is a `BitBoard` encoding
-    /// the squares where the piece that started on `s` may have reached
-    /// during the game.
+    /// For `s : Square`, `reachable[s]` is a `BitBoard` encoding the squares
+    /// where the piece that started on `s` may have reached during the game.
     ///
-    /// If `BitBoard::from_square(t) & reachable[s.to_index()] == EMPTY`, then
-    /// the piece which started on `s` has definitely not reached square `t`.
-    pub(crate) reachable: Counter<[BitBoard; NUM_SQUARES]>,
+    /// If `BitBoard::from_square(t) & reachable[s] == EMPTY`, then the piece
+    /// which started on `s` has definitely not reached square `t`.
+    pub(crate) reachable: Counter<ByOrigin<BitBoard>>,
+
+    /// The candidate squares that may have been reached by a pawn in pawn form.
+    ///
+    /// For `s : Square`, `reachable_as_pawn[s]` is a `BitBoard` encoding the
+    /// squares that the pawn that started on `s` may have reached before
+    /// promoting (if it ever promoted), whereas `reachable[s]` also includes
+    /// the squares reached after promotion. It is empty for officers.
+    pub(crate) reachable_as_pawn: Counter<ByOrigin<BitBoard>>,
 
     /// The squares that may have been reached by officers from their origin.
     ///
@@ -129,6 +437,17 @@ pub struct Analysis {
     /// have captured to reach square `s` as a pawn.
     pub(crate) pawn_forced_captures: Counter<[[[BitBoard; NUM_SQUARES]; NUM_FILES]; NUM_COLORS]>,
 
+    /// The minimum number of captures necessary for an officer to reach
+    /// targets.
+    ///
+    /// `officer_capture_distances[o][s.to_index()]`, for `o : Square` (on the
+    /// 1st or 8th rank) and `s : Square`, is a lower bound on the number of
+    /// captures necessary for the officer that started on square `o` to reach
+    /// square `s`.
+    ///
+    /// Unreachable squares store a value of 16 by default.
+    pub(crate) officer_capture_distances: Counter<ByOrigin<[u8; NUM_SQUARES]>>,
+
     /// The squares where the missing pieces of each color started the game.
     ///
     /// For `c : Color`, `missing[c.to_index()]` is an `UncertainSet` encoding
@@ -137,18 +456,18 @@ pub struct Analysis {
 
     /// The squares where opponent pieces have certainly been captured.
     ///
-    /// For `s : Square`, `captures[s.to_index()]` is a `BitBoard` encoding
-    /// a set of squares where the piece that started on `s` has certainly
-    /// captured an enemy piece.
-    pub(crate) captures: Counter<[BitBoard; NUM_SQUARES]>,
+    /// For `s : Square`, `captures[s]` is a `BitBoard` encoding a set of
+    /// squares where the piece that started on `s` has certainly captured an
+    /// enemy piece.
+    pub(crate) captures: Counter<ByOrigin<BitBoard>>,
 
     /// A lower-upper bound pair on the number of captures performed by every
     /// piece.
     ///
-    /// For `s : Square`, `nb_captures[s.to_index()] = (l, u)` means that
-    /// the number of captures, `n` performed by the piece that started the
-    /// game on `s` is such that `l <= n <= u`.
-    pub(crate) nb_captures: Counter<[(i32, i32); NUM_SQUARES]>,
+    /// For `s : Square`, `nb_captures[s] = (l, u)` means that the number of
+    /// captures, `n` performed by the piece that started the game on `s` is
+    /// such that `l <= n <= u`.
+    pub(crate) nb_captures: Counter<ByOrigin<(i32, i32)>>,
 
     /// Mobility graphs, for each color and piece type, where nodes are squares
     /// and arrows indicate the possible moves that a piece of the
@@ -164,47 +483,121 @@ pub struct Analysis {
     /// parity is the one of `n`.
     pub(crate) knight_parity: Counter<[Option<u8>; NUM_COLORS]>,
 
+    /// The squares of the pieces that may have performed the last move.
+    ///
+    /// The 0's in the corresponding `BitBoard` represent pieces that
+    /// definitely did not perform the last move (including all the pieces of
+    /// the player to move). This information is used for refining the first
+    /// retractions of the search (see
+    /// [`RetractionGen::refine_iterator`](crate::RetractionGen::refine_iterator)).
+    pub(crate) last_movers: Counter<BitBoard>,
+
     /// A flag about the legality of the position. `None` if undetermined,
     /// `Some(true)` if the position has been determined to be illegal, and
     /// `Some(false)` if the position is known to be legal.
     pub(crate) result: Option<Legality>,
+
+    /// The mechanism that proved the position illegal, if it is.
+    pub(crate) illegality_reason: Option<IllegalityReason>,
+
+    /// The class given by the rule that declared the position illegal, if any.
+    pub(crate) illegality_class: Option<IllegalityClass>,
+
+    /// The number of times a rule has been applied during the analysis.
+    pub(crate) nb_rule_applications: usize,
+
+    /// The assumptions under which the analysis is performed.
+    pub(crate) options: AnalysisOptions,
+
+    /// A cache of tables derived from the mobility graphs, possibly shared with
+    /// other analyses (see [`analyze_with_cache`](crate::analyze_with_cache)).
+    /// It is only present while the analysis is being computed.
+    pub(crate) cache: Option<AnalysisCache>,
+
+    /// The names of the rules that have been applied, indexed by the tags of
+    /// the mobility graph journals. Only used when tracing edge removals.
+    pub(crate) rule_names: Vec<&'static str>,
 }
 
 impl Analysis {
     /// Initializes a legality analysis for the given board.
     pub fn new(board: &RetractableBoard) -> Self {
+        Self::new_with_geometry(board, &Orthodox)
+    }
+
+    /// Initializes a legality analysis for the given board, whose mobility
+    /// graphs follow the given geometry (see [`Geometry`] for the rules that
+    /// only support the orthodox geometry).
+    pub fn new_with_geometry<G: Geometry>(board: &RetractableBoard, geometry: &G) -> Self {
+        let mobility: [[MobilityGraph; NUM_PIECES]; NUM_COLORS] = [
+            core::array::from_fn(|i| {
+                MobilityGraph::init_with_geometry(ALL_PIECES[i], Color::White, geometry)
+            }),
+            core::array::from_fn(|i| {
+                MobilityGraph::init_with_geometry(ALL_PIECES[i], Color::Black, geometry)
+            }),
+        ];
+        // no capture is necessary (yet) to reach the squares reachable by the
+        // officers, the rest are unreachable
+        let officer_capture_distances = ByOrigin::from_fn(|Origin(origin)| {
+            let color = match origin.get_rank() {
+                Rank::First => Color::White,
+                Rank::Eighth => Color::Black,
+                _ => return [16; NUM_SQUARES],
+            };
+            let piece = initial_piece_on(origin);
+            mobility[color.to_index()][piece.to_index()].distances_from_source(origin)
+        });
         Analysis {
             board: *board,
             steady: Counter::new(EMPTY),
-            origins: Counter::new([!EMPTY; NUM_SQUARES]),
-            destinies: Counter::new([!EMPTY; NUM_SQUARES]),
-            reachable: Counter::new([!EMPTY; NUM_SQUARES]),
+            origins: Counter::new(BySquare::new(!EMPTY)),
+            destinies: Counter::new(ByOrigin::new(!EMPTY)),
+            death_squares: Counter::new(ByOrigin::new(!EMPTY)),
+            reachable: Counter::new(ByOrigin::new(!EMPTY)),
+            reachable_as_pawn: Counter::new(ByOrigin::from_fn(|Origin(origin)| {
+                let rank = origin.get_rank();
+                if rank == Rank::Second || rank == Rank::Seventh {
+                    !EMPTY
+                } else {
+                    EMPTY
+                }
+            })),
             reachable_from_origin: Counter::new([[!EMPTY; NUM_FILES]; NUM_COLORS]),
             reachable_from_promotion: Counter::new(
                 [[[!EMPTY; NUM_FILES]; NUM_PROMOTION_PIECES]; NUM_COLORS],
             ),
             pawn_capture_distances: Counter::new([[[0; NUM_SQUARES]; NUM_FILES]; NUM_COLORS]),
             pawn_forced_captures: Counter::new([[[EMPTY; NUM_SQUARES]; NUM_FILES]; NUM_COLORS]),
+            officer_capture_distances: Counter::new(officer_capture_distances),
             missing: Counter::new([
                 UncertainSet::new(16 - board.color_combined(Color::White).popcnt()),
                 UncertainSet::new(16 - board.color_combined(Color::Black).popcnt()),
             ]),
-            captures: Counter::new([EMPTY; NUM_SQUARES]),
-            nb_captures: Counter::new([(0, 15); NUM_SQUARES]),
-            mobility: Counter::new([
-                core::array::from_fn(|i| MobilityGraph::init(ALL_PIECES[i], Color::White)),
-                core::array::from_fn(|i| MobilityGraph::init(ALL_PIECES[i], Color::Black)),
-            ]),
+            captures: Counter::new(ByOrigin::new(EMPTY)),
+            nb_captures: Counter::new(ByOrigin::new((0, 15))),
+            mobility: Counter::new(mobility),
             knight_parity: Counter::new([None; NUM_COLORS]),
+            last_movers: Counter::new(*board.color_combined(!board.side_to_move())),
             result: None,
+            illegality_reason: None,
+            illegality_class: None,
+            nb_rule_applications: 0,
+            options: AnalysisOptions::default(),
+            cache: None,
+            rule_names: vec![],
         }
     }
 
-    /// The squares that may have been reached by the piece that started on the
-    /// given square.
-    #[inline]
-    pub(crate) fn reachable(&self, square: Square) -> BitBoard {
-        self.reachable.value[square.to_index()]
+    /// A copy of this analysis for the given board, which must have the same
+    /// piece placement, possibly with the other player to move. Only the
+    /// information that does not depend on the side to move must have been
+    /// derived (see [`analyze_both_turns`](crate::analyze_both_turns)).
+    pub(crate) fn with_turn_of(&self, board: &RetractableBoard) -> Self {
+        let mut analysis = self.clone();
+        analysis.board = *board;
+        analysis.last_movers = Counter::new(*board.color_combined(!board.side_to_move()));
+        analysis
     }
 
     /// The squares that may have been reached by the officer of the given color
@@ -231,15 +624,10 @@ impl Analysis {
         self.pawn_capture_distances.value[color.to_index()][file.to_index()][target.to_index()]
     }
 
-    /// The squares where the pawn of the given color that started on the given
-    /// file must have captured in order to reach the given target.
-    pub(crate) fn pawn_forced_captures(
-        &self,
-        color: Color,
-        file: File,
-        target: Square,
-    ) -> BitBoard {
-        self.pawn_forced_captures.value[color.to_index()][file.to_index()][target.to_index()]
+    /// The minimum number of captures necessary for the officer that started
+    /// on the given origin to reach the given target.
+    pub(crate) fn officer_capture_distances(&self, origin: Square, target: Square) -> u8 {
+        self.officer_capture_distances.value[Origin(origin)][target.to_index()]
     }
 
     /// The missing pieces of the given color.
@@ -250,21 +638,105 @@ impl Analysis {
     /// The squares where the piece that started on the given square has
     /// certainly captured opponents pieces.
     pub(crate) fn captures(&self, square: Square) -> BitBoard {
-        self.captures.value[square.to_index()]
+        self.captures.value[Origin(square)]
     }
 
     /// The known lower bound on the number of captures performed by the piece
     /// that started the game on the given square.
 
     pub(crate) fn nb_captures_lower_bound(&self, square: Square) -> i32 {
-        self.nb_captures.value[square.to_index()].0
+        self.nb_captures.value[Origin(square)].0
     }
 
     /// The known upper bound on the number of captures performed by the piece
     /// that started the game on the given square.
     #[inline]
     pub(crate) fn nb_captures_upper_bound(&self, square: Square) -> i32 {
-        self.nb_captures.value[square.to_index()].1
+        self.nb_captures.value[Origin(square)].1
+    }
+
+    /// The pieces of the given color on the board that certainly are promoted
+    /// pawns, i.e. the officers whose candidate origins are all pawn origins.
+    pub(crate) fn promoted_officers(&self, color: Color) -> BitBoard {
+        let pawn_origins = get_rank(color.to_second_rank());
+        let officers = self.board.color_combined(color) & !self.board.pieces(Piece::Pawn);
+        officers
+            .into_iter()
+            .filter(|square| self.origins(*square) & !pawn_origins == EMPTY)
+            .fold(EMPTY, |acc, square| acc | BitBoard::from_square(square))
+    }
+
+    /// The origins of the pawns of the given color that certainly promoted,
+    /// i.e. the pawns that are known to be on the board but that cannot be any
+    /// of the pawns on the board.
+    pub(crate) fn promoted_pawn_origins(&self, color: Color) -> BitBoard {
+        let pawns = self.board.color_combined(color) & self.board.pieces(Piece::Pawn);
+        let pawn_origins = pawns
+            .into_iter()
+            .fold(EMPTY, |acc, square| acc | self.origins(square));
+        (get_rank(color.to_second_rank()) & !pawn_origins)
+            .into_iter()
+            .filter(|origin| self.is_definitely_on_the_board(*origin))
+            .fold(EMPTY, |acc, origin| acc | BitBoard::from_square(origin))
+    }
+
+    /// The squares where the piece that started the game on the given square
+    /// may have ended its journey: its destinies, unless it is known to be
+    /// missing, and its death squares, unless it is known to be on the board.
+    pub(crate) fn final_squares(&self, origin: Square) -> BitBoard {
+        let mut final_squares = EMPTY;
+        if !self.is_definitely_missing(origin) {
+            final_squares |= self.destinies(origin);
+        }
+        if !self.is_definitely_on_the_board(origin) {
+            final_squares |= self.death_squares(origin);
+        }
+        final_squares
+    }
+
+    /// A lower bound on the number of moves performed by the piece that
+    /// started the game on the given square.
+    pub(crate) fn min_nb_moves(&self, origin: Square) -> u32 {
+        if self.is_steady(origin) {
+            return 0;
+        }
+        let piece = initial_piece_on(origin);
+        let color = origin_color(origin);
+        let graph = &self.mobility.value[color.to_index()][piece.to_index()];
+        let distances = graph.move_distances_from_source(origin);
+        let mut targets = self.final_squares(origin);
+        // a pawn may have promoted and continued its journey in promoted form
+        if piece == Piece::Pawn {
+            targets |= get_rank(color.to_their_backrank());
+        }
+        targets
+            .into_iter()
+            .filter_map(|square| distances[square.to_index()])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// An upper bound on the number of moves performed by the piece that
+    /// started the game on the given square, if it can be established. This
+    /// is only the case for steady pieces and pawns that cannot have promoted,
+    /// since pawns cannot move back.
+    pub(crate) fn max_nb_moves(&self, origin: Square) -> Option<u32> {
+        if self.is_steady(origin) {
+            return Some(0);
+        }
+        let color = origin_color(origin);
+        let reachable_as_pawn = self.reachable_as_pawn(origin);
+        if initial_piece_on(origin) != Piece::Pawn
+            || reachable_as_pawn & get_rank(color.to_their_backrank()) != EMPTY
+        {
+            return None;
+        }
+        let nb_ranks = |square: Square| {
+            let rank = square.get_rank().to_index() as i32;
+            (rank - origin.get_rank().to_index() as i32).unsigned_abs()
+        };
+        let targets = self.final_squares(origin) & reachable_as_pawn;
+        Some(targets.into_iter().map(nb_ranks).max().unwrap_or(0))
     }
 
     /// The piece type of the piece on the given square in the analysis's board.
@@ -287,7 +759,7 @@ impl Analysis {
 
 impl Analysis {
     /// Update the information on steady pieces with the given value.
-    pub(crate) fn update_steady(&mut self, value: BitBoard) -> bool {
+    pub fn update_steady(&mut self, value: BitBoard) -> bool {
         if (self.steady.value | value) == self.steady.value {
             return false;
         }
@@ -299,17 +771,18 @@ impl Analysis {
     /// Update the candidate origins of the piece on the given square, with the
     /// given value.
     /// Returns a boolean value indicating whether the update changed anything.
-    pub(crate) fn update_origins(&mut self, square: Square, value: BitBoard) -> bool {
-        let new_origins = self.origins.value[square.to_index()] & value;
-        if self.origins.value[square.to_index()] == new_origins {
+    pub fn update_origins(&mut self, square: Square, value: BitBoard) -> bool {
+        let new_origins = self.origins.value[square] & value;
+        if self.origins.value[square] == new_origins {
             return false;
         }
-        self.origins.value[square.to_index()] = new_origins;
+        self.origins.value[square] = new_origins;
         self.origins.counter += 1;
 
         // if the set of candidate origins of a piece is empty, the position is illegal
         if value == EMPTY {
             self.result = Some(Legality::Illegal);
+            self.illegality_reason = Some(IllegalityReason::EmptyOrigins(square));
         }
         true
     }
@@ -317,35 +790,62 @@ impl Analysis {
     /// Update the candidate destinies of the piece that started on the given
     /// square, with the given value.
     /// Returns a boolean value indicating whether the update changed anything.
-    pub(crate) fn update_destinies(&mut self, square: Square, value: BitBoard) -> bool {
-        let new_destinies = self.destinies.value[square.to_index()] & value;
-        if self.destinies.value[square.to_index()] == new_destinies {
+    pub fn update_destinies(&mut self, square: Square, value: BitBoard) -> bool {
+        let new_destinies = self.destinies.value[Origin(square)] & value;
+        if self.destinies.value[Origin(square)] == new_destinies {
             return false;
         }
-        self.destinies.value[square.to_index()] = new_destinies;
+        self.destinies.value[Origin(square)] = new_destinies;
         self.destinies.counter += 1;
 
         // if the set of candidate destinies of a piece is empty, the position is
         // illegal
         if value == EMPTY {
             self.result = Some(Legality::Illegal);
+            self.illegality_reason = Some(IllegalityReason::EmptyDestinies(square));
         }
         true
     }
 
+    /// Update the candidate death squares of the piece that started on the
+    /// given square, with the given value.
+    /// Returns a boolean value indicating whether the update changed anything.
+    pub fn update_death_squares(&mut self, square: Square, value: BitBoard) -> bool {
+        let new_death_squares = self.death_squares.value[Origin(square)] & value;
+        if self.death_squares.value[Origin(square)] == new_death_squares {
+            return false;
+        }
+        self.death_squares.value[Origin(square)] = new_death_squares;
+        self.death_squares.counter += 1;
+        true
+    }
+
     /// Update the reachable squares of the piece that started on the given
     /// square, with the given value.
     /// Returns a boolean value indicating whether the update changed anything.
-    pub(crate) fn update_reachable(&mut self, square: Square, value: BitBoard) -> bool {
-        let new_reachable = self.reachable.value[square.to_index()] & value;
-        if self.reachable.value[square.to_index()] == new_reachable {
+    pub fn update_reachable(&mut self, square: Square, value: BitBoard) -> bool {
+        let new_reachable = self.reachable.value[Origin(square)] & value;
+        if self.reachable.value[Origin(square)] == new_reachable {
             return false;
         }
-        self.reachable.value[square.to_index()] = new_reachable;
+        self.reachable.value[Origin(square)] = new_reachable;
         self.reachable.counter += 1;
         true
     }
 
+    /// Update the squares reached in pawn form by the pawn that started on the
+    /// given square, with the given value.
+    /// Returns a boolean value indicating whether the update changed anything.
+    pub(crate) fn update_reachable_as_pawn(&mut self, square: Square, value: BitBoard) -> bool {
+        let new_reachable = self.reachable_as_pawn.value[Origin(square)] & value;
+        if self.reachable_as_pawn.value[Origin(square)] == new_reachable {
+            return false;
+        }
+        self.reachable_as_pawn.value[Origin(square)] = new_reachable;
+        self.reachable_as_pawn.counter += 1;
+        true
+    }
+
     /// Update the reachable squares of the officer of the given color that
     /// started on the given file, with the given value. Returns a boolean
     /// value indicating whether the update changed anything.
@@ -432,31 +932,87 @@ impl Analysis {
         true
     }
 
+    /// Update the information on officer capture distances for the officer
+    /// that started on the given origin, with the given distances.
+    /// Returns a boolean value indicating whether the update changed anything.
+    pub(crate) fn update_officer_capture_distances(
+        &mut self,
+        origin: Square,
+        distances: &[u8; NUM_SQUARES],
+    ) -> bool {
+        let mut progress = false;
+        for target in ALL_SQUARES {
+            let distance = distances[target.to_index()];
+            if self.officer_capture_distances(origin, target) < distance {
+                progress = true;
+                self.officer_capture_distances.value[Origin(origin)][target.to_index()] = distance;
+            }
+        }
+        if progress {
+            self.officer_capture_distances.counter += 1;
+        }
+        progress
+    }
+
     /// Update the information of missing pieces of the given color, with a
     /// given set of pieces that are certainly not missing.
-    pub(crate) fn update_certainly_not_missing(&mut self, color: Color, value: BitBoard) -> bool {
-        self.missing.value[color.to_index()].remove(value)
+    pub fn update_certainly_not_missing(&mut self, color: Color, value: BitBoard) -> bool {
+        if !self.missing.value[color.to_index()].remove(value) {
+            return false;
+        }
+        self.missing.counter += 1;
+        true
     }
 
     /// Update the information of missing pieces of the given color, with a
     /// given set of pieces that are certainly missing.
-    pub(crate) fn update_certainly_missing(&mut self, color: Color, value: BitBoard) -> bool {
-        self.missing.value[color.to_index()].add(value)
+    pub fn update_certainly_missing(&mut self, color: Color, value: BitBoard) -> bool {
+        if !self.missing.value[color.to_index()].add(value) {
+            return false;
+        }
+        self.missing.counter += 1;
+        true
     }
 
     /// Update the captures of the piece that started on the given square, with
     /// the given value.
     /// Returns a boolean value indicating whether the update changed anything.
-    pub(crate) fn update_captures(&mut self, square: Square, value: BitBoard) -> bool {
-        let new_captures = self.captures.value[square.to_index()] | value;
-        if self.captures.value[square.to_index()] == new_captures {
+    pub fn update_captures(&mut self, square: Square, value: BitBoard) -> bool {
+        let new_captures = self.captures.value[Origin(square)] | value;
+        if self.captures.value[Origin(square)] == new_captures {
             return false;
         }
-        self.captures.value[square.to_index()] = new_captures;
+        self.captures.value[Origin(square)] = new_captures;
         self.captures.counter += 1;
         true
     }
 
+    /// Starts recording the edges removed from all the mobility graphs.
+    pub(crate) fn enable_edge_journal(&mut self) {
+        for graphs in self.mobility.value.iter_mut() {
+            for graph in graphs.iter_mut() {
+                graph.enable_journal();
+            }
+        }
+    }
+
+    /// Attributes the edges removed from now on to the rule with the given
+    /// name.
+    pub(crate) fn set_current_rule(&mut self, name: &'static str) {
+        let tag = match self.rule_names.iter().position(|n| *n == name) {
+            Some(tag) => tag,
+            None => {
+                self.rule_names.push(name);
+                self.rule_names.len() - 1
+            }
+        };
+        for graphs in self.mobility.value.iter_mut() {
+            for graph in graphs.iter_mut() {
+                graph.set_journal_tag(tag as u8);
+            }
+        }
+    }
+
     /// Updates the mobility graph of the given piece and the given color, by
     /// removing all connections from the given square.
     /// Returns a boolean value indicating whether the update changed anything.
@@ -491,6 +1047,29 @@ impl Analysis {
         progress
     }
 
+    /// Updates the mobility graph of the given piece and the given color, by
+    /// marking all the connections into the given square as capturing ones,
+    /// i.e. the piece cannot have entered the square without capturing (e.g.
+    /// if the square was occupied by an enemy piece for as long as the piece
+    /// may have been around).
+    /// Returns a boolean value indicating whether the update changed anything.
+    pub(crate) fn mark_capturing_edges_into(
+        &mut self,
+        piece: Piece,
+        color: Color,
+        square: Square,
+    ) -> bool {
+        let graph = &mut self.mobility.value[color.to_index()][piece.to_index()];
+        let mut progress = false;
+        for source in graph.predecessors(square) {
+            progress |= graph.mark_capturing_edge(source, square);
+        }
+        if progress {
+            self.mobility.counter += 1
+        }
+        progress
+    }
+
     /// Updates the mobility graph of the given piece and the given color, by
     /// removing all the connections that pass through the given square.
     /// Returns a boolean value indicating whether the update changed anything.
@@ -555,11 +1134,11 @@ impl Analysis {
     /// Update the known lower bound on the number of captures performed by the
     /// piece that started the game on the given square, with the given
     /// value.
-    pub(crate) fn update_captures_lower_bound(&mut self, square: Square, bound: i32) -> bool {
-        if self.nb_captures.value[square.to_index()].0 >= bound {
+    pub fn update_captures_lower_bound(&mut self, square: Square, bound: i32) -> bool {
+        if self.nb_captures.value[Origin(square)].0 >= bound {
             return false;
         }
-        self.nb_captures.value[square.to_index()].0 = bound;
+        self.nb_captures.value[Origin(square)].0 = bound;
         self.nb_captures.counter += 1;
         true
     }
@@ -567,11 +1146,11 @@ impl Analysis {
     /// Update the known upper bound on the number of captures performed by the
     /// piece that started the game on the given square, with the given
     /// value.
-    pub(crate) fn update_captures_upper_bound(&mut self, square: Square, bound: i32) -> bool {
-        if self.nb_captures.value[square.to_index()].1 <= bound {
+    pub fn update_captures_upper_bound(&mut self, square: Square, bound: i32) -> bool {
+        if self.nb_captures.value[Origin(square)].1 <= bound {
             return false;
         }
-        self.nb_captures.value[square.to_index()].1 = bound;
+        self.nb_captures.value[Origin(square)].1 = bound;
         self.nb_captures.counter += 1;
         true
     }
@@ -586,6 +1165,90 @@ impl Analysis {
         self.knight_parity.counter += 1;
         true
     }
+
+    /// Update the pieces that may have performed the last move, with the given
+    /// value.
+    /// Returns a boolean value indicating whether the update changed anything.
+    pub fn update_last_movers(&mut self, value: BitBoard) -> bool {
+        let new_last_movers = self.last_movers.value & value;
+        if self.last_movers.value == new_last_movers {
+            return false;
+        }
+        self.last_movers.value = new_last_movers;
+        self.last_movers.counter += 1;
+        true
+    }
+}
+
+/// Checks the consistency between the different fields of a (saturated)
+/// analysis, panicking if any invariant is broken. This is meant to detect
+/// bugs in the rules, which otherwise may only surface as wrong verdicts much
+/// later. Analyses of illegal positions are not checked, as the derived
+/// information may be contradictory.
+#[cfg(feature = "invariants")]
+pub(crate) fn verify_invariants(analysis: &Analysis) {
+    if analysis.result == Some(Legality::Illegal) {
+        return;
+    }
+    let fen = analysis.board.to_string();
+
+    for square in analysis.steady.value {
+        assert_eq!(
+            analysis.origins(square),
+            BitBoard::from_square(square),
+            "steady piece on {} with other candidate origins in {}",
+            square,
+            fen
+        );
+    }
+
+    for square in *analysis.board.combined() {
+        let origins = analysis.origins(square);
+        assert_ne!(origins, EMPTY, "no origins for {} in {}", square, fen);
+        if origins.popcnt() == 1 {
+            let origin = origins.to_square();
+            assert_ne!(
+                analysis.destinies.value[Origin(origin)] & BitBoard::from_square(square),
+                EMPTY,
+                "the piece on {} comes from {}, which cannot end there in {}",
+                square,
+                origin,
+                fen
+            );
+        }
+    }
+
+    for origin in ALL_ORIGINS {
+        assert_ne!(
+            analysis.destinies.value[Origin(origin)],
+            EMPTY,
+            "no destinies for {} in {}",
+            origin,
+            fen
+        );
+        let (lower, upper) = analysis.nb_captures.value[Origin(origin)];
+        assert!(
+            lower <= upper,
+            "capture bounds [{}, {}] of {} in {}",
+            lower,
+            upper,
+            origin,
+            fen
+        );
+        #[cfg(feature = "atomic")]
+        if analysis.options.variant == Variant::Atomic {
+            continue;
+        }
+        let nb_tombs = analysis.captures(origin).popcnt() as i32;
+        assert!(
+            nb_tombs <= lower,
+            "{} captured on {} squares but its lower bound is {} in {}",
+            origin,
+            nb_tombs,
+            lower,
+            fen
+        );
+    }
 }
 
 fn write_bitboard(f: &mut fmt::Formatter, name: String, bitboard: BitBoard) -> fmt::Result {
@@ -606,6 +1269,83 @@ fn write_bitboard(f: &mut fmt::Formatter, name: String, bitboard: BitBoard) -> f
     Ok(())
 }
 
+impl Analysis {
+    /// The (sorted) edges of all the mobility graphs.
+    fn mobility_edges(&self) -> impl Iterator<Item = Vec<(Square, Square)>> + '_ {
+        self.mobility
+            .value
+            .iter()
+            .flat_map(|graphs| graphs.iter().map(|graph| graph.edges()))
+    }
+}
+
+/// Two analyses are equal iff they have derived the same information about the
+/// same board, independently of the order in which it was derived (or the
+/// options and caches used in the derivation).
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::analyze;
+///
+/// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -")
+///     .expect("Valid Position");
+/// assert!(analyze(&board.into()) == analyze(&board.into()));
+/// assert!(analyze(&board.into()) != analyze(&Board::default().into()));
+/// ```
+impl PartialEq for Analysis {
+    fn eq(&self, other: &Self) -> bool {
+        self.board == other.board
+            && self.steady.value == other.steady.value
+            && self.origins.value == other.origins.value
+            && self.destinies.value == other.destinies.value
+            && self.death_squares.value == other.death_squares.value
+            && self.reachable.value == other.reachable.value
+            && self.reachable_as_pawn.value == other.reachable_as_pawn.value
+            && self.reachable_from_origin.value == other.reachable_from_origin.value
+            && self.reachable_from_promotion.value == other.reachable_from_promotion.value
+            && self.pawn_capture_distances.value == other.pawn_capture_distances.value
+            && self.pawn_forced_captures.value == other.pawn_forced_captures.value
+            && self.officer_capture_distances.value == other.officer_capture_distances.value
+            && self.missing.value == other.missing.value
+            && self.captures.value == other.captures.value
+            && self.nb_captures.value == other.nb_captures.value
+            && self.knight_parity.value == other.knight_parity.value
+            && self.last_movers.value == other.last_movers.value
+            && self.result == other.result
+            && self.mobility_edges().eq(other.mobility_edges())
+    }
+}
+
+impl Eq for Analysis {}
+
+impl Hash for Analysis {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.board.hash(state);
+        self.steady.value.hash(state);
+        self.origins.value.hash(state);
+        self.destinies.value.hash(state);
+        self.death_squares.value.hash(state);
+        self.reachable.value.hash(state);
+        self.reachable_as_pawn.value.hash(state);
+        self.reachable_from_origin.value.hash(state);
+        self.reachable_from_promotion.value.hash(state);
+        self.pawn_capture_distances.value.hash(state);
+        self.pawn_forced_captures.value.hash(state);
+        self.officer_capture_distances.value.hash(state);
+        self.missing.value.hash(state);
+        self.captures.value.hash(state);
+        self.nb_captures.value.hash(state);
+        self.knight_parity.value.hash(state);
+        self.last_movers.value.hash(state);
+        self.result.hash(state);
+        for edges in self.mobility_edges() {
+            edges.hash(state);
+        }
+    }
+}
+
 impl fmt::Display for Analysis {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "FEN: {}", self.board,)?;
@@ -613,20 +1353,26 @@ impl fmt::Display for Analysis {
         write_bitboard(f, String::from("steady"), self.steady.value)?;
         writeln!(f, "\norigins (cnt: {}):\n", self.origins.counter())?;
         for square in *self.board.combined() {
-            write_bitboard(f, square.to_string(), self.origins.value[square.to_index()])?;
+            write_bitboard(f, square.to_string(), self.origins.value[square])?;
         }
         writeln!(f, "\ndestinies (cnt: {}):\n", self.destinies.counter())?;
         for square in ALL_ORIGINS {
-            write_bitboard(
-                f,
-                square.to_string(),
-                self.destinies.value[square.to_index()],
-            )?;
+            write_bitboard(f, square.to_string(), self.destinies.value[Origin(square)])?;
         }
         writeln!(f, "\nreachable (cnt: {}):\n", self.reachable.counter())?;
         for square in ALL_ORIGINS {
             write_bitboard(f, square.to_string(), self.reachable(square))?;
         }
+        writeln!(
+            f,
+            "\nreachable_as_pawn (cnt: {}):\n",
+            self.reachable_as_pawn.counter()
+        )?;
+        for square in ALL_ORIGINS {
+            if self.reachable_as_pawn(square) != EMPTY {
+                write_bitboard(f, square.to_string(), self.reachable_as_pawn(square))?;
+            }
+        }
         writeln!(
             f,
             "\nreachable_from_origin (cnt: {}):",
@@ -702,6 +1448,29 @@ impl fmt::Display for Analysis {
                 }
             }
         }
+        writeln!(
+            f,
+            "\nofficer_capture_distances (cnt: {}):",
+            self.officer_capture_distances.counter()
+        )?;
+        for color in ALL_COLORS {
+            for file in ALL_FILES {
+                let origin = Square::make_square(color.to_my_backrank(), file);
+                if self.is_steady(origin) {
+                    continue;
+                }
+                write!(f, "\n  {:?} {}:", color, origin)?;
+                for d in 1..=6 {
+                    write!(f, "\n    {}:", d)?;
+                    for target in ALL_SQUARES {
+                        if self.officer_capture_distances(origin, target) == d {
+                            write!(f, " {}", target)?;
+                        }
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
         writeln!(f, "\nmissing (cnt: {}):\n", self.missing.counter())?;
         for color in ALL_COLORS {
             writeln!(f, "{:?} missing:\n{}", color, self.missing(color))?;
@@ -735,6 +1504,8 @@ impl fmt::Display for Analysis {
             "\nknight_parity (cnt: {}): {:?}",
             self.knight_parity.counter, self.knight_parity.value
         )?;
+        writeln!(f, "\nlast_movers (cnt: {}):\n", self.last_movers.counter)?;
+        write_bitboard(f, String::from("last_movers"), self.last_movers.value)?;
         writeln!(f, "\nresult: {:?}", self.result)
     }
 }
@@ -0,0 +1,423 @@
+//! Batch analysis of positions.
+//!
+//! A batch reads one FEN per line, analyzes the positions with several worker
+//! threads and writes one line per position, with the originating FEN and
+//! the verdict, separated by a tab. The output follows the order of the input
+//! and is flushed after every chunk of positions, so that an interrupted batch
+//! can be resumed from the offset of the first position whose verdict was not
+//! written. If the batch fails on an I/O error, that offset is returned along
+//! with the error (see [`BatchError`]).
+//!
+//! ```
+//! use sherlock::batch::{run, BatchError, BatchOptions, Level};
+//!
+//! let input = "4k3/8/8/8/8/8/8/4K3 w - -\n4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -\nnot a fen\n";
+//! let options = BatchOptions {
+//!     level: Level::Static,
+//!     ..Default::default()
+//! };
+//! let mut output = vec![];
+//! assert_eq!(run(input.as_bytes(), &mut output, &options)?, 3);
+//! assert_eq!(
+//!     String::from_utf8(output).unwrap(),
+//!     "4k3/8/8/8/8/8/8/4K3 w - -\tlegal\n\
+//!      4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -\tillegal\n\
+//!      not a fen\tinvalid\n"
+//! );
+//!
+//! // resume after the first two lines
+//! let options = BatchOptions {
+//!     offset: 2,
+//!     ..options
+//! };
+//! let mut output = vec![];
+//! assert_eq!(run(input.as_bytes(), &mut output, &options)?, 3);
+//! assert_eq!(String::from_utf8(output).unwrap(), "not a fen\tinvalid\n");
+//! # Ok::<(), BatchError>(())
+//! ```
+
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+    str::FromStr,
+    thread,
+};
+
+use chess::Board;
+
+use crate::{analyze, is_legal, is_probably_legal, IllegalityClass, Legality::Illegal};
+
+/// The depth of the analysis performed on every position of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    /// The static analysis only (see [`analyze`]).
+    Static,
+    /// The static analysis and a one-ply retraction search (see
+    /// [`is_probably_legal`]).
+    Probable,
+    /// The complete retraction search (see [`is_legal`]).
+    #[default]
+    Complete,
+}
+
+/// The verdict on a line of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The position was not proven illegal at the requested level.
+    Legal,
+    /// The position is illegal.
+    Illegal,
+    /// The line does not contain a valid FEN.
+    Invalid,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Legal => write!(f, "legal"),
+            Verdict::Illegal => write!(f, "illegal"),
+            Verdict::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+/// An I/O error that interrupted a batch.
+#[derive(Debug)]
+pub struct BatchError {
+    /// The offset of the first line whose verdict was not written, from which
+    /// the batch can be resumed.
+    pub offset: usize,
+    /// The error that interrupted the batch.
+    pub error: io::Error,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "batch interrupted at line {}: {}",
+            self.offset, self.error
+        )
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The configuration of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// The depth of the analysis.
+    pub level: Level,
+    /// The number of worker threads.
+    pub nb_threads: usize,
+    /// The number of input lines to skip, e.g. the offset returned by an
+    /// interrupted batch.
+    pub offset: usize,
+    /// The number of lines analyzed by every worker between two flushes of
+    /// the output.
+    pub chunk_size: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            level: Level::default(),
+            nb_threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            offset: 0,
+            chunk_size: 64,
+        }
+    }
+}
+
+/// Analyzes the given line (a FEN) at the given level.
+pub fn verdict(line: &str, level: Level) -> Verdict {
+    let board = match Board::from_str(line) {
+        Ok(board) => board,
+        Err(_) => return Verdict::Invalid,
+    };
+    let legal = match level {
+        Level::Static => analyze(&board.into()).result() != Some(Illegal),
+        Level::Probable => is_probably_legal(&board),
+        Level::Complete => is_legal(&board),
+    };
+    if legal {
+        Verdict::Legal
+    } else {
+        Verdict::Illegal
+    }
+}
+
+/// Like [`verdict`], but it also classifies the mechanism that proved the
+/// position illegal (see [`IllegalityClass`]), if it is illegal.
+///
+/// ```
+/// use sherlock::{
+///     batch::{classified_verdict, Level, Verdict},
+///     IllegalityClass,
+/// };
+///
+/// assert_eq!(
+///     classified_verdict("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -", Level::Static),
+///     (Verdict::Illegal, Some(IllegalityClass::Material))
+/// );
+/// assert_eq!(
+///     classified_verdict("4k3/8/8/8/8/8/8/4K3 w - -", Level::Complete),
+///     (Verdict::Legal, None)
+/// );
+/// ```
+pub fn classified_verdict(line: &str, level: Level) -> (Verdict, Option<IllegalityClass>) {
+    let board = match Board::from_str(line) {
+        Ok(board) => board,
+        Err(_) => return (Verdict::Invalid, None),
+    };
+    let mut analysis = analyze(&board.into());
+    if analysis.result() == Some(Illegal) {
+        let class = analysis.illegality_reason().map(|reason| reason.class());
+        return (Verdict::Illegal, class);
+    }
+    let legal = match level {
+        Level::Static => true,
+        Level::Probable => is_probably_legal(&board),
+        Level::Complete => analysis.legality() != Illegal,
+    };
+    if legal {
+        (Verdict::Legal, None)
+    } else {
+        (Verdict::Illegal, Some(IllegalityClass::RetractionExhausted))
+    }
+}
+
+/// The classified verdicts (see [`classified_verdict`]) on the FENs of the
+/// given reader, together with the (trimmed) lines, in the order of the input.
+/// Blank lines are skipped. Unlike [`run`], the positions are analyzed lazily,
+/// on the current thread.
+///
+/// ```
+/// use sherlock::{
+///     batch::{classified_verdicts, Level, Verdict},
+///     IllegalityClass,
+/// };
+///
+/// let input = "4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -\n\n4k3/8/8/8/8/8/8/4K3 w - -\n";
+/// let verdicts: Vec<_> = classified_verdicts(input.as_bytes(), Level::Static)
+///     .map(|line| line.map(|(_, verdict, class)| (verdict, class)))
+///     .collect::<std::io::Result<_>>()?;
+/// assert_eq!(
+///     verdicts,
+///     vec![
+///         (Verdict::Illegal, Some(IllegalityClass::Material)),
+///         (Verdict::Legal, None)
+///     ]
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn classified_verdicts<R: BufRead>(
+    reader: R,
+    level: Level,
+) -> impl Iterator<Item = io::Result<(String, Verdict, Option<IllegalityClass>)>> {
+    reader.lines().filter_map(move |line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => {
+            let line = line.trim().to_string();
+            let (verdict, class) = classified_verdict(&line, level);
+            Some(Ok((line, verdict, class)))
+        }
+        Err(err) => Some(Err(err)),
+    })
+}
+
+/// Runs a batch on the FENs of the given reader, writing the verdicts on the
+/// given writer. Blank lines are skipped (but they count towards offsets).
+///
+/// Returns the offset of the first line that has not been processed, i.e.
+/// the number of lines of the input if it was entirely processed. On an I/O
+/// error, the offset from which the batch can be resumed is returned with the
+/// error: that of the first line whose verdict was not accepted by the writer
+/// or, if the output could not be flushed, that of the first line of the
+/// chunk, whose verdicts may have been lost.
+pub fn run<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    options: &BatchOptions,
+) -> Result<usize, BatchError> {
+    let nb_threads = options.nb_threads.max(1);
+    let chunk_size = nb_threads * options.chunk_size.max(1);
+    let mut lines = reader.lines().skip(options.offset);
+    let mut offset = options.offset;
+    loop {
+        let chunk = lines
+            .by_ref()
+            .take(chunk_size)
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|error| BatchError { offset, error })?;
+        if chunk.is_empty() {
+            return Ok(offset);
+        }
+        let chunk_offset = offset;
+        for (line, verdict) in chunk
+            .iter()
+            .zip(analyze_chunk(&chunk, options.level, nb_threads))
+        {
+            if let Some(verdict) = verdict {
+                writeln!(writer, "{}\t{}", line.trim(), verdict)
+                    .map_err(|error| BatchError { offset, error })?;
+            }
+            offset += 1;
+        }
+        writer.flush().map_err(|error| BatchError {
+            offset: chunk_offset,
+            error,
+        })?;
+    }
+}
+
+/// The verdicts on the given lines (`None` on blank lines), where the `i`-th
+/// line is analyzed by the worker `i % nb_threads`.
+fn analyze_chunk(chunk: &[String], level: Level, nb_threads: usize) -> Vec<Option<Verdict>> {
+    let mut verdicts = vec![None; chunk.len()];
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..nb_threads)
+            .map(|worker| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .skip(worker)
+                        .step_by(nb_threads)
+                        .filter(|(_, line)| !line.trim().is_empty())
+                        .map(|(i, line)| (i, verdict(line.trim(), level)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (i, verdict) in worker.join().unwrap() {
+                verdicts[i] = Some(verdict);
+            }
+        }
+    });
+    verdicts
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// A writer that fails once it has accepted the given number of lines.
+    struct FailingWriter {
+        output: Vec<u8>,
+        nb_lines_left: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.nb_lines_left == 0 {
+                return Err(io::Error::other("disk full"));
+            }
+            self.nb_lines_left -= buf.iter().filter(|&&byte| byte == b'\n').count();
+            self.output.write_all(buf)?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_resume_after_failing_writer() {
+        let input = "4k3/8/8/8/8/8/8/4K3 w - -\n\n\
+                     4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -\n\
+                     not a fen\n\
+                     4k3/8/8/8/8/8/8/4K3 b - -\n";
+        let options = BatchOptions {
+            level: Level::Static,
+            nb_threads: 2,
+            chunk_size: 8,
+            ..Default::default()
+        };
+        let mut expected = vec![];
+        assert_eq!(run(input.as_bytes(), &mut expected, &options).unwrap(), 5);
+
+        let mut writer = FailingWriter {
+            output: vec![],
+            nb_lines_left: 2,
+        };
+        let err = run(input.as_bytes(), &mut writer, &options).unwrap_err();
+        assert_eq!(err.offset, 3);
+
+        let options = BatchOptions {
+            offset: err.offset,
+            ..options
+        };
+        assert_eq!(
+            run(input.as_bytes(), &mut writer.output, &options).unwrap(),
+            5
+        );
+        assert_eq!(writer.output, expected);
+    }
+
+    /// A buffered writer whose buffer is lost once it has been flushed the
+    /// given number of times.
+    struct FailingFlushWriter {
+        output: Vec<u8>,
+        buffer: Vec<u8>,
+        nb_flushes_left: usize,
+    }
+
+    impl Write for FailingFlushWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.buffer.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.nb_flushes_left == 0 {
+                self.buffer.clear();
+                return Err(io::Error::other("broken pipe"));
+            }
+            self.nb_flushes_left -= 1;
+            self.output.append(&mut self.buffer);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_resume_after_failing_flush() {
+        let input = "4k3/8/8/8/8/8/8/4K3 w - -\n\n\
+                     4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -\n\
+                     not a fen\n\
+                     4k3/8/8/8/8/8/8/4K3 b - -\n";
+        let options = BatchOptions {
+            level: Level::Static,
+            nb_threads: 1,
+            chunk_size: 2,
+            ..Default::default()
+        };
+        let mut expected = vec![];
+        assert_eq!(run(input.as_bytes(), &mut expected, &options).unwrap(), 5);
+
+        // the verdicts of the second chunk are lost
+        let mut writer = FailingFlushWriter {
+            output: vec![],
+            buffer: vec![],
+            nb_flushes_left: 1,
+        };
+        let err = run(input.as_bytes(), &mut writer, &options).unwrap_err();
+        assert_eq!(err.offset, 2);
+
+        let options = BatchOptions {
+            offset: err.offset,
+            ..options
+        };
+        assert_eq!(
+            run(input.as_bytes(), &mut writer.output, &options).unwrap(),
+            5
+        );
+        assert_eq!(writer.output, expected);
+    }
+}
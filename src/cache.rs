@@ -0,0 +1,300 @@
+//! Analysis cache.
+//!
+//! Many positions in a bulk scan (or along a retraction search) share the same
+//! pawn skeleton, steady pieces and castling rights, which means they often
+//! share the same mobility graphs. The tables derived from such graphs
+//! (reachable squares, pawn capture distances, forced captures) are expensive
+//! to compute, so we memoize them here, across different analyses.
+//!
+//! Memoized results are keyed on a `MobilitySignature` and are only reused
+//! when the mobility graph of interest is identical to the one the results
+//! were computed on, so reusing them is always sound.
+
+use std::collections::HashMap;
+#[cfg(feature = "global-cache")]
+use std::sync::Mutex;
+
+#[cfg(feature = "global-cache")]
+use chess::Board;
+use chess::{
+    BitBoard, Color, Piece, Square, ALL_COLORS, ALL_PIECES, NUM_COLORS, NUM_PIECES, NUM_SQUARES,
+};
+
+use crate::{analysis::Analysis, utils::MobilityGraph};
+
+/// The part of a position that (together with the steady pieces) determines
+/// the mobility graphs before any position-specific refinement.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub(crate) struct MobilitySignature {
+    pawns: [BitBoard; NUM_COLORS],
+    steady: BitBoard,
+    castle_rights: [usize; NUM_COLORS],
+}
+
+impl MobilitySignature {
+    fn new(analysis: &Analysis) -> Self {
+        let board = &analysis.board;
+        let pawns = |color| board.pieces(Piece::Pawn) & board.color_combined(color);
+        let castle_rights = |color| board.castle_rights(color).to_index();
+        MobilitySignature {
+            pawns: [pawns(Color::White), pawns(Color::Black)],
+            steady: analysis.steady.value,
+            castle_rights: [castle_rights(Color::White), castle_rights(Color::Black)],
+        }
+    }
+}
+
+/// The maximum number of different mobility graphs (of the same color and
+/// piece type) memoized per signature. When reached, the oldest is evicted.
+const MAX_GRAPHS_PER_SIGNATURE: usize = 8;
+
+/// The memoized results of queries on a given mobility graph.
+#[derive(Clone)]
+struct GraphMemo {
+    edges: Vec<(Square, Square)>,
+    capturing_edges: Vec<(Square, Square)>,
+    reachable: HashMap<Square, BitBoard>,
+    distances: HashMap<Square, [u8; NUM_SQUARES]>,
+    forced_captures: HashMap<(Square, Square, u8), BitBoard>,
+}
+
+impl GraphMemo {
+    fn new(edges: Vec<(Square, Square)>, capturing_edges: Vec<(Square, Square)>) -> Self {
+        GraphMemo {
+            edges,
+            capturing_edges,
+            reachable: HashMap::new(),
+            distances: HashMap::new(),
+            forced_captures: HashMap::new(),
+        }
+    }
+}
+
+/// A cache of expensive tables derived from the mobility graphs, which can be
+/// shared across several calls to
+/// [`analyze_with_cache`](crate::analyze_with_cache).
+///
+/// ```
+/// use chess::Square;
+/// use sherlock::{analyze_with_cache, AnalysisCache, RetractableBoard};
+///
+/// let mut cache = AnalysisCache::new();
+/// let board = RetractableBoard::default();
+/// let analysis = analyze_with_cache(&board, &mut cache);
+/// assert!(analysis.is_steady(Square::D1));
+/// assert!(!cache.is_empty());
+/// ```
+#[derive(Clone, Default)]
+pub struct AnalysisCache {
+    graphs: HashMap<(MobilitySignature, usize, usize), Vec<GraphMemo>>,
+    signature: Option<MobilitySignature>,
+    active: [[usize; NUM_PIECES]; NUM_COLORS],
+}
+
+impl AnalysisCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of mobility graphs with memoized results.
+    pub fn len(&self) -> usize {
+        self.graphs.values().map(|memos| memos.len()).sum()
+    }
+
+    /// Tells whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.graphs.is_empty()
+    }
+
+    /// Removes all the memoized results.
+    pub fn clear(&mut self) {
+        self.graphs.clear();
+        self.signature = None;
+    }
+
+    /// Selects the memoized results that are valid for the current mobility
+    /// graphs of the given analysis. This must be called before the queries
+    /// below, every time the mobility graphs may have changed.
+    pub(crate) fn prepare(&mut self, analysis: &Analysis) {
+        let signature = MobilitySignature::new(analysis);
+        for color in ALL_COLORS {
+            for piece in ALL_PIECES {
+                let key = (signature, color.to_index(), piece.to_index());
+                let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+                let edges = graph.edges();
+                let capturing_edges = graph.capturing_edges();
+                let memos = self.graphs.entry(key).or_default();
+                let index = match memos
+                    .iter()
+                    .position(|memo| memo.edges == edges && memo.capturing_edges == capturing_edges)
+                {
+                    Some(index) => index,
+                    None => {
+                        if memos.len() == MAX_GRAPHS_PER_SIGNATURE {
+                            memos.remove(0);
+                        }
+                        memos.push(GraphMemo::new(edges, capturing_edges));
+                        memos.len() - 1
+                    }
+                };
+                self.active[color.to_index()][piece.to_index()] = index;
+            }
+        }
+        self.signature = Some(signature);
+    }
+
+    fn memo(&mut self, color: Color, piece: Piece) -> Option<&mut GraphMemo> {
+        let signature = self.signature?;
+        let index = self.active[color.to_index()][piece.to_index()];
+        self.graphs
+            .get_mut(&(signature, color.to_index(), piece.to_index()))?
+            .get_mut(index)
+    }
+
+    /// Memoized version of [`MobilityGraph::reachable_from_source`].
+    pub(crate) fn reachable_from_source(
+        &mut self,
+        color: Color,
+        piece: Piece,
+        graph: &MobilityGraph,
+        source: Square,
+    ) -> BitBoard {
+        match self.memo(color, piece) {
+            None => graph.reachable_from_source(source),
+            Some(memo) => *memo
+                .reachable
+                .entry(source)
+                .or_insert_with(|| graph.reachable_from_source(source)),
+        }
+    }
+
+    /// Memoized version of [`MobilityGraph::distances_from_source`].
+    pub(crate) fn distances_from_source(
+        &mut self,
+        color: Color,
+        piece: Piece,
+        graph: &MobilityGraph,
+        source: Square,
+    ) -> [u8; NUM_SQUARES] {
+        match self.memo(color, piece) {
+            None => graph.distances_from_source(source),
+            Some(memo) => *memo
+                .distances
+                .entry(source)
+                .or_insert_with(|| graph.distances_from_source(source)),
+        }
+    }
+
+    /// Memoized version of [`MobilityGraph::forced_captures`].
+    pub(crate) fn forced_captures(
+        &mut self,
+        color: Color,
+        piece: Piece,
+        graph: &MobilityGraph,
+        source: Square,
+        target: Square,
+        allowed_nb_captures: u8,
+    ) -> BitBoard {
+        match self.memo(color, piece) {
+            None => graph.forced_captures(source, target, allowed_nb_captures),
+            Some(memo) => *memo
+                .forced_captures
+                .entry((source, target, allowed_nb_captures))
+                .or_insert_with(|| graph.forced_captures(source, target, allowed_nb_captures)),
+        }
+    }
+}
+
+/// The maximum number of verdicts memoized by the global cache. When reached,
+/// the cache is cleared.
+#[cfg(feature = "global-cache")]
+const GLOBAL_CACHE_CAPACITY: usize = 1 << 16;
+
+/// The legality verdicts of [`is_legal`](crate::is_legal), memoized across the
+/// whole program.
+#[cfg(feature = "global-cache")]
+static GLOBAL_VERDICTS: Mutex<Option<HashMap<Board, bool>>> = Mutex::new(None);
+
+/// The memoized verdict on the given board, which is computed with the given
+/// function (and memoized) if it is not in the global cache. The cache is not
+/// locked during the computation, so other threads can use it meanwhile.
+#[cfg(feature = "global-cache")]
+pub(crate) fn global_verdict(board: &Board, compute: impl FnOnce() -> bool) -> bool {
+    let memoized = GLOBAL_VERDICTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|verdicts| verdicts.get(board).copied());
+    if let Some(verdict) = memoized {
+        return verdict;
+    }
+    let verdict = compute();
+    let mut verdicts = GLOBAL_VERDICTS.lock().unwrap();
+    let verdicts = verdicts.get_or_insert_with(HashMap::new);
+    if verdicts.len() >= GLOBAL_CACHE_CAPACITY {
+        verdicts.clear();
+    }
+    verdicts.insert(*board, verdict);
+    verdict
+}
+
+/// Removes all the verdicts memoized by the process-wide cache of
+/// [`is_legal`](crate::is_legal), which is only available with the
+/// `global-cache` feature.
+///
+/// ```
+/// use chess::Board;
+/// use sherlock::{clear_global_cache, is_legal};
+///
+/// assert!(is_legal(&Board::default()));
+/// clear_global_cache();
+/// assert!(is_legal(&Board::default()));
+/// ```
+#[cfg(feature = "global-cache")]
+pub fn clear_global_cache() {
+    *GLOBAL_VERDICTS.lock().unwrap() = None;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::{ALL_FILES, ALL_SQUARES};
+
+    use super::*;
+    use crate::{analyze, analyze_with_cache, RetractableBoard};
+
+    #[test]
+    fn test_cache_is_sound() {
+        let mut cache = AnalysisCache::new();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKB1R w KQkq -",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKB1R w KQkq -",
+            "r1bqkb1r/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq -",
+            "2bqkb2/1ppppp2/8/8/8/8/1PPPPPP1/2BQKB2 w - -",
+        ] {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            let expected = analyze(&board);
+            let analysis = analyze_with_cache(&board, &mut cache);
+            assert_eq!(analysis.result, expected.result);
+            for color in ALL_COLORS {
+                for file in ALL_FILES {
+                    assert_eq!(
+                        analysis.reachable_from_origin(color, file),
+                        expected.reachable_from_origin(color, file)
+                    );
+                    for square in ALL_SQUARES {
+                        assert_eq!(
+                            analysis.pawn_capture_distances(color, file, square),
+                            expected.pawn_capture_distances(color, file, square)
+                        );
+                    }
+                }
+            }
+        }
+        // the cache is filled by the queries of the mobility rules
+        #[cfg(feature = "rules-mobility")]
+        assert!(!cache.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Retro conventions.
+//!
+//! Diagrams do not show whether castling or an en passant capture is
+//! permitted. Chess compositions resolve this with conventions that grant
+//! these rights depending on what the retrograde analysis of the position can
+//! prove about its history.
+
+use chess::{
+    BitBoard, Board, BoardBuilder, CastleRights, Color, Piece, ALL_COLORS, ALL_FILES, EMPTY,
+    NUM_COLORS,
+};
+
+use crate::{is_legal, is_legal_with_options, AnalysisOptions};
+
+/// The conventions that determine the castling rights and the en passant
+/// captures permitted in a diagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetroConvention {
+    /// The conventions of the Codex for Chess Composition: castling is
+    /// permitted unless it can be proved not to be, whereas an en passant
+    /// capture is only permitted if it can be proved that the last move was the
+    /// corresponding double pawn push.
+    #[default]
+    Codex,
+    /// Castling and en passant captures are permitted as long as they are
+    /// consistent with some legal game leading to the position.
+    Permissive,
+}
+
+/// The castling rights and en passant captures permitted in a diagram (see
+/// [`permitted_rights`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermittedRights {
+    /// The castling rights of each color, indexed by `color.to_index()`.
+    pub castle_rights: [CastleRights; NUM_COLORS],
+    /// The squares on which an en passant capture is permitted, i.e. the
+    /// squares skipped by the pawns that may have just made a double push.
+    pub en_passant: BitBoard,
+}
+
+/// The castling rights and en passant captures that are permitted in the
+/// position of the given board under the given convention. Only the piece
+/// placement and the side to move of the board are taken into account.
+///
+/// Every castling right is evaluated independently, after the en passant
+/// captures have been settled. Rights that are individually permitted but
+/// mutually exclusive (which the Codex resolves through partial retrograde
+/// analysis) are all reported.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{BitBoard, Board, CastleRights, Square, EMPTY};
+/// use sherlock::{permitted_rights, RetroConvention};
+///
+/// // the last move may have been e2-e4, but also e3-e4
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b - -")?;
+///
+/// let rights = permitted_rights(&board, RetroConvention::Codex);
+/// assert_eq!(
+///     rights.castle_rights,
+///     [CastleRights::Both, CastleRights::Both]
+/// );
+/// assert_eq!(rights.en_passant, EMPTY);
+///
+/// let rights = permitted_rights(&board, RetroConvention::Permissive);
+/// assert_eq!(rights.en_passant, BitBoard::from_square(Square::E3));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn permitted_rights(board: &Board, convention: RetroConvention) -> PermittedRights {
+    let mut base = BoardBuilder::from(board);
+    for color in ALL_COLORS {
+        base.castle_rights(color, CastleRights::NoRights);
+    }
+    base.en_passant(None);
+
+    // the boards where the last move was a double push on each file
+    let mut en_passant = EMPTY;
+    let mut en_passant_file = None;
+    for file in ALL_FILES {
+        let mut builder = base;
+        builder.en_passant(Some(file));
+        match Board::try_from(&builder) {
+            Ok(ep_board) if ep_board.en_passant().is_some() => {
+                let pawn_square = ep_board.en_passant().unwrap();
+                if ep_board.piece_on(pawn_square) != Some(Piece::Pawn) || !is_legal(&ep_board) {
+                    continue;
+                }
+                en_passant |= BitBoard::from_square(pawn_square.ubackward(!board.side_to_move()));
+                en_passant_file = Some(file);
+            }
+            _ => (),
+        }
+    }
+    let without_en_passant = Board::try_from(&base).is_ok_and(|board| is_legal(&board));
+
+    // the double push is proved if no other last move is possible
+    if convention == RetroConvention::Codex && (en_passant.popcnt() > 1 || without_en_passant) {
+        en_passant = EMPTY;
+    }
+    if en_passant == EMPTY {
+        en_passant_file = None;
+    }
+    base.en_passant(en_passant_file);
+
+    let mut castle_rights = [CastleRights::NoRights; NUM_COLORS];
+    for color in ALL_COLORS {
+        for rights in [CastleRights::KingSide, CastleRights::QueenSide] {
+            let mut builder = base;
+            builder.castle_rights(color, rights);
+            if Board::try_from(&builder)
+                .is_ok_and(|board| board.castle_rights(color) == rights && is_legal(&board))
+            {
+                castle_rights[color.to_index()] = castle_rights[color.to_index()].add(rights);
+            }
+        }
+    }
+
+    PermittedRights {
+        castle_rights,
+        en_passant,
+    }
+}
+
+/// Checks whether the given board is legal (see [`is_legal`]) and its castling
+/// rights and en passant square are permitted under the given convention (see
+/// [`permitted_rights`]). The en passant square is judged by the retraction
+/// search (see [`AnalysisOptions::convention`]).
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::{is_legal_under_convention, RetroConvention};
+///
+/// // the en passant capture is not permitted unless e2-e4 was necessarily the
+/// // last move
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3")?;
+/// assert!(!is_legal_under_convention(&board, RetroConvention::Codex));
+/// assert!(is_legal_under_convention(
+///     &board,
+///     RetroConvention::Permissive
+/// ));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn is_legal_under_convention(board: &Board, convention: RetroConvention) -> bool {
+    let options = AnalysisOptions {
+        convention: Some(convention),
+        ..Default::default()
+    };
+    if !is_legal_with_options(board, &options) {
+        return false;
+    }
+    let permitted = permitted_rights(board, convention);
+    let permitted_castling = |color: Color| {
+        let rights = permitted.castle_rights[color.to_index()];
+        rights.add(board.castle_rights(color)) == rights
+    };
+    ALL_COLORS.into_iter().all(permitted_castling)
+}
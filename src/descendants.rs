@@ -0,0 +1,194 @@
+//! Enumeration of illegal descendants.
+//!
+//! Every position reachable from a legal position via legal moves is legal,
+//! thus any descendant of a legal position that is classified as illegal by
+//! [`is_legal`] reveals an unsound derivation. This module offers a search for
+//! such descendants, with deduplication, parallelism and the possibility of
+//! checkpointing the search state, in order to resume it later.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    str::FromStr,
+    thread,
+};
+
+use chess::{Board, MoveGen};
+
+use crate::{is_legal, Error};
+
+/// Constraints on the search performed by [`enumerate_illegal_descendants`].
+pub struct DescendantConstraints<'a> {
+    /// Only the descendants satisfying this predicate are analyzed and
+    /// further expanded.
+    pub filter: &'a (dyn Fn(&Board) -> bool + Sync),
+
+    /// The number of threads used for deciding the legality of positions.
+    pub nb_threads: usize,
+}
+
+impl Default for DescendantConstraints<'_> {
+    fn default() -> Self {
+        DescendantConstraints {
+            filter: &|_| true,
+            nb_threads: 1,
+        }
+    }
+}
+
+/// The state of a search for illegal descendants, which can be advanced in
+/// steps and checkpointed.
+#[derive(Clone, Debug, Default)]
+pub struct DescendantSearch {
+    queue: VecDeque<(Board, u8)>,
+    visited: HashSet<Board>,
+    illegal: Vec<Board>,
+}
+
+impl DescendantSearch {
+    /// Initializes a search for illegal descendants of the given position.
+    pub fn new(start: &Board) -> Self {
+        let mut search = DescendantSearch::default();
+        search.queue.push_back((*start, 0));
+        search.visited.insert(*start);
+        search
+    }
+
+    /// Tells whether there are no more positions to explore.
+    pub fn is_finished(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// The descendants found to be illegal so far.
+    pub fn illegal(&self) -> &[Board] {
+        &self.illegal
+    }
+
+    /// Explores up to `nb_positions` positions from the queue, with at most
+    /// `depth` plies from the start position. Returns `true` iff the search is
+    /// finished.
+    pub fn step(
+        &mut self,
+        constraints: &DescendantConstraints,
+        depth: u8,
+        nb_positions: usize,
+    ) -> bool {
+        let n = nb_positions.min(self.queue.len());
+        let batch: Vec<_> = self.queue.drain(..n).collect();
+
+        // decide the legality of the batch in parallel
+        let chunk_size = batch.len().div_ceil(constraints.nb_threads.max(1)).max(1);
+        let legality: Vec<bool> = thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|(b, _)| is_legal(b)).collect::<Vec<_>>())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        for ((board, d), legal) in batch.into_iter().zip(legality) {
+            if !legal {
+                self.illegal.push(board);
+                continue;
+            }
+            if d >= depth {
+                continue;
+            }
+            for m in MoveGen::new_legal(&board) {
+                let new_board = board.make_move_new(m);
+                if (constraints.filter)(&new_board) && self.visited.insert(new_board) {
+                    self.queue.push_back((new_board, d + 1));
+                }
+            }
+        }
+
+        self.is_finished()
+    }
+
+    /// Serializes the state of the search, one position per line, in the
+    /// format `<tag> <depth> <fen>`, where `<tag>` is `q` for positions in the
+    /// queue, `v` for visited positions and `i` for illegal positions.
+    pub fn checkpoint(&self) -> String {
+        let mut lines = vec![];
+        for (board, d) in self.queue.iter() {
+            lines.push(format!("q {} {}", d, board));
+        }
+        for board in self.visited.iter() {
+            lines.push(format!("v 0 {}", board));
+        }
+        for board in self.illegal.iter() {
+            lines.push(format!("i 0 {}", board));
+        }
+        lines.join("\n")
+    }
+
+    /// Restores a search from the output of [`DescendantSearch::checkpoint`].
+    pub fn resume(checkpoint: &str) -> Result<Self, Error> {
+        let mut search = DescendantSearch::default();
+        for line in checkpoint.lines().filter(|line| !line.is_empty()) {
+            let mut parts = line.splitn(3, ' ');
+            let (tag, d, fen) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(tag), Some(d), Some(fen)) => (tag, d, fen),
+                _ => return Err(Error::InvalidCheckpoint),
+            };
+            let d = d.parse::<u8>().map_err(|_| Error::InvalidCheckpoint)?;
+            let board = Board::from_str(fen).map_err(|_| Error::InvalidCheckpoint)?;
+            match tag {
+                "q" => search.queue.push_back((board, d)),
+                "v" => {
+                    search.visited.insert(board);
+                }
+                "i" => search.illegal.push(board),
+                _ => return Err(Error::InvalidCheckpoint),
+            }
+        }
+        Ok(search)
+    }
+}
+
+/// Enumerates all the positions reachable from `start` in at most `depth`
+/// plies (and satisfying the given constraints) that are classified as
+/// illegal.
+///
+/// If `start` is legal, the output should be empty, otherwise the legality
+/// analysis is unsound.
+///
+/// ```
+/// use chess::Board;
+/// use sherlock::{enumerate_illegal_descendants, DescendantConstraints};
+///
+/// let constraints = DescendantConstraints::default();
+/// let illegal = enumerate_illegal_descendants(&Board::default(), &constraints, 1);
+/// assert!(illegal.is_empty());
+/// ```
+pub fn enumerate_illegal_descendants(
+    start: &Board,
+    constraints: &DescendantConstraints,
+    depth: u8,
+) -> Vec<Board> {
+    let mut search = DescendantSearch::new(start);
+    while !search.step(constraints, depth, 1024) {}
+    search.illegal
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_and_resume() {
+        let constraints = DescendantConstraints::default();
+        let mut search = DescendantSearch::new(&Board::default());
+        search.step(&constraints, 2, 1);
+        let resumed = DescendantSearch::resume(&search.checkpoint()).unwrap();
+        assert_eq!(resumed.queue, search.queue);
+        assert_eq!(resumed.visited, search.visited);
+        assert_eq!(resumed.illegal, search.illegal);
+        assert!(DescendantSearch::resume("x 0 8/8/8/8/8/8/8/8 w - -").is_err());
+    }
+}
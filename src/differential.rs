@@ -0,0 +1,171 @@
+//! Differential testing of the legality verdicts.
+//!
+//! The verdicts of [`is_legal`] are cross-checked against an independent
+//! oracle, reporting the positions where they disagree. An oracle that proves
+//! a position legal when we declare it illegal reveals an unsound rule, which
+//! the curated test vectors may have missed.
+//!
+//! Two oracles are provided: a brute-force proof game search, which can only
+//! prove positions legal (those reachable in a few plies), and an external
+//! retro engine that follows the line protocol of [`crate::batch`].
+//!
+//! ```
+//! use chess::{Board, ChessMove, Square};
+//! use sherlock::differential::{cross_check, ProofGameOracle};
+//!
+//! let board = Board::default().make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+//! let mut oracle = ProofGameOracle::new(2);
+//! assert!(cross_check([Board::default(), board], &mut oracle).is_empty());
+//! ```
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use chess::{Board, BoardStatus, MoveGen};
+
+use crate::is_legal;
+
+/// An independent judge of the legality of positions.
+pub trait Oracle {
+    /// Whether the given position is legal, or `None` if the oracle cannot
+    /// tell.
+    fn is_legal(&mut self, board: &Board) -> Option<bool>;
+}
+
+/// A position on which [`is_legal`] and an oracle disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disagreement {
+    /// The position, in FEN notation.
+    pub fen: String,
+    /// The verdict of [`is_legal`].
+    pub verdict: bool,
+    /// The verdict of the oracle.
+    pub oracle_verdict: bool,
+}
+
+impl Disagreement {
+    /// Tells whether the disagreement reveals an unsound verdict, i.e. a legal
+    /// position (according to the oracle) that we declared illegal. Otherwise,
+    /// it only reveals a position that we could not prove illegal.
+    pub fn is_unsound(&self) -> bool {
+        self.oracle_verdict && !self.verdict
+    }
+}
+
+/// Cross-checks the verdicts of [`is_legal`] on the given positions against
+/// the given oracle, returning the positions where they disagree. The
+/// positions on which the oracle is inconclusive are skipped.
+pub fn cross_check<I, O>(boards: I, oracle: &mut O) -> Vec<Disagreement>
+where
+    I: IntoIterator<Item = Board>,
+    O: Oracle,
+{
+    let mut disagreements = vec![];
+    for board in boards {
+        if let Some(oracle_verdict) = oracle.is_legal(&board) {
+            let verdict = is_legal(&board);
+            if verdict != oracle_verdict {
+                disagreements.push(Disagreement {
+                    fen: board.to_string(),
+                    verdict,
+                    oracle_verdict,
+                });
+            }
+        }
+    }
+    disagreements
+}
+
+/// An oracle that searches for a proof game of at most the given number of
+/// plies leading to the position. It proves legal the positions reachable
+/// within the bound and is inconclusive on the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofGameOracle {
+    max_plies: usize,
+}
+
+impl ProofGameOracle {
+    /// An oracle that explores all the games of at most `max_plies` plies.
+    /// Beware that their number grows exponentially with the bound.
+    pub fn new(max_plies: usize) -> Self {
+        ProofGameOracle { max_plies }
+    }
+}
+
+impl Oracle for ProofGameOracle {
+    fn is_legal(&mut self, board: &Board) -> Option<bool> {
+        if reaches(&Board::default(), board, self.max_plies) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tells whether the given target can be reached from the given board in at
+/// most `nb_plies` plies.
+fn reaches(board: &Board, target: &Board, nb_plies: usize) -> bool {
+    if board == target {
+        return true;
+    }
+    // the number of pieces never increases
+    if nb_plies == 0
+        || board.status() != BoardStatus::Ongoing
+        || board.combined().popcnt() < target.combined().popcnt()
+    {
+        return false;
+    }
+    MoveGen::new_legal(board).any(|m| reaches(&board.make_move_new(m), target, nb_plies - 1))
+}
+
+/// An oracle that queries an external retro engine, which must read one FEN
+/// per line from its standard input and answer every line with a line whose
+/// last (tab-separated) field is `legal` or `illegal`, as in the output of
+/// [`crate::batch::run`]. Any other answer is considered inconclusive.
+#[derive(Debug)]
+pub struct ExternalOracle {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ExternalOracle {
+    /// Spawns the given program (with the given arguments) as an oracle.
+    pub fn spawn(program: &str, args: &[&str]) -> io::Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let stdout = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        Ok(ExternalOracle {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+}
+
+impl Oracle for ExternalOracle {
+    fn is_legal(&mut self, board: &Board) -> Option<bool> {
+        writeln!(self.stdin, "{}", board).ok()?;
+        self.stdin.flush().ok()?;
+        let mut answer = String::new();
+        self.stdout.read_line(&mut answer).ok()?;
+        match answer.trim().rsplit('\t').next() {
+            Some("legal") => Some(true),
+            Some("illegal") => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for ExternalOracle {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
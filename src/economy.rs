@@ -0,0 +1,139 @@
+//! Economy of proof games.
+//!
+//! Chess compositions are judged, among other criteria, by their economy.
+//! This module computes the standard metrics of a proof game (a sequence of
+//! moves from the starting position leading to the composed position).
+
+use std::collections::VecDeque;
+
+use chess::{BitBoard, Board, ChessMove, Color, File, Piece, Square, ALL_SQUARES, NUM_SQUARES};
+
+use crate::{
+    rules::ALL_ORIGINS,
+    soundness::GameHistory,
+    utils::{initial_piece_on, moves_on_empty_board, origin_color},
+};
+
+/// Standard composition metrics of a proof game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EconomyStats {
+    /// The number of captures, including en passant captures.
+    pub nb_captures: u32,
+    /// The number of promotions.
+    pub nb_promotions: u32,
+    /// The number of moves of a piece to a square it had already visited.
+    pub nb_switchbacks: u32,
+    /// The number of moves performed by (non-pawn) pieces beyond the shortest
+    /// route on an empty board between their origin (or their square after
+    /// castling) and their final square (the square where they were captured
+    /// if they were captured).
+    pub nb_tempo_moves: u32,
+}
+
+/// Plays the given sequence of (legal) moves from the starting position and
+/// computes the economy metrics of such proof game.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::ChessMove;
+/// use sherlock::{economy_stats, EconomyStats};
+///
+/// let moves: Vec<_> = ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5d8"]
+///     .iter()
+///     .map(|m| ChessMove::from_str(m).unwrap())
+///     .collect();
+///
+/// // the black queen performs a switchback, wasting two moves
+/// assert_eq!(
+///     economy_stats(&moves),
+///     EconomyStats {
+///         nb_captures: 2,
+///         nb_promotions: 0,
+///         nb_switchbacks: 1,
+///         nb_tempo_moves: 2,
+///     }
+/// );
+/// ```
+pub fn economy_stats(moves: &[ChessMove]) -> EconomyStats {
+    let mut stats = EconomyStats::default();
+    let mut history = GameHistory::new();
+    let mut board = Board::default();
+
+    // the square where the route of every piece starts and the number of moves
+    // performed along such route (castling resets the routes of king and rook)
+    let mut route_start: [Square; NUM_SQUARES] = ALL_SQUARES;
+    let mut route_length = [0u32; NUM_SQUARES];
+
+    for m in moves {
+        let (source, target) = (m.get_source(), m.get_dest());
+        let piece = board.piece_on(source).unwrap();
+        let origin = history.origin_on(source).unwrap();
+
+        let is_en_passant = piece == Piece::Pawn
+            && source.get_file() != target.get_file()
+            && board.piece_on(target).is_none();
+        if board.piece_on(target).is_some() || is_en_passant {
+            stats.nb_captures += 1;
+        }
+        if m.get_promotion().is_some() {
+            stats.nb_promotions += 1;
+        }
+        if history.has_visited(origin, target) {
+            stats.nb_switchbacks += 1;
+        }
+
+        let file_distance =
+            (source.get_file().to_index() as i32 - target.get_file().to_index() as i32).abs();
+        if piece == Piece::King && file_distance == 2 {
+            let rank = source.get_rank();
+            let (rook_source, rook_target) = match target.get_file() {
+                File::G => (File::H, File::F),
+                _ => (File::A, File::D),
+            };
+            let rook_source = Square::make_square(rank, rook_source);
+            let rook_origin = history.origin_on(rook_source).unwrap();
+            route_start[origin.to_index()] = target;
+            route_length[origin.to_index()] = 0;
+            route_start[rook_origin.to_index()] = Square::make_square(rank, rook_target);
+            route_length[rook_origin.to_index()] = 0;
+        } else {
+            route_length[origin.to_index()] += 1;
+        }
+
+        history.make_move(&board, *m);
+        board = board.make_move_new(*m);
+    }
+
+    for origin in ALL_ORIGINS {
+        let piece = initial_piece_on(origin);
+        if piece == Piece::Pawn {
+            continue;
+        }
+        let distance = empty_board_distance(
+            piece,
+            origin_color(origin),
+            route_start[origin.to_index()],
+            history.destiny(origin),
+        );
+        stats.nb_tempo_moves += route_length[origin.to_index()].saturating_sub(distance);
+    }
+    stats
+}
+
+/// The minimum number of moves that a (non-pawn) piece needs to go from
+/// `source` to `target` on an empty board.
+fn empty_board_distance(piece: Piece, color: Color, source: Square, target: Square) -> u32 {
+    let mut visited = BitBoard::from_square(source);
+    let mut queue = VecDeque::from([(source, 0)]);
+    while let Some((square, distance)) = queue.pop_front() {
+        if square == target {
+            return distance;
+        }
+        for next in moves_on_empty_board(piece, color, square) & !visited {
+            visited |= BitBoard::from_square(next);
+            queue.push_back((next, distance + 1));
+        }
+    }
+    u32::MAX
+}
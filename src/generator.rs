@@ -0,0 +1,102 @@
+//! Random legal positions.
+//!
+//! Positions are generated by playing random games from the starting position
+//! (so they are legal by construction) and filtering them according to some
+//! constraints, some of which require a legality analysis.
+
+use chess::{Board, BoardStatus, Color, MoveGen, ALL_COLORS, ALL_PIECES, NUM_COLORS, NUM_PIECES};
+use rand::Rng;
+
+use crate::requires_promoted_piece;
+
+/// Constraints on the positions produced by [`generate_position`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct PositionConstraints {
+    /// If set, the exact number of pieces of each color and type, indexed by
+    /// `color.to_index()` and `piece.to_index()`.
+    pub material: Option<[[u32; NUM_PIECES]; NUM_COLORS]>,
+
+    /// If set, the side to move in the position.
+    pub side_to_move: Option<Color>,
+
+    /// Whether the position must contain a promoted piece (see
+    /// [`requires_promoted_piece`]).
+    pub must_contain_promoted_piece: bool,
+
+    /// The maximum number of plies of every random game.
+    pub max_plies: usize,
+
+    /// The maximum number of random games to be played.
+    pub max_attempts: usize,
+}
+
+impl Default for PositionConstraints {
+    fn default() -> Self {
+        PositionConstraints {
+            material: None,
+            side_to_move: None,
+            must_contain_promoted_piece: false,
+            max_plies: 200,
+            max_attempts: 100,
+        }
+    }
+}
+
+impl PositionConstraints {
+    /// Tells whether the given position satisfies the constraints.
+    fn are_satisfied_by(&self, board: &Board) -> bool {
+        if let Some(color) = self.side_to_move {
+            if board.side_to_move() != color {
+                return false;
+            }
+        }
+        if let Some(material) = self.material {
+            for color in ALL_COLORS {
+                for piece in ALL_PIECES {
+                    let n = (board.pieces(piece) & board.color_combined(color)).popcnt();
+                    if n != material[color.to_index()][piece.to_index()] {
+                        return false;
+                    }
+                }
+            }
+        }
+        !self.must_contain_promoted_piece || requires_promoted_piece(board)
+    }
+}
+
+/// Generates a legal position satisfying the given constraints, by playing
+/// random games. Returns `None` if no such position was found within the
+/// budget specified in the constraints.
+///
+/// ```
+/// use chess::Color;
+/// use rand::{rngs::SmallRng, SeedableRng};
+/// use sherlock::{generate_position, PositionConstraints};
+///
+/// let mut rng = SmallRng::seed_from_u64(42);
+/// let constraints = PositionConstraints {
+///     side_to_move: Some(Color::Black),
+///     ..Default::default()
+/// };
+/// let board = generate_position(&mut rng, &constraints).unwrap();
+/// assert_eq!(board.side_to_move(), Color::Black);
+/// ```
+pub fn generate_position<R: Rng>(rng: &mut R, constraints: &PositionConstraints) -> Option<Board> {
+    for _ in 0..constraints.max_attempts {
+        let mut board = Board::default();
+        for _ in 0..constraints.max_plies {
+            if board.status() != BoardStatus::Ongoing {
+                break;
+            }
+            let moves: Vec<_> = MoveGen::new_legal(&board).collect();
+            board = board.make_move_new(moves[rng.gen_range(0, moves.len())]);
+            if rng.gen_range(0, 4) == 0 && constraints.are_satisfied_by(&board) {
+                return Some(board);
+            }
+        }
+        if constraints.are_satisfied_by(&board) {
+            return Some(board);
+        }
+    }
+    None
+}
@@ -0,0 +1,144 @@
+//! Heatmaps and annotations for visualizing an analysis.
+//!
+//! The candidate origins of a piece (or the candidate destinies of an origin)
+//! are exported as heatmaps, where every candidate is given the same weight
+//! and the weights add up to 1, so that they can be rendered directly.
+//! They can also be exported as the arrows and highlighted squares of the
+//! PGN comments of lichess studies.
+
+use chess::{BitBoard, Square, EMPTY, NUM_FILES, NUM_RANKS};
+
+use crate::Analysis;
+
+/// A weight per square, indexed by `[rank.to_index()][file.to_index()]`.
+pub type Heatmap = [[f32; NUM_FILES]; NUM_RANKS];
+
+/// A heatmap where the given squares have the same weight, adding up to 1
+/// (all weights are 0 if no square is given).
+pub fn uniform_heatmap(squares: BitBoard) -> Heatmap {
+    let mut heatmap = [[0.0; NUM_FILES]; NUM_RANKS];
+    let weight = 1.0 / squares.popcnt() as f32;
+    for square in squares {
+        heatmap[square.get_rank().to_index()][square.get_file().to_index()] = weight;
+    }
+    heatmap
+}
+
+/// A PGN comment with the given arrows (pairs of source and target squares)
+/// and highlighted squares, in the format of lichess study annotations, e.g.
+/// `[%cal Gb1c3,Gg1c3][%csl Gc3]`. Arrows from a square to itself are drawn
+/// as highlighted squares.
+///
+/// ```
+/// use chess::{BitBoard, Square};
+/// use sherlock::lichess_comment;
+///
+/// let arrows = [(Square::B1, Square::C3), (Square::G1, Square::C3)];
+/// assert_eq!(
+///     lichess_comment(&arrows, BitBoard::from_square(Square::C3)),
+///     "[%cal Gb1c3,Gg1c3][%csl Gc3]"
+/// );
+/// ```
+pub fn lichess_comment(arrows: &[(Square, Square)], highlighted: BitBoard) -> String {
+    let mut highlighted = highlighted;
+    let arrows: Vec<_> = arrows
+        .iter()
+        .filter(|(source, target)| {
+            if source == target {
+                highlighted |= BitBoard::from_square(*source);
+            }
+            source != target
+        })
+        .map(|(source, target)| format!("G{}{}", source, target))
+        .collect();
+    let mut comment = String::new();
+    if !arrows.is_empty() {
+        comment.push_str(&format!("[%cal {}]", arrows.join(",")));
+    }
+    if highlighted != EMPTY {
+        let squares: Vec<_> = highlighted.map(|square| format!("G{}", square)).collect();
+        comment.push_str(&format!("[%csl {}]", squares.join(",")));
+    }
+    comment
+}
+
+impl Analysis {
+    /// The candidate origins of the piece on the given square (see
+    /// [`Analysis::origins`]) as a heatmap.
+    ///
+    /// ```
+    /// use chess::{Rank, Square};
+    /// use sherlock::{analyze, RetractableBoard};
+    ///
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// let heatmap = analysis.origin_heatmap(Square::E2);
+    /// assert_eq!(heatmap[Rank::Second.to_index()][4], 1.0);
+    /// ```
+    pub fn origin_heatmap(&self, square: Square) -> Heatmap {
+        uniform_heatmap(self.origins(square))
+    }
+
+    /// The candidate destinies of the piece that started on the given square
+    /// (see [`Analysis::destinies`]) as a heatmap.
+    pub fn destiny_heatmap(&self, origin: Square) -> Heatmap {
+        uniform_heatmap(self.destinies(origin))
+    }
+
+    /// A lichess PGN comment (see [`lichess_comment`]) with an arrow from
+    /// every candidate origin of the piece on the given square.
+    ///
+    /// ```
+    /// use chess::Square;
+    /// use sherlock::{analyze, RetractableBoard};
+    ///
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// assert_eq!(analysis.origin_annotation(Square::E2), "[%csl Ge2]");
+    /// ```
+    pub fn origin_annotation(&self, square: Square) -> String {
+        let arrows: Vec<_> = self
+            .origins(square)
+            .map(|origin| (origin, square))
+            .collect();
+        lichess_comment(&arrows, EMPTY)
+    }
+
+    /// A lichess PGN comment (see [`lichess_comment`]) with an arrow to every
+    /// candidate destiny of the piece that started on the given square.
+    pub fn destiny_annotation(&self, origin: Square) -> String {
+        let arrows: Vec<_> = self
+            .destinies(origin)
+            .map(|destiny| (origin, destiny))
+            .collect();
+        lichess_comment(&arrows, EMPTY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_uniform_heatmap() {
+        let heatmap = uniform_heatmap(bitboard_of_squares(&[B1, G1, A2, H8]));
+        assert_eq!(heatmap[0][1], 0.25);
+        assert_eq!(heatmap[1][0], 0.25);
+        assert_eq!(heatmap[7][7], 0.25);
+        assert_eq!(heatmap[0][0], 0.0);
+        let total: f32 = heatmap.iter().flatten().sum();
+        assert_eq!(total, 1.0);
+
+        assert_eq!(uniform_heatmap(EMPTY), [[0.0; NUM_FILES]; NUM_RANKS]);
+    }
+
+    #[test]
+    fn test_lichess_comment() {
+        assert_eq!(lichess_comment(&[], EMPTY), "");
+        assert_eq!(lichess_comment(&[(E2, E4)], EMPTY), "[%cal Ge2e4]");
+        assert_eq!(
+            lichess_comment(&[(E2, E2)], BitBoard::from_square(D4)),
+            "[%csl Ge2,Gd4]"
+        );
+    }
+}
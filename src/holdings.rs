@@ -0,0 +1,149 @@
+//! Boards with holdings.
+//!
+//! In Crazyhouse, captured pieces change sides and are kept "in hand", from
+//! where they can later be dropped on the board. Furthermore, promoted pieces
+//! demote to pawns when captured, so they must be distinguished from the
+//! others.
+
+use std::str::FromStr;
+
+use chess::{BitBoard, Board, Color, Piece, ALL_SQUARES, EMPTY, NUM_COLORS, NUM_PIECES};
+
+use crate::Error;
+
+/// A chess board together with the pieces held by each player and the
+/// information about which pieces on the board are promoted.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct HoldingsBoard {
+    board: Board,
+    holdings: [[u8; NUM_PIECES]; NUM_COLORS],
+    promoted: BitBoard,
+}
+
+impl HoldingsBoard {
+    /// Creates a board with the given holdings, indexed by
+    /// `color.to_index()` and `piece.to_index()`, and the given set of
+    /// promoted pieces.
+    pub fn new(board: Board, holdings: [[u8; NUM_PIECES]; NUM_COLORS], promoted: BitBoard) -> Self {
+        HoldingsBoard {
+            board,
+            holdings,
+            promoted,
+        }
+    }
+
+    /// The position on the board.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The number of pieces of the given type held by the given player.
+    pub fn holdings(&self, color: Color, piece: Piece) -> u8 {
+        self.holdings[color.to_index()][piece.to_index()]
+    }
+
+    /// The squares of the board containing promoted pieces.
+    pub fn promoted(&self) -> BitBoard {
+        self.promoted
+    }
+}
+
+impl FromStr for HoldingsBoard {
+    type Err = Error;
+
+    /// Parses a Crazyhouse FEN, where the holdings are given in brackets after
+    /// the piece placement and promoted pieces are followed by a `~`.
+    ///
+    /// ```
+    /// use chess::{Color, Piece};
+    /// use sherlock::HoldingsBoard;
+    ///
+    /// let fen = "r1bqkb1r/pppp1ppp/2n5/4p3/2B1P3/5Q~2/PPPP2PP/RNB1K2R[NPp] b KQkq - 0 5";
+    /// let board: HoldingsBoard = fen.parse()?;
+    /// assert_eq!(board.holdings(Color::White, Piece::Knight), 1);
+    /// assert_eq!(board.holdings(Color::Black, Piece::Pawn), 1);
+    /// assert_eq!(board.promoted().popcnt(), 1);
+    /// # Ok::<(), sherlock::Error>(())
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (placement, rest) = s.trim().split_once(' ').ok_or(Error::InvalidHoldings)?;
+        let (placement, held) = match placement.split_once('[') {
+            Some((placement, held)) => (placement, held.strip_suffix(']')),
+            None => (placement, Some("")),
+        };
+        let held = held.ok_or(Error::InvalidHoldings)?;
+
+        let mut holdings = [[0u8; NUM_PIECES]; NUM_COLORS];
+        for c in held.chars() {
+            let (piece, color) = parse_piece(c).ok_or(Error::InvalidHoldings)?;
+            let held = &mut holdings[color.to_index()][piece.to_index()];
+            *held = held.checked_add(1).ok_or(Error::InvalidHoldings)?;
+        }
+
+        // find the promoted pieces, ranks are given from the 8th to the 1st
+        let mut promoted = EMPTY;
+        for (i, rank) in placement.split('/').enumerate() {
+            let rank_index = 7usize.checked_sub(i).ok_or(Error::InvalidHoldings)?;
+            let mut file = 0;
+            let mut last_piece = None;
+            for c in rank.chars() {
+                if c == '~' {
+                    let index = last_piece.ok_or(Error::InvalidHoldings)?;
+                    let square = ALL_SQUARES.get(index).ok_or(Error::InvalidHoldings)?;
+                    promoted |= BitBoard::from_square(*square);
+                } else if let Some(n) = c.to_digit(10) {
+                    file += n as usize;
+                    last_piece = None;
+                } else {
+                    last_piece = Some(8 * rank_index + file);
+                    file += 1;
+                }
+            }
+        }
+
+        let fen = format!("{} {}", placement.replace('~', ""), rest);
+        let board = Board::from_str(&fen).map_err(|_| Error::InvalidHoldings)?;
+        Ok(HoldingsBoard::new(board, holdings, promoted))
+    }
+}
+
+/// Parses a piece given as a FEN character.
+fn parse_piece(c: char) -> Option<(Piece, Color)> {
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let piece = match c.to_ascii_lowercase() {
+        'p' => Piece::Pawn,
+        'n' => Piece::Knight,
+        'b' => Piece::Bishop,
+        'r' => Piece::Rook,
+        'q' => Piece::Queen,
+        'k' => Piece::King,
+        _ => return None,
+    };
+    Some((piece, color))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_holdings_from_str() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[QQn] w KQkq - 0 1";
+        let board = HoldingsBoard::from_str(fen).expect("Valid Position");
+        assert_eq!(board.holdings(Color::White, Piece::Queen), 2);
+        assert_eq!(board.holdings(Color::Black, Piece::Knight), 1);
+        assert_eq!(board.holdings(Color::Black, Piece::Queen), 0);
+
+        // the holdings do not fit in the counters
+        let fen = format!(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[{}] w KQkq - 0 1",
+            "P".repeat(256)
+        );
+        assert_eq!(HoldingsBoard::from_str(&fen), Err(Error::InvalidHoldings));
+    }
+}
@@ -1,38 +1,244 @@
-use std::collections::HashMap;
-
-use chess::Board;
-
-use crate::{analysis::Analysis, rules::*, Legality::Illegal, RetractableBoard, RetractionGen};
-
-/// Initialize all the available rules.
-fn init_rules() -> Vec<Box<dyn Rule>> {
-    vec![
-        Box::new(MaterialRule::new()),
-        Box::new(OriginsRule::new()),
-        Box::new(SteadyRule::new()),
-        Box::new(RefineOriginsRule::new()),
-        Box::new(DestiniesRule::new()),
-        Box::new(SteadyMobilityRule::new()),
-        Box::new(RoyaltyOn1stRankRule::new()),
-        Box::new(PawnOn2ndRankRule::new()),
-        Box::new(PawnOn3rdRankRule::new()),
-        Box::new(CornerKnightRule::new()),
-        Box::new(CapturesBoundsRule::new()),
-        Box::new(SurpassedPawnsRule::new()),
-        Box::new(UnretractableRule::new()),
-        Box::new(MobilityRule::new()),
-        Box::new(RouteFromOriginsRule::new()),
-        Box::new(RouteToReachable::new()),
-        Box::new(MissingRule::new()),
-        Box::new(CapturesRule::new()),
-        Box::new(TombsRule::new()),
-        Box::new(ParityRule::new()),
-    ]
+use std::{
+    cmp::max,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    mem::size_of,
+    ops::Range,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    thread,
+};
+
+use chess::{
+    get_rank, BitBoard, Board, BoardBuilder, BoardStatus, CastleRights, ChessMove, Color, Piece,
+    Rank, Square, ALL_COLORS, ALL_FILES, ALL_PIECES, EMPTY, NUM_COLORS,
+};
+
+use crate::{
+    analysis::{
+        Analysis, AnalysisOptions, CastlingHypothesis, IllegalityClass, IllegalityReason, Legality,
+        RuleSchedule, Variant, ALL_CASTLING_HYPOTHESES,
+    },
+    cache::AnalysisCache,
+    rules::*,
+    static_screen, ChessRetraction, EnPassantFlag, Error, Fact, HoldingsBoard,
+    Legality::Illegal,
+    RetractableBoard, RetractionConstraint, RetractionGen, RetroConvention, UncapturedCandidates,
+};
+
+/// Initialize all the available rules under the given options. Besides the
+/// core rules, which are always available, the groups of rules enabled through
+/// the `rules-*` features are included.
+fn init_rules(options: &AnalysisOptions) -> Vec<GroupedRule> {
+    let (mut rules, turn_rules) = init_rule_layers(options);
+    rules.extend(turn_rules);
+    rules
+}
+
+/// Like [`init_rules`], but split into the rules that do not depend on the
+/// side to move and the rules that do (the turn-dependent overlay of an
+/// analysis, see [`analyze_both_turns`]). User-defined rules are part of the
+/// overlay, since they may depend on the side to move.
+fn init_rule_layers(options: &AnalysisOptions) -> (Vec<GroupedRule>, Vec<GroupedRule>) {
+    let variant = options.variant;
+    // these rules rely on the orthodox notion of check
+    let orthodox_checks = variant == Variant::Orthodox;
+    // these rules rely on every missing piece having been captured
+    #[cfg(all(
+        feature = "atomic",
+        any(feature = "rules-captures", feature = "rules-parity")
+    ))]
+    let orthodox_captures = variant != Variant::Atomic;
+    #[cfg(all(
+        not(feature = "atomic"),
+        any(feature = "rules-captures", feature = "rules-parity")
+    ))]
+    let orthodox_captures = true;
+
+    let mut rules: Vec<GroupedRule> = vec![
+        (RuleGroup::Foundational, Box::new(MaterialRule::new())),
+        (RuleGroup::Foundational, Box::new(OriginsRule::new())),
+        (RuleGroup::Foundational, Box::new(SteadyRule::new())),
+        (RuleGroup::Other, Box::new(RefineOriginsRule::new())),
+        (RuleGroup::Other, Box::new(OriginsMatchingRule::new())),
+        (RuleGroup::Other, Box::new(DestiniesRule::new())),
+    ];
+    #[cfg(feature = "rules-mobility")]
+    if orthodox_checks {
+        rules.push((RuleGroup::Mobility, Box::new(SteadyMobilityRule::new())));
+    }
+    if orthodox_checks {
+        rules.push((RuleGroup::Other, Box::new(RoyaltyOn1stRankRule::new())));
+        rules.push((RuleGroup::Other, Box::new(PawnOn2ndRankRule::new())));
+        rules.push((RuleGroup::Other, Box::new(PawnOn3rdRankRule::new())));
+    }
+    rules.push((RuleGroup::Other, Box::new(CornerKnightRule::new())));
+    if options.max_promotions.iter().any(Option::is_some) {
+        rules.push((RuleGroup::Other, Box::new(PromotionsRule::new())));
+    }
+    rules.push((RuleGroup::Captures, Box::new(CapturesBoundsRule::new())));
+    rules.push((RuleGroup::Captures, Box::new(SurpassedPawnsRule::new())));
+    if orthodox_checks {
+        rules.push((RuleGroup::Other, Box::new(UnretractableRule::new())));
+    }
+    #[cfg(feature = "rules-mobility")]
+    {
+        rules.push((RuleGroup::Mobility, Box::new(MobilityRule::new())));
+        rules.push((RuleGroup::Mobility, Box::new(RouteFromOriginsRule::new())));
+        rules.push((RuleGroup::Mobility, Box::new(PromotedOriginsRule::new())));
+        rules.push((RuleGroup::Mobility, Box::new(RouteToReachable::new())));
+        if orthodox_checks {
+            rules.push((RuleGroup::Mobility, Box::new(RouteInterferenceRule::new())));
+            rules.push((RuleGroup::Mobility, Box::new(KingRouteRule::new())));
+        }
+    }
+    #[cfg(feature = "rules-captures")]
+    if orthodox_captures {
+        rules.push((RuleGroup::Captures, Box::new(PawnFilesRule::new())));
+        rules.push((RuleGroup::Captures, Box::new(MissingRule::new())));
+        rules.push((RuleGroup::Captures, Box::new(CapturesRule::new())));
+        rules.push((RuleGroup::Captures, Box::new(TombsRule::new())));
+        rules.push((RuleGroup::Captures, Box::new(CapturesAccountingRule::new())));
+        rules.push((RuleGroup::Captures, Box::new(CapturesFlowRule::new())));
+    }
+
+    let mut turn_rules: Vec<GroupedRule> = vec![];
+    if orthodox_checks {
+        turn_rules.push((RuleGroup::Other, Box::new(KingProximityRule::new())));
+    }
+    #[cfg(feature = "rules-parity")]
+    if orthodox_captures && !options.allow_null_moves {
+        turn_rules.push((RuleGroup::Other, Box::new(ParityRule::new())));
+    }
+    turn_rules.extend(
+        options
+            .extra_rules
+            .iter()
+            .map(|factory| (RuleGroup::Other, factory())),
+    );
+    (rules, turn_rules)
+}
+
+/// The group of a rule, which determines its priority under the adaptive
+/// schedule (see [`schedule_rules`]). Groups are assigned when the rules are
+/// registered (see [`init_rule_layers`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleGroup {
+    /// Rules that are applied first under any schedule, since most of the
+    /// other rules build on the information they derive.
+    Foundational,
+    /// Rules that reason about the captures performed during the game.
+    Captures,
+    /// Rules that reason about the movements of the pieces.
+    Mobility,
+    /// The rest of the rules, including user-defined ones.
+    Other,
+}
+
+/// A rule, tagged with its group.
+type GroupedRule = (RuleGroup, Box<dyn Rule>);
+
+impl IllegalityReason {
+    /// The class of the mechanism.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::Board;
+    /// use sherlock::{illegality_reason, IllegalityClass};
+    ///
+    /// // White cannot have 9 pawns
+    /// let board = Board::from_str("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -")?;
+    /// assert_eq!(
+    ///     illegality_reason(&board).map(|reason| reason.class()),
+    ///     Some(IllegalityClass::Material)
+    /// );
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn class(&self) -> IllegalityClass {
+        match self {
+            IllegalityReason::Rule(_, class) => *class,
+            IllegalityReason::EmptyOrigins(_) => IllegalityClass::Counting,
+            IllegalityReason::EmptyDestinies(_) => IllegalityClass::Route,
+            IllegalityReason::RetractionSearchExhausted => IllegalityClass::RetractionExhausted,
+            #[cfg(feature = "sat")]
+            IllegalityReason::ExternalSolver => IllegalityClass::Counting,
+            IllegalityReason::StaticScreen(class) => *class,
+        }
+    }
+}
+
+/// Reorders the given rules according to the schedule policy of the analysis
+/// and cheap features of the position being analyzed. The relative order of
+/// rules that are equally prioritized is preserved.
+fn schedule_rules(rules: &mut [GroupedRule], analysis: &Analysis) {
+    if analysis.options.rule_schedule == RuleSchedule::Fixed {
+        return;
+    }
+    let board = &analysis.board;
+
+    // many missing pieces make captures the main source of information
+    let nb_missing = 32 - board.combined().popcnt();
+    let captures_first = nb_missing >= 8;
+
+    // pawns blocked by an enemy pawn constrain the mobility of the rest
+    let white_pawns = board.pieces(Piece::Pawn) & board.color_combined(Color::White);
+    let black_pawns = board.pieces(Piece::Pawn) & board.color_combined(Color::Black);
+    let nb_locked_pawns = (BitBoard::new(white_pawns.0 << 8) & black_pawns).popcnt();
+    let mobility_first = nb_locked_pawns >= 2;
+
+    let priority = |(group, _): &GroupedRule| match group {
+        RuleGroup::Foundational => 0,
+        RuleGroup::Captures if captures_first => 1,
+        RuleGroup::Mobility if mobility_first => 1,
+        _ => 2,
+    };
+    rules.sort_by_key(priority);
+}
+
+/// Applies all the rules until no more progress can be made or the legality of
+/// the position has been determined.
+pub(crate) fn saturate(analysis: &mut Analysis) {
+    let mut rules = init_rules(&analysis.options);
+    schedule_rules(&mut rules, analysis);
+    saturate_with(analysis, &mut rules);
+}
+
+/// Applies the given rules until none of them makes progress or the legality
+/// of the position has been determined.
+fn saturate_with(analysis: &mut Analysis, rules: &mut [GroupedRule]) {
+    loop {
+        let mut progress = false;
+        for (_, rule) in rules.iter_mut() {
+            if rule.is_applicable(analysis) && analysis.result.is_none() {
+                if analysis.options.trace_edge_removals {
+                    analysis.set_current_rule(rule.name());
+                }
+                rule.update(analysis);
+                progress |= rule.apply(analysis);
+                analysis.nb_rule_applications += 1;
+                if analysis.result == Some(Illegal) && analysis.illegality_reason.is_none() {
+                    let class = analysis.illegality_class.unwrap_or(IllegalityClass::Other);
+                    analysis.illegality_reason =
+                        Some(IllegalityReason::Rule(rule.name().to_string(), class));
+                }
+            }
+        }
+        if !progress || analysis.result.is_some() {
+            break;
+        }
+    }
+    #[cfg(feature = "invariants")]
+    crate::analysis::verify_invariants(analysis);
 }
 
 /// Analyzes the legality of the position using all the existing rules.
 /// Returns a report containing all the information derived about the
-/// position.
+/// position. The positions discarded by [`static_screen`] are declared illegal
+/// right away, without applying any rule.
 /// ```
 /// use chess::{Board, Square};
 /// use sherlock::{analyze, RetractableBoard};
@@ -42,59 +248,596 @@ fn init_rules() -> Vec<Box<dyn Rule>> {
 /// assert_eq!(analysis.is_steady(Square::B1), false);
 /// ```
 pub fn analyze(board: &RetractableBoard) -> Analysis {
-    let mut rules = init_rules();
+    analyze_with_options(board, &AnalysisOptions::default())
+}
+
+/// Like [`analyze`], but under the assumptions specified by the given options.
+/// ```
+/// use chess::Square;
+/// use sherlock::{analyze_with_options, AnalysisOptions, RetractableBoard};
+///
+/// let options = AnalysisOptions {
+///     assume_no_promoted_pieces: true,
+///     ..Default::default()
+/// };
+/// let analysis = analyze_with_options(&RetractableBoard::default(), &options);
+/// assert_eq!(analysis.is_steady(Square::D1), true);
+/// ```
+pub fn analyze_with_options(board: &RetractableBoard, options: &AnalysisOptions) -> Analysis {
     let mut analysis = Analysis::new(board);
-    loop {
-        let mut progress = false;
-        for rule in rules.iter_mut() {
-            if rule.is_applicable(&analysis) && analysis.result.is_none() {
-                rule.update(&analysis);
-                progress |= rule.apply(&mut analysis);
-            }
+    apply_options(&mut analysis, options);
+    if !screen_out(&mut analysis) {
+        saturate(&mut analysis);
+    }
+    analysis.cache = None;
+    analysis
+}
+
+/// Declares the analyzed position illegal if it is discarded by
+/// [`static_screen`], in which case it returns `true` and no rule needs to be
+/// applied.
+fn screen_out(analysis: &mut Analysis) -> bool {
+    if analysis.options.variant != Variant::Orthodox {
+        return false;
+    }
+    match static_screen(&analysis.board) {
+        Some(class) => {
+            analysis.set_illegal(class);
+            analysis.illegality_reason = Some(IllegalityReason::StaticScreen(class));
+            true
         }
-        if !progress || analysis.result.is_some() {
-            break;
+        None => false,
+    }
+}
+
+/// Injects the constraints implied by the given options into the analysis.
+fn apply_options(analysis: &mut Analysis, options: &AnalysisOptions) {
+    analysis.options = *options;
+    if options.trace_edge_removals {
+        analysis.enable_edge_journal();
+    }
+    for color in ALL_COLORS {
+        if options.assume_no_promoted_pieces || options.max_promotions[color.to_index()] == Some(0)
+        {
+            let backrank_origins =
+                COLOR_ORIGINS[color.to_index()] & get_rank(color.to_my_backrank());
+            let pawns = analysis.board.pieces(Piece::Pawn);
+            for square in analysis.board.color_combined(color) & !pawns {
+                analysis.update_origins(square, backrank_origins);
+            }
         }
     }
+}
+
+/// Like [`analyze`], but reusing (and extending) the tables memoized in the
+/// given cache, which can be shared across calls. This is useful when
+/// analyzing many positions with the same pawn structure, steady pieces and
+/// castling rights.
+pub fn analyze_with_cache(board: &RetractableBoard, cache: &mut AnalysisCache) -> Analysis {
+    analyze_with_options_and_cache(board, &AnalysisOptions::default(), cache)
+}
+
+/// Like [`analyze_with_cache`], under the assumptions of the given options.
+fn analyze_with_options_and_cache(
+    board: &RetractableBoard,
+    options: &AnalysisOptions,
+    cache: &mut AnalysisCache,
+) -> Analysis {
+    let mut analysis = Analysis::new(board);
+    apply_options(&mut analysis, options);
+    if screen_out(&mut analysis) {
+        return analysis;
+    }
+    analysis.cache = Some(std::mem::take(cache));
+    saturate(&mut analysis);
+    if let Some(updated_cache) = analysis.cache.take() {
+        *cache = updated_cache;
+    }
     analysis
 }
 
 /// If the position is illegal, it returns `false`. Otherwise, if the position
 /// is [limited in retractions](RetractionGen::is_limited_in_retractions), it
 /// retracts it in all possible ways and recurses.
-fn is_retractable(table: &mut HashMap<RetractableBoard, bool>, board: &RetractableBoard) -> bool {
-    if let Some(b) = table.get(board) {
-        return *b;
+///
+/// The given options must hold on every position of the retraction search.
+///
+/// If a shared table is provided, the positions proven to be retractable are
+/// published to it (and looked up in it), and the search is aborted as soon as
+/// the shared table is marked as done.
+///
+/// If a node budget is provided, the positions found once the budget has been
+/// exhausted are not analyzed, but recorded in the frontier of the budget.
+fn is_retractable(
+    search: &mut SearchState,
+    cache: &mut AnalysisCache,
+    options: &AnalysisOptions,
+    board: &RetractableBoard,
+    candidates: &UncapturedCandidates,
+    shared: Option<&SharedTable>,
+    mut budget: Option<&mut NodeBudget>,
+) -> bool {
+    // the convention only concerns the en passant square given in the diagram,
+    // so it is settled at the root of the search
+    if let Some(convention) = options.convention {
+        let options = AnalysisOptions {
+            convention: None,
+            ..*options
+        };
+        // under the Codex, the double push must be proved to be the last move
+        if has_en_passant(board) && convention == RetroConvention::Codex {
+            let mut without_en_passant = *board;
+            without_en_passant.unset_ep();
+            if is_retractable(
+                search,
+                cache,
+                &options,
+                &without_en_passant,
+                candidates,
+                shared,
+                budget.as_deref_mut(),
+            ) {
+                return false;
+            }
+        }
+        // a missing en passant square does not rule out a double push
+        let mut board = *board;
+        board.set_uncertain_ep();
+        return is_retractable(search, cache, &options, &board, candidates, shared, budget);
+    }
+
+    let key = PositionKey::new(board).with_promotions(options.max_promotions);
+    match search.table.get(&key) {
+        Some(SearchStatus::Retractable) => return true,
+        Some(SearchStatus::Unretractable) => return false,
+        // the position is being explored, so we have found a cycle, which
+        // cannot help retracting it
+        Some(SearchStatus::InProgress(index)) => {
+            search.lowest_cycle = search.lowest_cycle.min(*index);
+            return false;
+        }
+        None => (),
     };
+    if let Some(shared) = shared {
+        if shared.is_done() {
+            return false;
+        }
+        if shared.contains(board) {
+            return true;
+        }
+    }
+    // retractions may uncapture into impossible material (e.g. 9 queens of the
+    // same color), such positions are pruned before being analyzed
+    if illegal_material(board) {
+        return false;
+    }
+    if let Some(budget) = budget.as_deref_mut() {
+        if budget.remaining == 0 {
+            budget.frontier.push(*board);
+            return false;
+        }
+        budget.remaining -= 1;
+    }
+    search.nb_nodes += 1;
 
-    let analysis = analyze(board);
+    let analysis = match search.root.take() {
+        Some(root) if PositionKey::new(&root.board) == PositionKey::new(board) => root,
+        _ => analyze_with_options_and_cache(board, options, cache),
+    };
     if analysis.result == Some(Illegal) {
         return false;
-    } else if !RetractionGen::is_limited_in_retractions(board) {
-        return true;
     }
+    let constraint = match RetractionGen::limiting_constraint(board) {
+        Some(constraint) => constraint,
+        None => return true,
+    };
 
-    // add the position to the table as "false" to avoid infinite-loops, we will
-    // correct this when the analysis is over
-    table.insert(*board, false);
+    // mark the position as in progress to detect cycles, we will settle it when
+    // the analysis is over
+    search.nb_indexed += 1;
+    let index = search.nb_indexed;
+    search.table.insert(key, SearchStatus::InProgress(index));
+    let outer_cycle = std::mem::replace(&mut search.lowest_cycle, usize::MAX);
+    let pending_start = search.pending.len();
     let mut res = false;
 
-    let mut retractions = RetractionGen::new_legal(board);
+    let mut retractions = RetractionGen::new_legal_with_candidates(board, candidates);
     retractions.refine_iterator(&analysis);
-    for r in retractions {
-        let new_board = board.make_retraction_new(r);
-        if is_retractable(table, &new_board) {
+    let successors = search.push_successors(board, candidates, constraint, retractions);
+    for i in successors.clone() {
+        let (new_board, new_candidates) = search.arena[i];
+        let new_options = match options_after_retraction(options, board, &new_board) {
+            Some(new_options) => new_options,
+            None => continue,
+        };
+        if is_retractable(
+            search,
+            cache,
+            &new_options,
+            &new_board,
+            &new_candidates,
+            shared,
+            budget.as_deref_mut(),
+        ) {
             res = true;
             break;
         }
     }
+    search.arena.truncate(successors.start);
+
+    // a null move can be retracted if it did not leave the passing player in
+    // check and did not create an en-passant opportunity
+    if !res && options.allow_null_moves && *board.checkers() == EMPTY && !has_en_passant(board) {
+        let mut new_board = *board;
+        new_board.flip();
+        res = is_retractable(
+            search,
+            cache,
+            options,
+            &new_board,
+            candidates,
+            shared,
+            budget.as_deref_mut(),
+        );
+    }
+
+    // a failure due to an exhausted budget may not be a failure with a larger
+    // one, so it is not settled
+    let cut = budget.is_some_and(|budget| !budget.frontier.is_empty());
 
     if res {
-        table.insert(*board, res);
+        search.lowest_cycle = outer_cycle;
+        search.table.insert(key, SearchStatus::Retractable);
+        // the failures that depended on this position are not valid anymore
+        for pending in search.pending.drain(pending_start..) {
+            search.table.remove(&pending);
+        }
+        if let Some(shared) = shared {
+            shared.insert(board);
+        }
+    } else if cut {
+        search.lowest_cycle = outer_cycle;
+        for pending in search.pending.drain(pending_start..) {
+            search.table.remove(&pending);
+        }
+        search.table.remove(&key);
+    } else if search.lowest_cycle < index {
+        // the failure may be due to a cycle through an ancestor that is still
+        // being explored, so the position is settled together with it
+        search.pending.push(key);
+        search.lowest_cycle = search.lowest_cycle.min(outer_cycle);
+    } else {
+        // all the failures in the subtree only depended on cycles through this
+        // position, which has failed too
+        search.lowest_cycle = outer_cycle;
+        for pending in search.pending.drain(pending_start..) {
+            search.table.insert(pending, SearchStatus::Unretractable);
+        }
+        search.table.insert(key, SearchStatus::Unretractable);
     }
     res
 }
 
+/// A compact key identifying a position in the table of a retraction search:
+/// its Zobrist hash, together with its occupancy, a signature of its material
+/// and its state (side to move, castling rights and en passant flag). Two
+/// different positions can only share a key if their hashes collide *and* they
+/// have the same occupancy and material, which is negligible in practice.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PositionKey {
+    hash: u64,
+    occupied: u64,
+    material: u64,
+    state: u64,
+}
+
+impl PositionKey {
+    fn new(board: &RetractableBoard) -> Self {
+        let mut material = 0;
+        for color in ALL_COLORS {
+            for piece in ALL_PIECES {
+                let count = (board.pieces(piece) & board.color_combined(color)).popcnt() as u64;
+                material = (material << 4) | count.min(15);
+            }
+        }
+        let en_passant = match board.en_passant() {
+            EnPassantFlag::None => 0,
+            EnPassantFlag::Any => 1,
+            EnPassantFlag::Some(square) => 2 + square.to_index() as u64,
+        };
+        let state = board.side_to_move().to_index() as u64
+            | (board.castle_rights(Color::White).to_index() as u64) << 1
+            | (board.castle_rights(Color::Black).to_index() as u64) << 3
+            | en_passant << 5;
+        PositionKey {
+            hash: board.get_hash(),
+            occupied: board.combined().0,
+            material,
+            state,
+        }
+    }
+
+    /// The key of the position under the given bounds on the number of
+    /// promotions (see [`AnalysisOptions::max_promotions`]), on which its
+    /// retractability depends. Unbounded promotions leave the key unchanged.
+    fn with_promotions(mut self, max_promotions: [Option<u8>; NUM_COLORS]) -> Self {
+        for (i, bound) in max_promotions.iter().enumerate() {
+            let bound = bound.map_or(0, |n| n as u64 + 1);
+            self.state |= bound << (32 + 9 * i);
+        }
+        self
+    }
+}
+
+/// The options under which the position `new_board`, obtained by a
+/// retraction on `board`, must be analyzed: an unpromotion uses up one of the
+/// promotions allowed to the retracting player. Returns `None` if the
+/// retracting player had no promotions left.
+fn options_after_retraction(
+    options: &AnalysisOptions,
+    board: &RetractableBoard,
+    new_board: &RetractableBoard,
+) -> Option<AnalysisOptions> {
+    let color = new_board.side_to_move();
+    let nb_pawns =
+        |b: &RetractableBoard| (b.pieces(Piece::Pawn) & b.color_combined(color)).popcnt();
+    let max_promotions = options.max_promotions[color.to_index()];
+    if max_promotions.is_none() || nb_pawns(new_board) <= nb_pawns(board) {
+        return Some(*options);
+    }
+    let mut new_options = *options;
+    new_options.max_promotions[color.to_index()] = Some(max_promotions?.checked_sub(1)?);
+    Some(new_options)
+}
+
+/// The status of a position in the table of a retraction search.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SearchStatus {
+    /// The position is being explored (or its failure depends on a position
+    /// that is still being explored), with the given index, in order of
+    /// exploration.
+    InProgress(usize),
+    /// The position has been proven to be retractable.
+    Retractable,
+    /// The position has been proven not to be retractable.
+    Unretractable,
+}
+
+/// A node of the retraction search: a position with its uncaptured candidates.
+type SearchNode = (RetractableBoard, UncapturedCandidates);
+
+/// The state of a retraction search: the table of the positions that have
+/// been settled (or are being explored) and an arena where the successors of
+/// the positions being explored are stored, as a stack. The successors of a
+/// position are pushed on top of those of its ancestors and popped once the
+/// position has been explored, so no memory is allocated per node.
+///
+/// A position is only settled as unretractable once its failure does not
+/// depend on a cycle through a position that is still being explored. As in
+/// Tarjan's algorithm for strongly connected components, the positions whose
+/// failure depends on such a cycle are kept in progress until the lowest
+/// position of the cycle is settled, and they are settled together with it.
+struct SearchState {
+    table: HashMap<PositionKey, SearchStatus>,
+    arena: Vec<SearchNode>,
+    /// The number of positions marked as in progress so far.
+    nb_indexed: usize,
+    /// The lowest index of the in-progress positions found through cycles in
+    /// the subtree being explored.
+    lowest_cycle: usize,
+    /// The positions whose failure depends on a cycle through a position that
+    /// is still being explored, in order of exploration.
+    pending: Vec<PositionKey>,
+    nb_nodes: usize,
+    arena_peak: usize,
+    /// An analysis of the root position computed beforehand, which is used
+    /// instead of analyzing the root again.
+    root: Option<Analysis>,
+}
+
+impl SearchState {
+    fn new() -> Self {
+        SearchState {
+            table: HashMap::new(),
+            arena: vec![],
+            nb_indexed: 0,
+            lowest_cycle: usize::MAX,
+            pending: vec![],
+            nb_nodes: 0,
+            arena_peak: 0,
+            root: None,
+        }
+    }
+
+    /// Pushes the boards resulting from the given retractions (together with
+    /// their uncaptured candidates) on the arena, in the order in which they
+    /// should be explored. Returns the range of the arena where they are.
+    ///
+    /// The search on a board only continues while its retractions are limited
+    /// by the given constraint, e.g. only a cornered rook can retract. Only
+    /// the retractions that touch the squares of the constraint can relax it
+    /// (see [`RetractionConstraint::squares`]), so they are explored first.
+    /// The rest are not pruned: they may still lead to a legal position, just
+    /// through a longer retraction sequence.
+    fn push_successors(
+        &mut self,
+        board: &RetractableBoard,
+        candidates: &UncapturedCandidates,
+        constraint: RetractionConstraint,
+        retractions: RetractionGen,
+    ) -> Range<usize> {
+        let start = self.arena.len();
+        let squares = constraint.squares(board);
+        let mut touching = start;
+        for r in retractions {
+            let new_board = board.make_retraction_new(r);
+            let mut new_candidates = *candidates;
+            new_candidates.update(&new_board, &r);
+            self.arena.push((new_board, new_candidates));
+            if RetractionConstraint::is_touched_by(squares, &r) {
+                let last = self.arena.len() - 1;
+                self.arena.swap(touching, last);
+                touching += 1;
+            }
+        }
+        self.arena_peak = max(self.arena_peak, self.arena.len());
+        start..self.arena.len()
+    }
+
+    /// A search state whose table already settles the given anchors as
+    /// retractable.
+    fn with_anchors(anchors: &AnchorSet) -> Self {
+        let mut search = SearchState::new();
+        search.table.extend(
+            anchors
+                .keys
+                .iter()
+                .map(|key| (*key, SearchStatus::Retractable)),
+        );
+        search
+    }
+
+    fn stats(&self) -> SearchStats {
+        SearchStats {
+            nb_nodes: self.nb_nodes,
+            table_entries: self.table.len(),
+            table_bytes: self.table.capacity() * size_of::<(PositionKey, SearchStatus)>(),
+            arena_peak_nodes: self.arena_peak,
+            arena_peak_bytes: self.arena_peak * size_of::<SearchNode>(),
+        }
+    }
+}
+
+/// Statistics about a retraction search, see [`is_legal_with_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchStats {
+    /// The number of positions analyzed by the search.
+    pub nb_nodes: usize,
+    /// The number of positions stored in the table of the search.
+    pub table_entries: usize,
+    /// The (approximate) memory allocated for the table, in bytes.
+    pub table_bytes: usize,
+    /// The maximum number of positions simultaneously stored in the arena of
+    /// the search.
+    pub arena_peak_nodes: usize,
+    /// The memory used by the arena at its peak, in bytes.
+    pub arena_peak_bytes: usize,
+}
+
+/// A limit on the number of positions analyzed by a retraction search.
+struct NodeBudget {
+    remaining: usize,
+    /// The positions that were not analyzed due to the exhaustion of the
+    /// budget.
+    frontier: Vec<RetractableBoard>,
+}
+
+/// A table of the positions proven to be retractable, shared among threads.
+/// It is split into shards (each one behind its own lock) in order to reduce
+/// contention.
+struct SharedTable {
+    shards: Vec<Mutex<HashSet<RetractableBoard>>>,
+    done: AtomicBool,
+}
+
+impl SharedTable {
+    fn new(nb_shards: usize) -> Self {
+        SharedTable {
+            shards: (0..nb_shards).map(|_| Mutex::new(HashSet::new())).collect(),
+            done: AtomicBool::new(false),
+        }
+    }
+
+    fn shard(&self, board: &RetractableBoard) -> &Mutex<HashSet<RetractableBoard>> {
+        let mut hasher = DefaultHasher::new();
+        board.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    fn contains(&self, board: &RetractableBoard) -> bool {
+        self.shard(board).lock().unwrap().contains(board)
+    }
+
+    fn insert(&self, board: &RetractableBoard) {
+        self.shard(board).lock().unwrap().insert(*board);
+    }
+
+    /// Marks the search as done (the root position has been proven to be
+    /// retractable).
+    fn finish(&self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+
+    fn is_done(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+    }
+}
+
+/// The number of positions analyzed by a thread of a parallel retraction
+/// search (see [`is_legal_parallel`]) before it gives the positions it has not
+/// explored yet back to the other threads.
+const NODES_PER_TASK: usize = 64;
+
+/// The positions waiting to be explored by the threads of a parallel
+/// retraction search.
+struct WorkQueue {
+    state: Mutex<WorkState>,
+    changed: Condvar,
+}
+
+struct WorkState {
+    pending: Vec<RetractableBoard>,
+    /// All the positions ever queued, which are not queued again.
+    queued: HashSet<RetractableBoard>,
+    /// The number of threads exploring a position, which may queue more.
+    nb_busy: usize,
+}
+
+impl WorkQueue {
+    fn new(queued: HashSet<RetractableBoard>, pending: Vec<RetractableBoard>) -> Self {
+        WorkQueue {
+            state: Mutex::new(WorkState {
+                pending,
+                queued,
+                nb_busy: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Takes a position to explore, waiting for the other threads if they may
+    /// still queue positions. Returns `None` once the search is over.
+    fn pop(&self, shared: &SharedTable) -> Option<RetractableBoard> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if shared.is_done() {
+                return None;
+            }
+            if let Some(board) = state.pending.pop() {
+                state.nb_busy += 1;
+                return Some(board);
+            }
+            if state.nb_busy == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Releases the position taken by the calling thread, queueing the given
+    /// positions found while exploring it.
+    fn push(&self, boards: Vec<RetractableBoard>) {
+        let mut state = self.state.lock().unwrap();
+        for board in boards {
+            if state.queued.insert(board) {
+                state.pending.push(board);
+            }
+        }
+        state.nb_busy -= 1;
+        self.changed.notify_all();
+    }
+}
+
 /// Checks whether the given `Board` is *legal*, i.e. reachable from the
 /// starting chess position via a sequence of legal moves.
 ///
@@ -110,7 +853,1435 @@ fn is_retractable(table: &mut HashMap<RetractableBoard, bool>, board: &Retractab
 /// let board = Board::default();
 /// assert!(is_legal(&board));
 /// ```
+///
+/// With the `global-cache` feature, the verdicts are memoized in a bounded
+/// process-wide cache, so repeated queries about the same positions are free
+/// (see `clear_global_cache`).
 pub fn is_legal(board: &Board) -> bool {
-    let mut table = HashMap::<RetractableBoard, bool>::new();
-    is_retractable(&mut table, &(*board).into())
+    let compute = || is_legal_retractable(&(*board).into());
+    #[cfg(feature = "global-cache")]
+    let compute = || crate::cache::global_verdict(board, compute);
+    compute()
+}
+
+/// Like [`is_legal`], but it reports the mechanism that proved the given
+/// position illegal, or `None` if the position is (probably) legal. This is
+/// useful for triaging unexpected verdicts.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::{illegality_reason, IllegalityClass, IllegalityReason};
+///
+/// assert_eq!(illegality_reason(&Board::default()), None);
+///
+/// // White cannot have 9 pawns
+/// let board = Board::from_str("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -")?;
+/// assert_eq!(
+///     illegality_reason(&board),
+///     Some(IllegalityReason::Rule(
+///         "MaterialRule".to_string(),
+///         IllegalityClass::Material
+///     ))
+/// );
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn illegality_reason(board: &Board) -> Option<IllegalityReason> {
+    let mut analysis = analyze(&(*board).into());
+    match analysis.legality() {
+        Legality::Legal => None,
+        Legality::Illegal => analysis.illegality_reason,
+    }
+}
+
+/// A fast, weaker version of [`is_legal`]: the position is analyzed statically
+/// and, if its retractions are limited (e.g. a player is in check), it is
+/// checked that at least one of its retractions leads to a position that is
+/// not statically illegal. No deeper retraction search is performed.
+///
+/// The guarantee is the same as that of [`is_legal`] in one direction: if the
+/// output is `false`, the position is *definitely illegal*. On the other hand,
+/// it may output `true` on more illegal positions than [`is_legal`], namely
+/// those whose illegality can only be proven by retracting two or more plies.
+/// See `examples/probably_legal.rs` for a comparison of both functions.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::is_probably_legal;
+///
+/// assert!(is_probably_legal(&Board::default()));
+///
+/// // Black cannot be in check by two knights
+/// let board = Board::from_str("rnbqkbnr/pppppppp/3N1N2/8/8/8/PPPPPPPP/R1BQKB1R b KQkq -")?;
+/// assert!(!is_probably_legal(&board));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn is_probably_legal(board: &Board) -> bool {
+    let board: RetractableBoard = (*board).into();
+    let mut cache = AnalysisCache::new();
+    let analysis = analyze_with_cache(&board, &mut cache);
+    if analysis.result == Some(Illegal) {
+        return false;
+    } else if !RetractionGen::is_limited_in_retractions(&board) {
+        return true;
+    }
+    let mut retractions =
+        RetractionGen::new_legal_with_candidates(&board, &UncapturedCandidates::new(&board));
+    retractions.refine_iterator(&analysis);
+    retractions.any(|r| {
+        let new_board = board.make_retraction_new(r);
+        !illegal_material(&new_board)
+            && analyze_with_cache(&new_board, &mut cache).result != Some(Illegal)
+    })
+}
+
+/// Like [`is_legal`], but on a [`RetractableBoard`], e.g. a position reached
+/// after some retractions, where the en passant information may be uncertain
+/// (a double pawn push may or may not have been the last move).
+///
+/// ```
+/// use sherlock::{is_legal_retractable, RetractableBoard};
+///
+/// // after 1. e4, whether or not the en passant square is recorded
+/// let board =
+///     RetractableBoard::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -")
+///         .unwrap();
+/// assert!(is_legal_retractable(&board));
+///
+/// // White has no last move, the king and the rook have never moved
+/// let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/8/4K2R b K -").unwrap();
+/// assert!(!is_legal_retractable(&board));
+/// ```
+pub fn is_legal_retractable(board: &RetractableBoard) -> bool {
+    let mut search = SearchState::new();
+    let mut cache = AnalysisCache::new();
+    is_retractable(
+        &mut search,
+        &mut cache,
+        &AnalysisOptions::default(),
+        board,
+        &UncapturedCandidates::new(board),
+        None,
+        None,
+    )
+}
+
+/// Like [`is_legal`], but it also reports statistics about the retraction
+/// search, such as the number of analyzed positions and its memory usage.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::is_legal_with_stats;
+///
+/// // White is in check, the last move must be unwound
+/// let board = Board::from_str("4k3/8/8/8/8/8/3p4/4K3 w - -")?;
+/// let (legal, stats) = is_legal_with_stats(&board);
+/// assert!(legal);
+/// assert!(stats.nb_nodes > 1);
+/// assert!(stats.table_entries > 0);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn is_legal_with_stats(board: &Board) -> (bool, SearchStats) {
+    let mut search = SearchState::new();
+    let mut cache = AnalysisCache::new();
+    let board = (*board).into();
+    let legal = is_retractable(
+        &mut search,
+        &mut cache,
+        &AnalysisOptions::default(),
+        &board,
+        &UncapturedCandidates::new(&board),
+        None,
+        None,
+    );
+    (legal, search.stats())
+}
+
+/// Checks the legality of the piece placement of the given board with each
+/// player to move, returning the verdicts with White to move and with Black to
+/// move, respectively. Many retro problems hinge on a position being legal
+/// with only one side to move.
+///
+/// The castling rights are preserved in both cases, whereas the en passant
+/// square is only preserved for the side to move of the given board. The
+/// root of both searches is analyzed once with [`analyze_both_turns`], so the
+/// facts that do not depend on the turn are only derived once.
+///
+/// The guarantees of each verdict are those of [`is_legal`].
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::{legality_both_turns, Legality};
+///
+/// // Black cannot be to move in the starting position
+/// assert_eq!(
+///     legality_both_turns(&Board::default()),
+///     (Legality::Legal, Legality::Illegal)
+/// );
+///
+/// // the side not to move cannot be in check
+/// let board = Board::from_str("4k3/8/8/8/8/8/8/r3K3 w - -")?;
+/// assert_eq!(
+///     legality_both_turns(&board),
+///     (Legality::Legal, Legality::Illegal)
+/// );
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn legality_both_turns(board: &Board) -> (Legality, Legality) {
+    if illegal_material(&(*board).into()) {
+        return (Illegal, Illegal);
+    }
+    let mut cache = AnalysisCache::new();
+    let [white, black] = analyze_both_turns(board).map(|analysis| {
+        let analysis = match analysis {
+            Some(analysis) if analysis.result != Some(Illegal) => analysis,
+            _ => return Illegal,
+        };
+        let board = analysis.board;
+        let mut search = SearchState::new();
+        search.root = Some(analysis);
+        if is_retractable(
+            &mut search,
+            &mut cache,
+            &AnalysisOptions::default(),
+            &board,
+            &UncapturedCandidates::new(&board),
+            None,
+            None,
+        ) {
+            Legality::Legal
+        } else {
+            Illegal
+        }
+    });
+    (white, black)
+}
+
+/// Analyzes (see [`analyze`]) the piece placement of the given board with each
+/// player to move, returning the analyses with White to move and with Black
+/// to move, respectively (`None` if the board is invalid with that player to
+/// move, e.g. if the other player would be in check). The turns are set as in
+/// [`legality_both_turns`].
+///
+/// The facts that do not depend on the side to move (origins, routes,
+/// captures...) are derived once and shared by both analyses, which only
+/// differ in the conclusions of the turn-dependent rules (e.g. parity or the
+/// pieces that may have performed the last move).
+///
+/// ```
+/// use chess::{Board, Square};
+/// use sherlock::{analyze_both_turns, Legality};
+///
+/// let [white, black] = analyze_both_turns(&Board::default());
+/// let (white, black) = (white.unwrap(), black.unwrap());
+/// assert_eq!(white.origins(Square::E2), black.origins(Square::E2));
+/// assert_eq!(white.result(), None);
+/// assert_eq!(black.result(), Some(Legality::Illegal));
+/// ```
+pub fn analyze_both_turns(board: &Board) -> [Option<Analysis>; NUM_COLORS] {
+    let boards = ALL_COLORS.map(|side_to_move| board_with_turn(board, side_to_move));
+    let core_board = match boards.iter().flatten().next() {
+        Some(core_board) => core_board,
+        None => return [None, None],
+    };
+    let options = AnalysisOptions::default();
+    let mut core = Analysis::new(core_board);
+    apply_options(&mut core, &options);
+    let (mut core_rules, _) = init_rule_layers(&options);
+    schedule_rules(&mut core_rules, &core);
+    saturate_with(&mut core, &mut core_rules);
+    boards.map(|board| {
+        let mut analysis = core.with_turn_of(&board?);
+        if !screen_out(&mut analysis) {
+            let (_, mut turn_rules) = init_rule_layers(&options);
+            saturate_with(&mut analysis, &mut turn_rules);
+        }
+        Some(analysis)
+    })
+}
+
+/// The given board with the given player to move. The castling rights are
+/// preserved, whereas the en passant square is only preserved if the side to
+/// move does not change. Returns `None` if the resulting board is invalid.
+fn board_with_turn(board: &Board, side_to_move: Color) -> Option<RetractableBoard> {
+    let mut builder = BoardBuilder::from(board);
+    if side_to_move != board.side_to_move() {
+        builder.side_to_move(side_to_move).en_passant(None);
+    }
+    Board::try_from(&builder).ok().map(|board| board.into())
+}
+
+/// Like [`is_legal`], but the retraction search is run on the given number of
+/// threads. The positions to explore are kept in a queue shared by all the
+/// threads: every thread explores a position from the queue for a limited
+/// number of nodes and queues back the positions it could not get to, so even
+/// a single expensive branch is spread among all the threads. The positions
+/// proven to be retractable are shared among all of them too.
+///
+/// This is useful for positions whose retraction search is expensive. The
+/// output may differ from [`is_legal`] only in positions that escape the
+/// current logic (where the order of exploration can make a difference).
+///
+/// ```
+/// use chess::Board;
+/// use sherlock::is_legal_parallel;
+///
+/// assert!(is_legal_parallel(&Board::default(), 4));
+/// ```
+pub fn is_legal_parallel(board: &Board, nb_threads: usize) -> bool {
+    search_in_parallel(&(*board).into(), nb_threads, NODES_PER_TASK)
+}
+
+/// The retraction search of [`is_legal_parallel`], where every thread explores
+/// at most `nodes_per_task` positions before queueing back the rest.
+fn search_in_parallel(board: &RetractableBoard, nb_threads: usize, nodes_per_task: usize) -> bool {
+    let board = *board;
+    let options = AnalysisOptions::default();
+    let mut cache = AnalysisCache::new();
+    let analysis = analyze_with_options_and_cache(&board, &options, &mut cache);
+    if analysis.result == Some(Illegal) {
+        return false;
+    } else if !RetractionGen::is_limited_in_retractions(&board) {
+        return true;
+    }
+
+    let mut retractions =
+        RetractionGen::new_legal_with_candidates(&board, &UncapturedCandidates::new(&board));
+    retractions.refine_iterator(&analysis);
+    let pending: Vec<_> = retractions.map(|r| board.make_retraction_new(r)).collect();
+    let queued = pending.iter().chain([&board]).copied().collect();
+    let queue = WorkQueue::new(queued, pending);
+
+    let nb_threads = nb_threads.max(1);
+    let shared = SharedTable::new(4 * nb_threads);
+    thread::scope(|scope| {
+        for _ in 0..nb_threads {
+            scope.spawn(|| {
+                let mut search = SearchState::new();
+                // looping back to the root position cannot help retracting it
+                search
+                    .table
+                    .insert(PositionKey::new(&board), SearchStatus::Unretractable);
+                let mut cache = AnalysisCache::new();
+                while let Some(position) = queue.pop(&shared) {
+                    let mut budget = NodeBudget {
+                        remaining: nodes_per_task,
+                        frontier: vec![],
+                    };
+                    if is_retractable(
+                        &mut search,
+                        &mut cache,
+                        &options,
+                        &position,
+                        &UncapturedCandidates::new(&position),
+                        Some(&shared),
+                        Some(&mut budget),
+                    ) {
+                        shared.finish();
+                    }
+                    queue.push(budget.frontier);
+                }
+            });
+        }
+    });
+    shared.is_done()
+}
+
+/// The verdict of a retraction search with a limited budget.
+#[derive(Debug)]
+pub enum BudgetedVerdict {
+    /// The position is (probably) legal, see [`is_legal`].
+    Legal,
+    /// The position is definitely illegal.
+    Illegal,
+    /// The budget was exhausted before a verdict could be reached.
+    Undetermined(PartialVerdict),
+}
+
+/// The information gathered by a retraction search whose budget was exhausted.
+#[derive(Debug)]
+pub struct PartialVerdict {
+    /// The positions of the retraction search that were not unwound. The
+    /// analyzed position is legal if any of them is.
+    pub frontier: Vec<RetractableBoard>,
+    /// The facts derived about the analyzed position.
+    pub facts: Vec<Fact>,
+}
+
+/// Like [`is_legal`], but the retraction search analyzes at most `max_nodes`
+/// positions. If the budget is exhausted before reaching a verdict, the
+/// positions that could not be unwound are reported, so that the search can be
+/// resumed on them.
+///
+/// ```
+/// use chess::Board;
+/// use sherlock::{is_legal_with_budget, BudgetedVerdict, RetractableBoard};
+///
+/// let board = Board::default();
+/// assert!(matches!(
+///     is_legal_with_budget(&board, 1),
+///     BudgetedVerdict::Legal
+/// ));
+///
+/// match is_legal_with_budget(&board, 0) {
+///     BudgetedVerdict::Undetermined(partial) => {
+///         assert_eq!(partial.frontier, vec![RetractableBoard::from(board)])
+///     }
+///     _ => panic!("the budget should have been exhausted"),
+/// }
+/// ```
+pub fn is_legal_with_budget(board: &Board, max_nodes: usize) -> BudgetedVerdict {
+    let mut search = SearchState::new();
+    let mut cache = AnalysisCache::new();
+    let mut budget = NodeBudget {
+        remaining: max_nodes,
+        frontier: vec![],
+    };
+    let board = (*board).into();
+    let retractable = is_retractable(
+        &mut search,
+        &mut cache,
+        &AnalysisOptions::default(),
+        &board,
+        &UncapturedCandidates::new(&board),
+        None,
+        Some(&mut budget),
+    );
+    if retractable {
+        BudgetedVerdict::Legal
+    } else if budget.frontier.is_empty() {
+        BudgetedVerdict::Illegal
+    } else {
+        BudgetedVerdict::Undetermined(PartialVerdict {
+            frontier: budget.frontier,
+            facts: analyze_with_cache(&board, &mut cache).facts().collect(),
+        })
+    }
+}
+
+/// Tells whether the en-passant flag of the given board is set.
+fn has_en_passant(board: &RetractableBoard) -> bool {
+    matches!(board.en_passant(), EnPassantFlag::Some(_))
+}
+
+/// The retractions that may correspond to the last move of a game leading to
+/// the given position, i.e., the retractions leading to a position that is not
+/// proven to be illegal.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Square};
+/// use sherlock::last_moves;
+///
+/// // the last move was d7-d5
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6")?;
+/// let retractions = last_moves(&board);
+/// assert_eq!(retractions.len(), 1);
+/// assert_eq!(retractions[0].source(), Square::D5);
+/// assert_eq!(retractions[0].target(), Square::D7);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn last_moves(board: &Board) -> Vec<ChessRetraction> {
+    let mut search = SearchState::new();
+    let mut cache = AnalysisCache::new();
+    let board: RetractableBoard = (*board).into();
+    let candidates = UncapturedCandidates::new(&board);
+    RetractionGen::new_legal_with_candidates(&board, &candidates)
+        .filter(|r| {
+            let new_board = board.make_retraction_new(*r);
+            let mut new_candidates = candidates;
+            new_candidates.update(&new_board, r);
+            is_retractable(
+                &mut search,
+                &mut cache,
+                &AnalysisOptions::default(),
+                &new_board,
+                &new_candidates,
+                None,
+                None,
+            )
+        })
+        .collect()
+}
+
+/// The predecessors of the given position that are not proven to be illegal,
+/// together with the retractions leading to them. Every predecessor is checked
+/// with a retraction search that analyzes at most `max` positions: with a
+/// larger budget, more illegal predecessors are discarded, whereas a budget of
+/// `0` keeps all the (pseudo-legal) predecessors.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Square};
+/// use sherlock::legal_predecessors;
+///
+/// // the last move was d7-d5
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6")?;
+/// let predecessors = legal_predecessors(&board, 1000);
+/// assert_eq!(predecessors.len(), 1);
+/// let (predecessor, retraction) = predecessors[0];
+/// assert_eq!(retraction.source(), Square::D5);
+/// assert_eq!(predecessor.piece_on(Square::D7), Some(chess::Piece::Pawn));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn legal_predecessors(board: &Board, max: usize) -> Vec<(RetractableBoard, ChessRetraction)> {
+    let mut cache = AnalysisCache::new();
+    let board: RetractableBoard = (*board).into();
+    let candidates = UncapturedCandidates::new(&board);
+    RetractionGen::new_legal_with_candidates(&board, &candidates)
+        .map(|r| (board.make_retraction_new(r), r))
+        .filter(|(new_board, r)| {
+            let mut new_candidates = candidates;
+            new_candidates.update(new_board, r);
+            // positions cut by the budget are not memoized across searches
+            let mut search = SearchState::new();
+            let mut budget = NodeBudget {
+                remaining: max,
+                frontier: vec![],
+            };
+            let retractable = is_retractable(
+                &mut search,
+                &mut cache,
+                &AnalysisOptions::default(),
+                new_board,
+                &new_candidates,
+                None,
+                Some(&mut budget),
+            );
+            retractable || !budget.frontier.is_empty()
+        })
+        .collect()
+}
+
+/// Solves the "release the position" stipulation: finds a shortest sequence
+/// of retractions that leads from the given position to a position that is
+/// not [limited in retractions](RetractionGen::is_limited_in_retractions),
+/// without going through statically illegal positions. The sequence is
+/// canonical: among the shortest ones, the first one in the order in which
+/// retractions are generated.
+///
+/// Returns `None` if the position cannot be released, which means that it is
+/// illegal. The retractions are displayed in retro notation, e.g. `a4b5` for a
+/// piece that moved from A4 to B5, or `a4xNb5` if it captured a knight.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Square};
+/// use sherlock::release_order;
+///
+/// // the position is not limited in retractions, it is already released
+/// assert_eq!(release_order(&Board::default()), Some(vec![]));
+///
+/// // Black is in check, so the checking bishop must be retracted first
+/// let board = Board::from_str("4k3/8/8/1B6/8/8/8/4K3 b - -")?;
+/// let order = release_order(&board).unwrap();
+/// assert_eq!(order.len(), 1);
+/// assert_eq!(order[0].source(), Square::B5);
+///
+/// // White cannot have 9 pawns
+/// let board = Board::from_str("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -")?;
+/// assert_eq!(release_order(&board), None);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn release_order(board: &Board) -> Option<Vec<ChessRetraction>> {
+    match release_search(board, None) {
+        BudgetedRelease::Released(order) => Some(order),
+        BudgetedRelease::Unreleasable | BudgetedRelease::Undetermined => None,
+    }
+}
+
+/// The outcome of a search for the release order of a position with a limited
+/// budget (see [`release_order_with_budget`]).
+#[derive(Debug, PartialEq, Eq)]
+pub enum BudgetedRelease {
+    /// The position is released by the given retractions, see
+    /// [`release_order`].
+    Released(Vec<ChessRetraction>),
+    /// The position cannot be released, so it is illegal.
+    Unreleasable,
+    /// The budget was exhausted before the position could be released.
+    Undetermined,
+}
+
+/// Like [`release_order`], but the search unwinds at most `max_nodes`
+/// positions that are limited in retractions. The search is breadth-first, so
+/// the number of positions grows exponentially with the length of the release
+/// order.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::{release_order_with_budget, BudgetedRelease};
+///
+/// // the starting position is released without unwinding anything
+/// let board = Board::default();
+/// assert_eq!(
+///     release_order_with_budget(&board, 0),
+///     BudgetedRelease::Released(vec![])
+/// );
+///
+/// // Black is in check, so the position must be unwound
+/// let board = Board::from_str("4k3/8/8/1B6/8/8/8/4K3 b - -")?;
+/// assert_eq!(
+///     release_order_with_budget(&board, 0),
+///     BudgetedRelease::Undetermined
+/// );
+/// assert!(matches!(
+///     release_order_with_budget(&board, 1),
+///     BudgetedRelease::Released(_)
+/// ));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn release_order_with_budget(board: &Board, max_nodes: usize) -> BudgetedRelease {
+    release_search(board, Some(max_nodes))
+}
+
+/// The breadth-first search of [`release_order`], which unwinds at most the
+/// given number of positions, if any.
+fn release_search(board: &Board, mut max_nodes: Option<usize>) -> BudgetedRelease {
+    let board: RetractableBoard = (*board).into();
+    let mut cache = AnalysisCache::new();
+    if analyze_with_cache(&board, &mut cache).result == Some(Illegal) {
+        return BudgetedRelease::Unreleasable;
+    }
+    // every node records its parent and the retraction that led to it
+    let mut nodes = vec![(board, None)];
+    let mut visited = HashSet::from([board]);
+    let mut queue = VecDeque::from([(0, UncapturedCandidates::new(&board))]);
+    while let Some((index, candidates)) = queue.pop_front() {
+        let board = nodes[index].0;
+        if !RetractionGen::is_limited_in_retractions(&board) {
+            let mut order = vec![];
+            let mut current = index;
+            while let Some((parent, retraction)) = nodes[current].1 {
+                order.push(retraction);
+                current = parent;
+            }
+            order.reverse();
+            return BudgetedRelease::Released(order);
+        }
+        match max_nodes.as_mut() {
+            Some(0) => return BudgetedRelease::Undetermined,
+            Some(remaining) => *remaining -= 1,
+            None => (),
+        }
+        let analysis = analyze_with_cache(&board, &mut cache);
+        let mut retractions = RetractionGen::new_legal_with_candidates(&board, &candidates);
+        retractions.refine_iterator(&analysis);
+        for r in retractions {
+            let new_board = board.make_retraction_new(r);
+            if !visited.insert(new_board)
+                || illegal_material(&new_board)
+                || analyze_with_cache(&new_board, &mut cache).result == Some(Illegal)
+            {
+                continue;
+            }
+            let mut new_candidates = candidates;
+            new_candidates.update(&new_board, &r);
+            nodes.push((new_board, Some((index, r))));
+            queue.push_back((nodes.len() - 1, new_candidates));
+        }
+    }
+    BudgetedRelease::Unreleasable
+}
+
+/// The maximum number of pieces of the positions decided by
+/// [`endgame_legality`].
+pub const MAX_ENDGAME_PIECES: u32 = 8;
+
+/// Decides whether the given sparse position (with at most
+/// [`MAX_ENDGAME_PIECES`] pieces) can be unwound by `horizon` retractions, with
+/// an exhaustive (memoized) retraction search that bypasses the static
+/// analysis: the retractions are only constrained by the rules of chess and
+/// the material of the players.
+///
+/// Every legal position can be unwound indefinitely (along the game that led
+/// to it and then, from the starting position, with knight moves), so an
+/// [`Illegal`] verdict is definitive, whereas [`Legality::Legal`] only means
+/// that the position is legal within the given horizon.
+///
+/// Returns `None` if the position has too many pieces.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::{endgame_legality, Legality};
+///
+/// let board = Board::from_str("k7/8/8/8/8/8/8/7K w - -")?;
+/// assert_eq!(endgame_legality(&board, 4), Some(Legality::Legal));
+///
+/// // a double check by two knights is impossible
+/// let board = Board::from_str("k7/8/8/8/8/6n1/5n2/7K w - -")?;
+/// assert_eq!(endgame_legality(&board, 4), Some(Legality::Illegal));
+///
+/// assert_eq!(endgame_legality(&Board::default(), 4), None);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn endgame_legality(board: &Board, horizon: usize) -> Option<Legality> {
+    if board.combined().popcnt() > MAX_ENDGAME_PIECES {
+        return None;
+    }
+    let mut table = HashMap::new();
+    if unwinds(&mut table, &(*board).into(), horizon) {
+        Some(Legality::Legal)
+    } else {
+        Some(Illegal)
+    }
+}
+
+/// Tells whether the given board can be unwound by `nb_plies` retractions
+/// without going through positions with illegal material. The table stores,
+/// for every board, the largest number of plies it is known to unwind and the
+/// smallest number of plies it is known not to unwind.
+fn unwinds(
+    table: &mut HashMap<RetractableBoard, (usize, usize)>,
+    board: &RetractableBoard,
+    nb_plies: usize,
+) -> bool {
+    if illegal_material(board) {
+        return false;
+    }
+    if nb_plies == 0 {
+        return true;
+    }
+    let (unwound, refuted) = table.get(board).copied().unwrap_or((0, usize::MAX));
+    if nb_plies <= unwound {
+        return true;
+    }
+    if nb_plies >= refuted {
+        return false;
+    }
+    let candidates = UncapturedCandidates::new(board);
+    let result = RetractionGen::new_legal_with_candidates(board, &candidates)
+        .any(|r| unwinds(table, &board.make_retraction_new(r), nb_plies - 1));
+    let entry = table.entry(*board).or_insert((0, usize::MAX));
+    if result {
+        entry.0 = entry.0.max(nb_plies);
+    } else {
+        entry.1 = entry.1.min(nb_plies);
+    }
+    result
+}
+
+/// A constraint on the history of the game leading to a position. Retro
+/// problems frequently provide partial information about the last move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryConstraint {
+    /// Whether the last move was a capture (en passant captures included).
+    LastMoveWasCapture(bool),
+    /// The type of the piece that performed the last move (before promoting,
+    /// if the last move was a promotion).
+    LastMoveBy(Piece),
+    /// The square the last move departed from.
+    LastMoveFrom(Square),
+}
+
+impl HistoryConstraint {
+    /// Tells whether the given retraction of the given board, as the last move
+    /// of the game, satisfies the constraint.
+    pub fn is_satisfied_by(&self, board: &RetractableBoard, r: &ChessRetraction) -> bool {
+        match *self {
+            HistoryConstraint::LastMoveWasCapture(capture) => r.uncaptured().is_some() == capture,
+            HistoryConstraint::LastMoveBy(piece) => {
+                let mover = if r.unpromotion() {
+                    Some(Piece::Pawn)
+                } else {
+                    board.piece_on(r.source())
+                };
+                mover == Some(piece)
+            }
+            HistoryConstraint::LastMoveFrom(square) => r.target() == square,
+        }
+    }
+}
+
+/// Like [`is_legal`], but the game leading to the given position must also
+/// satisfy the given constraints on its history. Only the retractions that
+/// satisfy all of them are considered as the last move of the game.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Piece, Square};
+/// use sherlock::{is_legal_with_history, HistoryConstraint::*};
+///
+/// // the last move was d7-d5
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6")?;
+/// assert!(is_legal_with_history(&board, &[LastMoveBy(Piece::Pawn)]));
+/// assert!(is_legal_with_history(&board, &[LastMoveFrom(Square::D7)]));
+/// assert!(!is_legal_with_history(&board, &[LastMoveWasCapture(true)]));
+/// assert!(!is_legal_with_history(&board, &[LastMoveFrom(Square::E7)]));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn is_legal_with_history(board: &Board, constraints: &[HistoryConstraint]) -> bool {
+    let board: RetractableBoard = (*board).into();
+    let mut cache = AnalysisCache::new();
+    let analysis = analyze_with_cache(&board, &mut cache);
+    if analysis.result == Some(Illegal) {
+        return false;
+    }
+    let mut search = SearchState::new();
+    let candidates = UncapturedCandidates::new(&board);
+    let mut retractions = RetractionGen::new_legal_with_candidates(&board, &candidates);
+    retractions.refine_iterator(&analysis);
+    retractions
+        .filter(|r| constraints.iter().all(|c| c.is_satisfied_by(&board, r)))
+        .any(|r| {
+            let new_board = board.make_retraction_new(r);
+            let mut new_candidates = candidates;
+            new_candidates.update(&new_board, &r);
+            is_retractable(
+                &mut search,
+                &mut cache,
+                &AnalysisOptions::default(),
+                &new_board,
+                &new_candidates,
+                None,
+                None,
+            )
+        })
+}
+
+/// A set of positions known to be legal, e.g. all the positions of a real
+/// game or a previously computed set. The retraction search of
+/// [`is_legal_with_anchors`] succeeds as soon as it reaches any of them, which
+/// greatly speeds up the study of families of related positions.
+///
+/// Anchors are trusted: inserting an illegal position may lead to illegal
+/// positions being reported as legal.
+#[derive(Clone, Default)]
+pub struct AnchorSet {
+    keys: HashSet<PositionKey>,
+}
+
+impl AnchorSet {
+    /// Creates an empty set of anchors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts the given (legal) position. Retracted positions carry an
+    /// uncertain en passant flag, so the position is also registered under
+    /// such flag, which is legal whenever the original one is.
+    pub fn insert(&mut self, board: &Board) {
+        let board: RetractableBoard = (*board).into();
+        let mut uncertain = board;
+        uncertain.set_any_ep();
+        self.keys.insert(PositionKey::new(&board));
+        self.keys.insert(PositionKey::new(&uncertain));
+    }
+
+    /// Inserts all the positions of the game that starts from the initial
+    /// position and consists of the given moves.
+    ///
+    /// Fails with [`Error::IllegalGameMove`] if some move is illegal, in which
+    /// case only the positions before such move are inserted.
+    pub fn insert_game(&mut self, moves: &[ChessMove]) -> Result<(), Error> {
+        let mut board = Board::default();
+        self.insert(&board);
+        for m in moves {
+            if !board.legal(*m) {
+                return Err(Error::IllegalGameMove);
+            }
+            board = board.make_move_new(*m);
+            self.insert(&board);
+        }
+        Ok(())
+    }
+
+    /// The number of entries of the set (every position may take several
+    /// entries, one per en passant flag).
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Tells whether the set has no anchors.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Like [`is_legal`], but the retraction search stops (successfully) as soon
+/// as it reaches any of the given anchors, which are assumed to be legal.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, ChessMove};
+/// use sherlock::{is_legal_with_anchors, AnchorSet};
+///
+/// let game = ["e2e4", "d7d5"];
+/// let moves: Vec<_> = game
+///     .iter()
+///     .map(|m| ChessMove::from_str(m).unwrap())
+///     .collect();
+/// let mut anchors = AnchorSet::new();
+/// anchors.insert_game(&moves).unwrap();
+/// assert!(!anchors.is_empty());
+///
+/// // Black is in check, the search stops once Bf1-b5 is retracted
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/1B1p4/4P3/8/PPPP1PPP/RNBQK1NR b KQkq -")?;
+/// assert!(is_legal_with_anchors(&board, &anchors));
+///
+/// // anchors cannot make an illegal position legal
+/// let board = Board::from_str("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -")?;
+/// assert!(!is_legal_with_anchors(&board, &anchors));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn is_legal_with_anchors(board: &Board, anchors: &AnchorSet) -> bool {
+    let board: RetractableBoard = (*board).into();
+    let mut search = SearchState::with_anchors(anchors);
+    let mut cache = AnalysisCache::new();
+    is_retractable(
+        &mut search,
+        &mut cache,
+        &AnalysisOptions::default(),
+        &board,
+        &UncapturedCandidates::new(&board),
+        None,
+        None,
+    )
+}
+
+/// The moves that may have delivered the check of the given position (the side
+/// to move is in check), i.e., the last moves of the game leading to it.
+///
+/// An empty output for a position in check means that such check cannot be
+/// explained, thus the position is illegal. The output is also empty if the
+/// side to move is not in check.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Square};
+/// use sherlock::checking_last_moves;
+///
+/// let board = Board::from_str("rnbqkbnr/ppppp1pp/5p2/7Q/4P3/8/PPPP1PPP/RNB1KBNR b KQkq -")?;
+/// let moves = checking_last_moves(&board);
+/// assert!(!moves.is_empty());
+/// assert!(moves.iter().all(|m| m.get_dest() == Square::H5));
+///
+/// assert!(checking_last_moves(&Board::default()).is_empty());
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn checking_last_moves(board: &Board) -> Vec<ChessMove> {
+    if *board.checkers() == EMPTY {
+        return vec![];
+    }
+    last_moves(board)
+        .into_iter()
+        .map(|r| retraction_as_move(board, &r))
+        .collect()
+}
+
+/// The move that is undone by the given retraction of the given board.
+fn retraction_as_move(board: &Board, r: &ChessRetraction) -> ChessMove {
+    let promotion = if r.unpromotion() {
+        board.piece_on(r.source())
+    } else {
+        None
+    };
+    ChessMove::new(r.target(), r.source(), promotion)
+}
+
+/// The last move of the game leading to the given position, if it is provably
+/// unique, i.e., if all the retractions that undo a different move lead to
+/// positions that are proven to be illegal. Returns `None` if several last
+/// moves are possible or if none is (e.g. the position is illegal).
+///
+/// Retractions that only differ in the uncaptured piece undo the same move.
+/// Note that, as with [`is_legal`], the returned move is guaranteed to be the
+/// only candidate, but the position before it might still be illegal if it
+/// escapes the current logic.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, ChessMove, Square};
+/// use sherlock::unique_last_move;
+///
+/// // the last move was d7-d5
+/// let board = Board::from_str("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6")?;
+/// assert_eq!(
+///     unique_last_move(&board),
+///     Some(ChessMove::new(Square::D7, Square::D5, None))
+/// );
+///
+/// // no move has been played yet
+/// assert_eq!(unique_last_move(&Board::default()), None);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn unique_last_move(board: &Board) -> Option<ChessMove> {
+    let mut search = SearchState::new();
+    let mut cache = AnalysisCache::new();
+    let retractable_board: RetractableBoard = (*board).into();
+    let candidates = UncapturedCandidates::new(&retractable_board);
+    let mut last_move = None;
+    for r in RetractionGen::new_legal_with_candidates(&retractable_board, &candidates) {
+        let m = retraction_as_move(board, &r);
+        if last_move == Some(m) {
+            continue;
+        }
+        let new_board = retractable_board.make_retraction_new(r);
+        let mut new_candidates = candidates;
+        new_candidates.update(&new_board, &r);
+        if is_retractable(
+            &mut search,
+            &mut cache,
+            &AnalysisOptions::default(),
+            &new_board,
+            &new_candidates,
+            None,
+            None,
+        ) {
+            if last_move.is_some() {
+                return None;
+            }
+            last_move = Some(m);
+        }
+    }
+    last_move
+}
+
+/// The last moves that may have delivered the given checkmate or stalemate,
+/// as retractions. The position is illegal iff the output is empty. This
+/// allows validating the retro soundness of problems with set mates in one
+/// call.
+///
+/// Returns an error if the given position is neither a checkmate nor a
+/// stalemate.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Square};
+/// use sherlock::{mating_last_moves, Error};
+///
+/// // Fool's mate, necessarily delivered by the black queen
+/// let fen = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq -";
+/// let retractions = mating_last_moves(&Board::from_str(fen)?).unwrap();
+/// assert!(!retractions.is_empty());
+/// assert!(retractions.iter().all(|r| r.source() == Square::H4));
+///
+/// assert_eq!(
+///     mating_last_moves(&Board::default()),
+///     Err(Error::NotMateOrStalemate)
+/// );
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn mating_last_moves(board: &Board) -> Result<Vec<ChessRetraction>, Error> {
+    if board.status() == BoardStatus::Ongoing {
+        return Err(Error::NotMateOrStalemate);
+    }
+    if analyze(&(*board).into()).result == Some(Illegal) {
+        return Ok(vec![]);
+    }
+    Ok(last_moves(board))
+}
+
+/// Returns the set of empty squares where placing a `piece` of the given
+/// `color` leads to an *illegal* position.
+///
+/// Squares where the addition results in an invalid board (e.g. a pawn on the
+/// 1st rank or the side not to move being in check) are also included.
+/// Note that this is the dual of [`is_legal`], so a square that is *not* in the
+/// output is not guaranteed to lead to a legal position.
+///
+/// ```
+/// use chess::{Board, Color, Piece};
+/// use sherlock::illegal_placements;
+///
+/// // a third white knight cannot have appeared without captures
+/// let board = Board::default();
+/// let squares = illegal_placements(&board, Piece::Knight, Color::White);
+/// assert_eq!(squares, !board.combined());
+/// ```
+pub fn illegal_placements(board: &Board, piece: Piece, color: Color) -> BitBoard {
+    let mut illegal = EMPTY;
+    for square in !board.combined() {
+        let mut builder = BoardBuilder::from(board);
+        builder.piece(square, piece, color);
+        let is_illegal = Board::try_from(&builder)
+            .ok()
+            .map_or(true, |new_board| !is_legal(&new_board));
+        if is_illegal {
+            illegal |= BitBoard::from_square(square);
+        }
+    }
+    illegal
+}
+
+/// Checks whether the given `Board` is *legal* (see [`is_legal`]) under the
+/// assumptions specified by the given options.
+///
+/// Note that the assumptions are only imposed on the given position, not on
+/// the positions found during the retraction search, except for
+/// [`AnalysisOptions::allow_null_moves`], which also allows the search to
+/// retract null moves. Furthermore, the retraction search follows the orthodox
+/// rules, so it is skipped for other variants.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::{is_legal, is_legal_with_options, AnalysisOptions};
+///
+/// // black to move in the starting position, white must have passed
+/// let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq -";
+/// let board = Board::from_str(fen)?;
+/// let options = AnalysisOptions {
+///     allow_null_moves: true,
+///     ..Default::default()
+/// };
+/// assert!(!is_legal(&board));
+/// assert!(is_legal_with_options(&board, &options));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn is_legal_with_options(board: &Board, options: &AnalysisOptions) -> bool {
+    let board = (*board).into();
+    let analysis = analyze_with_options(&board, options);
+    if analysis.result == Some(Illegal) {
+        return false;
+    }
+    search_with_options(&board, options)
+}
+
+/// Runs the retraction search on the given board, under the given options.
+/// Only the orthodox variant is supported by the search: on other variants,
+/// the position is deemed to be retractable.
+pub(crate) fn search_with_options(board: &RetractableBoard, options: &AnalysisOptions) -> bool {
+    if options.variant != Variant::Orthodox {
+        return true;
+    }
+    let search_options = AnalysisOptions {
+        allow_null_moves: options.allow_null_moves,
+        max_promotions: options.max_promotions,
+        convention: options.convention,
+        ..Default::default()
+    };
+    let mut search = SearchState::new();
+    let mut cache = AnalysisCache::new();
+    is_retractable(
+        &mut search,
+        &mut cache,
+        &search_options,
+        board,
+        &UncapturedCandidates::new(board),
+        None,
+        None,
+    )
+}
+
+/// Tells whether the given position forces a promoted piece to be on the
+/// board, i.e., whether the position is illegal under the assumption that
+/// none of the pieces on the board is the result of a promotion.
+///
+/// Illegal positions vacuously force a promoted piece.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::requires_promoted_piece;
+///
+/// assert!(!requires_promoted_piece(&Board::default()));
+///
+/// let board = Board::from_str("k7/8/8/8/8/8/8/1Q1K1Q2 w - -")?;
+/// assert!(requires_promoted_piece(&board));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn requires_promoted_piece(board: &Board) -> bool {
+    let options = AnalysisOptions {
+        assume_no_promoted_pieces: true,
+        ..Default::default()
+    };
+    !is_legal_with_options(board, &options)
+}
+
+/// Returns the maximum number of reversible plies (moves that are neither
+/// captures nor pawn moves) that can be retracted from the given position,
+/// without reaching a position that has been proven illegal. Returns `None` if
+/// the given position is illegal and `u8::MAX` if reversible retractions can go
+/// on indefinitely.
+fn reversible_depth(
+    table: &mut HashMap<RetractableBoard, Option<u8>>,
+    cache: &mut AnalysisCache,
+    board: &RetractableBoard,
+) -> Option<u8> {
+    match table.get(board) {
+        Some(Some(depth)) => return Some(*depth),
+        // the position is being explored, so we have found a cycle
+        Some(None) => return Some(u8::MAX),
+        None => (),
+    }
+
+    let analysis = analyze_with_cache(board, cache);
+    if analysis.result == Some(Illegal) {
+        return None;
+    }
+
+    table.insert(*board, None);
+    let mut depth = 0;
+
+    let mut retractions = RetractionGen::new_legal(board);
+    retractions.refine_iterator(&analysis);
+    for r in retractions {
+        if r.uncaptured().is_some()
+            || r.unpromotion()
+            || board.piece_on(r.source()) == Some(Piece::Pawn)
+        {
+            continue;
+        }
+        let new_board = board.make_retraction_new(r);
+        if let Some(d) = reversible_depth(table, cache, &new_board) {
+            depth = max(depth, d.saturating_add(1));
+        }
+        if depth == u8::MAX {
+            break;
+        }
+    }
+
+    table.insert(*board, Some(depth));
+    Some(depth)
+}
+
+/// A legal completion of a piece placement with the FEN fields that are
+/// usually omitted in diagrams: the side to move, the castling rights and the
+/// en passant square.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CompletedFen {
+    /// The side to move.
+    pub side_to_move: Color,
+    /// The castling rights of each color, indexed by `color.to_index()`.
+    pub castle_rights: [CastleRights; NUM_COLORS],
+    /// The en passant square, if the last move was a double pawn push that
+    /// can be captured en passant.
+    pub en_passant: Option<Square>,
+    /// The completed position.
+    pub board: Board,
+}
+
+/// Enumerates the completions of the given piece placement (the first field
+/// of a FEN string) that are legal, i.e. the combinations of side to move,
+/// castling rights and en passant square that lead to a legal position.
+/// Returns an empty vector if the placement cannot be parsed or no completion
+/// is legal.
+///
+/// ```
+/// use chess::{CastleRights, Color, Square};
+/// use sherlock::legal_completions;
+///
+/// let completions = legal_completions("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+/// assert!(completions.iter().all(|c| c.side_to_move == Color::White));
+/// assert!(completions
+///     .iter()
+///     .any(|c| c.castle_rights == [CastleRights::Both, CastleRights::Both]));
+///
+/// // the last move may have been e2-e4
+/// let completions = legal_completions("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR");
+/// assert!(completions.iter().any(|c| c.en_passant == Some(Square::E3)));
+/// ```
+pub fn legal_completions(placement: &str) -> Vec<CompletedFen> {
+    let all_rights = [
+        CastleRights::NoRights,
+        CastleRights::KingSide,
+        CastleRights::QueenSide,
+        CastleRights::Both,
+    ];
+    let mut completions = vec![];
+    for side_to_move in ALL_COLORS {
+        // the squares behind the pawns that may have just made a double push
+        let pushed_rank = match side_to_move {
+            Color::White => Rank::Fifth,
+            Color::Black => Rank::Fourth,
+        };
+        let ep_candidates: Vec<_> = ALL_FILES
+            .iter()
+            .map(|file| Square::make_square(pushed_rank, *file).ubackward(!side_to_move))
+            .collect();
+
+        for white_rights in all_rights {
+            for black_rights in all_rights {
+                let rights =
+                    white_rights.to_string(Color::White) + &black_rights.to_string(Color::Black);
+                let rights = if rights.is_empty() {
+                    "-".to_string()
+                } else {
+                    rights
+                };
+                let side = if side_to_move == Color::White {
+                    "w"
+                } else {
+                    "b"
+                };
+                let ep_fields = std::iter::once("-".to_string())
+                    .chain(ep_candidates.iter().map(|square| square.to_string()));
+                for ep in ep_fields {
+                    let fen = format!("{} {} {} {} 0 1", placement, side, rights, ep);
+                    let board = match Board::from_str(&fen) {
+                        Ok(board) => board,
+                        Err(_) => continue,
+                    };
+                    // the rights must be consistent with the placement and the
+                    // en passant square must not have been discarded
+                    if board.castle_rights(Color::White) != white_rights
+                        || board.castle_rights(Color::Black) != black_rights
+                        || (ep != "-" && board.en_passant().is_none())
+                    {
+                        continue;
+                    }
+                    if is_legal(&board) {
+                        completions.push(CompletedFen {
+                            side_to_move,
+                            castle_rights: [white_rights, black_rights],
+                            en_passant: board
+                                .en_passant()
+                                .map(|square| square.ubackward(!side_to_move)),
+                            board,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    completions
+}
+
+/// Computes an upper bound on the halfmove clock of the given position, i.e.,
+/// on the number of plies since the last capture or pawn move: the largest `k`
+/// such that there may exist a legal game leading to the position whose last
+/// `k` plies are reversible.
+///
+/// It returns `u8::MAX` if the bound is unknown (reversible moves may have
+/// been played indefinitely) and `0` if the position is illegal.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::Board;
+/// use sherlock::max_halfmove_clock;
+///
+/// // the knights may have been dancing forever
+/// assert_eq!(max_halfmove_clock(&Board::default()), u8::MAX);
+///
+/// // the last move was f2-f4
+/// let fen = "rnbqkbnr/pppp1ppp/8/8/4pP2/8/PPPPP1PP/RNBQKBNR b KQkq f3";
+/// let board = Board::from_str(fen)?;
+/// assert_eq!(max_halfmove_clock(&board), 0);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn max_halfmove_clock(board: &Board) -> u8 {
+    let mut table = HashMap::<RetractableBoard, Option<u8>>::new();
+    let mut cache = AnalysisCache::new();
+    reversible_depth(&mut table, &mut cache, &(*board).into()).unwrap_or(0)
+}
+
+/// Analyzes the given position under every combination of castling hypotheses
+/// for both players (see [`CastlingHypothesis`]). The analysis without
+/// hypotheses is performed only once and shared by all combinations.
+///
+/// Since the hypotheses are exhaustive, the position is illegal if it is
+/// illegal under all of them.
+///
+/// ```
+/// use sherlock::{analyze_castling_hypotheses, Legality, RetractableBoard};
+///
+/// let analyses = analyze_castling_hypotheses(&RetractableBoard::default());
+/// assert!(analyses
+///     .iter()
+///     .any(|(_, analysis)| analysis.result() != Some(Legality::Illegal)));
+/// ```
+pub fn analyze_castling_hypotheses(
+    board: &RetractableBoard,
+) -> Vec<([CastlingHypothesis; NUM_COLORS], Analysis)> {
+    let common = analyze(board);
+    let mut analyses = vec![];
+    for white in ALL_CASTLING_HYPOTHESES {
+        for black in ALL_CASTLING_HYPOTHESES {
+            let hypotheses = [white, black];
+            let mut analysis = common.clone();
+            if analysis.result.is_none() {
+                analysis.options.castling = hypotheses.map(Some);
+                saturate(&mut analysis);
+                analysis.cache = None;
+            }
+            analyses.push((hypotheses, analysis));
+        }
+    }
+    analyses
+}
+
+/// The legality of the given board with holdings in Crazyhouse, if it can be
+/// determined: `Some(Illegal)` if the position is *definitely illegal*, `None`
+/// otherwise.
+///
+/// Pieces can be dropped anywhere (except pawns on the 1st and 8th ranks), so
+/// the history of the pieces on the board is hardly constrained and our
+/// analysis on orthodox routes does not apply. Instead, we check that the
+/// material on the board and in hand is consistent with the conservation of
+/// material of Crazyhouse, where promoted pieces demote to pawns when captured.
+/// Since captured units are dropped back, the material of each player does not
+/// bound the number of captures of its opponent, so the counting arguments of
+/// the orthodox analysis do not apply either. Drops are not retracted, so the
+/// positions that pass this check are left undetermined.
+///
+/// ```
+/// use sherlock::{crazyhouse_legality, HoldingsBoard, Legality};
+///
+/// // the knights have been exchanged
+/// let fen = "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR[Nn] w KQkq - 0 5";
+/// assert_eq!(crazyhouse_legality(&fen.parse()?), None);
+///
+/// // a white knight has vanished
+/// let fen = "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR[n] w KQkq - 0 5";
+/// assert_eq!(crazyhouse_legality(&fen.parse()?), Some(Legality::Illegal));
+/// # Ok::<(), sherlock::Error>(())
+/// ```
+pub fn crazyhouse_legality(board: &HoldingsBoard) -> Option<Legality> {
+    if illegal_holdings_material(board) {
+        Some(Illegal)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_search_in_parallel() {
+        [
+            "2nb3K/pkPRp1p1/p2p4/P1p5/1Pp4Q/2PP2P1/4P2P/n7 w - -",
+            "2nb3K/pkPRp1p1/p2p4/P1p5/1Pp4B/2PP2P1/4P2P/n7 w - -",
+            "2nR3K/pk1Rp1p1/p2p4/P1p5/1Pp5/2PP2P1/4P2P/n7 b - -",
+            "b3k3/8/8/3pP3/8/5K2/8/8 w - d6",
+        ]
+        .iter()
+        .for_each(|fen| {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            // every thread gives its positions back after analyzing a single one
+            for nb_threads in [1, 3] {
+                assert_eq!(
+                    search_in_parallel(&board, nb_threads, 1),
+                    is_legal_retractable(&board),
+                    "{}",
+                    fen
+                );
+            }
+        })
+    }
 }
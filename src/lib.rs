@@ -15,24 +15,104 @@
 //! let board = Board::default();
 //! assert!(is_legal(&board));
 //! ```
+//!
+//! The most commonly needed items are gathered in the [`prelude`].
 
 #![deny(missing_docs)]
+// the helpers of the groups of rules disabled through features are unused
+#![cfg_attr(
+    not(all(
+        feature = "rules-mobility",
+        feature = "rules-captures",
+        feature = "rules-parity"
+    )),
+    allow(dead_code)
+)]
 
-use chess::{BitBoard, Square, EMPTY};
-use rules::ALL_ORIGINS;
-use utils::origin_color;
+/// The `chess` crate this crate builds on, whose types appear in our API.
+pub use chess;
+use chess::{get_file, get_rank, BitBoard, Color, File, Piece, Square, ALL_COLORS, EMPTY};
 
 mod analysis;
+pub mod batch;
+mod cache;
+mod conventions;
+mod descendants;
+pub mod differential;
+mod economy;
+#[cfg(feature = "random")]
+mod generator;
+mod heatmap;
+mod holdings;
 mod legality;
+pub mod prelude;
 mod retractor;
 mod rules;
+#[cfg(feature = "sat")]
+mod sat;
+mod screen;
+mod signature;
+mod soundness;
+mod stipulation;
+mod twins;
 mod utils;
 
-pub use crate::{analysis::*, legality::*, retractor::*, utils::ALL_COLORED_PIECES};
+#[cfg(feature = "global-cache")]
+pub use crate::cache::clear_global_cache;
+#[cfg(feature = "random")]
+pub use crate::generator::*;
+#[cfg(feature = "sat")]
+pub use crate::sat::*;
+pub use crate::{
+    analysis::*,
+    cache::AnalysisCache,
+    conventions::*,
+    descendants::*,
+    economy::*,
+    heatmap::*,
+    holdings::*,
+    legality::*,
+    retractor::*,
+    rules::{origins_of_piece_on, Rule, RuleFactory, ALL_ORIGINS, COLOR_ORIGINS, RULE_API_VERSION},
+    screen::*,
+    signature::*,
+    soundness::*,
+    stipulation::*,
+    twins::*,
+    utils::{
+        initial_color_on, initial_piece_on, origin_color, Geometry, Orthodox, VerticalCylinder,
+        ALL_COLORED_PIECES,
+    },
+};
+use crate::{rules::lower_bound_promoted, signature::signature, utils::Origin};
 
 #[doc = include_str!("../README.md")]
 
 impl Analysis {
+    /// The edges removed from the mobility graph of the given piece type and
+    /// color, in order of removal, together with the name of the rule that
+    /// removed them. This is only recorded if the analysis was performed with
+    /// [`AnalysisOptions::trace_edge_removals`], otherwise it is empty.
+    ///
+    /// ```
+    /// use chess::{Color, Piece, Square};
+    /// use sherlock::{analyze_with_options, AnalysisOptions, RetractableBoard};
+    ///
+    /// let options = AnalysisOptions {
+    ///     trace_edge_removals: true,
+    ///     ..Default::default()
+    /// };
+    /// let analysis = analyze_with_options(&RetractableBoard::default(), &options);
+    /// let removed = analysis.removed_edges(Piece::Rook, Color::White);
+    /// assert!(removed.contains(&(Square::A1, Square::A3, "SteadyMobilityRule")));
+    /// ```
+    pub fn removed_edges(&self, piece: Piece, color: Color) -> Vec<(Square, Square, &str)> {
+        self.mobility.value[color.to_index()][piece.to_index()]
+            .journal()
+            .map(|(source, target, tag)| (source, target, self.rule_names[tag as usize]))
+            .collect()
+    }
+
     /// Tells whether the piece on the given square was classified as steady
     /// (it has never moved and is still on their starting square).
     ///
@@ -60,6 +140,217 @@ impl Analysis {
         BitBoard::from_square(square) & self.steady.value != EMPTY
     }
 
+    /// Tells whether the piece on the given square has certainly moved during
+    /// the game, i.e. it cannot have started the game on its current square.
+    /// This is the dual of [`Analysis::is_steady`]. Returns `false` if the
+    /// square is empty.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use chess::{Board, Square};
+    /// # use sherlock::analyze;
+    /// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq -")?;
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // The knight on F3 has moved, but the one on B1 may have not
+    /// assert!(analysis.must_have_moved(Square::F3));
+    /// assert!(!analysis.must_have_moved(Square::B1));
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    #[inline]
+    pub fn must_have_moved(&self, square: Square) -> bool {
+        self.board.piece_on(square).is_some()
+            && self.origins(square) & BitBoard::from_square(square) == EMPTY
+    }
+
+    /// The squares where the king of the given color can never have stood,
+    /// because they are occupied or attacked by steady enemy pawns.
+    ///
+    /// ```
+    /// # use chess::{get_rank, Board, Color, Rank};
+    /// # use sherlock::analyze;
+    /// let analysis = analyze(&Board::default().into());
+    ///
+    /// // No white pawn has moved, so the black king never reached the 3rd rank
+    /// let third_rank = get_rank(Rank::Third);
+    /// assert_eq!(
+    ///     analysis.king_forbidden_squares(Color::Black) & third_rank,
+    ///     third_rank
+    /// );
+    /// ```
+    pub fn king_forbidden_squares(&self, color: Color) -> BitBoard {
+        rules::steady_pawns_coverage(self, !color)
+    }
+
+    /// The squares that the king of the given color may have visited since the
+    /// beginning of the game. Besides its mobility, this takes into account
+    /// the squares it cannot have crossed (see
+    /// [`Analysis::king_forbidden_squares`]).
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use chess::{BitBoard, Board, Color, Square, EMPTY};
+    /// # use sherlock::analyze;
+    /// // The white pawns never moved, so the black king came down through A3
+    /// let board = Board::from_str("8/8/8/8/8/8/2PPPPPP/k3K3 w - -")?;
+    /// let analysis = analyze(&board.into());
+    /// let route = analysis.king_route(Color::Black);
+    /// assert_ne!(route & BitBoard::from_square(Square::A3), EMPTY);
+    /// assert_eq!(route & BitBoard::from_square(Square::D3), EMPTY);
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    #[inline]
+    pub fn king_route(&self, color: Color) -> BitBoard {
+        self.reachable_from_origin(color, File::E)
+    }
+
+    /// The legality of the position. If the analysis was inconclusive, the
+    /// retraction search is run (under the options of the analysis) and its
+    /// verdict is stored in the analysis, so that further calls are free and
+    /// [`Analysis::result`] reflects it. All the derived information remains
+    /// available.
+    ///
+    /// As with [`is_legal`], an `Illegal` verdict is definitive, whereas a
+    /// `Legal` one may be wrong on positions that escape the current logic.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use chess::Board;
+    /// # use sherlock::{analyze, Legality};
+    /// // Black is in check, the last move must be retracted to settle legality
+    /// let board = Board::from_str("4k3/8/8/1B6/8/8/8/4K3 b - -")?;
+    /// let mut analysis = analyze(&board.into());
+    /// assert_eq!(analysis.legality(), Legality::Legal);
+    /// assert_eq!(analysis.result(), Some(Legality::Legal));
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn legality(&mut self) -> Legality {
+        if let Some(result) = self.result {
+            return result;
+        }
+        let result = if legality::search_with_options(&self.board, &self.options) {
+            Legality::Legal
+        } else {
+            self.illegality_reason = Some(IllegalityReason::RetractionSearchExhausted);
+            Legality::Illegal
+        };
+        self.result = Some(result);
+        result
+    }
+
+    /// The mechanism that proved the position illegal, if it has been proven
+    /// illegal (see [`illegality_reason`] for an example).
+    #[inline]
+    pub fn illegality_reason(&self) -> Option<&IllegalityReason> {
+        self.illegality_reason.as_ref()
+    }
+
+    /// The number of rule applications that were performed to derive the
+    /// information of the analysis. Useful for measuring the effect of the
+    /// [`AnalysisOptions::rule_schedule`] policy.
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use chess::Board;
+    /// # use sherlock::{analyze_with_options, AnalysisOptions, RuleSchedule};
+    /// let board = Board::from_str("4k3/2p5/1pPp4/pP1Pp3/P3Pp2/5P2/8/4K3 w - -")?;
+    /// let fixed = analyze_with_options(&board.into(), &AnalysisOptions::default());
+    /// let options = AnalysisOptions {
+    ///     rule_schedule: RuleSchedule::Adaptive,
+    ///     ..Default::default()
+    /// };
+    /// let adaptive = analyze_with_options(&board.into(), &options);
+    ///
+    /// // the policy does not affect the derived information
+    /// assert_eq!(fixed.result(), adaptive.result());
+    /// assert!(adaptive.nb_rule_applications() > 0);
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    #[inline]
+    pub fn nb_rule_applications(&self) -> usize {
+        self.nb_rule_applications
+    }
+
+    /// The legality of the position, if it has been determined by the analysis.
+    #[inline]
+    pub fn result(&self) -> Option<Legality> {
+        self.result
+    }
+
+    /// The position being analyzed.
+    #[inline]
+    pub fn board(&self) -> &RetractableBoard {
+        &self.board
+    }
+
+    /// Declares the position illegal, by an argument of the given class (see
+    /// [`IllegalityReason::class`]). This is meant for (user-defined) rules,
+    /// see [`Rule`] for the soundness requirements.
+    pub fn set_illegal(&mut self, class: IllegalityClass) {
+        self.result = Some(Legality::Illegal);
+        self.illegality_class.get_or_insert(class);
+    }
+
+    /// The current progress counters of the analysis. A counter changes iff
+    /// the corresponding information has been refined, which is useful for
+    /// deciding whether a rule is worth (re-)applying.
+    ///
+    /// ```
+    /// use sherlock::{analyze, Analysis, RetractableBoard};
+    ///
+    /// let board = RetractableBoard::default();
+    /// let initial = Analysis::new(&board).counters();
+    /// let analysis = analyze(&board);
+    ///
+    /// // the analysis has refined the steady pieces, but not the captures
+    /// assert!(analysis.counters().steady > initial.steady);
+    /// assert_eq!(analysis.counters().captures, initial.captures);
+    /// ```
+    pub fn counters(&self) -> AnalysisCounters {
+        AnalysisCounters {
+            steady: self.steady.counter(),
+            origins: self.origins.counter(),
+            destinies: self.destinies.counter(),
+            death_squares: self.death_squares.counter(),
+            reachable: self.reachable.counter(),
+            reachable_as_pawn: self.reachable_as_pawn.counter(),
+            reachable_from_origin: self.reachable_from_origin.counter(),
+            reachable_from_promotion: self.reachable_from_promotion.counter(),
+            pawn_capture_distances: self.pawn_capture_distances.counter(),
+            pawn_forced_captures: self.pawn_forced_captures.counter(),
+            officer_capture_distances: self.officer_capture_distances.counter(),
+            missing: self.missing.counter(),
+            captures: self.captures.counter(),
+            nb_captures: self.nb_captures.counter(),
+            mobility: self.mobility.counter(),
+            knight_parity: self.knight_parity.counter(),
+            last_movers: self.last_movers.counter(),
+        }
+    }
+
+    /// The squares of the pieces that may have performed the last move. The
+    /// retractions of any other piece are skipped by the retraction search.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{BitBoard, Board, Square, EMPTY};
+    /// use sherlock::analyze;
+    ///
+    /// // the white king cannot have come from any of the empty squares around it,
+    /// // as they are all next to the black king
+    /// let board = Board::from_str("8/8/8/3k4/8/2PKP3/2PPP3/8 b - -")?;
+    /// let analysis = analyze(&board.into());
+    /// assert_eq!(
+    ///     analysis.last_movers() & BitBoard::from_square(Square::D3),
+    ///     EMPTY
+    /// );
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    #[inline]
+    pub fn last_movers(&self) -> BitBoard {
+        self.last_movers.value
+    }
+
     /// Tells whether the piece that started the game on the given square is
     /// known to be missing (it was captured during the game).
     #[inline]
@@ -115,7 +406,7 @@ impl Analysis {
     /// ```
     #[inline]
     pub fn origins(&self, square: Square) -> BitBoard {
-        self.origins.value[square.to_index()]
+        self.origins.value[square]
     }
 
     /// The candidate destinies of the piece that started on the given square.
@@ -158,7 +449,321 @@ impl Analysis {
     /// ```
     #[inline]
     pub fn destinies(&self, square: Square) -> BitBoard {
-        self.destinies.value[square.to_index()]
+        self.destinies.value[Origin(square)]
+    }
+
+    /// The candidate squares where the piece that started on the given square
+    /// may have been captured. This set is empty if the piece is known to be
+    /// on the board.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{BitBoard, Board, Square, EMPTY};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("r2qkb1r/ppp1pppp/8/7n/b2P4/8/PPPPP1PP/RNBQKBNR b KQkq -")
+    ///     .expect("Valid Position");
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // The pawn that started on D7 was captured on D4
+    /// assert_eq!(
+    ///     analysis.death_squares(Square::D7),
+    ///     BitBoard::from_square(Square::D4)
+    /// );
+    ///
+    /// // No white piece has been captured
+    /// assert_eq!(analysis.death_squares(Square::F2), EMPTY);
+    /// ```
+    #[inline]
+    pub fn death_squares(&self, origin: Square) -> BitBoard {
+        self.death_squares.value[Origin(origin)]
+    }
+
+    /// The candidate squares where the piece that started on the given square
+    /// may have been captured, for pieces that may be missing. These are the
+    /// candidate death squares (see [`Analysis::death_squares`]) that may have
+    /// been reached by an opponent. Furthermore, if the squares where the
+    /// opponents have certainly captured are as many as the missing pieces of
+    /// the given piece's color, the piece must have been captured on one of
+    /// them.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{BitBoard, Board, Square, EMPTY};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("r2qkb1r/ppp1pppp/8/7n/b2P4/8/PPPPP1PP/RNBQKBNR b KQkq -")
+    ///     .expect("Valid Position");
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // The pawn that started on D7 was captured on D4
+    /// assert_eq!(
+    ///     analysis.death_square_candidates(Square::D7),
+    ///     BitBoard::from_square(Square::D4)
+    /// );
+    ///
+    /// // The white king is on the board
+    /// assert_eq!(analysis.death_square_candidates(Square::E1), EMPTY);
+    /// ```
+    pub fn death_square_candidates(&self, origin: Square) -> BitBoard {
+        if self.is_definitely_on_the_board(origin) {
+            return EMPTY;
+        }
+        let color = origin_color(origin);
+        let (reached, tombs) = COLOR_ORIGINS[(!color).to_index()].into_iter().fold(
+            (EMPTY, EMPTY),
+            |(reached, tombs), opponent| {
+                (
+                    reached | self.reachable(opponent),
+                    tombs | self.captures(opponent),
+                )
+            },
+        );
+        let mut candidates = self.death_squares(origin) & reached;
+        if tombs.popcnt() == 16 - self.board.color_combined(color).popcnt() {
+            candidates &= tombs;
+        }
+        candidates
+    }
+
+    /// The squares that may have been reached during the game by the piece
+    /// that started on the given square. For pawns, this includes the squares
+    /// reached after promoting (see [`Analysis::reachable_as_pawn`]).
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{BitBoard, Board, Square, EMPTY};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -")
+    ///     .expect("Valid Position");
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // No piece has moved, so the white king has not left E1
+    /// assert_eq!(
+    ///     analysis.reachable(Square::E1),
+    ///     BitBoard::from_square(Square::E1)
+    /// );
+    /// ```
+    #[inline]
+    pub fn reachable(&self, origin: Square) -> BitBoard {
+        self.reachable.value[Origin(origin)]
+    }
+
+    /// Tells whether the piece on the given square may have visited the given
+    /// target during the game, i.e. whether it may have reached the target
+    /// from one of its candidate origins and then come back to its current
+    /// square. Unlike [`Analysis::reachable`], this rules out the visits after
+    /// which the piece could not have returned.
+    ///
+    /// Returns `false` if the given square is empty.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{Board, Square};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq -")?;
+    /// let analysis = analyze(&board.into());
+    ///
+    /// assert!(analysis.may_have_visited(Square::F3, Square::G1));
+    /// assert!(analysis.may_have_visited(Square::E4, Square::E3));
+    ///
+    /// // the pawn on E4 cannot have gone further and come back
+    /// assert!(!analysis.may_have_visited(Square::E4, Square::E5));
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn may_have_visited(&self, square: Square, target: Square) -> bool {
+        let piece = match self.board.piece_on(square) {
+            Some(piece) => piece,
+            None => return false,
+        };
+        let color = self.piece_color_on(square);
+        let target_bb = BitBoard::from_square(target);
+        let graphs = &self.mobility.value[color.to_index()];
+        let returns = graphs[piece.to_index()].reachable_from_source(target)
+            & BitBoard::from_square(square)
+            != EMPTY;
+        self.origins(square).into_iter().any(|origin| {
+            if self.reachable(origin) & target_bb == EMPTY {
+                return false;
+            }
+            if initial_piece_on(origin) == piece {
+                return returns;
+            }
+            // a promoted piece may have visited the target before promoting
+            returns
+                || (self.reachable_as_pawn(origin) & target_bb != EMPTY
+                    && graphs[Piece::Pawn.to_index()].reachable_from_source(target)
+                        & get_rank(color.to_their_backrank())
+                        != EMPTY)
+        })
+    }
+
+    /// The distinct route skeletons (sequences of capture squares) of the
+    /// piece that started on the given square, from its origin to the given
+    /// target, with at most `max_captures` captures, according to the mobility
+    /// graph of its piece type. Pawns are only considered in pawn form (before
+    /// promoting). Non-pawn pieces have at most one (empty) skeleton, as their
+    /// moves do not reveal whether they capture.
+    ///
+    /// Panics if the given square is not an origin square.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{Board, Square};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("4k3/8/8/3P4/8/8/8/4K3 w - -")?;
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // the pawn from E2 may have captured on D5 (after e2-e4)
+    /// let journeys: Vec<_> = analysis.journeys(Square::E2, Square::D5, 1).collect();
+    /// assert!(journeys.iter().all(|j| j.captures.len() == 1));
+    /// assert!(journeys.iter().any(|j| j.captures == vec![Square::D5]));
+    ///
+    /// // it cannot have reached D5 without capturing
+    /// assert_eq!(analysis.journeys(Square::E2, Square::D5, 0).count(), 0);
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn journeys(
+        &self,
+        origin: Square,
+        target: Square,
+        max_captures: u8,
+    ) -> impl Iterator<Item = Journey> {
+        let piece = initial_piece_on(origin);
+        let color = origin_color(origin);
+        let graph = &self.mobility.value[color.to_index()][piece.to_index()];
+        graph
+            .capture_skeletons(origin, target, max_captures)
+            .into_iter()
+            .map(|captures| Journey { captures })
+    }
+
+    /// Bounds on the number of plies (half-moves) of any game leading to the
+    /// position: a lower bound, which takes into account the minimum number of
+    /// moves of every piece and the side to move, and an upper bound if one can
+    /// be established (e.g. if all the pieces except pawns are steady).
+    ///
+    /// This is useful for checking stipulations such as "the position after
+    /// exactly 7.5 moves" (15 plies).
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::Board;
+    /// use sherlock::analyze;
+    ///
+    /// let analysis = analyze(&Board::default().into());
+    /// assert_eq!(analysis.game_length_bounds().0, 0);
+    ///
+    /// // the white knight on E4 has moved at least twice
+    /// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/4N3/8/PPPPPPPP/R1BQKBNR b KQkq -")?;
+    /// let analysis = analyze(&board.into());
+    /// assert_eq!(analysis.game_length_bounds(), (3, None));
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn game_length_bounds(&self) -> (u32, Option<u32>) {
+        let mut min_nb_moves = [0; 2];
+        let mut max_nb_moves = [Some(0); 2];
+        for origin in ALL_ORIGINS {
+            let color = origin_color(origin).to_index();
+            min_nb_moves[color] += self.min_nb_moves(origin);
+            max_nb_moves[color] =
+                max_nb_moves[color].and_then(|n| self.max_nb_moves(origin).map(|m| n + m));
+        }
+
+        // castling moves the king and a rook at once
+        for color in ALL_COLORS {
+            let rank = color.to_my_backrank();
+            let king_moved = self.min_nb_moves(Square::make_square(rank, File::E)) > 0;
+            let rook_moved = [File::A, File::H]
+                .iter()
+                .any(|file| self.min_nb_moves(Square::make_square(rank, *file)) > 0);
+            if king_moved && rook_moved {
+                min_nb_moves[color.to_index()] -= 1;
+            }
+        }
+
+        // white moves first, so after n plies White has made ceil(n/2) moves
+        let [white_min, black_min] = min_nb_moves;
+        let [white_max, black_max] = max_nb_moves;
+        match self.board.side_to_move() {
+            Color::White => (
+                2 * white_min.max(black_min),
+                white_max.zip(black_max).map(|(w, b)| 2 * w.min(b)),
+            ),
+            Color::Black => (
+                (2 * white_min).saturating_sub(1).max(2 * black_min + 1),
+                white_max
+                    .zip(black_max)
+                    .map(|(w, b)| 2 * w.saturating_sub(1).min(b) + 1),
+            ),
+        }
+    }
+
+    /// The squares that may have been reached in pawn form by the pawn that
+    /// started on the given square, i.e., before promoting (if it ever
+    /// promoted). This set is empty for officers.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{get_file, Board, File, Square, EMPTY};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/1PPPPPPP/RNBQKBN1 w Qkq -")
+    ///     .expect("Valid Position");
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // No black piece is missing, so the white pawn from A2 stayed on its file
+    /// assert_eq!(
+    ///     analysis.reachable_as_pawn(Square::A2) & !get_file(File::A),
+    ///     EMPTY
+    /// );
+    /// assert_eq!(analysis.reachable_as_pawn(Square::A1), EMPTY);
+    /// ```
+    #[inline]
+    pub fn reachable_as_pawn(&self, origin: Square) -> BitBoard {
+        self.reachable_as_pawn.value[Origin(origin)]
+    }
+
+    /// The candidate squares where the piece that started on the given square
+    /// may be standing, in case it has not been captured. This set is empty if
+    /// the piece is known to be missing.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{BitBoard, Board, Square, EMPTY};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("r2qkb1r/ppp1pppp/8/7n/b2P4/8/PPPPP1PP/RNBQKBNR b KQkq -")
+    ///     .expect("Valid Position");
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // The white pawn on D4 comes from F2
+    /// assert_eq!(
+    ///     analysis.final_square_if_on_board(Square::F2),
+    ///     BitBoard::from_square(Square::D4)
+    /// );
+    ///
+    /// // The pawn that started on D7 is missing
+    /// assert_eq!(analysis.final_square_if_on_board(Square::D7), EMPTY);
+    /// ```
+    pub fn final_square_if_on_board(&self, origin: Square) -> BitBoard {
+        if self.is_definitely_missing(origin) {
+            return EMPTY;
+        }
+        let color = origin_color(origin);
+        let candidates = self.destinies(origin) & self.board.color_combined(color);
+        self.origins.value.containing(origin, candidates)
     }
 
     /// The squares where opponent pieces have certainly been captured by the
@@ -200,7 +805,343 @@ impl Analysis {
         if BitBoard::from_square(square) & ALL_ORIGINS == EMPTY {
             Err(Error::NotOriginSquare)
         } else {
-            Ok(self.captures.value[square.to_index()])
+            Ok(self.captures.value[Origin(square)])
+        }
+    }
+
+    /// The minimum number of captures necessary for the piece that started the
+    /// game on the given origin to reach the given square in its original form
+    /// (i.e., before promoting, if it is a pawn), or `None` if it cannot have
+    /// reached the square in such form.
+    ///
+    /// ```
+    /// use chess::Square;
+    /// use sherlock::{analyze, RetractableBoard};
+    ///
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// assert_eq!(
+    ///     analysis.min_captures_to_reach(Square::B1, Square::C3),
+    ///     Some(0)
+    /// );
+    /// assert_eq!(analysis.min_captures_to_reach(Square::E2, Square::H3), None);
+    /// ```
+    pub fn min_captures_to_reach(&self, origin: Square, square: Square) -> Option<u32> {
+        let color = origin_color(origin);
+        let (reachable, distance) = if initial_piece_on(origin) == Piece::Pawn {
+            (
+                self.reachable_as_pawn(origin),
+                self.pawn_capture_distances(color, origin.get_file(), square),
+            )
+        } else {
+            (
+                self.reachable_from_origin(color, origin.get_file()),
+                self.officer_capture_distances(origin, square),
+            )
+        };
+        // a distance of 16 stands for an unreachable square
+        if reachable & BitBoard::from_square(square) == EMPTY || distance >= 16 {
+            None
+        } else {
+            Some(distance as u32)
+        }
+    }
+
+    /// The squares where a pawn of the given color and file must have captured
+    /// to reach the given target as a pawn.
+    ///
+    /// This information is only meaningful if the target is reachable by the
+    /// pawn, with the number of captures it may have performed.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{BitBoard, Board, Color, File, Square};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("r2qkb1r/ppp1pppp/8/7n/b2P4/8/PPPPP1PP/RNBQKBNR b KQkq -")
+    ///     .expect("Valid Position");
+    /// let analysis = analyze(&board.into());
+    ///
+    /// // Only two black pieces are missing, so the F2-pawn can only have reached D4 by
+    /// // capturing on E3 and D4
+    /// assert_eq!(
+    ///     analysis.pawn_forced_captures(Color::White, File::F, Square::D4),
+    ///     BitBoard::from_square(Square::E3) | BitBoard::from_square(Square::D4)
+    /// );
+    /// ```
+    #[inline]
+    pub fn pawn_forced_captures(&self, color: Color, file: File, target: Square) -> BitBoard {
+        self.pawn_forced_captures.value[color.to_index()][file.to_index()][target.to_index()]
+    }
+
+    /// A lower bound on the number of pawns of the given color that promoted
+    /// during the game. It combines the material on the board (e.g. a second
+    /// queen must be a promoted piece) with the candidate origins of the pieces
+    /// (officers that come from pawn origins and pawns that are known to be on
+    /// the board but not in pawn form).
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{Board, Color};
+    /// use sherlock::analyze;
+    ///
+    /// assert_eq!(
+    ///     analyze(&Board::default().into()).min_promotions(Color::White),
+    ///     0
+    /// );
+    ///
+    /// // one of the white queens is the A2-pawn promoted
+    /// let board = Board::from_str("4k3/8/8/8/8/8/1PPPPPPP/QNBQKBNR w - -")?;
+    /// let analysis = analyze(&board.into());
+    /// assert_eq!(analysis.min_promotions(Color::White), 1);
+    /// assert_eq!(analysis.min_promotions(Color::Black), 0);
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn min_promotions(&self, color: Color) -> u32 {
+        let promoted_pieces = lower_bound_promoted(&self.board, color) as u32;
+        promoted_pieces
+            .max(self.promoted_officers(color).popcnt())
+            .max(self.promoted_pawn_origins(color).popcnt())
+    }
+
+    /// The files where a pawn of the given color has certainly promoted, as
+    /// the union of the corresponding files (in `BitBoard` form). A promotion
+    /// is located when all the candidate promotion squares of a pawn that
+    /// certainly promoted (or of all the candidate origins of a promoted
+    /// piece) lie on the same file.
+    ///
+    /// ```
+    /// use chess::{Color, EMPTY};
+    /// use sherlock::{analyze, RetractableBoard};
+    ///
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// assert_eq!(analysis.promotion_files(Color::White), EMPTY);
+    /// ```
+    pub fn promotion_files(&self, color: Color) -> BitBoard {
+        let backrank = get_rank(color.to_their_backrank());
+        let promotion_squares = |origins: BitBoard| {
+            origins
+                .into_iter()
+                .fold(EMPTY, |acc, origin| acc | self.reachable_as_pawn(origin))
+                & backrank
+        };
+        let promotions = self
+            .promoted_pawn_origins(color)
+            .into_iter()
+            .map(|origin| promotion_squares(BitBoard::from_square(origin)))
+            .chain(
+                self.promoted_officers(color)
+                    .into_iter()
+                    .map(|square| promotion_squares(self.origins(square))),
+            );
+        promotions
+            .filter(|squares| squares.popcnt() == 1)
+            .fold(EMPTY, |acc, squares| {
+                acc | get_file(squares.to_square().get_file())
+            })
+    }
+
+    /// The material signature of the position (see [`material_signature`]),
+    /// where the lower bounds are refined with the information derived by the
+    /// analysis: the captures that the pawns need to reach their squares from
+    /// their candidate origins and the promotions identified by
+    /// [`Analysis::min_promotions`].
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use chess::{Board, Color};
+    /// use sherlock::analyze;
+    ///
+    /// let board = Board::from_str("4k3/8/8/8/8/8/1PPPPPPP/QNBQKBNR w - -")?;
+    /// let signature = analyze(&board.into()).material_signature();
+    /// assert_eq!(signature.gbr, "2122.70");
+    /// assert_eq!(signature.promoted[Color::White.to_index()], 1);
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn material_signature(&self) -> MaterialSignature {
+        let mut refined = signature(&self.board);
+        for color in ALL_COLORS {
+            let pawn_captures: u32 = (self.board.pieces(Piece::Pawn)
+                & self.board.color_combined(color))
+            .into_iter()
+            .map(|square| {
+                self.origins(square)
+                    .into_iter()
+                    .map(|origin| {
+                        self.pawn_capture_distances(color, origin.get_file(), square) as u32
+                    })
+                    .min()
+                    .unwrap_or(0)
+            })
+            .sum();
+            let i = color.to_index();
+            refined.pawn_captures[i] = refined.pawn_captures[i].max(pawn_captures);
+            refined.promoted[i] = refined.promoted[i].max(self.min_promotions(color));
+        }
+        refined
+    }
+
+    /// All the (non-trivial) facts derived by the analysis.
+    ///
+    /// ```
+    /// use chess::Square;
+    /// use sherlock::{analyze, Fact, RetractableBoard};
+    ///
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// assert!(analysis
+    ///     .facts()
+    ///     .any(|fact| fact == Fact::Steady(Square::E1)));
+    /// ```
+    pub fn facts(&self) -> impl Iterator<Item = Fact> {
+        let mut facts = vec![];
+        for square in self.steady.value {
+            facts.push(Fact::Steady(square));
+        }
+        for square in *self.board.combined() {
+            let origins = self.origins(square);
+            if origins != !EMPTY {
+                facts.push(Fact::OriginsSubset(square, origins));
+            }
+        }
+        for origin in ALL_ORIGINS {
+            let destinies = self.destinies(origin);
+            if destinies != !EMPTY {
+                facts.push(Fact::DestiniesSubset(origin, destinies));
+            }
+            let death_squares = self.death_squares(origin);
+            if death_squares != !EMPTY {
+                facts.push(Fact::DeathSquaresSubset(origin, death_squares));
+            }
+            let reachable = self.reachable(origin);
+            if reachable != !EMPTY {
+                facts.push(Fact::ReachableSubset(origin, reachable));
+            }
+            let captures = self.captures(origin);
+            if captures != EMPTY {
+                facts.push(Fact::CapturedOn(origin, captures));
+            }
+            let lower_bound = self.nb_captures_lower_bound(origin);
+            if lower_bound > 0 {
+                facts.push(Fact::CapturesAtLeast(origin, lower_bound as u8));
+            }
+            let upper_bound = self.nb_captures_upper_bound(origin);
+            if upper_bound < 15 {
+                facts.push(Fact::CapturesAtMost(origin, upper_bound as u8));
+            }
+        }
+        for color in ALL_COLORS {
+            let missing = self.missing(color).certainly_in_the_set();
+            if missing != EMPTY {
+                facts.push(Fact::Missing(color, missing));
+            }
+            if let Some(parity) = self.knight_parity.value[color.to_index()] {
+                facts.push(Fact::KnightsParity(color, parity));
+            }
+        }
+        if let Some(result) = self.result {
+            facts.push(Fact::Result(result));
+        }
+        facts.into_iter()
+    }
+
+    /// The facts derived by the analysis that go beyond what is trivially true
+    /// about the position: origins narrowed below the default ones of the
+    /// piece type (see [`origins_of_piece_on`]), positive lower bounds on the
+    /// number of captures, upper bounds below the number of missing opponents,
+    /// and so on. This is a concise digest of [`Analysis::facts`].
+    ///
+    /// ```
+    /// use chess::Square;
+    /// use sherlock::{analyze, Fact, RetractableBoard};
+    ///
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// let facts = analysis.interesting_facts();
+    /// assert!(facts.contains(&Fact::Steady(Square::E1)));
+    ///
+    /// // nothing is missing, so no piece can have captured
+    /// assert!(!facts
+    ///     .iter()
+    ///     .any(|fact| matches!(fact, Fact::CapturesAtMost(_, _))));
+    /// assert!(facts.len() < analysis.facts().count());
+    /// ```
+    pub fn interesting_facts(&self) -> Vec<Fact> {
+        self.facts()
+            .filter(|fact| match *fact {
+                Fact::OriginsSubset(square, origins) => {
+                    let piece = self.board.piece_on(square).unwrap();
+                    let white = self.board.color_combined(Color::White);
+                    let color = if white & BitBoard::from_square(square) != EMPTY {
+                        Color::White
+                    } else {
+                        Color::Black
+                    };
+                    let default =
+                        origins_of_piece_on(piece, square) & COLOR_ORIGINS[color.to_index()];
+                    origins & default != default
+                }
+                Fact::CapturesAtMost(origin, n) => {
+                    let color = origin_color(origin);
+                    let nb_missing_opponents = 16 - self.board.color_combined(!color).popcnt();
+                    (n as u32) < nb_missing_opponents
+                }
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Tells whether the player of the given color must have castled or cannot
+    /// have castled, based on the routes of their king and rooks.
+    ///
+    /// ```
+    /// use chess::Color;
+    /// use sherlock::{analyze, CastlingStatus, RetractableBoard};
+    ///
+    /// // the kings have castling rights, so they have never moved
+    /// let analysis = analyze(&RetractableBoard::default());
+    /// assert_eq!(
+    ///     analysis.castling_status(Color::White),
+    ///     CastlingStatus::CannotHaveCastled
+    /// );
+    /// ```
+    pub fn castling_status(&self, color: Color) -> CastlingStatus {
+        let rank = color.to_my_backrank();
+        let king_origin = Square::make_square(rank, File::E);
+        let may_reach = |file, target_file| {
+            let target = BitBoard::from_square(Square::make_square(rank, target_file));
+            self.reachable_from_origin(color, file) & target != EMPTY
+        };
+        let kingside = may_reach(File::E, File::G) && may_reach(File::H, File::F);
+        let queenside = may_reach(File::E, File::C) && may_reach(File::A, File::D);
+        if self.is_steady(king_origin) || (!kingside && !queenside) {
+            return CastlingStatus::CannotHaveCastled;
+        }
+
+        // the current file of the piece that started on the given file, if it
+        // never left the 1st rank (it cannot have passed over other pieces)
+        let file_if_never_left_1st_rank = |file: File| {
+            let origin = Square::make_square(rank, file);
+            if self.reachable_from_origin(color, file) & !get_rank(rank) != EMPTY {
+                return None;
+            }
+            (self.board.color_combined(color) & get_rank(rank))
+                .into_iter()
+                .find(|&s| self.origins(s) == BitBoard::from_square(origin))
+                .map(|s| s.get_file())
+        };
+        if let Some(king_file) = file_if_never_left_1st_rank(File::E) {
+            if let Some(rook_file) = file_if_never_left_1st_rank(File::H) {
+                if kingside && rook_file < king_file {
+                    return CastlingStatus::MustHaveCastledKingside;
+                }
+            }
+            if let Some(rook_file) = file_if_never_left_1st_rank(File::A) {
+                if queenside && rook_file > king_file {
+                    return CastlingStatus::MustHaveCastledQueenside;
+                }
+            }
         }
+        CastlingStatus::Unknown
     }
 }
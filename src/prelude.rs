@@ -0,0 +1,21 @@
+//! The most commonly needed items of this crate, to be glob-imported.
+//!
+//! The types of the `chess` crate that appear in our signatures are also
+//! re-exported, so that consumers do not need to depend on a matching version
+//! of such crate.
+//!
+//! ```
+//! use sherlock::prelude::*;
+//!
+//! let board = Board::default();
+//! assert!(is_legal(&board));
+//!
+//! let analysis = analyze(&RetractableBoard::from(board));
+//! assert!(analysis.is_steady(Square::D1));
+//! ```
+
+pub use chess::{BitBoard, Board, ChessMove, Color, File, Piece, Rank, Square};
+
+pub use crate::{
+    analyze, is_legal, Analysis, ChessRetraction, Error, Legality, RetractableBoard, RetractionGen,
+};
@@ -1,8 +1,13 @@
 mod board;
 mod chess_retraction;
 mod piece_type;
+mod rejection;
 mod retraction_gen;
 mod zobrist;
 
 pub use board::*;
+pub use chess_retraction::ChessRetraction;
+#[cfg(feature = "atomic")]
+pub use chess_retraction::Explosion;
+pub use rejection::*;
 pub use retraction_gen::*;
@@ -6,8 +6,8 @@ use std::{
 
 use chess::{
     between, get_bishop_rays, get_knight_moves, get_pawn_attacks, get_rook_rays, BitBoard, Board,
-    CastleRights, Color, File, Piece, Rank, Square, ALL_FILES, ALL_RANKS, EMPTY, NUM_COLORS,
-    NUM_PIECES,
+    BoardBuilder, CastleRights, Color, File, Piece, Rank, Square, ALL_FILES, ALL_RANKS, EMPTY,
+    NUM_COLORS, NUM_PIECES,
 };
 
 use super::{chess_retraction::ChessRetraction, zobrist::Zobrist};
@@ -71,6 +71,48 @@ impl From<Board> for RetractableBoard {
     }
 }
 
+/// Converts a `RetractableBoard` back into a `chess::Board`. An uncertain
+/// en passant flag is resolved as no en passant being possible.
+///
+/// Fails with `chess::Error::InvalidBoard` if the position is not sane (e.g.
+/// the side not to move is in check).
+///
+/// ```
+/// use chess::Board;
+/// use sherlock::{last_moves, RetractableBoard};
+///
+/// let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -";
+/// let board = RetractableBoard::from_fen(fen).unwrap();
+/// let retracted = board.make_retraction_new(last_moves(&fen.parse().unwrap())[0]);
+///
+/// // the predecessor has an uncertain en passant flag
+/// assert_eq!(retracted.to_string().split(' ').last(), Some("?"));
+/// assert!(Board::try_from(retracted).is_ok());
+/// ```
+impl TryFrom<RetractableBoard> for Board {
+    type Error = chess::Error;
+
+    fn try_from(board: RetractableBoard) -> Result<Self, Self::Error> {
+        let mut builder = BoardBuilder::new();
+        for square in *board.combined() {
+            let piece = board.piece_on(square).unwrap();
+            let color = match board.color_combined(Color::White) & BitBoard::from_square(square) {
+                EMPTY => Color::Black,
+                _ => Color::White,
+            };
+            builder.piece(square, piece, color);
+        }
+        builder
+            .side_to_move(board.side_to_move())
+            .castle_rights(Color::White, board.castle_rights(Color::White))
+            .castle_rights(Color::Black, board.castle_rights(Color::Black));
+        if let EnPassantFlag::Some(square) = board.en_passant() {
+            builder.en_passant(Some(square.get_file()));
+        }
+        Board::try_from(&builder)
+    }
+}
+
 impl Default for RetractableBoard {
     fn default() -> Self {
         Board::default().into()
@@ -165,7 +207,8 @@ impl fmt::Display for RetractableBoard {
 
         write!(f, " ")?;
         if let EnPassantFlag::Some(sq) = self.en_passant {
-            write!(f, "{}", sq)?;
+            // the square behind the pawn that has just been double-pushed
+            write!(f, "{}", sq.ubackward(!self.side_to_move))?;
         } else if self.en_passant == EnPassantFlag::None {
             write!(f, "-")?;
         } else {
@@ -176,10 +219,45 @@ impl fmt::Display for RetractableBoard {
     }
 }
 
+/// Parses a `RetractableBoard` from a FEN string. Besides the standard
+/// dialect, the en passant field may be `?`, meaning that the en passant
+/// information is uncertain, as printed by the `Display` implementation.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use sherlock::{last_moves, RetractableBoard};
+///
+/// let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -";
+/// let board = RetractableBoard::from_str(fen).unwrap();
+/// let retracted = board.make_retraction_new(last_moves(&fen.parse().unwrap())[0]);
+///
+/// let parsed = RetractableBoard::from_str(&retracted.to_string()).unwrap();
+/// assert_eq!(parsed, retracted);
+/// ```
+impl FromStr for RetractableBoard {
+    type Err = chess::Error;
+
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        match fields.get(3) {
+            Some(&"?") => {
+                let mut fields = fields;
+                fields[3] = "-";
+                let mut board: RetractableBoard = Board::from_str(&fields.join(" "))?.into();
+                board.set_uncertain_ep();
+                Ok(board)
+            }
+            _ => Board::from_str(fen).map(|board| board.into()),
+        }
+    }
+}
+
 impl RetractableBoard {
-    /// Create a `RetractableBoard` from a FEN string.
+    /// Create a `RetractableBoard` from a FEN string (where the en passant
+    /// field may be `?`, see the `FromStr` implementation).
     pub fn from_fen(fen: &str) -> Result<RetractableBoard, chess::Error> {
-        Board::from_str(fen).map(|board| board.into())
+        Self::from_str(fen)
     }
 
     /// A `BitBoard` with all the pieces of the given type (and both colors).
@@ -237,6 +315,12 @@ impl RetractableBoard {
         self.en_passant
     }
 
+    /// The Zobrist hash of the board.
+    #[inline]
+    pub(crate) fn get_hash(&self) -> u64 {
+        self.hash
+    }
+
     /// The `CastleRights` of the given `Color`.
     #[inline]
     pub fn castle_rights(&self, color: Color) -> CastleRights {
@@ -271,6 +355,16 @@ impl RetractableBoard {
         }
     }
 
+    /// Specify that the last move was not a double pawn push, this will only
+    /// have an effect if the en-passant flag is currently set to `Some`.
+    #[inline]
+    pub(crate) fn unset_ep(&mut self) {
+        if let EnPassantFlag::Some(_) = self.en_passant {
+            self.hash ^= self.en_passant.zobrist(self.side_to_move);
+            self.en_passant = EnPassantFlag::None;
+        }
+    }
+
     /// Add or remove a piece from the bitboards in this struct.
     fn xor(&mut self, piece: Piece, bb: BitBoard, color: Color) {
         unsafe {
@@ -283,12 +377,21 @@ impl RetractableBoard {
         }
     }
 
-    /// Flip the turn. The en-passant flag is set to [EnPassantFlag::Any].
-    pub fn flip(&mut self) {
+    /// Specify that the last move may or may not have been a double pawn push,
+    /// whatever the current en-passant flag is. Unlike
+    /// [`RetractableBoard::set_uncertain_ep`], this also forgets a given
+    /// en-passant square.
+    #[inline]
+    pub(crate) fn set_any_ep(&mut self) {
         if self.en_passant != EnPassantFlag::Any {
             self.hash ^= self.en_passant.zobrist(self.side_to_move) ^ Zobrist::ep_any();
             self.en_passant = EnPassantFlag::Any;
         }
+    }
+
+    /// Flip the turn. The en-passant flag is set to [EnPassantFlag::Any].
+    pub fn flip(&mut self) {
+        self.set_any_ep();
         self.side_to_move = !self.side_to_move;
         self.hash ^= Zobrist::color();
     }
@@ -296,6 +399,11 @@ impl RetractableBoard {
     /// Apply a chess retraction to the given board, creating a new board.
     #[inline]
     pub fn make_retraction_new(&self, r: ChessRetraction) -> RetractableBoard {
+        #[cfg(feature = "atomic")]
+        if r.explosion().is_some() {
+            return self.make_unexplosion_new(r);
+        }
+
         let mut result = *self;
         result.side_to_move = !self.side_to_move;
         result.en_passant = EnPassantFlag::Any;
@@ -403,10 +511,213 @@ impl RetractableBoard {
             }
         }
 
+        #[cfg(debug_assertions)]
+        result.debug_assert_invariants();
+
+        result
+    }
+
+    /// Checks the structural invariants of the board in debug builds: the
+    /// bitboards of the different piece types (and of the different colors) are
+    /// disjoint and consistent with the combined bitboard and every color has
+    /// exactly one king.
+    #[cfg(debug_assertions)]
+    fn debug_assert_invariants(&self) {
+        let mut pieces = EMPTY;
+        for bb in self.pieces {
+            debug_assert_eq!(pieces & bb, EMPTY, "overlapping piece types in {}", self);
+            pieces |= bb;
+        }
+        let [white, black] = self.color_combined;
+        debug_assert_eq!(white & black, EMPTY, "overlapping colors in {}", self);
+        debug_assert_eq!(pieces, self.combined, "inconsistent pieces in {}", self);
+        debug_assert_eq!(
+            white | black,
+            self.combined,
+            "inconsistent colors in {}",
+            self
+        );
+        for color in self.color_combined {
+            let kings = self.pieces[Piece::King.to_index()] & color;
+            debug_assert_eq!(
+                kings.popcnt(),
+                1,
+                "not exactly one king per color in {}",
+                self
+            );
+        }
+    }
+
+    /// Apply a chess retraction to the given board, creating all the boards
+    /// that may have preceded it under every consistent assumption on the
+    /// castling rights.
+    ///
+    /// A move can only lose castling rights, so the rights of a predecessor
+    /// include those of the current board, plus any of the rights that the
+    /// retracted move may have lost (by moving the king or a rook from their
+    /// starting square, or by capturing a rook on its starting square), as long
+    /// as the king and rook are on their starting squares in the predecessor.
+    /// The first board is always the one given by
+    /// [`make_retraction_new`](RetractableBoard::make_retraction_new).
+    ///
+    /// ```
+    /// use chess::{CastleRights, Color, Square};
+    /// use sherlock::{ChessRetraction, RetractableBoard};
+    ///
+    /// let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/8/R3K1R1 b - -").unwrap();
+    /// let r = ChessRetraction::new(Square::G1, Square::H1, None, false);
+    /// let predecessors = board.make_retraction_with_rights(r);
+    ///
+    /// // White may have had the right to castle kingside before the rook moved
+    /// assert_eq!(predecessors.len(), 2);
+    /// assert_eq!(
+    ///     predecessors[0].castle_rights(Color::White),
+    ///     CastleRights::NoRights
+    /// );
+    /// assert_eq!(
+    ///     predecessors[1].castle_rights(Color::White),
+    ///     CastleRights::KingSide
+    /// );
+    /// ```
+    pub fn make_retraction_with_rights(&self, r: ChessRetraction) -> Vec<RetractableBoard> {
+        let board = self.make_retraction_new(r);
+        let retracting = board.side_to_move;
+        let mut lost = [CastleRights::NoRights; NUM_COLORS];
+        match self.piece_on(r.source()) {
+            Some(Piece::King) => lost[retracting.to_index()] = CastleRights::Both,
+            Some(Piece::Rook) => {
+                lost[retracting.to_index()] = corner_rights(retracting, r.target())
+            }
+            _ => {}
+        }
+        if r.uncaptured() == Some(Piece::Rook) {
+            lost[(!retracting).to_index()] = corner_rights(!retracting, r.source());
+        }
+
+        let mut predecessors = vec![board];
+        for color in [Color::White, Color::Black] {
+            let current = board.castle_rights(color);
+            let grantable = lost[color.to_index()].remove(current);
+            let mut extended = vec![];
+            for rights in [
+                CastleRights::KingSide,
+                CastleRights::QueenSide,
+                CastleRights::Both,
+            ] {
+                if grantable.add(rights) != grantable
+                    || board.grantable_rights(color).add(rights) != board.grantable_rights(color)
+                {
+                    continue;
+                }
+                for predecessor in predecessors.iter() {
+                    let mut predecessor = *predecessor;
+                    predecessor.set_castle_rights(color, current.add(rights));
+                    extended.push(predecessor);
+                }
+            }
+            predecessors.extend(extended);
+        }
+        predecessors
+    }
+
+    /// The castling rights of the given color that are consistent with the
+    /// placement of its king and rooks.
+    fn grantable_rights(&self, color: Color) -> CastleRights {
+        let backrank = color.to_my_backrank();
+        let kings = self.pieces(Piece::King) & self.color_combined(color);
+        let rooks = self.pieces(Piece::Rook) & self.color_combined(color);
+        if kings & BitBoard::set(backrank, File::E) == EMPTY {
+            return CastleRights::NoRights;
+        }
+        let kingside = rooks & BitBoard::set(backrank, File::H) != EMPTY;
+        let queenside = rooks & BitBoard::set(backrank, File::A) != EMPTY;
+        match (kingside, queenside) {
+            (true, true) => CastleRights::Both,
+            (true, false) => CastleRights::KingSide,
+            (false, true) => CastleRights::QueenSide,
+            (false, false) => CastleRights::NoRights,
+        }
+    }
+
+    /// Sets the castling rights of the given color.
+    fn set_castle_rights(&mut self, color: Color, rights: CastleRights) {
+        self.hash ^= Zobrist::castles(self.castle_rights[color.to_index()], color)
+            ^ Zobrist::castles(rights, color);
+        self.castle_rights[color.to_index()] = rights;
+    }
+
+    /// Apply an atomic chess retraction (one that undoes an explosion) to the
+    /// given board, creating a new board.
+    #[cfg(feature = "atomic")]
+    fn make_unexplosion_new(&self, r: ChessRetraction) -> RetractableBoard {
+        let explosion = r.explosion().unwrap();
+        let mut result = *self;
+        result.side_to_move = !self.side_to_move;
+        result.en_passant = EnPassantFlag::Any;
+        let side_to_retract = result.side_to_move;
+
+        result.xor(
+            explosion.capturer(),
+            BitBoard::from_square(r.target()),
+            side_to_retract,
+        );
+        result.xor(
+            r.uncaptured().unwrap(),
+            BitBoard::from_square(r.source()),
+            self.side_to_move,
+        );
+        for (square, piece, color) in explosion.neighbors() {
+            result.xor(piece, BitBoard::from_square(square), color);
+        }
+
+        // update zobrist hash about turn and en-passant
+        result.hash ^= Zobrist::color();
+        if result.en_passant != self.en_passant || result.en_passant.is_some() {
+            result.hash ^= self.en_passant.zobrist(self.side_to_move)
+                ^ result.en_passant.zobrist(result.side_to_move);
+        }
+
+        // several pieces may have reappeared, so we compute the checkers and
+        // pins from scratch
+        let king_square = result.king_square(side_to_retract);
+        let opponent = *result.color_combined(self.side_to_move);
+        result.checkers = get_knight_moves(king_square) & result.pieces(Piece::Knight) & opponent
+            | get_pawn_attacks(
+                king_square,
+                side_to_retract,
+                result.pieces(Piece::Pawn) & opponent,
+            );
+        result.pinned = EMPTY;
+        let bishops = result.pieces(Piece::Bishop) | result.pieces(Piece::Queen);
+        let rooks = result.pieces(Piece::Rook) | result.pieces(Piece::Queen);
+        let sliding_attackers = opponent
+            & (get_bishop_rays(king_square) & bishops | get_rook_rays(king_square) & rooks);
+        for square in sliding_attackers {
+            let between = between(square, king_square) & result.combined();
+            if between == EMPTY {
+                result.checkers ^= BitBoard::from_square(square);
+            } else if between.popcnt() == 1 {
+                result.pinned ^= between;
+            }
+        }
+
         result
     }
 }
 
+/// The castling rights associated to the rook of the given color that starts
+/// the game on the given square, if any.
+fn corner_rights(color: Color, square: Square) -> CastleRights {
+    let backrank = color.to_my_backrank();
+    if square == Square::make_square(backrank, File::H) {
+        CastleRights::KingSide
+    } else if square == Square::make_square(backrank, File::A) {
+        CastleRights::QueenSide
+    } else {
+        CastleRights::NoRights
+    }
+}
+
 #[cfg(test)]
 use crate::utils::*;
 
@@ -460,3 +771,87 @@ fn test_make_retraction_new() {
         );
     })
 }
+
+#[cfg(feature = "atomic")]
+#[test]
+fn test_make_unexplosion_new() {
+    use super::Explosion;
+
+    // the white knight on f3 captured a black pawn on e5, destroying a black
+    // bishop on d6 and a white rook on f4
+    let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+    let explosion = Explosion::new(Piece::Knight)
+        .with_neighbor(D6, Piece::Bishop, Color::Black)
+        .with_neighbor(F4, Piece::Rook, Color::White);
+    let r = ChessRetraction::new_explosion(E5, F3, Piece::Pawn, explosion);
+    let retracted_board = board.make_retraction_new(r);
+    assert_eq!(
+        retracted_board.hash ^ Zobrist::ep_any(),
+        Board::from_str("4k3/8/3b4/4p3/5R2/5N2/8/4K3 w - -")
+            .unwrap()
+            .get_hash()
+    );
+}
+
+#[test]
+fn test_make_retraction_with_rights() {
+    // the black rook on H8 was captured by the white rook, so both White and
+    // Black may have had kingside castling rights before
+    let board = RetractableBoard::from_fen("4k2R/8/8/8/8/8/8/4K3 b - -").unwrap();
+    let r = ChessRetraction::new(H8, H1, Some(Piece::Rook), false);
+    let predecessors = board.make_retraction_with_rights(r);
+    let rights: Vec<_> = predecessors
+        .iter()
+        .map(|b| (b.castle_rights(Color::White), b.castle_rights(Color::Black)))
+        .collect();
+    assert_eq!(
+        rights,
+        vec![
+            (CastleRights::NoRights, CastleRights::NoRights),
+            (CastleRights::KingSide, CastleRights::NoRights),
+            (CastleRights::NoRights, CastleRights::KingSide),
+            (CastleRights::KingSide, CastleRights::KingSide),
+        ]
+    );
+
+    // the hash is consistent with the rights
+    assert_eq!(
+        predecessors[3].hash ^ Zobrist::ep_any(),
+        Board::from_str("4k2r/8/8/8/8/8/8/4K2R w Kk -")
+            .unwrap()
+            .get_hash()
+    );
+
+    // a king move (not from its starting square) does not lose any rights
+    let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/8/R4K1R b - -").unwrap();
+    let r = ChessRetraction::new(F1, F2, None, false);
+    assert_eq!(board.make_retraction_with_rights(r).len(), 1);
+}
+
+#[test]
+fn test_from_str() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+        "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6",
+        "4k3/8/8/8/8/8/3p4/4K3 w - ?",
+        "r3k3/8/8/8/8/8/8/4K2R b Kq ?",
+    ] {
+        let board = RetractableBoard::from_str(fen).unwrap();
+        assert_eq!(board.to_string(), fen);
+        assert_eq!(
+            RetractableBoard::from_str(&board.to_string()).unwrap(),
+            board
+        );
+    }
+
+    let board = RetractableBoard::from_str("4k3/8/8/8/8/8/3p4/4K3 w - ?").unwrap();
+    assert_eq!(board.en_passant, EnPassantFlag::Any);
+    assert_eq!(
+        board.hash ^ Zobrist::ep_any(),
+        Board::from_str("4k3/8/8/8/8/8/3p4/4K3 w - -")
+            .unwrap()
+            .get_hash()
+    );
+
+    assert!(RetractableBoard::from_str("4k3/8/8/8/8/8/8/8 w - ?").is_err());
+}
@@ -1,6 +1,10 @@
 use std::fmt;
 
-use chess::{Piece, Square};
+#[cfg(feature = "atomic")]
+use chess::Color;
+use chess::{Piece, Rank, Square};
+
+use crate::RetractableBoard;
 
 /// Represent a ChessRetraction in memory.
 #[derive(Clone, Copy, Eq, PartialOrd, PartialEq, Default, Debug, Hash)]
@@ -9,6 +13,59 @@ pub struct ChessRetraction {
     target: Square,
     uncaptured: Option<Piece>,
     unpromotion: bool,
+    #[cfg(feature = "atomic")]
+    explosion: Option<Explosion>,
+}
+
+/// The maximum number of pieces that can be adjacent to a capture square.
+#[cfg(feature = "atomic")]
+const MAX_EXPLODED_NEIGHBORS: usize = 8;
+
+/// The units that disappeared in an atomic capture, besides the captured
+/// piece: the capturing piece and the non-pawn pieces adjacent to the capture
+/// square.
+#[cfg(feature = "atomic")]
+#[derive(Clone, Copy, Eq, PartialOrd, PartialEq, Debug, Hash)]
+pub struct Explosion {
+    capturer: Piece,
+    neighbors: [Option<(Square, Piece, Color)>; MAX_EXPLODED_NEIGHBORS],
+}
+
+#[cfg(feature = "atomic")]
+impl Explosion {
+    /// An explosion caused by the given capturing piece type, which did not
+    /// destroy any neighbors.
+    pub fn new(capturer: Piece) -> Explosion {
+        Explosion {
+            capturer,
+            neighbors: [None; MAX_EXPLODED_NEIGHBORS],
+        }
+    }
+
+    /// The piece type of the capturing piece (of the retracting color).
+    #[inline]
+    pub fn capturer(&self) -> Piece {
+        self.capturer
+    }
+
+    /// The exploded neighbors: their squares, piece types and colors.
+    pub fn neighbors(&self) -> impl Iterator<Item = (Square, Piece, Color)> + '_ {
+        self.neighbors.iter().flatten().copied()
+    }
+
+    /// Adds an exploded neighbor to the explosion. Pawns and kings cannot be
+    /// destroyed by an explosion (the game would have ended after the latter).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the piece is a pawn or a king, or if there is no room for
+    /// the neighbor.
+    pub fn with_neighbor(mut self, square: Square, piece: Piece, color: Color) -> Explosion {
+        assert!(piece != Piece::Pawn && piece != Piece::King);
+        let slot = self.neighbors.iter_mut().find(|n| n.is_none()).unwrap();
+        *slot = Some((square, piece, color));
+        self
+    }
 }
 
 impl ChessRetraction {
@@ -29,6 +86,29 @@ impl ChessRetraction {
             target,
             uncaptured,
             unpromotion,
+            #[cfg(feature = "atomic")]
+            explosion: None,
+        }
+    }
+
+    /// Create a new atomic chess retraction, which un-explodes a capture: the
+    /// capturing piece is retracted from the (empty) `source` square into
+    /// `target`, the `uncaptured` piece reappears on `source` and so do all
+    /// the neighbors destroyed by the explosion.
+    #[cfg(feature = "atomic")]
+    #[inline]
+    pub fn new_explosion(
+        source: Square,
+        target: Square,
+        uncaptured: Piece,
+        explosion: Explosion,
+    ) -> ChessRetraction {
+        ChessRetraction {
+            source,
+            target,
+            uncaptured: Some(uncaptured),
+            unpromotion: false,
+            explosion: Some(explosion),
         }
     }
 
@@ -55,6 +135,49 @@ impl ChessRetraction {
     pub fn unpromotion(&self) -> bool {
         self.unpromotion
     }
+
+    /// The square jumped over by the pawn, if the retraction (of the given
+    /// board) undoes a double pawn push. The jumped square is empty in the
+    /// position before the move; consumers may use it to validate en passant
+    /// related conditions (e.g. that the move made an en passant capture
+    /// possible).
+    ///
+    /// ```
+    /// use chess::Square;
+    /// use sherlock::{ChessRetraction, RetractableBoard};
+    ///
+    /// let board = RetractableBoard::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3").unwrap();
+    /// let r = ChessRetraction::new(Square::E4, Square::E2, None, false);
+    /// assert_eq!(r.jumped_square(&board), Some(Square::E3));
+    ///
+    /// let r = ChessRetraction::new(Square::E4, Square::E3, None, false);
+    /// assert_eq!(r.jumped_square(&board), None);
+    /// ```
+    pub fn jumped_square(&self, board: &RetractableBoard) -> Option<Square> {
+        let rank_distance = (self.source.get_rank().to_index() as i32
+            - self.target.get_rank().to_index() as i32)
+            .abs();
+        if board.piece_on(self.source) == Some(Piece::Pawn)
+            && !self.unpromotion
+            && self.uncaptured.is_none()
+            && self.source.get_file() == self.target.get_file()
+            && rank_distance == 2
+        {
+            let rank = Rank::from_index(
+                (self.source.get_rank().to_index() + self.target.get_rank().to_index()) / 2,
+            );
+            Some(Square::make_square(rank, self.source.get_file()))
+        } else {
+            None
+        }
+    }
+
+    /// The explosion undone by the retraction, if it is an atomic capture.
+    #[cfg(feature = "atomic")]
+    #[inline]
+    pub fn explosion(&self) -> Option<&Explosion> {
+        self.explosion.as_ref()
+    }
 }
 
 impl fmt::Display for ChessRetraction {
@@ -219,13 +219,14 @@ impl PieceType for PawnType {
                 };
 
             // pawn unpushes
-            let mut targets = BitBoard::from_square(src.ubackward(retracting_color));
+            let jumped = src.ubackward(retracting_color);
+            let mut targets = BitBoard::from_square(jumped);
+            // a double unpush requires the jumped square to be empty as well
             if src.get_rank() == retracting_color.to_fourth_rank()
                 && board.en_passant() == EnPassantFlag::Any
+                && BitBoard::from_square(jumped) & combined == EMPTY
             {
-                targets |= BitBoard::from_square(
-                    src.ubackward(retracting_color).ubackward(retracting_color),
-                );
+                targets |= BitBoard::from_square(jumped.ubackward(retracting_color));
             }
             targets &= !combined & !check_mask & !first_rank & other_checker_ray & mask;
             if BitBoard::from_square(src) & pinned != EMPTY {
@@ -0,0 +1,242 @@
+//! Explanations on why a retraction is not available on a board.
+
+use chess::{
+    between, get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rank,
+    get_rook_moves, BitBoard, CastleRights, Color, File, Piece, Rank, Square, EMPTY,
+};
+
+use super::{ChessRetraction, RetractionGen, UncapturedCandidates};
+use crate::{
+    utils::{is_attacked, PROMOTION_RANKS},
+    EnPassantFlag, RetractableBoard,
+};
+
+/// The reason why a retraction is not available on a board (see
+/// [`explain_no_retraction`]).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RetractionRejection {
+    /// The retraction is not rejected, it is available on the board.
+    Accepted,
+    /// The source square does not contain a piece of the retracting player.
+    NoRetractingPiece,
+    /// The target square is occupied.
+    TargetOccupied,
+    /// The en-passant information of the board is inconsistent with the
+    /// retraction: either the last move must have been a specific double pawn
+    /// push, or it cannot have been a double pawn push.
+    EnPassantMismatch,
+    /// The retraction is declared as an unpromotion, but the retracting piece
+    /// cannot be a promoted piece or is not on the promotion rank.
+    UnpromotionMismatch,
+    /// The retracting piece cannot have moved from the target square to the
+    /// source square (e.g. a bishop retracting along a file).
+    IllegalMovement,
+    /// The uncaptured piece cannot have been captured on the source square
+    /// (e.g. a king, a pawn on the last rank or a piece that would exceed the
+    /// material of its player).
+    ImpossibleUncapture,
+    /// The retraction must uncapture a piece (e.g. a diagonal pawn retraction
+    /// that is not an en-passant uncapture).
+    MissingUncapture,
+    /// The retraction cannot uncapture a piece (e.g. a pawn push or castling).
+    ForbiddenUncapture,
+    /// The retraction would leave the king of the non-retracting player in
+    /// check, with the retracting player to move.
+    ImpossibleCheck,
+    /// The retraction is impossible for any other reason (e.g. an uncastling
+    /// without rooks in their castling squares).
+    Other,
+}
+
+/// Explains why the given retraction is not available on the given board,
+/// returning [`RetractionRejection::Accepted`] if it actually is.
+///
+/// The conditions are checked in the order in which [`RetractionRejection`]
+/// lists them, the first one that fails is reported.
+///
+/// ```
+/// use chess::{Piece, Square};
+/// use sherlock::{explain_no_retraction, ChessRetraction, RetractableBoard, RetractionRejection};
+///
+/// let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 b - -")?;
+/// let retraction = |source, target, uncaptured| {
+///     explain_no_retraction(
+///         &board,
+///         ChessRetraction::new(source, target, uncaptured, false),
+///     )
+/// };
+///
+/// assert_eq!(
+///     retraction(Square::A1, Square::A5, None),
+///     RetractionRejection::Accepted
+/// );
+/// assert_eq!(
+///     retraction(Square::A1, Square::E1, None),
+///     RetractionRejection::TargetOccupied
+/// );
+/// assert_eq!(
+///     retraction(Square::A1, Square::B2, None),
+///     RetractionRejection::IllegalMovement
+/// );
+/// assert_eq!(
+///     retraction(Square::A1, Square::A8, None),
+///     RetractionRejection::ImpossibleCheck
+/// );
+/// assert_eq!(
+///     retraction(Square::A1, Square::A5, Some(Piece::King)),
+///     RetractionRejection::ImpossibleUncapture
+/// );
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn explain_no_retraction(
+    board: &RetractableBoard,
+    candidate: ChessRetraction,
+) -> RetractionRejection {
+    use RetractionRejection::*;
+
+    let source = candidate.source();
+    let target = candidate.target();
+    let retracting_color = !board.side_to_move();
+    let combined = *board.combined();
+
+    if RetractionGen::retractions_of(board, source).any(|r| r == candidate) {
+        return Accepted;
+    }
+
+    let source_bb = BitBoard::from_square(source);
+    let piece = match board.piece_on(source) {
+        Some(piece) if board.color_combined(retracting_color) & source_bb != EMPTY => piece,
+        _ => return NoRetractingPiece,
+    };
+
+    if BitBoard::from_square(target) & combined != EMPTY {
+        return TargetOccupied;
+    }
+
+    let file_distance = source
+        .get_file()
+        .to_index()
+        .abs_diff(target.get_file().to_index());
+    let rank_distance = source
+        .get_rank()
+        .to_index()
+        .abs_diff(target.get_rank().to_index());
+    let moved_piece = if candidate.unpromotion() {
+        Piece::Pawn
+    } else {
+        piece
+    };
+    let double_push = moved_piece == Piece::Pawn && rank_distance == 2;
+    match board.en_passant() {
+        EnPassantFlag::Some(_) => return EnPassantMismatch,
+        EnPassantFlag::None if double_push => return EnPassantMismatch,
+        _ => (),
+    }
+
+    if candidate.unpromotion()
+        && (piece == Piece::Pawn
+            || piece == Piece::King
+            || source.get_rank() != retracting_color.to_their_backrank())
+    {
+        return UnpromotionMismatch;
+    }
+
+    // whether the piece can have moved from the target to the source square
+    let uncastling = piece == Piece::King
+        && board.castle_rights(retracting_color) == CastleRights::NoRights
+        && source.get_rank() == retracting_color.to_my_backrank()
+        && target == Square::make_square(source.get_rank(), File::E)
+        && file_distance == 2;
+    let is_reachable = match moved_piece {
+        Piece::Pawn => {
+            let backward = |square: Square| square.backward(retracting_color);
+            let unpush = backward(source) == Some(target)
+                || (double_push
+                    && source.get_rank() == retracting_color.to_fourth_rank()
+                    && backward(source).and_then(backward) == Some(target)
+                    && between(source, target) & combined == EMPTY);
+            let uncapture = get_pawn_attacks(source, !retracting_color, !EMPTY)
+                & BitBoard::from_square(target)
+                != EMPTY;
+            (unpush || uncapture) && BitBoard::from_square(target) & PROMOTION_RANKS == EMPTY
+        }
+        Piece::Knight => get_knight_moves(source) & BitBoard::from_square(target) != EMPTY,
+        Piece::Bishop => {
+            get_bishop_moves(source, combined) & BitBoard::from_square(target) != EMPTY
+        }
+        Piece::Rook => get_rook_moves(source, combined) & BitBoard::from_square(target) != EMPTY,
+        Piece::Queen => {
+            (get_bishop_moves(source, combined) | get_rook_moves(source, combined))
+                & BitBoard::from_square(target)
+                != EMPTY
+        }
+        Piece::King => {
+            uncastling || get_king_moves(source) & BitBoard::from_square(target) != EMPTY
+        }
+    };
+    if !is_reachable {
+        return IllegalMovement;
+    }
+
+    if let Some(uncaptured) = candidate.uncaptured() {
+        let candidates = UncapturedCandidates::new(board);
+        if !candidates.may_uncapture(board.side_to_move(), uncaptured, source) {
+            return ImpossibleUncapture;
+        }
+        if uncastling || (moved_piece == Piece::Pawn && file_distance == 0) {
+            return ForbiddenUncapture;
+        }
+    } else if moved_piece == Piece::Pawn && file_distance == 1 {
+        // only en-passant uncaptures may leave the source square empty
+        let en_passant_rank = match retracting_color {
+            Color::White => Rank::Sixth,
+            Color::Black => Rank::Third,
+        };
+        let un_en_passant = !candidate.unpromotion()
+            && source_bb & get_rank(en_passant_rank) != EMPTY
+            && BitBoard::from_square(source.ubackward(retracting_color)) & combined == EMPTY
+            && BitBoard::from_square(source.uforward(retracting_color)) & combined == EMPTY;
+        if !un_en_passant {
+            return MissingUncapture;
+        }
+    }
+
+    let predecessor = board.make_retraction_new(candidate);
+    let king_square = predecessor.king_square(board.side_to_move());
+    if is_attacked(&predecessor, king_square, retracting_color) {
+        return ImpossibleCheck;
+    }
+
+    Other
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_en_passant_uncapture() {
+        let un_en_passant = ChessRetraction::new(E6, D5, None, false);
+        let rejection = |fen| {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            explain_no_retraction(&board, un_en_passant)
+        };
+
+        assert_eq!(
+            rejection("4k3/8/4P3/8/8/8/8/4K3 b - -"),
+            RetractionRejection::Accepted
+        );
+        // the white pawn on D5 would give check to the king on C6
+        assert_eq!(
+            rejection("8/8/2k1P3/8/8/8/8/4K3 b - -"),
+            RetractionRejection::ImpossibleCheck
+        );
+        // the uncaptured pawn cannot have come from E7
+        assert_eq!(
+            rejection("4k3/4p3/4P3/8/8/8/8/4K3 b - -"),
+            RetractionRejection::MissingUncapture
+        );
+    }
+}
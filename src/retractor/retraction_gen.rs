@@ -1,7 +1,11 @@
-use std::cmp::max;
-
+#[cfg(feature = "inline-retractions")]
 use arrayvec::ArrayVec;
-use chess::{get_file, get_rank, BitBoard, Piece, Square, ALL_SQUARES, EMPTY};
+use chess::{
+    between, get_bishop_moves, get_file, get_king_moves, get_knight_moves, get_rank,
+    get_rook_moves, BitBoard, Color, Piece, Square, ALL_COLORS, ALL_PIECES, ALL_SQUARES, EMPTY,
+    NUM_COLORS,
+};
+#[cfg(feature = "inline-retractions")]
 use nodrop::NoDrop;
 
 use super::{
@@ -12,11 +16,77 @@ use super::{
     },
 };
 use crate::{
-    rules::{origins_of_piece_on, COLOR_ORIGINS},
-    utils::{DARK_SQUARES, LIGHT_SQUARES, PROMOTION_RANKS},
+    rules::{lower_bound_promoted, origins_of_piece_on, COLOR_ORIGINS},
+    utils::{is_attacked, DARK_SQUARES, LIGHT_SQUARES, PROMOTION_RANKS},
     Analysis, EnPassantFlag, RetractableBoard,
 };
 
+/// A constraint that limits the retractions available on a board, making it
+/// worth analyzing its predecessors (see
+/// [`RetractionGen::limiting_constraint`]).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RetractionConstraint {
+    /// A player is in check.
+    Check,
+    /// The last move must have been a double pawn push.
+    EnPassant,
+    /// The given player has no officer retractions (in the position where
+    /// they are to retract).
+    NoOfficerRetractions(Color),
+}
+
+impl RetractionConstraint {
+    /// The squares involved in the constraint on the given board: a retraction
+    /// can only relax the constraint if it retracts a piece from or to one of
+    /// them (see [`RetractionConstraint::is_touched_by`]).
+    ///
+    /// These are the checked king, the checkers and the squares between them
+    /// for a check, the file of the double-pushed pawn for an en-passant
+    /// constraint and the officers of the blocked player together with the
+    /// squares they attack otherwise.
+    pub fn squares(&self, board: &RetractableBoard) -> BitBoard {
+        match self {
+            RetractionConstraint::Check => {
+                let king = board.king_square(board.side_to_move());
+                board
+                    .checkers()
+                    .fold(BitBoard::from_square(king), |acc, checker| {
+                        acc | BitBoard::from_square(checker) | between(king, checker)
+                    })
+            }
+            RetractionConstraint::EnPassant => match board.en_passant() {
+                EnPassantFlag::Some(square) => get_file(square.get_file()),
+                _ => EMPTY,
+            },
+            RetractionConstraint::NoOfficerRetractions(color) => {
+                let occupied = *board.combined();
+                let officers = board.color_combined(*color) & !board.pieces(Piece::Pawn);
+                officers.fold(officers, |acc, square| {
+                    let attacks = match board.piece_on(square) {
+                        Some(Piece::Knight) => get_knight_moves(square),
+                        Some(Piece::Bishop) => get_bishop_moves(square, occupied),
+                        Some(Piece::Rook) => get_rook_moves(square, occupied),
+                        Some(Piece::Queen) => {
+                            get_bishop_moves(square, occupied) | get_rook_moves(square, occupied)
+                        }
+                        _ => get_king_moves(square),
+                    };
+                    acc | attacks
+                })
+            }
+        }
+    }
+
+    /// Whether the given retraction touches the given squares of a constraint
+    /// (see [`RetractionConstraint::squares`]).
+    #[inline]
+    pub fn is_touched_by(squares: BitBoard, retraction: &ChessRetraction) -> bool {
+        let touched =
+            BitBoard::from_square(retraction.source()) | BitBoard::from_square(retraction.target());
+        squares & touched != EMPTY
+    }
+}
+
 /// The kind of uncapture of a [SourceAndTargets] object, specifying whether the
 /// uncapture is optional, necessary, forbidden or an en-passant uncapture.
 #[derive(Copy, Clone, PartialEq, PartialOrd)]
@@ -76,9 +146,56 @@ impl SourceAndTargets {
 /// TODO: Double-check this and reduce it by modifying the `SourceAndTargets`
 /// type. We could have several target bitboard depending on the uncapture kind
 /// and remove the uncapture kind field.
+#[cfg(feature = "inline-retractions")]
 const BUFFER_SIZE: usize = 47;
+#[cfg(feature = "inline-retractions")]
 pub(crate) type RetractionList = NoDrop<ArrayVec<SourceAndTargets, BUFFER_SIZE>>;
 
+#[cfg(feature = "inline-retractions")]
+#[inline(always)]
+fn new_retraction_list() -> RetractionList {
+    NoDrop::new(ArrayVec::new())
+}
+
+/// A list of retractions on the heap, for builds without the
+/// `inline-retractions` feature. It offers the subset of the interface of
+/// `ArrayVec` used by the generator.
+#[cfg(not(feature = "inline-retractions"))]
+#[derive(Default)]
+pub(crate) struct RetractionList(Vec<SourceAndTargets>);
+
+#[cfg(not(feature = "inline-retractions"))]
+impl RetractionList {
+    /// Appends the given element; unlike its `ArrayVec` counterpart, this is
+    /// always safe.
+    #[inline(always)]
+    pub(crate) unsafe fn push_unchecked(&mut self, element: SourceAndTargets) {
+        self.0.push(element)
+    }
+}
+
+#[cfg(not(feature = "inline-retractions"))]
+impl std::ops::Deref for RetractionList {
+    type Target = Vec<SourceAndTargets>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(not(feature = "inline-retractions"))]
+impl std::ops::DerefMut for RetractionList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(not(feature = "inline-retractions"))]
+#[inline(always)]
+fn new_retraction_list() -> RetractionList {
+    RetractionList::default()
+}
+
 /// How many pieces can be uncaptured?
 const NUM_UNCAPTURES: usize = 6;
 
@@ -92,6 +209,9 @@ const UNCAPTURES: [Option<Piece>; NUM_UNCAPTURES] = [
     Some(Piece::Queen),
 ];
 
+/// The index of pawns in [UNCAPTURES].
+const PAWN_UNCAPTURE_INDEX: usize = 1;
+
 /// An incremental retractions generator.
 ///
 /// This structure allows us to enumerate all retractions through an iterator
@@ -106,27 +226,24 @@ pub struct RetractionGen {
     uncaptured_index: usize,
 }
 
-/// A simple routine to initialize the "uncaptured candidates" of a given board.
-/// That is, a `BitBoard` for every element in [UNCAPTURES] specifying the
-/// squares where the relevant piece type (or `None`) may have been uncaptured.
+/// A simple routine to initialize the "uncaptured candidates" of the given
+/// color on a given board. That is, a `BitBoard` for every element in
+/// [UNCAPTURES] specifying the squares where the relevant piece type (or
+/// `None`) of the given color may have been uncaptured.
 ///
 /// This simple routine just attends to material information (e.g. if all 8
 /// white pawns and 2 white knights are on the board, we should not uncapture
 /// white knights on any square). This information can be further refined
 /// through a Sherlock analysis.
-fn uncaptured_candidates(board: &RetractableBoard) -> [BitBoard; NUM_UNCAPTURES] {
-    let color_pieces = board.color_combined(board.side_to_move());
+fn uncaptured_candidates(board: &RetractableBoard, color: Color) -> [BitBoard; NUM_UNCAPTURES] {
+    let color_pieces = board.color_combined(color);
     let pawns = board.pieces(Piece::Pawn) & color_pieces;
     let knights = board.pieces(Piece::Knight) & color_pieces;
     let bishops = board.pieces(Piece::Bishop) & color_pieces;
     let rooks = board.pieces(Piece::Rook) & color_pieces;
     let queens = board.pieces(Piece::Queen) & color_pieces;
 
-    let lower_bound_promoted = max(0, knights.popcnt() as i32 - 2)
-        + max(0, (bishops & LIGHT_SQUARES).popcnt() as i32 - 1)
-        + max(0, (bishops & DARK_SQUARES).popcnt() as i32 - 1)
-        + max(0, rooks.popcnt() as i32 - 2)
-        + max(0, queens.popcnt() as i32 - 1);
+    let lower_bound_promoted = lower_bound_promoted(board, color);
 
     let lower_bound_nb_pawn_souls = pawns.popcnt() + lower_bound_promoted as u32;
 
@@ -168,26 +285,96 @@ fn uncaptured_candidates(board: &RetractableBoard) -> [BitBoard; NUM_UNCAPTURES]
     ]
 }
 
+/// The "uncaptured candidates" (see [uncaptured_candidates]) of both players.
+///
+/// The candidates of each player only depend on their material on the board,
+/// which uncaptures and un-en-passant retractions alter for the player who is
+/// not retracting and unpromotions alter for the retracting player. This
+/// structure keeps the candidates of both sides, so that only the ones whose
+/// material changed are recomputed along a retraction path. The result is the
+/// same as recomputing them from scratch on every position.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct UncapturedCandidates([[BitBoard; NUM_UNCAPTURES]; NUM_COLORS]);
+
+impl UncapturedCandidates {
+    /// Computes the candidates of both players on the given board.
+    pub(crate) fn new(board: &RetractableBoard) -> Self {
+        UncapturedCandidates(ALL_COLORS.map(|color| uncaptured_candidates(board, color)))
+    }
+
+    /// The candidates for the pieces of the given color.
+    pub(crate) fn of(&self, color: Color) -> [BitBoard; NUM_UNCAPTURES] {
+        self.0[color.to_index()]
+    }
+
+    /// Tells whether the given piece of the given color may be uncaptured on
+    /// the given square.
+    pub(crate) fn may_uncapture(&self, color: Color, piece: Piece, square: Square) -> bool {
+        UNCAPTURES
+            .iter()
+            .position(|uncaptured| *uncaptured == Some(piece))
+            .is_some_and(|index| {
+                self.0[color.to_index()][index] & BitBoard::from_square(square) != EMPTY
+            })
+    }
+
+    /// Updates the candidates after the given retraction, which led to `board`.
+    /// Only the material of the player whose piece was uncaptured and, on
+    /// unpromotions, the material of the retracting player can have changed, so
+    /// only those candidates are recomputed.
+    pub(crate) fn update(&mut self, board: &RetractableBoard, retraction: &ChessRetraction) {
+        let retracting_color = board.side_to_move();
+        let un_en_passant = retraction.uncaptured().is_none()
+            && board.piece_on(retraction.target()) == Some(Piece::Pawn)
+            && retraction.source().get_file() != retraction.target().get_file();
+        if retraction.uncaptured().is_some() || un_en_passant {
+            self.0[(!retracting_color).to_index()] =
+                uncaptured_candidates(board, !retracting_color);
+        }
+        if retraction.unpromotion() {
+            self.0[retracting_color.to_index()] = uncaptured_candidates(board, retracting_color);
+        }
+    }
+}
+
 impl RetractionGen {
     /// Create a new `RetractionGen` structure, only generating legal
     /// retractions, i.e. retractions that do not leave the king of the
     /// non-retracting player in check.
     #[inline(always)]
     pub fn new_legal(board: &RetractableBoard) -> Self {
+        Self::new_legal_with_candidates(board, &UncapturedCandidates::new(board))
+    }
+
+    /// Like [RetractionGen::new_legal], but using the given (possibly
+    /// incrementally computed) uncaptured candidates.
+    #[inline(always)]
+    pub(crate) fn new_legal_with_candidates(
+        board: &RetractableBoard,
+        candidates: &UncapturedCandidates,
+    ) -> Self {
         RetractionGen {
             retractions: RetractionGen::enumerate_retractions(board),
             index: 0,
             targets_mask: !EMPTY,
-            uncaptured_candidates: uncaptured_candidates(board),
+            uncaptured_candidates: candidates.of(board.side_to_move()),
             uncaptured_index: 0,
         }
     }
 
     /// Refines the iterator on moves with the information provided from the
-    /// board `Analysis`.
-    /// TODO: Can we do better? ATM this routine is very simple.
+    /// board `Analysis`: a piece can only be uncaptured on a square if some of
+    /// its candidate missing origins may have reached such square and been
+    /// captured there, and only the pieces that may have performed the last
+    /// move (see [`Analysis::last_movers`]) are retracted.
     #[inline(always)]
     pub fn refine_iterator(&mut self, analysis: &Analysis) {
+        for retraction in self.retractions.iter_mut() {
+            if analysis.last_movers() & BitBoard::from_square(retraction.source) == EMPTY {
+                retraction.targets = EMPTY;
+            }
+        }
+
         // Only the pieces of the side to move matter.
         let color = analysis.board.side_to_move();
         for (i, uncaptured_piece) in UNCAPTURES.iter().enumerate() {
@@ -202,11 +389,18 @@ impl RetractionGen {
                 }
                 self.uncaptured_candidates[i] &= destinies_of_piece_origins;
 
+                // The piece uncaptured on a square must be a missing piece that may
+                // have reached such square and been captured there.
                 let mut piece_uncaptured = EMPTY;
                 for square in ALL_SQUARES {
-                    if analysis.missing(color).all() & origins_of_piece_on(*piece, square) != EMPTY
-                    {
-                        piece_uncaptured ^= BitBoard::from_square(square);
+                    let square_bb = BitBoard::from_square(square);
+                    let candidates =
+                        analysis.missing(color).all() & origins_of_piece_on(*piece, square);
+                    if candidates.into_iter().any(|origin| {
+                        analysis.reachable(origin) & analysis.death_squares(origin) & square_bb
+                            != EMPTY
+                    }) {
+                        piece_uncaptured ^= square_bb;
                     }
                 }
                 self.uncaptured_candidates[i] &= piece_uncaptured;
@@ -228,48 +422,142 @@ impl RetractionGen {
         }
     }
 
+    /// Create a new `RetractionGen` structure, only generating the legal
+    /// retractions of the piece on the given square (none if the square does
+    /// not contain a piece of the retracting player). Only the retractions of
+    /// the relevant piece types are computed.
+    ///
+    /// ```
+    /// use chess::Square;
+    /// use sherlock::{RetractableBoard, RetractionGen};
+    ///
+    /// let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 b - -")?;
+    /// let retractions: Vec<_> = RetractionGen::retractions_of(&board, Square::A1).collect();
+    /// assert!(!retractions.is_empty());
+    /// assert!(retractions.iter().all(|r| r.source() == Square::A1));
+    ///
+    /// // it is white who retracts
+    /// assert_eq!(RetractionGen::retractions_of(&board, Square::E8).count(), 0);
+    /// # Ok::<(), chess::Error>(())
+    /// ```
+    pub fn retractions_of(board: &RetractableBoard, square: Square) -> Self {
+        RetractionGen {
+            retractions: RetractionGen::enumerate_retractions_of(board, square),
+            index: 0,
+            targets_mask: !EMPTY,
+            uncaptured_candidates: uncaptured_candidates(board, board.side_to_move()),
+            uncaptured_index: 0,
+        }
+    }
+
     #[inline(always)]
     fn enumerate_retractions(board: &RetractableBoard) -> RetractionList {
-        let checkers = *board.checkers();
-        let mask = !board.color_combined(board.side_to_move());
-        let mut retraction_list = NoDrop::new(ArrayVec::<SourceAndTargets, BUFFER_SIZE>::new());
+        let mut retraction_list = new_retraction_list();
+
+        if let EnPassantFlag::Some(src) = board.en_passant() {
+            if Self::is_consistent_double_push(board, src) {
+                unsafe {
+                    retraction_list.push_unchecked(SourceAndTargets::new(
+                        src,
+                        get_file(src.get_file())
+                            & get_rank((!board.side_to_move()).to_second_rank()),
+                        UnCaptureKind::Forbidden,
+                        false,
+                    ));
+                }
+            }
+            return retraction_list;
+        }
+
+        for piece in ALL_PIECES {
+            Self::legals_of(&mut retraction_list, board, piece);
+        }
+        retraction_list
+    }
+
+    /// Tells whether the last move on the given board can have been a double
+    /// push of the pawn on `src`, as required by its en passant flag (e.g.
+    /// after retracting an en passant capture): the squares the pawn jumped
+    /// from and over must be empty, and the push cannot have blocked an attack
+    /// on the king of the opponent, who was not in check before the push.
+    fn is_consistent_double_push(board: &RetractableBoard, src: Square) -> bool {
+        let retracting_color = !board.side_to_move();
+        let jumped = src.ubackward(retracting_color);
+        let target = jumped.ubackward(retracting_color);
+        if board.combined() & (BitBoard::from_square(jumped) | BitBoard::from_square(target))
+            != EMPTY
+        {
+            return false;
+        }
+        let new_board = board.make_retraction_new(ChessRetraction::new(src, target, None, false));
+        let king_square = new_board.king_square(board.side_to_move());
+        !is_attacked(&new_board, king_square, retracting_color)
+    }
+
+    /// Enumerates the retractions of the piece on the given square.
+    fn enumerate_retractions_of(board: &RetractableBoard, square: Square) -> RetractionList {
+        let mut retraction_list = new_retraction_list();
+        let retracting_color = !board.side_to_move();
+        let square_bb = BitBoard::from_square(square);
+        let piece = match board.piece_on(square) {
+            Some(piece) if board.color_combined(retracting_color) & square_bb != EMPTY => piece,
+            _ => return retraction_list,
+        };
 
         if let EnPassantFlag::Some(src) = board.en_passant() {
-            unsafe {
-                retraction_list.push_unchecked(SourceAndTargets::new(
-                    src,
-                    get_file(src.get_file()) & get_rank((!board.side_to_move()).to_second_rank()),
-                    UnCaptureKind::Forbidden,
-                    false,
-                ));
+            if src == square {
+                return Self::enumerate_retractions(board);
             }
             return retraction_list;
         }
 
+        // pieces on the last rank may also retract as unpromotions
+        Self::legals_of(&mut retraction_list, board, piece);
+        if piece != Piece::Pawn
+            && square_bb & get_rank(retracting_color.to_their_backrank()) != EMPTY
+        {
+            Self::legals_of(&mut retraction_list, board, Piece::Pawn);
+        }
+        retraction_list.retain(|retraction| retraction.source == square);
+        retraction_list
+    }
+
+    /// Appends the legal retractions of the pieces of the given type to the
+    /// given list.
+    #[inline(always)]
+    fn legals_of(retraction_list: &mut RetractionList, board: &RetractableBoard, piece: Piece) {
+        let checkers = *board.checkers();
+        let mask = !board.color_combined(board.side_to_move());
+
         if checkers == EMPTY {
-            PawnType::legals::<NotInCheck>(&mut retraction_list, board, mask);
-            KnightType::legals::<NotInCheck>(&mut retraction_list, board, mask);
-            BishopType::legals::<NotInCheck>(&mut retraction_list, board, mask);
-            RookType::legals::<NotInCheck>(&mut retraction_list, board, mask);
-            QueenType::legals::<NotInCheck>(&mut retraction_list, board, mask);
-            KingType::legals::<NotInCheck>(&mut retraction_list, board, mask);
+            match piece {
+                Piece::Pawn => PawnType::legals::<NotInCheck>(retraction_list, board, mask),
+                Piece::Knight => KnightType::legals::<NotInCheck>(retraction_list, board, mask),
+                Piece::Bishop => BishopType::legals::<NotInCheck>(retraction_list, board, mask),
+                Piece::Rook => RookType::legals::<NotInCheck>(retraction_list, board, mask),
+                Piece::Queen => QueenType::legals::<NotInCheck>(retraction_list, board, mask),
+                Piece::King => KingType::legals::<NotInCheck>(retraction_list, board, mask),
+            }
         } else if checkers.popcnt() == 1 {
-            PawnType::legals::<InSimpleCheck>(&mut retraction_list, board, mask);
-            KnightType::legals::<InSimpleCheck>(&mut retraction_list, board, mask);
-            BishopType::legals::<InSimpleCheck>(&mut retraction_list, board, mask);
-            RookType::legals::<InSimpleCheck>(&mut retraction_list, board, mask);
-            QueenType::legals::<InSimpleCheck>(&mut retraction_list, board, mask);
-            KingType::legals::<InSimpleCheck>(&mut retraction_list, board, mask);
+            match piece {
+                Piece::Pawn => PawnType::legals::<InSimpleCheck>(retraction_list, board, mask),
+                Piece::Knight => KnightType::legals::<InSimpleCheck>(retraction_list, board, mask),
+                Piece::Bishop => BishopType::legals::<InSimpleCheck>(retraction_list, board, mask),
+                Piece::Rook => RookType::legals::<InSimpleCheck>(retraction_list, board, mask),
+                Piece::Queen => QueenType::legals::<InSimpleCheck>(retraction_list, board, mask),
+                Piece::King => KingType::legals::<InSimpleCheck>(retraction_list, board, mask),
+            }
         } else if checkers.popcnt() == 2 {
-            PawnType::legals::<InDoubleCheck>(&mut retraction_list, board, mask);
-            KnightType::legals::<InDoubleCheck>(&mut retraction_list, board, mask);
-            BishopType::legals::<InDoubleCheck>(&mut retraction_list, board, mask);
-            RookType::legals::<InDoubleCheck>(&mut retraction_list, board, mask);
-            // King moves cannot deliver double checks and I bet we can forget
-            // about queens too. Double check this (literally)!
+            match piece {
+                Piece::Pawn => PawnType::legals::<InDoubleCheck>(retraction_list, board, mask),
+                Piece::Knight => KnightType::legals::<InDoubleCheck>(retraction_list, board, mask),
+                Piece::Bishop => BishopType::legals::<InDoubleCheck>(retraction_list, board, mask),
+                Piece::Rook => RookType::legals::<InDoubleCheck>(retraction_list, board, mask),
+                // King moves cannot deliver double checks and I bet we can forget
+                // about queens too. Double check this (literally)!
+                Piece::Queen | Piece::King => (),
+            }
         }
-
-        retraction_list
     }
 
     /// Returns `true` iff any of the following conditions holds:
@@ -279,15 +567,22 @@ impl RetractionGen {
     ///    to retract.
     #[inline(always)]
     pub fn is_limited_in_retractions(board: &RetractableBoard) -> bool {
+        Self::limiting_constraint(board).is_some()
+    }
+
+    /// The constraint that limits the retractions on the given board, if any
+    /// (see [`RetractionGen::is_limited_in_retractions`]).
+    pub fn limiting_constraint(board: &RetractableBoard) -> Option<RetractionConstraint> {
         if board.checkers() != &EMPTY {
-            return true;
+            return Some(RetractionConstraint::Check);
         }
 
         if let EnPassantFlag::Some(_) = board.en_passant() {
-            return true;
+            return Some(RetractionConstraint::EnPassant);
         }
 
-        let mut retraction_list = NoDrop::new(ArrayVec::<SourceAndTargets, BUFFER_SIZE>::new());
+        let candidates = UncapturedCandidates::new(board);
+        let mut retraction_list = new_retraction_list();
         let mask = !board.color_combined(board.side_to_move());
         KnightType::legals::<NotInCheck>(&mut retraction_list, board, mask);
         BishopType::legals::<NotInCheck>(&mut retraction_list, board, mask);
@@ -299,18 +594,20 @@ impl RetractionGen {
             retractions: retraction_list,
             index: 0,
             targets_mask: !EMPTY,
-            uncaptured_candidates: uncaptured_candidates(board),
+            uncaptured_candidates: candidates.of(board.side_to_move()),
             uncaptured_index: 0,
         };
 
         if iterator.next().is_none() {
-            return true;
+            return Some(RetractionConstraint::NoOfficerRetractions(
+                !board.side_to_move(),
+            ));
         }
 
         let mut flipped = *board;
         flipped.flip();
 
-        let mut retraction_list = NoDrop::new(ArrayVec::<SourceAndTargets, BUFFER_SIZE>::new());
+        let mut retraction_list = new_retraction_list();
         let mask = !flipped.color_combined(flipped.side_to_move());
         KnightType::legals::<NotInCheck>(&mut retraction_list, &flipped, mask);
         BishopType::legals::<NotInCheck>(&mut retraction_list, &flipped, mask);
@@ -322,15 +619,17 @@ impl RetractionGen {
             retractions: retraction_list,
             index: 0,
             targets_mask: !EMPTY,
-            uncaptured_candidates: uncaptured_candidates(&flipped),
+            uncaptured_candidates: candidates.of(flipped.side_to_move()),
             uncaptured_index: 0,
         };
 
         if iterator.next().is_none() {
-            return true;
+            return Some(RetractionConstraint::NoOfficerRetractions(
+                !flipped.side_to_move(),
+            ));
         }
 
-        false
+        None
     }
 }
 
@@ -353,6 +652,10 @@ impl Iterator for RetractionGen {
 
         if retraction.uncapture_kind == UnCaptureKind::UnEnPassant {
             retraction.targets ^= BitBoard::from_square(target);
+            // un-en-passant retractions make an opponent pawn reappear
+            if self.uncaptured_candidates[PAWN_UNCAPTURE_INDEX] == EMPTY {
+                return self.next();
+            }
             return Some(ChessRetraction::new(retraction.source, target, None, false));
         };
 
@@ -383,6 +686,39 @@ impl Iterator for RetractionGen {
     }
 }
 
+/// The number of legal retractions available on the given board, a measure
+/// of its branching factor in a retraction search.
+///
+/// ```
+/// use sherlock::{count_retractions, RetractableBoard};
+///
+/// // the black king can retract from A7 or B8, uncapturing nothing or an officer
+/// let board = RetractableBoard::from_fen("k7/8/2K5/8/8/8/8/8 w - -")?;
+/// assert_eq!(count_retractions(&board), 10);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn count_retractions(board: &RetractableBoard) -> usize {
+    RetractionGen::new_legal(board).count()
+}
+
+/// Like [`count_retractions`], but only counting the retractions that are
+/// consistent with the given analysis of the board (see
+/// [`RetractionGen::refine_iterator`]), which is never more.
+///
+/// ```
+/// use sherlock::{analyze, count_retractions, count_retractions_with, RetractableBoard};
+///
+/// let board = RetractableBoard::from_fen("k7/8/2K5/8/8/8/8/8 w - -")?;
+/// let analysis = analyze(&board);
+/// assert!(count_retractions_with(&board, &analysis) <= count_retractions(&board));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn count_retractions_with(board: &RetractableBoard, analysis: &Analysis) -> usize {
+    let mut retractions = RetractionGen::new_legal(board);
+    retractions.refine_iterator(analysis);
+    retractions.count()
+}
+
 #[cfg(test)]
 use std::str::FromStr;
 
@@ -456,11 +792,175 @@ fn test_nb_retractions() {
         let board = Board::from_str(fen).unwrap();
         let mut retractable_board: RetractableBoard = board.into();
         retractable_board.set_uncertain_ep();
-        let iterable = RetractionGen::new_legal(&retractable_board);
-        let mut cnt = 0;
-        for _r in iterable {
-            cnt += 1;
+        assert_eq!(count_retractions(&retractable_board), *n);
+    })
+}
+
+#[test]
+fn test_uncaptured_candidates_update() {
+    [
+        "2nR3K/pk1Rp1p1/p2p4/P1p5/1Pp4B/2PP2P1/4P2P/n7 b - -",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+        "1k6/6b1/8/8/8/2p5/1K6/8 w - -",
+        "BQRNNRQB/8/1PPPPPPP/8/8/8/8/2k3K1 b - -",
+    ]
+    .iter()
+    .for_each(|fen| {
+        let mut board = RetractableBoard::from_fen(fen).unwrap();
+        board.set_uncertain_ep();
+        let candidates = UncapturedCandidates::new(&board);
+        for r in RetractionGen::new_legal(&board) {
+            let new_board = board.make_retraction_new(r);
+            let mut updated = candidates;
+            updated.update(&new_board, &r);
+            assert_eq!(updated, UncapturedCandidates::new(&new_board));
         }
-        assert_eq!(cnt, *n);
+    })
+}
+
+#[test]
+fn test_double_unpush() {
+    let mut board = RetractableBoard::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - -").unwrap();
+    board.set_uncertain_ep();
+    let unpushes: Vec<_> = RetractionGen::retractions_of(&board, Square::E4)
+        .filter(|r| r.uncaptured().is_none())
+        .collect();
+    assert_eq!(unpushes.len(), 2);
+    let double = unpushes.iter().find(|r| r.target() == Square::E2).unwrap();
+    assert_eq!(double.jumped_square(&board), Some(Square::E3));
+
+    // the pawn cannot have jumped over the knight on E3
+    let mut board = RetractableBoard::from_fen("4k3/8/8/8/4P3/4n3/8/4K3 b - -").unwrap();
+    board.set_uncertain_ep();
+    assert_eq!(
+        RetractionGen::retractions_of(&board, Square::E4)
+            .filter(|r| r.uncaptured().is_none())
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_en_passant_retractions() {
+    [
+        // the last move was d7-d5, which may be retracted
+        ("4k3/8/8/3pP3/8/5K2/8/8 w - d6", 1),
+        // d7-d5 would have left the white king in check from A8
+        ("b3k3/8/8/3pP3/8/5K2/8/8 w - d6", 0),
+        // the D7-square is occupied, so d7-d5 is impossible
+        ("4k3/3n4/8/3pP3/8/5K2/8/8 w - d6", 0),
+        // the last move was b2-b4, after retracting the en passant capture
+        ("8/4k3/8/KP4Pp/pP6/8/8/8 w - h6", 1),
+    ]
+    .iter()
+    .for_each(|(fen, n)| {
+        let board = RetractableBoard::from_fen(fen).unwrap();
+        assert_eq!(count_retractions(&board), *n);
+    });
+
+    // after retracting an en passant capture, the double push must be retracted
+    // next, which requires the jumped square to be empty
+    let board = RetractableBoard::from_fen("4k3/8/4P3/8/8/8/8/4K3 b - -").unwrap();
+    for r in RetractionGen::new_legal(&board) {
+        let new_board = board.make_retraction_new(r);
+        if let EnPassantFlag::Some(src) = new_board.en_passant() {
+            assert_eq!(src, Square::E5);
+            let double_pushes: Vec<_> = RetractionGen::new_legal(&new_board).collect();
+            assert_eq!(double_pushes.len(), 1);
+            assert_eq!(double_pushes[0].target(), Square::E7);
+        }
+    }
+
+    // retracting exd6 e.p. makes a black pawn reappear, which is impossible if
+    // Black has all 8 pawns
+    [
+        ("4k3/ppp1pppp/3P4/8/8/8/8/4K3 b - -", true),
+        ("4k3/ppp1pppp/3P3p/8/8/8/8/4K3 b - -", false),
+    ]
+    .iter()
+    .for_each(|(fen, expected)| {
+        let board = RetractableBoard::from_fen(fen).unwrap();
+        let un_en_passant = RetractionGen::new_legal(&board).any(|r| {
+            r.source() == Square::D6 && r.target() == Square::E5 && r.uncaptured().is_none()
+        });
+        assert_eq!(un_en_passant, *expected);
+    });
+}
+
+#[test]
+fn test_retractions_of() {
+    [
+        "8/8/8/8/4P3/2kp1p2/8/4K2R b K -",
+        "2k2N1R/8/7p/8/8/8/8/R3K3 b Q -",
+        "BQRNNRQB/8/1PPPPPPP/8/8/8/8/2k3K1 b - -",
+        "8/4k3/8/KP4Pp/pP6/8/8/8 w - h6",
+        "3kQ3/8/8/8/8/8/4K3/3R4 b - -",
+    ]
+    .iter()
+    .for_each(|fen| {
+        let mut board = RetractableBoard::from_fen(fen).unwrap();
+        board.set_uncertain_ep();
+        let all: Vec<_> = RetractionGen::new_legal(&board).collect();
+        for square in ALL_SQUARES {
+            let expected: Vec<_> = all.iter().filter(|r| r.source() == square).collect();
+            let retractions: Vec<_> = RetractionGen::retractions_of(&board, square).collect();
+            assert_eq!(retractions.len(), expected.len());
+            assert!(expected.iter().all(|r| retractions.contains(*r)));
+        }
+    })
+}
+
+#[test]
+fn test_constraint_squares() {
+    // the white king and bishop are blocked by their own pawns
+    let board = RetractableBoard::from_fen("k7/8/8/8/8/8/PPP5/KB6 b - -").unwrap();
+    let constraint = RetractionConstraint::NoOfficerRetractions(Color::White);
+    let squares = constraint.squares(&board);
+    assert_eq!(
+        squares,
+        BitBoard::from_square(Square::A1)
+            | BitBoard::from_square(Square::B1)
+            | BitBoard::from_square(Square::A2)
+            | BitBoard::from_square(Square::B2)
+            | BitBoard::from_square(Square::C2)
+    );
+
+    // the rook on D1 gives check to the king on D8, so every retraction either
+    // retracts the rook or discovers its check
+    let board = RetractableBoard::from_fen("3k4/8/8/8/8/8/4K3/3R4 b - -").unwrap();
+    let squares = RetractionConstraint::Check.squares(&board);
+    assert_eq!(squares, get_file(chess::File::D));
+    let retractions: Vec<_> = RetractionGen::new_legal(&board).collect();
+    assert!(retractions.iter().any(|r| r.source() == Square::E2));
+    assert!(retractions
+        .iter()
+        .all(|r| RetractionConstraint::is_touched_by(squares, r)));
+}
+
+#[test]
+fn test_limiting_constraint() {
+    [
+        (
+            "3kQ3/8/8/8/8/8/4K3/3R4 b - -",
+            Some(RetractionConstraint::Check),
+        ),
+        (
+            "8/4k3/8/KP4Pp/pP6/8/8/8 w - h6",
+            Some(RetractionConstraint::EnPassant),
+        ),
+        (
+            "k7/8/8/8/8/8/PPP5/KB6 b - -",
+            Some(RetractionConstraint::NoOfficerRetractions(Color::White)),
+        ),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", None),
+    ]
+    .iter()
+    .for_each(|(fen, expected)| {
+        let board = RetractableBoard::from_fen(fen).unwrap();
+        assert_eq!(RetractionGen::limiting_constraint(&board), *expected);
+        assert_eq!(
+            RetractionGen::is_limited_in_retractions(&board),
+            expected.is_some()
+        );
     })
 }
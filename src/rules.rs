@@ -2,8 +2,72 @@ use std::fmt;
 
 use crate::analysis::Analysis;
 
+/// The version of the contract of [`Rule`]. It is increased whenever the
+/// semantics of the trait, or of the information of an [`Analysis`] that rules
+/// can read and refine, change in a way that may affect existing rules.
+pub const RULE_API_VERSION: u32 = 2;
+
 /// A legality rule, it updates the analysis on the legality of the position,
 /// after deriving new information.
+///
+/// Besides the built-in rules, user-defined rules can take part in the
+/// analysis through [`AnalysisOptions::extra_rules`](crate::AnalysisOptions).
+/// Every rule must be *sound*: `apply` must only derive facts that are true in
+/// every game that reaches the position, and it must only declare the position
+/// illegal (see [`Analysis::set_illegal`]) if no such game exists. Rules are
+/// applied repeatedly, in an unspecified order, until none of them makes
+/// progress, so `apply` must eventually stop reporting progress. See
+/// [`check_rules_soundness`](crate::check_rules_soundness) for testing the
+/// soundness of a rule against random games.
+///
+/// ```
+/// use chess::{BitBoard, CastleRights, Color, Square};
+/// use sherlock::{
+///     analyze_with_options, Analysis, AnalysisOptions, RetractableBoard, Rule, RuleFactory,
+/// };
+///
+/// /// If the white king still has castling rights, it started the game on E1.
+/// #[derive(Debug)]
+/// struct CastlingKingRule {
+///     origins_counter: usize,
+/// }
+///
+/// impl Rule for CastlingKingRule {
+///     fn new() -> Self {
+///         CastlingKingRule {
+///             origins_counter: usize::MAX,
+///         }
+///     }
+///
+///     fn update(&mut self, analysis: &Analysis) {
+///         self.origins_counter = analysis.counters().origins;
+///     }
+///
+///     fn is_applicable(&self, analysis: &Analysis) -> bool {
+///         self.origins_counter != analysis.counters().origins
+///     }
+///
+///     fn apply(&self, analysis: &mut Analysis) -> bool {
+///         let board = analysis.board();
+///         if board.castle_rights(Color::White) == CastleRights::NoRights {
+///             return false;
+///         }
+///         let king = board.king_square(Color::White);
+///         analysis.update_origins(king, BitBoard::from_square(Square::E1))
+///     }
+/// }
+///
+/// static RULES: [RuleFactory; 1] = [|| Box::new(CastlingKingRule::new())];
+/// let options = AnalysisOptions {
+///     extra_rules: &RULES,
+///     ..Default::default()
+/// };
+/// let analysis = analyze_with_options(&RetractableBoard::default(), &options);
+/// assert_eq!(
+///     analysis.origins(Square::E1),
+///     BitBoard::from_square(Square::E1)
+/// );
+/// ```
 pub trait Rule: fmt::Debug {
     /// Initializes the rule state for a given board.
     fn new() -> Self
@@ -21,8 +85,19 @@ pub trait Rule: fmt::Debug {
     /// derived new information.
     /// Returns `true` iff progress has been made.
     fn apply(&self, analysis: &mut Analysis) -> bool;
+
+    /// The name of the rule, e.g. "MaterialRule". By default, the name of the
+    /// type implementing the rule (without its path).
+    fn name(&self) -> &'static str {
+        let path = std::any::type_name::<Self>();
+        path.rsplit("::").next().unwrap_or(path)
+    }
 }
 
+/// A constructor of a (boxed) rule, used for registering user-defined rules
+/// (see [`AnalysisOptions::extra_rules`](crate::AnalysisOptions::extra_rules)).
+pub type RuleFactory = fn() -> Box<dyn Rule>;
+
 mod material;
 pub use material::*;
 
@@ -35,10 +110,15 @@ pub use origins::*;
 mod refine_origins;
 pub use refine_origins::*;
 
+mod origins_matching;
+pub use origins_matching::*;
+
 mod destinies;
 pub use destinies::*;
 
+#[cfg(feature = "rules-mobility")]
 mod steady_mobility;
+#[cfg(feature = "rules-mobility")]
 pub use steady_mobility::*;
 
 mod royalty_on_1st_rank;
@@ -53,32 +133,82 @@ pub use pawn_on_3rd_rank::*;
 mod corner_knight;
 pub use corner_knight::*;
 
+mod promotions;
+pub use promotions::*;
+
+#[cfg(feature = "rules-mobility")]
 mod mobility;
+#[cfg(feature = "rules-mobility")]
 pub use mobility::*;
 
+#[cfg(feature = "rules-mobility")]
 mod route_from_origins;
+#[cfg(feature = "rules-mobility")]
 pub use route_from_origins::*;
 
+#[cfg(feature = "rules-mobility")]
+mod promoted_origins;
+#[cfg(feature = "rules-mobility")]
+pub use promoted_origins::*;
+
+#[cfg(feature = "rules-mobility")]
 mod route_to_reachable;
+#[cfg(feature = "rules-mobility")]
 pub use route_to_reachable::*;
 
+#[cfg(feature = "rules-mobility")]
+mod route_interference;
+#[cfg(feature = "rules-mobility")]
+pub use route_interference::*;
+
+#[cfg(feature = "rules-mobility")]
+mod king_route;
+#[cfg(feature = "rules-mobility")]
+pub use king_route::*;
+
 mod nb_captures;
 pub use nb_captures::*;
 
 mod surpassed_pawns;
 pub use surpassed_pawns::*;
 
+#[cfg(feature = "rules-captures")]
+mod pawn_files;
+#[cfg(feature = "rules-captures")]
+pub use pawn_files::*;
+
 mod unretractable;
 pub use unretractable::*;
 
+mod king_proximity;
+pub use king_proximity::*;
+
+#[cfg(feature = "rules-captures")]
 mod missing;
+#[cfg(feature = "rules-captures")]
 pub use missing::*;
 
+#[cfg(feature = "rules-captures")]
 mod captures;
+#[cfg(feature = "rules-captures")]
 pub use captures::*;
 
+#[cfg(feature = "rules-captures")]
 mod tombs;
+#[cfg(feature = "rules-captures")]
 pub use tombs::*;
 
+#[cfg(feature = "rules-captures")]
+mod captures_accounting;
+#[cfg(feature = "rules-captures")]
+pub use captures_accounting::*;
+
+#[cfg(feature = "rules-captures")]
+mod captures_flow;
+#[cfg(feature = "rules-captures")]
+pub use captures_flow::*;
+
+#[cfg(feature = "rules-parity")]
 mod parity;
+#[cfg(feature = "rules-parity")]
 pub use parity::*;
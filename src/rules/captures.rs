@@ -4,13 +4,21 @@
 //! piece in its route to its destinies.
 //! If a capturing square is common to all its possible destinies, we add this
 //! information to their set of captures.
+//!
+//! Furthermore, a piece that never left its origin occupies it until it is
+//! captured there. If a single enemy piece may have visited that square, it
+//! must have captured on its first visit, so all the edges into the square are
+//! marked as capturing edges of the enemy mobility graphs.
 
 use std::cmp::min;
 
-use chess::{get_rank, BitBoard, Color, Piece, Rank, Square, EMPTY};
+use chess::{get_rank, BitBoard, Color, Piece, Rank, Square, ALL_PIECES, EMPTY};
 
-use super::Rule;
-use crate::{analysis::Analysis, utils::common_piece_in_all_squares};
+use super::{Rule, ALL_ORIGINS, COLOR_ORIGINS};
+use crate::{
+    analysis::Analysis,
+    utils::{common_piece_in_all_squares, origin_color},
+};
 
 #[derive(Debug)]
 pub struct CapturesRule {
@@ -20,6 +28,7 @@ pub struct CapturesRule {
     destinies_counter: usize,
     origins_counter: usize,
     nb_captures_counter: usize,
+    reachable_counter: usize,
 }
 
 impl Rule for CapturesRule {
@@ -31,6 +40,7 @@ impl Rule for CapturesRule {
             destinies_counter: 0,
             origins_counter: 0,
             nb_captures_counter: 0,
+            reachable_counter: 0,
         }
     }
 
@@ -41,6 +51,7 @@ impl Rule for CapturesRule {
         self.destinies_counter = analysis.destinies.counter();
         self.origins_counter = analysis.origins.counter();
         self.nb_captures_counter = analysis.nb_captures.counter();
+        self.reachable_counter = analysis.reachable.counter();
     }
 
     fn is_applicable(&self, analysis: &Analysis) -> bool {
@@ -50,10 +61,11 @@ impl Rule for CapturesRule {
             || self.destinies_counter != analysis.destinies.counter()
             || self.origins_counter != analysis.origins.counter()
             || self.nb_captures_counter != analysis.nb_captures.counter()
+            || self.reachable_counter != analysis.reachable.counter()
     }
 
     fn apply(&self, analysis: &mut Analysis) -> bool {
-        let mut progress = false;
+        let mut progress = mark_unmoved_victims(analysis);
 
         for origin in (get_rank(Rank::Second) | get_rank(Rank::Seventh)) & !analysis.steady.value {
             let mut captures = !EMPTY;
@@ -86,6 +98,31 @@ impl Rule for CapturesRule {
     }
 }
 
+/// Marks as capturing edges all the edges into the origins of the (possibly
+/// missing) pieces that never left them, if a single enemy piece may have
+/// visited the origin.
+fn mark_unmoved_victims(analysis: &mut Analysis) -> bool {
+    let mut progress = false;
+    for origin in ALL_ORIGINS {
+        if analysis.reachable(origin) != BitBoard::from_square(origin)
+            || analysis.is_definitely_on_the_board(origin)
+        {
+            continue;
+        }
+        let enemy = !origin_color(origin);
+        let nb_visitors = COLOR_ORIGINS[enemy.to_index()]
+            .into_iter()
+            .filter(|visitor| analysis.reachable(*visitor) & BitBoard::from_square(origin) != EMPTY)
+            .count();
+        if nb_visitors == 1 {
+            for piece in ALL_PIECES {
+                progress |= analysis.mark_capturing_edges_into(piece, enemy, origin);
+            }
+        }
+    }
+    progress
+}
+
 /// The squares where the pawn that started the game on `origin` must have
 /// captured enemy pieces in order to go from `origin` to `target`, with at most
 /// `nb_allowed_captures` captures, according to the current information about
@@ -165,3 +202,31 @@ pub fn captures_to_target(
 
     (captures, min_distance)
 }
+
+// the test relies on the mobility rules for deriving the reachable squares
+#[cfg(all(test, feature = "rules-mobility"))]
+mod tests {
+
+    use super::*;
+    use crate::{rules::MobilityRule, utils::*, RetractableBoard};
+
+    #[test]
+    fn test_mark_unmoved_victims() {
+        let board =
+            RetractableBoard::from_fen("R3k3/8/8/8/8/8/8/4K3 b - -").expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+
+        // the black rook never left A8 and only the A1-rook may have got there
+        analysis.update_reachable(A8, BitBoard::from_square(A8));
+        for origin in COLOR_ORIGINS[Color::White.to_index()] & !BitBoard::from_square(A1) {
+            analysis.update_reachable(origin, !BitBoard::from_square(A8));
+        }
+        MobilityRule::new().apply(&mut analysis);
+        assert_eq!(analysis.min_captures_to_reach(A1, A8), Some(0));
+
+        assert!(CapturesRule::new().apply(&mut analysis));
+        MobilityRule::new().apply(&mut analysis);
+        assert_eq!(analysis.min_captures_to_reach(A1, A8), Some(1));
+        assert_eq!(analysis.min_captures_to_reach(A1, A7), Some(0));
+    }
+}
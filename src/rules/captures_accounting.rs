@@ -0,0 +1,155 @@
+//! Captures accounting rule.
+//!
+//! For every color, we cross-check the number of missing opponent pieces
+//! against the captures that have been attributed to the pieces of such color:
+//! the sum of the lower bounds on their number of captures, the tombs they are
+//! known to have captured on and the opponent pieces that may still be
+//! missing (not proven to be on the board).
+//!
+//! If the captures are over-committed, the position is illegal. If they match
+//! the number of missing opponents exactly, every capture is accounted for, so
+//! no piece can capture more than attributed and every captured opponent must
+//! have been captured on one of the known tombs.
+
+use chess::{BitBoard, ALL_COLORS, EMPTY};
+
+use super::{Analysis, Rule, COLOR_ORIGINS};
+use crate::IllegalityClass;
+
+#[derive(Debug)]
+pub struct CapturesAccountingRule {
+    nb_captures_counter: usize,
+    captures_counter: usize,
+    missing_counter: usize,
+    death_squares_counter: usize,
+}
+
+impl Rule for CapturesAccountingRule {
+    fn new() -> Self {
+        CapturesAccountingRule {
+            nb_captures_counter: 0,
+            captures_counter: 0,
+            missing_counter: 0,
+            death_squares_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.nb_captures_counter = analysis.nb_captures.counter();
+        self.captures_counter = analysis.captures.counter();
+        self.missing_counter = analysis.missing.counter();
+        self.death_squares_counter = analysis.death_squares.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.nb_captures_counter != analysis.nb_captures.counter()
+            || self.captures_counter != analysis.captures.counter()
+            || self.missing_counter != analysis.missing.counter()
+            || self.death_squares_counter != analysis.death_squares.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let mut progress = false;
+        for color in ALL_COLORS {
+            let nb_missing_opponents = 16 - analysis.board.color_combined(!color).popcnt() as i32;
+            let candidate_victims = analysis.missing(!color).all();
+
+            // the pieces proven to be on the board cannot have been captured
+            if (candidate_victims.popcnt() as i32) < nb_missing_opponents {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return progress;
+            }
+
+            // every known tomb is a capture, so it contributes to the lower bound
+            let mut nb_committed = 0;
+            let mut tombs = EMPTY;
+            for origin in COLOR_ORIGINS[color.to_index()] {
+                let captures = analysis.captures(origin);
+                progress |= analysis.update_captures_lower_bound(origin, captures.popcnt() as i32);
+                nb_committed += analysis.nb_captures_lower_bound(origin);
+                tombs |= captures;
+            }
+
+            if nb_committed > nb_missing_opponents {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return progress;
+            }
+
+            // every capture on a tomb requires a different victim that may have been
+            // captured there
+            let mut nb_tomb_captures = 0;
+            let mut all_tomb_victims = EMPTY;
+            for tomb in tombs {
+                let nb_captures_on_tomb = COLOR_ORIGINS[color.to_index()]
+                    .into_iter()
+                    .filter(|origin| {
+                        analysis.captures(*origin) & BitBoard::from_square(tomb) != EMPTY
+                    })
+                    .count() as u32;
+                let victims = candidate_victims
+                    .into_iter()
+                    .filter(|victim| {
+                        analysis.death_squares(*victim) & BitBoard::from_square(tomb) != EMPTY
+                    })
+                    .fold(EMPTY, |acc, victim| acc | BitBoard::from_square(victim));
+                if victims.popcnt() < nb_captures_on_tomb {
+                    analysis.set_illegal(IllegalityClass::Counting);
+                    return progress;
+                }
+                nb_tomb_captures += nb_captures_on_tomb;
+                all_tomb_victims |= victims;
+            }
+            if all_tomb_victims.popcnt() < nb_tomb_captures {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return progress;
+            }
+
+            // if all captures are accounted for, the bounds are tight
+            if nb_committed == nb_missing_opponents {
+                for origin in COLOR_ORIGINS[color.to_index()] {
+                    let lower = analysis.nb_captures_lower_bound(origin);
+                    progress |= analysis.update_captures_upper_bound(origin, lower);
+                }
+                if nb_tomb_captures as i32 == nb_missing_opponents {
+                    for victim in candidate_victims {
+                        progress |= analysis.update_death_squares(victim, tombs);
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{analysis::Analysis, utils::*, Legality::Illegal, RetractableBoard};
+
+    #[test]
+    fn test_captures_accounting_rule() {
+        // White is missing 10 pieces, Black is missing 8 (all pawns)
+        let board = RetractableBoard::from_fen("rnbqkbnr/8/8/8/8/8/8/1NBQKBN1 w - -")
+            .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        let rule = CapturesAccountingRule::new();
+
+        // the white knight from B1 captured on 8 different squares
+        let tombs = bitboard_of_squares(&[C3, D5, E3, F5, G3, H5, A3, B5]);
+        analysis.update_captures(B1, tombs);
+        assert!(rule.apply(&mut analysis));
+
+        // all captures by White are accounted for
+        assert_eq!(analysis.nb_captures_lower_bound(B1), 8);
+        assert_eq!(analysis.nb_captures_upper_bound(B1), 8);
+        assert_eq!(analysis.nb_captures_upper_bound(G1), 0);
+        assert_eq!(analysis.death_squares(A7), tombs);
+        assert_eq!(analysis.result, None);
+
+        // one more capture by White is one too many
+        analysis.update_captures(G1, bitboard_of_squares(&[F3]));
+        rule.apply(&mut analysis);
+        assert_eq!(analysis.result, Some(Illegal));
+    }
+}
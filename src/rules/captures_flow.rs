@@ -0,0 +1,256 @@
+//! Captures flow rule.
+//!
+//! Every missing piece was captured by exactly one opponent piece, which must
+//! have reached one of the candidate death squares of its victim. Furthermore,
+//! the number of captures performed by every piece must lie within its known
+//! bounds. We model all the captures of a color as a flow network, with:
+//!  - an edge from a source to every capturer, whose flow is the number of
+//!    captures performed by it (within its bounds),
+//!  - an edge from every capturer to every victim it may have captured,
+//!  - an edge from every missing opponent to a sink, whose flow is 1 if the
+//!    opponent was captured (it certainly was if it is certainly missing),
+//!  - an edge from the sink to the source, whose flow is the number of missing
+//!    opponents.
+//!
+//! If no feasible flow exists, the position is illegal. Otherwise, the edges
+//! that carry flow in every (resp. no) feasible flow reveal the victims that
+//! must have been captured by a certain piece (resp. cannot have been captured
+//! by it), which refines their death squares and the bounds on the number of
+//! captures of the capturers.
+//!
+//! A pawn captured en passant dies on its en passant rank, a square that its
+//! capturer never stands on. Such death squares are thus reached through the
+//! square behind them, where the capturing pawn lands.
+
+use chess::{get_rank, BitBoard, Piece, Square, ALL_COLORS, EMPTY, NUM_SQUARES};
+
+use super::{Analysis, Rule, COLOR_ORIGINS};
+use crate::{
+    utils::{en_passant_rank, initial_piece_on, origin_color, FlowNetwork},
+    IllegalityClass,
+};
+
+#[derive(Debug)]
+pub struct CapturesFlowRule {
+    nb_captures_counter: usize,
+    missing_counter: usize,
+    death_squares_counter: usize,
+    reachable_counter: usize,
+}
+
+impl Rule for CapturesFlowRule {
+    fn new() -> Self {
+        CapturesFlowRule {
+            nb_captures_counter: 0,
+            missing_counter: 0,
+            death_squares_counter: 0,
+            reachable_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.nb_captures_counter = analysis.nb_captures.counter();
+        self.missing_counter = analysis.missing.counter();
+        self.death_squares_counter = analysis.death_squares.counter();
+        self.reachable_counter = analysis.reachable.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.nb_captures_counter != analysis.nb_captures.counter()
+            || self.missing_counter != analysis.missing.counter()
+            || self.death_squares_counter != analysis.death_squares.counter()
+            || self.reachable_counter != analysis.reachable.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let mut progress = false;
+        for color in ALL_COLORS {
+            let capturers = COLOR_ORIGINS[color.to_index()] & !analysis.steady.value;
+            let missing = analysis.missing(!color);
+            let victims = missing.all() & COLOR_ORIGINS[(!color).to_index()];
+            let nb_missing = 16 - analysis.board.color_combined(!color).popcnt() as i32;
+
+            // nodes are indexed by squares, plus the source and the sink
+            let (source, sink) = (NUM_SQUARES, NUM_SQUARES + 1);
+            let mut network = FlowNetwork::new(NUM_SQUARES + 2);
+            let mut capturer_edges = [0; NUM_SQUARES];
+            for capturer in capturers {
+                capturer_edges[capturer.to_index()] = network.add_edge(
+                    source,
+                    capturer.to_index(),
+                    analysis.nb_captures_lower_bound(capturer).max(0),
+                    analysis.nb_captures_upper_bound(capturer),
+                );
+            }
+            let mut edges = vec![];
+            for victim in victims {
+                let lower = if missing.mem(victim) { 1 } else { 0 };
+                network.add_edge(victim.to_index(), sink, lower, 1);
+                let mut landings = EMPTY;
+                for (_, landing) in en_passant_captures(analysis, victim) {
+                    landings |= BitBoard::from_square(landing);
+                }
+                for capturer in capturers {
+                    let mut targets = analysis.death_squares(victim);
+                    if initial_piece_on(capturer) == Piece::Pawn {
+                        targets |= landings;
+                    }
+                    if analysis.reachable(capturer) & targets != EMPTY {
+                        let edge = network.add_edge(capturer.to_index(), victim.to_index(), 0, 1);
+                        edges.push((capturer, victim, edge));
+                    }
+                }
+            }
+            network.add_edge(sink, source, nb_missing, nb_missing);
+
+            if !network.find_circulation() {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return progress;
+            }
+
+            // the capturers that may (resp. must) have captured every victim
+            let mut possible = [EMPTY; NUM_SQUARES];
+            let mut forced = [EMPTY; NUM_SQUARES];
+            for (capturer, victim, edge) in edges {
+                let flow = network.flow(edge);
+                if flow > 0 || network.can_increase(edge) {
+                    possible[victim.to_index()] |= BitBoard::from_square(capturer);
+                }
+                if flow > 0 && !network.can_decrease(edge) {
+                    forced[victim.to_index()] |= BitBoard::from_square(capturer);
+                }
+            }
+
+            for victim in victims {
+                let capturers = possible[victim.to_index()];
+                if capturers == EMPTY {
+                    progress |= analysis
+                        .update_certainly_not_missing(!color, BitBoard::from_square(victim));
+                    continue;
+                }
+                let reachable = capturers
+                    .into_iter()
+                    .fold(EMPTY, |acc, capturer| acc | analysis.reachable(capturer));
+                // the en passant death squares whose landing square was reached
+                // by a pawn capturer are kept
+                let pawn_reachable = capturers
+                    .into_iter()
+                    .filter(|capturer| initial_piece_on(*capturer) == Piece::Pawn)
+                    .fold(EMPTY, |acc, capturer| acc | analysis.reachable(capturer));
+                let mut death_squares = reachable;
+                for (square, landing) in en_passant_captures(analysis, victim) {
+                    if pawn_reachable & BitBoard::from_square(landing) != EMPTY {
+                        death_squares |= BitBoard::from_square(square);
+                    }
+                }
+                progress |= analysis.update_death_squares(victim, death_squares);
+            }
+
+            // the number of captures of a capturer is extreme if it cannot be
+            // changed, otherwise it is bounded by its possible and forced victims
+            for capturer in capturers {
+                let bb = BitBoard::from_square(capturer);
+                let count = |capturers_of: &[BitBoard; NUM_SQUARES]| {
+                    victims
+                        .into_iter()
+                        .filter(|victim| capturers_of[victim.to_index()] & bb != EMPTY)
+                        .count() as i32
+                };
+                let edge = capturer_edges[capturer.to_index()];
+                let flow = network.flow(edge);
+                let lower = if network.can_decrease(edge) {
+                    count(&forced)
+                } else {
+                    flow
+                };
+                let upper = if network.can_increase(edge) {
+                    count(&possible).min(nb_missing)
+                } else {
+                    flow
+                };
+                progress |= analysis.update_captures_lower_bound(capturer, lower);
+                progress |= analysis.update_captures_upper_bound(capturer, upper);
+            }
+        }
+        progress
+    }
+}
+
+/// The death squares of the given victim where it may have been captured en
+/// passant (only pawns can be), each with the square where its capturer landed.
+fn en_passant_captures(analysis: &Analysis, victim: Square) -> Vec<(Square, Square)> {
+    if initial_piece_on(victim) != Piece::Pawn {
+        return vec![];
+    }
+    let color = origin_color(victim);
+    (analysis.death_squares(victim) & get_rank(en_passant_rank(color)))
+        .into_iter()
+        .filter_map(|square| square.backward(color).map(|landing| (square, landing)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::Color;
+
+    use super::*;
+    use crate::{utils::*, Legality::Illegal, RetractableBoard};
+
+    #[test]
+    fn test_captures_flow_rule() {
+        // the white pawn from H2 is missing, so it was captured by Black
+        let board =
+            RetractableBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR b KQkq -")
+                .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        let rule = CapturesFlowRule::new();
+        assert!(rule.apply(&mut analysis));
+        assert_eq!(analysis.result, None);
+        assert_eq!(analysis.nb_captures_upper_bound(E7), 1);
+
+        // only the knight from G8 may have captured
+        for origin in COLOR_ORIGINS[Color::Black.to_index()] & !bitboard_of_squares(&[G8]) {
+            analysis.update_captures_upper_bound(origin, 0);
+        }
+        analysis.update_reachable(G8, bitboard_of_squares(&[G8, F6, G4, H2]));
+        analysis.update_certainly_missing(Color::White, bitboard_of_squares(&[H2]));
+        assert!(rule.apply(&mut analysis));
+        assert_eq!(analysis.nb_captures_lower_bound(G8), 1);
+        assert_eq!(
+            analysis.death_squares(H2),
+            bitboard_of_squares(&[G8, F6, G4, H2])
+        );
+        assert!(!analysis
+            .missing(Color::White)
+            .all()
+            .into_iter()
+            .any(|o| o == A2));
+
+        // nobody may have captured
+        analysis.update_captures_upper_bound(G8, 0);
+        rule.apply(&mut analysis);
+        assert_eq!(analysis.result, Some(Illegal));
+    }
+
+    #[test]
+    fn test_captures_flow_rule_en_passant() {
+        // the white pawn from E2 is missing, it can only have been captured en
+        // passant on E4 by the black pawn from D7, which landed on E3
+        let board =
+            RetractableBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR b KQkq -")
+                .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        for origin in COLOR_ORIGINS[Color::Black.to_index()] & !bitboard_of_squares(&[D7]) {
+            analysis.update_captures_upper_bound(origin, 0);
+        }
+        analysis.update_reachable(D7, bitboard_of_squares(&[D7, D6, D5, D4, E3]));
+        analysis.update_death_squares(E2, bitboard_of_squares(&[E4]));
+        analysis.update_certainly_missing(Color::White, bitboard_of_squares(&[E2]));
+        let rule = CapturesFlowRule::new();
+        rule.apply(&mut analysis);
+        assert_eq!(analysis.result, None);
+        assert_eq!(analysis.nb_captures_lower_bound(D7), 1);
+        assert_eq!(analysis.death_squares(E2), bitboard_of_squares(&[E4]));
+    }
+}
@@ -1,13 +1,24 @@
 //! Destinies rule.
 //!
 //! We filter out destinies that are not reachable.
+//!
+//! Furthermore, a piece can only have been captured on a square reached by an
+//! opponent piece (or on its relative 4th rank, if it was captured en passant
+//! as a pawn), and destinies are the union of death squares and the squares of
+//! the board where the piece may be standing.
+
+use chess::{get_rank, BitBoard, ALL_COLORS, EMPTY, NUM_COLORS};
 
-use super::{Analysis, Rule, ALL_ORIGINS};
+use super::{Analysis, Rule, ALL_ORIGINS, COLOR_ORIGINS};
+use crate::utils::{en_passant_rank, origin_color};
+#[cfg(feature = "atomic")]
+use crate::Variant;
 
 #[derive(Debug)]
 pub struct DestiniesRule {
     origins_counter: usize,
     reachable_counter: usize,
+    missing_counter: usize,
 }
 
 impl Rule for DestiniesRule {
@@ -15,17 +26,20 @@ impl Rule for DestiniesRule {
         DestiniesRule {
             origins_counter: 0,
             reachable_counter: 0,
+            missing_counter: 0,
         }
     }
 
     fn update(&mut self, analysis: &Analysis) {
         self.origins_counter = analysis.origins.counter();
         self.reachable_counter = analysis.reachable.counter();
+        self.missing_counter = analysis.missing.counter();
     }
 
     fn is_applicable(&self, analysis: &Analysis) -> bool {
         self.origins_counter != analysis.origins.counter()
             || self.reachable_counter != analysis.reachable.counter()
+            || self.missing_counter != analysis.missing.counter()
     }
 
     fn apply(&self, analysis: &mut Analysis) -> bool {
@@ -35,6 +49,34 @@ impl Rule for DestiniesRule {
             let reachable_destinies = analysis.destinies(square) & analysis.reachable(square);
             progress |= analysis.update_destinies(square, reachable_destinies)
         }
+
+        // in atomic chess, pieces may explode away from any opponent piece
+        #[cfg(feature = "atomic")]
+        if analysis.options.variant == Variant::Atomic {
+            return progress;
+        }
+
+        let reached_by: [BitBoard; NUM_COLORS] = ALL_COLORS.map(|color| {
+            COLOR_ORIGINS[color.to_index()]
+                .into_iter()
+                .fold(EMPTY, |acc, origin| acc | analysis.reachable(origin))
+        });
+
+        for origin in ALL_ORIGINS {
+            let color = origin_color(origin);
+            let mut death_squares = analysis.destinies(origin) & reached_by[(!color).to_index()];
+            if BitBoard::from_square(origin) & get_rank(color.to_second_rank()) != EMPTY {
+                death_squares |= analysis.destinies(origin) & get_rank(en_passant_rank(color));
+            }
+            if analysis.is_definitely_on_the_board(origin) {
+                death_squares = EMPTY;
+            }
+            progress |= analysis.update_death_squares(origin, death_squares);
+
+            let death_squares = analysis.death_squares(origin);
+            let final_square = analysis.final_square_if_on_board(origin);
+            progress |= analysis.update_destinies(origin, death_squares | final_square);
+        }
         progress
     }
 }
@@ -0,0 +1,80 @@
+//! King proximity rule.
+//!
+//! Kings can never stand on adjacent squares. If the king of the player who
+//! made the last move is close to the opponent king, all the squares it may
+//! have come from (which must be empty now) may be next to the opponent king
+//! or occupied. In that case, the king cannot have performed the last move,
+//! which is recorded in the analysis so that the retraction search does not
+//! explore the retractions of the king.
+
+use chess::{get_king_moves, BitBoard, File, Piece, Square, EMPTY};
+
+use super::{Analysis, Rule};
+
+#[derive(Debug)]
+pub struct KingProximityRule {
+    applied: bool,
+}
+
+impl Rule for KingProximityRule {
+    fn new() -> Self {
+        KingProximityRule { applied: false }
+    }
+
+    fn update(&mut self, _analysis: &Analysis) {
+        self.applied = true;
+    }
+
+    fn is_applicable(&self, _analysis: &Analysis) -> bool {
+        !self.applied
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let board = &analysis.board;
+        let color = !board.side_to_move();
+        let king = board.king_square(color);
+        let opponent_king = board.king_square(!color);
+
+        let mut predecessors = get_king_moves(king);
+        // the king may have just castled
+        let backrank = color.to_my_backrank();
+        if king.get_rank() == backrank && [File::C, File::G].contains(&king.get_file()) {
+            predecessors |= BitBoard::from_square(Square::make_square(backrank, File::E));
+        }
+        predecessors &= !board.combined() & !get_king_moves(opponent_king);
+
+        if predecessors != EMPTY {
+            return false;
+        }
+        let king_bb = board.pieces(Piece::King) & BitBoard::from_square(king);
+        analysis.update_last_movers(!king_bb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{utils::*, RetractableBoard};
+
+    #[test]
+    fn test_king_proximity() {
+        [
+            ("8/8/8/3k4/8/2PKP3/2PPP3/8 b - -", Some(D3)),
+            ("8/8/8/3k4/8/3KP3/2PPP3/8 b - -", None),
+            ("8/8/8/8/8/6k1/5N1N/5RKB b - -", None),
+            ("8/8/8/8/8/6k1/5N1N/4BRKB b - -", Some(G1)),
+            ("8/8/3K4/8/2pkp3/2ppp3/8/8 w - -", Some(D4)),
+        ]
+        .into_iter()
+        .for_each(|(fen, frozen_king)| {
+            let board = RetractableBoard::from_fen(fen).unwrap();
+            let mut analysis = Analysis::new(&board);
+            let progress = KingProximityRule::new().apply(&mut analysis);
+            assert_eq!(progress, frozen_king.is_some(), "{}", fen);
+            if let Some(king) = frozen_king {
+                assert_eq!(analysis.last_movers() & BitBoard::from_square(king), EMPTY);
+            }
+        });
+    }
+}
@@ -0,0 +1,120 @@
+//! King route rule.
+//!
+//! A king can never have stood on a square occupied by a steady enemy pawn nor
+//! on a square attacked by it, since the pawn has been there (attacking such
+//! square) during the whole game. A phalanx of steady pawns may thus cut the
+//! king off from parts of the board.
+//!
+//! We restrict the squares reachable by every king from its origin to those
+//! that are connected to it by king steps without crossing the coverage of the
+//! steady enemy pawns. If the king's current square is not among them, the
+//! position is illegal. Otherwise, the king's route is restricted accordingly
+//! (see [`Analysis::king_route`]).
+
+use chess::{get_king_moves, BitBoard, File, Square, ALL_COLORS, EMPTY};
+
+use super::{steady_pawns_coverage, Analysis, Rule};
+use crate::IllegalityClass;
+
+#[derive(Debug)]
+pub struct KingRouteRule {
+    steady_counter: usize,
+    reachable_from_origin_counter: usize,
+}
+
+impl Rule for KingRouteRule {
+    fn new() -> Self {
+        KingRouteRule {
+            steady_counter: 0,
+            reachable_from_origin_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.steady_counter = analysis.steady.counter();
+        self.reachable_from_origin_counter = analysis.reachable_from_origin.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.steady_counter != analysis.steady.counter()
+            || self.reachable_from_origin_counter != analysis.reachable_from_origin.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let mut progress = false;
+
+        for color in ALL_COLORS {
+            let coverage = steady_pawns_coverage(analysis, !color);
+            if coverage == EMPTY {
+                continue;
+            }
+
+            let origin = Square::make_square(color.to_my_backrank(), File::E);
+            let allowed = analysis.reachable_from_origin(color, File::E) & !coverage;
+            let route = connected_region(origin, allowed);
+
+            let king_square = analysis.board.king_square(color);
+            if route & BitBoard::from_square(king_square) == EMPTY {
+                analysis.set_illegal(IllegalityClass::Route);
+                return true;
+            }
+            progress |= analysis.update_reachable_from_origin(color, File::E, route);
+        }
+        progress
+    }
+}
+
+/// The squares of the given set that a king can reach from the given source
+/// (which is always included) by steps through the set.
+fn connected_region(source: Square, allowed: BitBoard) -> BitBoard {
+    let mut region = BitBoard::from_square(source);
+    let mut frontier = region;
+    while frontier != EMPTY {
+        let next = frontier.fold(EMPTY, |acc, square| acc | get_king_moves(square));
+        frontier = next & allowed & !region;
+        region |= frontier;
+    }
+    region
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::Color;
+
+    use super::*;
+    use crate::{
+        rules::{MobilityRule, OriginsRule, SteadyRule},
+        utils::*,
+        Legality::Illegal,
+        RetractableBoard,
+    };
+
+    #[test]
+    fn test_king_route_rule() {
+        // the white pawns on the 2nd rank never moved, so the black king cannot
+        // have crossed the 3rd rank
+        let board =
+            RetractableBoard::from_fen("8/8/8/8/8/8/1PPPPPPP/1k2K3 w - -").expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        OriginsRule::new().apply(&mut analysis);
+        SteadyRule::new().apply(&mut analysis);
+        MobilityRule::new().apply(&mut analysis);
+        assert!(KingRouteRule::new().apply(&mut analysis));
+        assert_eq!(analysis.result, Some(Illegal));
+
+        // without the pawn on B2, the black king may have crossed through A3
+        let board =
+            RetractableBoard::from_fen("8/8/8/8/8/8/2PPPPPP/k3K3 w - -").expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        OriginsRule::new().apply(&mut analysis);
+        SteadyRule::new().apply(&mut analysis);
+        MobilityRule::new().apply(&mut analysis);
+        KingRouteRule::new().apply(&mut analysis);
+        assert_eq!(analysis.result, None);
+        assert_eq!(
+            analysis.reachable_from_origin(Color::Black, File::E) & bitboard_of_squares(&[D3]),
+            EMPTY
+        );
+    }
+}
@@ -2,14 +2,13 @@
 
 use std::cmp::max;
 
-use chess::{Piece, ALL_COLORS};
+use chess::{Color, Piece, ALL_COLORS, ALL_PIECES, EMPTY, NUM_PIECES};
 
 use super::Rule;
 use crate::{
     analysis::Analysis,
-    utils::{DARK_SQUARES, LIGHT_SQUARES},
-    Legality::Illegal,
-    RetractableBoard,
+    utils::{DARK_SQUARES, LIGHT_SQUARES, PROMOTION_RANKS},
+    HoldingsBoard, IllegalityClass, RetractableBoard,
 };
 
 /// A rule that performs a simple check on the position material,
@@ -38,7 +37,7 @@ impl Rule for MaterialRule {
 
     fn apply(&self, analysis: &mut Analysis) -> bool {
         if illegal_material(&analysis.board) {
-            analysis.result = Some(Illegal);
+            analysis.set_illegal(IllegalityClass::Material);
             true
         } else {
             false
@@ -52,22 +51,61 @@ impl Rule for MaterialRule {
 pub fn illegal_material(board: &RetractableBoard) -> bool {
     for color in ALL_COLORS {
         let pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
-        let knights = board.pieces(Piece::Knight) & board.color_combined(color);
-        let bishops = board.pieces(Piece::Bishop) & board.color_combined(color);
-        let rooks = board.pieces(Piece::Rook) & board.color_combined(color);
-        let queens = board.pieces(Piece::Queen) & board.color_combined(color);
-        let lower_bound_promoted = max(0, knights.popcnt() as i32 - 2)
-            + max(0, (bishops & LIGHT_SQUARES).popcnt() as i32 - 1)
-            + max(0, (bishops & DARK_SQUARES).popcnt() as i32 - 1)
-            + max(0, rooks.popcnt() as i32 - 2)
-            + max(0, queens.popcnt() as i32 - 1);
-        if 8 - (pawns.popcnt() as i32) < lower_bound_promoted {
+        if 8 - (pawns.popcnt() as i32) < lower_bound_promoted(board, color) {
             return true;
         }
     }
     false
 }
 
+/// A lower bound on the number of promoted pieces of the given color on the
+/// given board, given by the officers in excess of the initial material (e.g.
+/// a third knight or a second light-squared bishop).
+#[inline]
+pub(crate) fn lower_bound_promoted(board: &RetractableBoard, color: Color) -> i32 {
+    let knights = board.pieces(Piece::Knight) & board.color_combined(color);
+    let bishops = board.pieces(Piece::Bishop) & board.color_combined(color);
+    let rooks = board.pieces(Piece::Rook) & board.color_combined(color);
+    let queens = board.pieces(Piece::Queen) & board.color_combined(color);
+    max(0, knights.popcnt() as i32 - 2)
+        + max(0, (bishops & LIGHT_SQUARES).popcnt() as i32 - 1)
+        + max(0, (bishops & DARK_SQUARES).popcnt() as i32 - 1)
+        + max(0, rooks.popcnt() as i32 - 2)
+        + max(0, queens.popcnt() as i32 - 1)
+}
+
+/// Returns `true` iff the material of the given board with holdings is
+/// impossible to reach in a legal Crazyhouse game.
+///
+/// In Crazyhouse, material is never removed from the game: every unit is
+/// either on the board or in the hand of a player. The only exception are
+/// promoted pieces, which come from (and return to) the pawns.
+pub fn illegal_holdings_material(board: &HoldingsBoard) -> bool {
+    let promoted = board.promoted();
+    if promoted & (board.board().pieces(Piece::Pawn) | board.board().pieces(Piece::King)) != EMPTY
+        || board.board().pieces(Piece::Pawn) & PROMOTION_RANKS != EMPTY
+    {
+        return true;
+    }
+    ALL_PIECES.into_iter().any(|piece| {
+        let on_board = (board.board().pieces(piece) & !promoted).popcnt();
+        let in_hand: u32 = ALL_COLORS
+            .into_iter()
+            .map(|color| board.holdings(color, piece) as u32)
+            .sum();
+        let demoted = if piece == Piece::Pawn {
+            promoted.popcnt()
+        } else {
+            0
+        };
+        on_board + in_hand + demoted != 2 * INITIAL_MATERIAL[piece.to_index()]
+    })
+}
+
+/// The number of pieces of every type (indexed by `piece.to_index()`) that
+/// each player has in the starting position.
+const INITIAL_MATERIAL: [u32; NUM_PIECES] = [8, 2, 2, 2, 1, 1];
+
 #[cfg(test)]
 mod tests {
     use super::*;
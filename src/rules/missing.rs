@@ -42,3 +42,34 @@ impl Rule for MissingRule {
         progress
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use chess::{BitBoard, Color};
+
+    use super::*;
+    use crate::{utils::*, RetractableBoard};
+
+    #[test]
+    fn test_missing_rule() {
+        // the white knight from G1 is missing
+        let board =
+            RetractableBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKB1R b KQkq -")
+                .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        for square in *board.combined() {
+            analysis.update_origins(square, BitBoard::from_square(square));
+        }
+        let counter = analysis.missing.counter();
+        let rule = MissingRule::new();
+        assert!(rule.apply(&mut analysis));
+        assert!(analysis.missing(Color::White).mem(G1));
+        assert!(analysis.missing.counter() > counter);
+
+        // nothing else can be derived, so the counter stays the same
+        let counter = analysis.missing.counter();
+        assert!(!rule.apply(&mut analysis));
+        assert_eq!(analysis.missing.counter(), counter);
+    }
+}
@@ -5,10 +5,13 @@
 //!  - reachable_from_promotion
 //!  - pawn_capture_distances
 //!  - pawn_forced_captures
+//!  - officer_capture_distances and the lower bound on the number of captures
+//!    of officers, if their mobility graph has capturing edges
 
-use chess::{Board, Piece, Square, ALL_COLORS, ALL_FILES, ALL_SQUARES, PROMOTION_PIECES};
+use chess::{Piece, Square, ALL_COLORS, ALL_FILES, ALL_SQUARES, PROMOTION_PIECES};
 
 use super::{Analysis, Rule};
+use crate::utils::initial_piece_on;
 
 #[derive(Debug)]
 pub struct MobilityRule {
@@ -41,26 +44,54 @@ impl Rule for MobilityRule {
     fn apply(&self, analysis: &mut Analysis) -> bool {
         let mut progress = false;
 
+        // memoized results are reused if the analysis is sharing a cache
+        let mut cache = analysis.cache.take().unwrap_or_default();
+        cache.prepare(analysis);
+
         // update reachable_from_origin
         for color in ALL_COLORS {
             let rank = color.to_my_backrank();
             for file in ALL_FILES {
                 let square = Square::make_square(rank, file);
-                let piece = Board::default().piece_on(square).unwrap();
-                let reachable = analysis.mobility.value[color.to_index()][piece.to_index()]
-                    .reachable_from_source(square);
+                let piece = initial_piece_on(square);
+                let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+                let reachable = cache.reachable_from_source(color, piece, graph, square);
                 progress |= analysis.update_reachable_from_origin(color, file, reachable)
             }
         }
 
+        // update the lower bound on the number of captures of officers
+        for color in ALL_COLORS {
+            let rank = color.to_my_backrank();
+            for file in ALL_FILES {
+                let origin = Square::make_square(rank, file);
+                let piece = initial_piece_on(origin);
+                let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+                if analysis.is_steady(origin) || graph.capturing_edges().is_empty() {
+                    continue;
+                }
+                let distances = cache.distances_from_source(color, piece, graph, origin);
+                progress |= analysis.update_officer_capture_distances(origin, &distances);
+                let final_squares = analysis.destinies(origin) | analysis.death_squares(origin);
+                let min_distance = final_squares
+                    .into_iter()
+                    .map(|square| distances[square.to_index()])
+                    .min();
+                // a distance of 16 stands for an unreachable square
+                if let Some(n) = min_distance.filter(|n| *n < 16) {
+                    progress |= analysis.update_captures_lower_bound(origin, n as i32);
+                }
+            }
+        }
+
         // update reachable_from_promotion
         for color in ALL_COLORS {
             let rank = color.to_their_backrank();
             for piece in PROMOTION_PIECES {
                 for file in ALL_FILES {
                     let square = Square::make_square(rank, file);
-                    let reachable = analysis.mobility.value[color.to_index()][piece.to_index()]
-                        .reachable_from_source(square);
+                    let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+                    let reachable = cache.reachable_from_source(color, piece, graph, square);
                     progress |=
                         analysis.update_reachable_from_promotion(color, piece, file, reachable)
                 }
@@ -72,8 +103,8 @@ impl Rule for MobilityRule {
             let rank = color.to_second_rank();
             for file in ALL_FILES {
                 let square = Square::make_square(rank, file);
-                let distances = analysis.mobility.value[color.to_index()][Piece::Pawn.to_index()]
-                    .distances_from_source(square);
+                let graph = &analysis.mobility.value[color.to_index()][Piece::Pawn.to_index()];
+                let distances = cache.distances_from_source(color, Piece::Pawn, graph, square);
                 progress |= analysis.update_pawn_capture_distances(color, file, &distances);
             }
         }
@@ -89,13 +120,49 @@ impl Rule for MobilityRule {
                     if n == 0 || n > nb_allowed_captures {
                         continue;
                     }
-                    let forced = analysis.mobility.value[color.to_index()][Piece::Pawn.to_index()]
-                        .forced_captures(square, target, nb_allowed_captures);
+                    let graph = &analysis.mobility.value[color.to_index()][Piece::Pawn.to_index()];
+                    let forced = cache.forced_captures(
+                        color,
+                        Piece::Pawn,
+                        graph,
+                        square,
+                        target,
+                        nb_allowed_captures,
+                    );
                     progress |= analysis.update_pawn_forced_captures(color, file, target, forced);
                 }
             }
         }
 
+        analysis.cache = Some(cache);
         progress
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use chess::Color;
+
+    use super::*;
+    use crate::{utils::*, RetractableBoard};
+
+    #[test]
+    fn test_officer_capture_distances() {
+        let board =
+            RetractableBoard::from_fen("4k3/8/8/8/8/8/8/R1B1K3 w - -").expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+
+        // the dark-squared bishop can never reach a light square
+        assert_eq!(analysis.officer_capture_distances(C1, D1), 16);
+        assert_eq!(analysis.officer_capture_distances(C1, D2), 0);
+        assert_eq!(analysis.officer_capture_distances(A1, A3), 0);
+
+        // every route of the rook to A3 ends with a capture
+        assert!(analysis.mark_capturing_edges_into(Piece::Rook, Color::White, A3));
+        MobilityRule::new().apply(&mut analysis);
+        assert_eq!(analysis.min_captures_to_reach(A1, A3), Some(1));
+        assert_eq!(analysis.min_captures_to_reach(A1, A4), Some(0));
+        assert_eq!(analysis.min_captures_to_reach(C1, D1), None);
+    }
+}
@@ -11,7 +11,7 @@
 use chess::{BitBoard, Board, ALL_COLORS};
 
 use super::{Analysis, Rule, COLOR_ORIGINS};
-use crate::Legality::Illegal;
+use crate::IllegalityClass;
 
 #[derive(Debug)]
 pub struct CapturesBoundsRule {
@@ -61,7 +61,7 @@ impl Rule for CapturesBoundsRule {
 
                 // if the bounds ever become incompatible, the position must be illegal
                 if new_upper < lower {
-                    analysis.result = Some(Illegal);
+                    analysis.set_illegal(IllegalityClass::Counting);
                 }
             }
         }
@@ -84,7 +84,7 @@ mod tests {
     use chess::Square;
 
     use super::*;
-    use crate::{analysis::Analysis, utils::*, RetractableBoard};
+    use crate::{analysis::Analysis, utils::*, Legality::Illegal, RetractableBoard};
 
     #[test]
     fn test_nb_captures_rule() {
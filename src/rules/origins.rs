@@ -51,7 +51,19 @@ impl Rule for OriginsRule {
 }
 
 /// The candidate squares from which a piece of the given type which is
-/// currently on the given square may have started the game.
+/// currently on the given square may have started the game (regardless of
+/// its color), including the pawn origins of promoted pieces.
+///
+/// ```
+/// use chess::{BitBoard, Piece, Square};
+/// use sherlock::origins_of_piece_on;
+///
+/// let origins = origins_of_piece_on(Piece::King, Square::A1);
+/// assert_eq!(
+///     origins,
+///     BitBoard::from_square(Square::E1) | BitBoard::from_square(Square::E8)
+/// );
+/// ```
 #[inline]
 pub fn origins_of_piece_on(piece: Piece, square: Square) -> BitBoard {
     match piece {
@@ -64,7 +76,12 @@ pub fn origins_of_piece_on(piece: Piece, square: Square) -> BitBoard {
     }
 }
 
+/// The squares where pieces start the game: the 1st, 2nd, 7th and 8th ranks.
+/// Sherlock identifies every piece with its origin square.
 pub const ALL_ORIGINS: BitBoard = BitBoard(18446462598732906495); // 1st, 2nd, 7th & 8th ranks
+
+/// The origin squares of the pieces of every color, indexed by
+/// `Color::to_index`.
 pub const COLOR_ORIGINS: [BitBoard; 2] = [
     BitBoard(65535),                // 1st & 2nd ranks
     BitBoard(18446462598732840960), // 7th & 8th ranks
@@ -73,6 +90,10 @@ pub const KING_ORIGINS: BitBoard = BitBoard(1152921504606846992); // E1, E8
 pub const QUEEN_ORIGINS: BitBoard = BitBoard(648236871364706056); // D1, D8, 2nd & 7th ranks
 pub const ROOK_ORIGINS: BitBoard = BitBoard(9367205749953986433); // A1, H1, A8, H8, 2nd & 7th ranks
 pub const KNIGHT_ORIGINS: BitBoard = BitBoard(4827577325564526402); // B1, G1, B8, G8, 2nd & 7th ranks
+pub const COLOR_B1_AND_G1: [BitBoard; 2] = [
+    BitBoard(66),                  // B1 & G1
+    BitBoard(4755801206503243776), // B8 & G8
+];
 pub const BISHOP_ORIGINS: [BitBoard; 2] = [
     BitBoard(360006495212994336),  // F1, C8, 2nd & 7th ranks
     BitBoard(2377619128274976516), // C1, F8, 2nd & 7th ranks
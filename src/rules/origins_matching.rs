@@ -0,0 +1,133 @@
+//! Origins matching rule.
+//!
+//! Every origin must be assigned to a different piece on the board, or to a
+//! missing slot (there are as many missing slots as missing pieces). We check
+//! that such an assignment exists through a bipartite matching between origins
+//! and pieces (plus missing slots). If it does not, the position is illegal.
+//!
+//! Furthermore, if an origin is assigned to the same piece (or to a missing
+//! slot) in every possible assignment, we commit to it.
+
+use chess::{BitBoard, ALL_COLORS, EMPTY};
+
+use super::{Analysis, Rule, COLOR_ORIGINS};
+use crate::{utils::Matching, IllegalityClass};
+
+#[derive(Debug)]
+pub struct OriginsMatchingRule {
+    origins_counter: usize,
+    missing_counter: usize,
+}
+
+impl Rule for OriginsMatchingRule {
+    fn new() -> Self {
+        OriginsMatchingRule {
+            origins_counter: 0,
+            missing_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.origins_counter = analysis.origins.counter();
+        self.missing_counter = analysis.missing.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.origins_counter != analysis.origins.counter()
+            || self.missing_counter != analysis.missing.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let mut progress = false;
+
+        for color in ALL_COLORS {
+            let origins = COLOR_ORIGINS[color.to_index()];
+            let pieces = *analysis.board.color_combined(color);
+            let missing = analysis.missing(color);
+
+            // the missing slots are represented by (arbitrary) squares without pieces
+            let nb_missing = 16usize.saturating_sub(pieces.popcnt() as usize);
+            let slots = (!pieces)
+                .into_iter()
+                .take(nb_missing)
+                .fold(EMPTY, |acc, square| acc | BitBoard::from_square(square));
+
+            // the candidate pieces (or missing slots) of every origin
+            let mut candidates = [EMPTY; 64];
+            for square in pieces {
+                for origin in analysis.origins(square) & origins {
+                    candidates[origin.to_index()] |= BitBoard::from_square(square);
+                }
+            }
+            for origin in origins {
+                if missing.mem(origin) {
+                    candidates[origin.to_index()] = EMPTY;
+                }
+                if missing.all() & BitBoard::from_square(origin) != EMPTY {
+                    candidates[origin.to_index()] |= slots;
+                }
+            }
+
+            let matching = Matching::new(&candidates, origins);
+            if !matching.is_perfect() {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return true;
+            }
+
+            // an assignment is forced if there is no perfect matching without it
+            for origin in origins {
+                let assigned = match matching.element_of(origin) {
+                    Some(square) => square,
+                    None => continue,
+                };
+                let is_slot = slots & BitBoard::from_square(assigned) != EMPTY;
+                let assigned_bb = if is_slot {
+                    slots
+                } else {
+                    BitBoard::from_square(assigned)
+                };
+                let mut reduced = candidates;
+                reduced[origin.to_index()] &= !assigned_bb;
+                if Matching::new(&reduced, origins).is_perfect() {
+                    continue;
+                }
+                if is_slot {
+                    progress |=
+                        analysis.update_certainly_missing(color, BitBoard::from_square(origin));
+                } else {
+                    progress |= analysis.update_origins(assigned, BitBoard::from_square(origin));
+                    progress |= analysis.update_destinies(origin, BitBoard::from_square(assigned));
+                }
+            }
+        }
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{utils::*, Legality::Illegal, RetractableBoard};
+
+    #[test]
+    fn test_origins_matching_rule() {
+        let mut analysis = Analysis::new(&RetractableBoard::default());
+        let rule = OriginsMatchingRule::new();
+
+        // nothing can be derived yet
+        assert!(!rule.apply(&mut analysis));
+
+        // learn that B1 is the only candidate origin of the piece on B1
+        analysis.update_origins(B1, bitboard_of_squares(&[B1]));
+        assert!(rule.apply(&mut analysis));
+        assert_eq!(analysis.destinies(B1), bitboard_of_squares(&[B1]));
+        assert_eq!(analysis.destinies(G1), !EMPTY);
+        assert_eq!(analysis.result, None);
+
+        // the pieces on B1 and G1 cannot both come from B1
+        analysis.update_origins(G1, bitboard_of_squares(&[B1]));
+        rule.apply(&mut analysis);
+        assert_eq!(analysis.result, Some(Illegal));
+    }
+}
@@ -3,16 +3,20 @@
 //! If the parity of the number of moves by every piece can be determined,
 //! then the turn can also be determined. If the turn is not the expected one,
 //! the position must be illegal.
+//!
+//! Pawns that promoted into a piece on the board contribute the parity of
+//! their route to the promotion square plus the parity of the route of the
+//! promoted piece, as long as both are determined for every promotion square.
 
 use std::collections::HashMap;
 
-use chess::{get_rank, BitBoard, Board, Color, Piece, Square, ALL_COLORS, EMPTY};
+use chess::{get_rank, BitBoard, Color, Piece, Square, ALL_COLORS, EMPTY};
 
-use super::{Analysis, Rule};
+use super::{Analysis, Rule, COLOR_B1_AND_G1};
 use crate::{
     rules::ALL_ORIGINS,
-    utils::{origin_color, LIGHT_SQUARES},
-    Legality,
+    utils::{initial_piece_on, origin_color, MobilityGraph, LIGHT_SQUARES},
+    IllegalityClass,
 };
 
 #[derive(Debug)]
@@ -77,11 +81,12 @@ impl Rule for ParityRule {
                 return false;
             }
 
-            // missing pawns that may have promoted spoil the parity argument
+            // missing pawns that may have promoted spoil the parity argument (we do
+            // not know into what or how many times the promoted piece moved)
             let color = origin_color(origin);
             if origin.get_rank() == color.to_second_rank()
                 && !analysis.is_definitely_on_the_board(origin)
-                && analysis.reachable(origin) & get_rank(color.to_their_backrank()) != EMPTY
+                && analysis.reachable_as_pawn(origin) & get_rank(color.to_their_backrank()) != EMPTY
             {
                 return false;
             }
@@ -89,7 +94,16 @@ impl Rule for ParityRule {
 
         // check if the parity of the number of moves by every piece can be determined
         for origin in origins {
-            match path_parity(analysis, origin, analysis.destinies(origin).to_square()) {
+            let destiny = analysis.destinies(origin).to_square();
+            let color = origin_color(origin);
+            let has_promoted = origin.get_rank() == color.to_second_rank()
+                && analysis.board.piece_on(destiny) != Some(Piece::Pawn);
+            let parity = if has_promoted {
+                promoted_path_parity(analysis, origin, destiny)
+            } else {
+                path_parity(analysis, origin, destiny)
+            };
+            match parity {
                 None => return false,
                 Some(n) => parity_nb_moves += n,
             }
@@ -106,33 +120,67 @@ impl Rule for ParityRule {
         let expected_parity = 1;
 
         if parity_nb_moves % 2 != expected_parity {
-            analysis.result = Some(Legality::Illegal);
+            analysis.set_illegal(IllegalityClass::Parity);
         }
 
         false
     }
 }
 
-pub const COLOR_B1_AND_G1: [BitBoard; 2] = [
-    BitBoard(66),                  // B1 & G1
-    BitBoard(4755801206503243776), // B8 & G8
-];
-
 // Returns `Some n` if all paths to `target` by the piece which started the game
 // in `origin`, from its starting square, require a number of moves whose parity
 // is unique (in which case it coincides with the parity of `n`). Returns `None`
 // if there exist paths of both parities or no paths at all.
 fn path_parity(analysis: &Analysis, origin: Square, target: Square) -> Option<u8> {
-    // we try to find a 2-coloring of the connected component of `target` (with
-    // reversed arrows) that is reachable from `origin`; this function returns
-    // `Some n` if such 2-coloring exists, in that case `n = 0` if the colors of
-    // `source` and `target` are the same and `n = 1` otherwise
     debug_assert!(BitBoard::from_square(origin) & ALL_ORIGINS != EMPTY);
-    let piece = Board::default().piece_on(origin).unwrap();
+    let piece = initial_piece_on(origin);
     let color = origin_color(origin);
     let mobility = &analysis.mobility.value[color.to_index()][piece.to_index()];
-    let reachable_from_origin = analysis.reachable(origin);
-    if BitBoard::from_square(target) & reachable_from_origin == EMPTY {
+    graph_parity(mobility, origin, target, analysis.reachable(origin))
+}
+
+// Like [path_parity], for the pawn that started the game in `origin` and has
+// promoted into the piece currently on `target`. The number of moves is the
+// number of pawn moves until the promotion plus the number of moves of the
+// promoted piece, whose parities must be unique for every promotion square.
+fn promoted_path_parity(analysis: &Analysis, origin: Square, target: Square) -> Option<u8> {
+    let color = origin_color(origin);
+    let piece = analysis.board.piece_on(target)?;
+    let pawn_mobility = &analysis.mobility.value[color.to_index()][Piece::Pawn.to_index()];
+    let mobility = &analysis.mobility.value[color.to_index()][piece.to_index()];
+    let reachable_as_pawn = analysis.reachable_as_pawn(origin);
+    let mut parity = None;
+    for promotion_square in reachable_as_pawn & get_rank(color.to_their_backrank()) {
+        let reachable =
+            analysis.reachable_from_promotion(color, piece, promotion_square.get_file());
+        if BitBoard::from_square(target) & reachable == EMPTY {
+            continue;
+        }
+        let n = graph_parity(pawn_mobility, origin, promotion_square, reachable_as_pawn)?
+            + graph_parity(mobility, promotion_square, target, reachable)?;
+        if parity.is_some_and(|p| p != n % 2) {
+            return None;
+        }
+        parity = Some(n % 2);
+    }
+    parity
+}
+
+// Returns `Some n` if all paths from `source` to `target` in the given mobility
+// graph, through the given squares, have a unique parity (in which case it
+// coincides with the parity of `n`). Returns `None` if there exist paths of
+// both parities or no paths at all.
+fn graph_parity(
+    mobility: &MobilityGraph,
+    source: Square,
+    target: Square,
+    reachable_from_source: BitBoard,
+) -> Option<u8> {
+    // we try to find a 2-coloring of the connected component of `target` (with
+    // reversed arrows) that is reachable from `source`; this function returns
+    // `Some n` if such 2-coloring exists, in that case `n = 0` if the colors of
+    // `source` and `target` are the same and `n = 1` otherwise
+    if BitBoard::from_square(target) & reachable_from_source == EMPTY {
         return None;
     }
     let mut coloring = HashMap::new();
@@ -144,7 +192,7 @@ fn path_parity(analysis: &Analysis, origin: Square, target: Square) -> Option<u8
             match coloring.get(&node) {
                 None => {
                     coloring.insert(node, current_color);
-                    new_nodes |= mobility.predecessors(node) & reachable_from_origin;
+                    new_nodes |= mobility.predecessors(node) & reachable_from_source;
                 }
                 Some(color) => {
                     if *color != current_color {
@@ -155,7 +203,7 @@ fn path_parity(analysis: &Analysis, origin: Square, target: Square) -> Option<u8
         }
 
         if new_nodes == EMPTY {
-            return coloring.get(&origin).map(|b| if *b { 0 } else { 1 });
+            return coloring.get(&source).map(|b| if *b { 0 } else { 1 });
         }
 
         current_nodes = new_nodes;
@@ -166,6 +214,8 @@ fn path_parity(analysis: &Analysis, origin: Square, target: Square) -> Option<u8
 #[cfg(test)]
 mod tests {
 
+    use chess::{get_file, File};
+
     use super::*;
     use crate::{utils::*, RetractableBoard};
 
@@ -181,7 +231,7 @@ mod tests {
         assert_eq!(path_parity(&analysis, C7, C2), None);
         // remove C7 manually, as we did not perform the actual analysis on reachable
         // squares
-        analysis.reachable.value[Square::A7.to_index()] &= !BitBoard::from_square(C7);
+        analysis.reachable.value[Origin(Square::A7)] &= !BitBoard::from_square(C7);
         assert_eq!(path_parity(&analysis, A7, C5), Some(0));
         // knights
         assert_eq!(path_parity(&analysis, B1, A1), Some(1));
@@ -189,4 +239,19 @@ mod tests {
         // bishops
         assert_eq!(path_parity(&analysis, C8, D7), None);
     }
+
+    #[test]
+    fn test_promoted_path_parity() {
+        let mut analysis = Analysis::new(&RetractableBoard::default());
+        analysis.update_reachable_as_pawn(A2, get_file(File::A));
+
+        // the pawn from A2 may have reached A8 in 5 or 6 moves
+        assert_eq!(promoted_path_parity(&analysis, A2, B1), None);
+
+        // without its double step, 6 pawn moves plus an even number of knight moves
+        analysis.mobility.value[Color::White.to_index()][Piece::Pawn.to_index()]
+            .remove_edge(A2, A4);
+        assert_eq!(promoted_path_parity(&analysis, A2, B1), Some(0));
+        assert_eq!(promoted_path_parity(&analysis, A2, C1), None);
+    }
 }
@@ -0,0 +1,194 @@
+//! Pawn files rule.
+//!
+//! Every pawn on the board started the game on a different pawn origin, and it
+//! must have captured at least once per file it has changed. The pieces of a
+//! color cannot have captured more times than the number of missing opponents,
+//! so we look for the assignment of pawns to origins that requires the fewest
+//! captures (e.g. tripled pawns require at least two captures). If even that
+//! one requires too many captures, the position is illegal.
+//!
+//! Furthermore, if every assignment of a pawn to a certain origin requires too
+//! many captures, such origin is discarded for that pawn.
+
+use std::cmp::max;
+
+use chess::{get_rank, BitBoard, Color, Piece, Square, ALL_COLORS, EMPTY};
+
+use super::{sum_lower_bounds_nb_captures, Analysis, Rule, COLOR_ORIGINS};
+use crate::IllegalityClass;
+
+#[derive(Debug)]
+pub struct PawnFilesRule {
+    origins_counter: usize,
+    nb_captures_counter: usize,
+    pawn_capture_distances_counter: usize,
+}
+
+impl Rule for PawnFilesRule {
+    fn new() -> Self {
+        PawnFilesRule {
+            origins_counter: 0,
+            nb_captures_counter: 0,
+            pawn_capture_distances_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.origins_counter = analysis.origins.counter();
+        self.nb_captures_counter = analysis.nb_captures.counter();
+        self.pawn_capture_distances_counter = analysis.pawn_capture_distances.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.origins_counter != analysis.origins.counter()
+            || self.nb_captures_counter != analysis.nb_captures.counter()
+            || self.pawn_capture_distances_counter != analysis.pawn_capture_distances.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let mut progress = false;
+        for color in ALL_COLORS {
+            let pawn_origins = COLOR_ORIGINS[color.to_index()] & get_rank(color.to_second_rank());
+            let officer_origins = COLOR_ORIGINS[color.to_index()] & !pawn_origins;
+            let nb_missing_opponents = 16 - analysis.board.color_combined(!color).popcnt() as i32;
+            let budget =
+                nb_missing_opponents - sum_lower_bounds_nb_captures(analysis, officer_origins);
+
+            let pawns: Vec<Square> = (analysis.board.pieces(Piece::Pawn)
+                & analysis.board.color_combined(color))
+            .collect();
+            let costs = AssignmentCosts::new(analysis, color, &pawns, pawn_origins);
+
+            if costs.min_cost(None) > budget {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return progress;
+            }
+
+            for (i, pawn) in pawns.iter().enumerate() {
+                for origin in analysis.origins(*pawn) & pawn_origins {
+                    if costs.min_cost(Some((origin, i))) > budget {
+                        let others = analysis.origins(*pawn) & !BitBoard::from_square(origin);
+                        progress |= analysis.update_origins(*pawn, others);
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// An upper bound on the cost of any assignment, used for infeasible ones.
+const INFEASIBLE: i32 = 1000;
+
+/// The number of captures required by the pieces that started the game on the
+/// pawn origins of a color, depending on which of them is (still) the pawn on
+/// each square of the board.
+struct AssignmentCosts {
+    /// The pawn origins together with their lower bound on the number of
+    /// captures and, for every pawn on the board, the captures required by such
+    /// pawn if it started on the origin (`None` if it cannot have).
+    origins: Vec<(Square, i32, Vec<Option<i32>>)>,
+    nb_pawns: usize,
+}
+
+impl AssignmentCosts {
+    fn new(analysis: &Analysis, color: Color, pawns: &[Square], pawn_origins: BitBoard) -> Self {
+        let origins = pawn_origins
+            .into_iter()
+            .map(|origin| {
+                let lower_bound = analysis.nb_captures_lower_bound(origin);
+                let costs = pawns
+                    .iter()
+                    .map(|pawn| {
+                        if analysis.origins(*pawn) & BitBoard::from_square(origin) == EMPTY {
+                            return None;
+                        }
+                        let file = origin.get_file();
+                        let nb_files =
+                            (file.to_index() as i32 - pawn.get_file().to_index() as i32).abs();
+                        let distance = analysis.pawn_capture_distances(color, file, *pawn) as i32;
+                        Some(max(lower_bound, max(nb_files, distance)))
+                    })
+                    .collect();
+                (origin, lower_bound, costs)
+            })
+            .collect();
+        AssignmentCosts {
+            origins,
+            nb_pawns: pawns.len(),
+        }
+    }
+
+    /// The minimum number of captures required by the pieces that started on
+    /// the pawn origins, over all the assignments of the pawns on the board to
+    /// different origins. If `forced = Some((origin, i))`, only the assignments
+    /// where the `i`-th pawn started on `origin` are considered.
+    fn min_cost(&self, forced: Option<(Square, usize)>) -> i32 {
+        // min_costs[mask] is the minimum cost of assigning the set of pawns
+        // encoded by `mask` to the origins considered so far
+        let nb_masks = 1 << self.nb_pawns;
+        let mut min_costs = vec![INFEASIBLE; nb_masks];
+        min_costs[0] = 0;
+        for (origin, lower_bound, costs) in self.origins.iter() {
+            let mut new_min_costs = vec![INFEASIBLE; nb_masks];
+            for (mask, min_cost) in min_costs.iter().enumerate() {
+                if *min_cost == INFEASIBLE {
+                    continue;
+                }
+                // the origin is not assigned to any pawn on the board
+                if !matches!(forced, Some((o, _)) if o == *origin) {
+                    new_min_costs[mask] = new_min_costs[mask].min(min_cost + lower_bound);
+                }
+                // the origin is assigned to the i-th pawn
+                for (i, cost) in costs.iter().enumerate() {
+                    let allowed = match forced {
+                        Some((o, j)) => (o == *origin) == (i == j),
+                        None => true,
+                    };
+                    if let Some(cost) = cost {
+                        if allowed && mask & (1 << i) == 0 {
+                            let new_mask = mask | (1 << i);
+                            new_min_costs[new_mask] = new_min_costs[new_mask].min(min_cost + cost);
+                        }
+                    }
+                }
+            }
+            min_costs = new_min_costs;
+        }
+        min_costs[nb_masks - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::Rank;
+
+    use super::*;
+    use crate::{utils::*, Legality::Illegal, RetractableBoard};
+
+    #[test]
+    fn test_pawn_files_rule() {
+        // tripled white pawns require two captures, but Black is only missing one piece
+        let board = RetractableBoard::from_fen("rnbqkbn1/pppppppp/8/8/4P3/4P3/4P3/RNBQKBNR w Q -")
+            .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        let rule = PawnFilesRule::new();
+        rule.apply(&mut analysis);
+        assert_eq!(analysis.result, Some(Illegal));
+
+        // with two missing black pieces the position is fine, but the white pawns
+        // must come from the D, E and F files
+        let board = RetractableBoard::from_fen("rnbqkb2/pppppppp/8/8/4P3/4P3/4P3/RNBQKBNR w Q -")
+            .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        assert!(rule.apply(&mut analysis));
+        assert_eq!(analysis.result, None);
+        for square in [E2, E3, E4] {
+            assert_eq!(
+                analysis.origins(square) & get_rank(Rank::Second),
+                bitboard_of_squares(&[D2, E2, F2])
+            );
+        }
+    }
+}
@@ -4,9 +4,15 @@
 //! origin, no other piece can possibly have moved between such origin and its
 //! current square. We remove all such moves from the mobility graphs
 //! accordingly.
+//!
+//! The same holds for a pawn on their relative 4th rank whose single candidate
+//! origin is on the same file, if it never captured: it went through the 3rd
+//! rank square (or jumped over it), so the file segment between its origin and
+//! its current square has been blocked during the whole game.
 
 use chess::{
-    get_pawn_attacks, get_pawn_quiets, get_rank, Color, Piece, Rank, ALL_COLORS, ALL_PIECES, EMPTY,
+    get_pawn_attacks, get_pawn_quiets, get_rank, BitBoard, Color, Piece, Rank, ALL_COLORS,
+    ALL_PIECES, EMPTY,
 };
 
 use super::{Analysis, Rule};
@@ -14,19 +20,25 @@ use super::{Analysis, Rule};
 #[derive(Debug)]
 pub struct PawnOn3rdRankRule {
     origins_counter: usize,
+    nb_captures_counter: usize,
 }
 
 impl Rule for PawnOn3rdRankRule {
     fn new() -> Self {
-        PawnOn3rdRankRule { origins_counter: 0 }
+        PawnOn3rdRankRule {
+            origins_counter: 0,
+            nb_captures_counter: 0,
+        }
     }
 
     fn update(&mut self, analysis: &Analysis) {
         self.origins_counter = analysis.origins.counter();
+        self.nb_captures_counter = analysis.nb_captures.counter();
     }
 
     fn is_applicable(&self, analysis: &Analysis) -> bool {
         self.origins_counter != analysis.origins.counter()
+            || self.nb_captures_counter != analysis.nb_captures.counter()
     }
 
     fn apply(&self, analysis: &mut Analysis) -> bool {
@@ -78,6 +90,38 @@ impl Rule for PawnOn3rdRankRule {
                     progress |= analysis.remove_incoming_edges(Piece::King, !color, square);
                 }
             }
+
+            let fourth_rank = match color {
+                Color::White => Rank::Fourth,
+                Color::Black => Rank::Fifth,
+            };
+            for square in analysis.board.color_combined(color)
+                & analysis.board.pieces(Piece::Pawn)
+                & get_rank(fourth_rank)
+            {
+                // if the 4th rank pawn has a single candidate origin on its file and it
+                // never captured
+                let origin = square.ubackward(color).ubackward(color);
+                if analysis.origins(square) != BitBoard::from_square(origin)
+                    || analysis.nb_captures_upper_bound(origin) > 0
+                {
+                    continue;
+                }
+
+                // remove all arrows that pass through that origin and its current square
+                for other_color in ALL_COLORS {
+                    for piece in ALL_PIECES {
+                        if piece != Piece::Pawn || other_color != color {
+                            progress |= analysis.remove_edges_passing_through_squares(
+                                piece,
+                                other_color,
+                                origin,
+                                square,
+                            );
+                        }
+                    }
+                }
+            }
         }
 
         progress
@@ -131,4 +175,30 @@ mod tests {
         // now we know
         assert!(!analysis.mobility.value[Black.to_index()][King.to_index()].exists_edge(G4, G3));
     }
+
+    #[test]
+    fn test_pawn_on_4th_rank() {
+        let board =
+            RetractableBoard::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -")
+                .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        let pawn_on_3rd_rank = PawnOn3rdRankRule::new();
+
+        // learn that E2 is the only origin of the pawn on E4
+        analysis.update_origins(E4, BitBoard::from_square(E2));
+        pawn_on_3rd_rank.apply(&mut analysis);
+
+        // the pawn may have captured (twice), so nothing can be derived
+        assert!(analysis.mobility.value[Black.to_index()][Rook.to_index()].exists_edge(E8, E1));
+
+        // learn that the pawn never captured
+        analysis.update_captures_upper_bound(E2, 0);
+        pawn_on_3rd_rank.apply(&mut analysis);
+
+        // the file segment between E2 and E4 was always blocked
+        assert!(!analysis.mobility.value[Black.to_index()][Rook.to_index()].exists_edge(E8, E1));
+        assert!(!analysis.mobility.value[Black.to_index()][Rook.to_index()].exists_edge(E4, E2));
+        assert!(!analysis.mobility.value[White.to_index()][Queen.to_index()].exists_edge(E1, E5));
+        assert!(analysis.mobility.value[White.to_index()][Queen.to_index()].exists_edge(E1, E3));
+    }
 }
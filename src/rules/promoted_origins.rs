@@ -0,0 +1,135 @@
+//! Promoted origins rule.
+//!
+//! A piece that is certainly the result of a promotion (all its candidate
+//! origins are on the 2nd rank) must have promoted on a square from which its
+//! current square is reachable by a piece of its type. The pawn that promoted
+//! into it must have reached such promotion square from its origin file, with
+//! no more captures than its upper bound on the number of captures, nor than
+//! the captures left to its color after accounting for the lower bounds of all
+//! the other pieces. The candidate origins that cannot satisfy this condition
+//! are filtered out.
+
+use chess::{get_rank, BitBoard, Piece, EMPTY};
+
+use super::{sum_lower_bounds_nb_captures, Analysis, Rule, COLOR_ORIGINS};
+
+#[derive(Debug)]
+pub struct PromotedOriginsRule {
+    origins_counter: usize,
+    nb_captures_counter: usize,
+    pawn_capture_distances_counter: usize,
+    reachable_from_promotion_counter: usize,
+    steady_counter: usize,
+}
+
+impl Rule for PromotedOriginsRule {
+    fn new() -> Self {
+        PromotedOriginsRule {
+            origins_counter: 0,
+            nb_captures_counter: 0,
+            pawn_capture_distances_counter: 0,
+            reachable_from_promotion_counter: 0,
+            steady_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.origins_counter = analysis.origins.counter();
+        self.nb_captures_counter = analysis.nb_captures.counter();
+        self.pawn_capture_distances_counter = analysis.pawn_capture_distances.counter();
+        self.reachable_from_promotion_counter = analysis.reachable_from_promotion.counter();
+        self.steady_counter = analysis.steady.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.origins_counter != analysis.origins.counter()
+            || self.nb_captures_counter != analysis.nb_captures.counter()
+            || self.pawn_capture_distances_counter != analysis.pawn_capture_distances.counter()
+            || self.reachable_from_promotion_counter != analysis.reachable_from_promotion.counter()
+            || self.steady_counter != analysis.steady.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        let mut progress = false;
+        let pawns = *analysis.board.pieces(Piece::Pawn);
+        for square in analysis.board.combined() & !pawns & !analysis.steady.value {
+            let piece = analysis.piece_type_on(square);
+            let color = analysis.piece_color_on(square);
+            let pawn_origins = COLOR_ORIGINS[color.to_index()] & get_rank(color.to_second_rank());
+            let origins = analysis.origins(square);
+            if origins == EMPTY || origins & !pawn_origins != EMPTY {
+                continue;
+            }
+
+            // the promotion squares from which the piece may have reached its square
+            let promotion_squares = (get_rank(color.to_their_backrank()) & !analysis.steady.value)
+                .into_iter()
+                .filter(|promotion_square| {
+                    BitBoard::from_square(square)
+                        & analysis.reachable_from_promotion(
+                            color,
+                            piece,
+                            promotion_square.get_file(),
+                        )
+                        != EMPTY
+                })
+                .collect::<Vec<_>>();
+
+            let nb_missing_opponents = 16 - analysis.board.color_combined(!color).popcnt() as i32;
+            let mut plausible_origins = EMPTY;
+            for origin in origins {
+                let others = COLOR_ORIGINS[color.to_index()] & !BitBoard::from_square(origin);
+                let budget = analysis
+                    .nb_captures_upper_bound(origin)
+                    .min(nb_missing_opponents - sum_lower_bounds_nb_captures(analysis, others));
+                if promotion_squares.iter().any(|promotion_square| {
+                    analysis.pawn_capture_distances(color, origin.get_file(), *promotion_square)
+                        as i32
+                        <= budget
+                }) {
+                    plausible_origins |= BitBoard::from_square(origin);
+                }
+            }
+            progress |= analysis.update_origins(square, plausible_origins);
+        }
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::{Color, Rank, ALL_FILES};
+
+    use super::*;
+    use crate::{
+        rules::{MobilityRule, OriginsRule},
+        utils::*,
+        RetractableBoard,
+    };
+
+    #[test]
+    fn test_promoted_origins_rule() {
+        // the queen on H5 is promoted, and it may only have been promoted on A8
+        let analyze = |fen| {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            let mut analysis = Analysis::new(&board);
+            OriginsRule::new().apply(&mut analysis);
+            MobilityRule::new().apply(&mut analysis);
+            analysis.update_origins(H5, get_rank(Rank::Second));
+            for file in ALL_FILES.into_iter().skip(1) {
+                analysis.update_reachable_from_promotion(Color::White, Piece::Queen, file, EMPTY);
+            }
+            PromotedOriginsRule::new().apply(&mut analysis);
+            analysis
+        };
+
+        // Black is not missing any piece, so the pawn promoted without capturing
+        let analysis = analyze("rnbqkbnr/pppppppp/8/7Q/8/8/1PPPPPPP/RNBQKBNR b KQkq -");
+        assert_eq!(analysis.origins(H5), bitboard_of_squares(&[A2]));
+
+        // with one missing black piece, the B-pawn may have captured once
+        let analysis = analyze("rnbqkbnr/ppppppp1/8/7Q/8/8/1PPPPPPP/RNBQKBNR b KQkq -");
+        assert_eq!(analysis.origins(H5), bitboard_of_squares(&[A2, B2]));
+    }
+}
@@ -0,0 +1,73 @@
+//! Promotions rule.
+//!
+//! If the analysis options bound the number of promotions of a player (see
+//! [`AnalysisOptions::max_promotions`](crate::AnalysisOptions)), the position
+//! is illegal as soon as the player must have promoted more pawns than
+//! allowed (see [`Analysis::min_promotions`]).
+
+use chess::ALL_COLORS;
+
+use super::{Analysis, Rule};
+use crate::IllegalityClass;
+
+#[derive(Debug)]
+pub struct PromotionsRule {
+    origins_counter: usize,
+    missing_counter: usize,
+}
+
+impl Rule for PromotionsRule {
+    fn new() -> Self {
+        PromotionsRule {
+            origins_counter: 0,
+            missing_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.origins_counter = analysis.origins.counter();
+        self.missing_counter = analysis.missing.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.origins_counter != analysis.origins.counter()
+            || self.missing_counter != analysis.missing.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        for color in ALL_COLORS {
+            if let Some(max_promotions) = analysis.options.max_promotions[color.to_index()] {
+                if analysis.min_promotions(color) > max_promotions as u32 {
+                    analysis.set_illegal(IllegalityClass::Counting);
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{AnalysisOptions, Legality, RetractableBoard};
+
+    #[test]
+    fn test_promotions() {
+        let board = RetractableBoard::from_fen("4k3/8/8/8/8/8/1PPPPPPP/QNBQKBNR w - -").unwrap();
+        for (max_promotions, expected) in [
+            (None, None),
+            (Some(2), None),
+            (Some(1), None),
+            (Some(0), Some(Legality::Illegal)),
+        ] {
+            let mut analysis = Analysis::new(&board);
+            analysis.options = AnalysisOptions {
+                max_promotions: [max_promotions, Some(0)],
+                ..Default::default()
+            };
+            PromotionsRule::new().apply(&mut analysis);
+            assert_eq!(analysis.result, expected);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Route interference rule.
+//!
+//! A pawn with a single candidate origin that reached its current square in a
+//! single move (or in two moves along its file) blocks its origin until that
+//! move and blocks its current square from then on until the end of the game
+//! (pawns never move backwards). The two squares are thus free during disjoint
+//! periods of time: no other piece can have passed through the pawn's current
+//! square after having passed through its origin.
+//!
+//! For every officer, we compute the squares it may have reached in a timeline
+//! where the pawn's origin is occupied before the pawn moves and the pawn's
+//! current square is occupied afterwards, refining its reachable squares
+//! accordingly. For example, a queen enclosed in the first rank by steady pawns
+//! except for the one that went from D2 to D3 can never have visited D3.
+//!
+//! Besides, some squares can only be used once: a square that no opponent can
+//! reach and that no piece of a given color can leave after stopping on it
+//! (e.g. a promotion corner surrounded by steady pieces) hosts at most one
+//! piece of that color during the whole game, which is still there at the end.
+//! If `k` officers must stop on such squares, but fewer than `k` of them are
+//! occupied by officers of the right type, the position is illegal.
+
+use chess::{get_rank, BitBoard, Color, Piece, Rank, Square, ALL_COLORS, ALL_PIECES, EMPTY};
+
+use super::{Analysis, Rule, COLOR_ORIGINS};
+use crate::{
+    utils::{initial_piece_on, MobilityGraph, Timeline},
+    IllegalityClass,
+};
+
+#[derive(Debug)]
+pub struct RouteInterferenceRule {
+    origins_counter: usize,
+    nb_captures_counter: usize,
+    mobility_counter: usize,
+    reachable_counter: usize,
+    destinies_counter: usize,
+}
+
+impl Rule for RouteInterferenceRule {
+    fn new() -> Self {
+        RouteInterferenceRule {
+            origins_counter: 0,
+            nb_captures_counter: 0,
+            mobility_counter: 0,
+            reachable_counter: 0,
+            destinies_counter: 0,
+        }
+    }
+
+    fn update(&mut self, analysis: &Analysis) {
+        self.origins_counter = analysis.origins.counter();
+        self.nb_captures_counter = analysis.nb_captures.counter();
+        self.mobility_counter = analysis.mobility.counter();
+        self.reachable_counter = analysis.reachable.counter();
+        self.destinies_counter = analysis.destinies.counter();
+    }
+
+    fn is_applicable(&self, analysis: &Analysis) -> bool {
+        self.origins_counter != analysis.origins.counter()
+            || self.nb_captures_counter != analysis.nb_captures.counter()
+            || self.mobility_counter != analysis.mobility.counter()
+            || self.reachable_counter != analysis.reachable.counter()
+            || self.destinies_counter != analysis.destinies.counter()
+    }
+
+    fn apply(&self, analysis: &mut Analysis) -> bool {
+        for color in ALL_COLORS {
+            if !enough_one_shot_squares(analysis, color) {
+                analysis.set_illegal(IllegalityClass::Route);
+                return false;
+            }
+        }
+
+        let mut progress = false;
+
+        for (origin, transit, square) in interfering_pawns(analysis) {
+            let mut timeline = Timeline::new();
+            timeline.occupy(0, BitBoard::from_square(origin));
+            if let Some(transit) = transit {
+                let phase = timeline.split();
+                timeline.occupy(phase, BitBoard::from_square(transit));
+            }
+            // the officers may have been captured by the pawn on its current
+            // square, but they cannot have moved from there
+            let phase = timeline.split();
+            timeline.occupy(phase, BitBoard::from_square(square));
+
+            for color in ALL_COLORS {
+                let officer_origins =
+                    COLOR_ORIGINS[color.to_index()] & get_rank(color.to_my_backrank());
+                for piece in [
+                    Piece::Knight,
+                    Piece::Bishop,
+                    Piece::Rook,
+                    Piece::Queen,
+                    Piece::King,
+                ] {
+                    let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+                    let reached = officer_origins
+                        .into_iter()
+                        .filter(|officer_origin| initial_piece_on(*officer_origin) == piece)
+                        .map(|officer_origin| {
+                            (
+                                officer_origin,
+                                timeline.reachable(graph, BitBoard::from_square(officer_origin)),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    for (officer_origin, reached) in reached {
+                        progress |= analysis.update_reachable(officer_origin, reached);
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// The squares that are visited by at most one piece of the given color during
+/// the whole game, which stays there until the end: the squares that no
+/// opponent may have reached (so no piece can have been captured there) and
+/// that have no exits in the mobility graphs of the given color.
+fn one_shot_squares(analysis: &Analysis, color: Color) -> BitBoard {
+    let opponent_reach = COLOR_ORIGINS[(!color).to_index()]
+        .into_iter()
+        .fold(EMPTY, |acc, origin| acc | analysis.reachable(origin));
+    let mut squares = !opponent_reach;
+    for piece in ALL_PIECES {
+        let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+        squares = squares
+            .into_iter()
+            .filter(|square| graph.successors(*square) == EMPTY)
+            .fold(EMPTY, |acc, square| acc | BitBoard::from_square(square));
+    }
+    squares
+}
+
+/// The squares that may be reached from the given origin according to the
+/// given mobility graph, without ever stopping on the given squares.
+fn reachable_without_stopping_on(
+    graph: &MobilityGraph,
+    origin: Square,
+    squares: BitBoard,
+) -> BitBoard {
+    let mut reached = BitBoard::from_square(origin) & !squares;
+    let mut frontier = reached;
+    while frontier != EMPTY {
+        let mut next = EMPTY;
+        for square in frontier {
+            next |= graph.successors(square);
+        }
+        frontier = next & !squares & !reached;
+        reached |= frontier;
+    }
+    reached
+}
+
+/// Checks the pigeonhole principle on the one-shot squares of the given color
+/// (see [`one_shot_squares`]): the officers of every type that cannot reach
+/// any of their final squares without stopping on a one-shot square must be on
+/// one of them, so they cannot outnumber the officers of their type on such
+/// squares.
+fn enough_one_shot_squares(analysis: &Analysis, color: Color) -> bool {
+    let one_shot = one_shot_squares(analysis, color);
+    if one_shot == EMPTY {
+        return true;
+    }
+    let officer_origins = COLOR_ORIGINS[color.to_index()] & get_rank(color.to_my_backrank());
+    for piece in [
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+        Piece::King,
+    ] {
+        let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+        let nb_bound = officer_origins
+            .into_iter()
+            .filter(|origin| initial_piece_on(*origin) == piece)
+            .filter(|origin| {
+                reachable_without_stopping_on(graph, *origin, one_shot)
+                    & analysis.final_squares(*origin)
+                    == EMPTY
+            })
+            .count() as u32;
+        let available =
+            one_shot & analysis.board.color_combined(color) & analysis.board.pieces(piece);
+        if nb_bound > available.popcnt() {
+            return false;
+        }
+    }
+    true
+}
+
+/// The pawns on the board that went from their (single) candidate origin to
+/// their current square in one move, or in two moves along their file. They
+/// are returned as triplets `(origin, transit, square)`, where `transit` is the
+/// square the pawn may have stood on between the two moves, if any.
+fn interfering_pawns(analysis: &Analysis) -> Vec<(Square, Option<Square>, Square)> {
+    let mut pawns = vec![];
+    for color in ALL_COLORS {
+        let (third_rank, fourth_rank) = match color {
+            Color::White => (Rank::Third, Rank::Fourth),
+            Color::Black => (Rank::Sixth, Rank::Fifth),
+        };
+        let color_pawns = analysis.board.color_combined(color) & analysis.board.pieces(Piece::Pawn);
+
+        for square in color_pawns & get_rank(third_rank) {
+            if analysis.origins(square).popcnt() == 1 {
+                pawns.push((analysis.origins(square).to_square(), None, square));
+            }
+        }
+
+        for square in color_pawns & get_rank(fourth_rank) {
+            let transit = square.ubackward(color);
+            let origin = transit.ubackward(color);
+            if analysis.origins(square) == BitBoard::from_square(origin)
+                && analysis.nb_captures_upper_bound(origin) == 0
+            {
+                pawns.push((origin, Some(transit), square));
+            }
+        }
+    }
+    pawns
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::EMPTY;
+
+    use super::*;
+    use crate::{
+        rules::{MobilityRule, OriginsRule, SteadyMobilityRule, SteadyRule},
+        utils::*,
+        RetractableBoard,
+    };
+
+    #[test]
+    fn test_route_interference_rule() {
+        let board =
+            RetractableBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/3P4/PPP1PPPP/RNBQKBNR b KQkq -")
+                .expect("Valid Position");
+        let mut analysis = Analysis::new(&board);
+        analysis.update_origins(D3, BitBoard::from_square(D2));
+        OriginsRule::new().apply(&mut analysis);
+        SteadyRule::new().apply(&mut analysis);
+        SteadyMobilityRule::new().apply(&mut analysis);
+        MobilityRule::new().apply(&mut analysis);
+
+        // the white queen may have reached D3 before the rule is applied
+        assert_ne!(analysis.reachable(D1) & BitBoard::from_square(D3), EMPTY);
+        assert!(RouteInterferenceRule::new().apply(&mut analysis));
+
+        // the queen can only leave the 1st rank through D2, after the pawn went to D3
+        assert_eq!(analysis.reachable(D1) & BitBoard::from_square(D3), EMPTY);
+        assert_ne!(analysis.reachable(D1) & BitBoard::from_square(D2), EMPTY);
+        assert_ne!(analysis.reachable(D1) & BitBoard::from_square(E3), EMPTY);
+
+        // black pieces are not affected
+        assert_ne!(analysis.reachable(B8) & BitBoard::from_square(D3), EMPTY);
+    }
+
+    #[test]
+    fn test_one_shot_squares() {
+        for (fen, legal) in [
+            ("4k3/8/8/8/8/8/8/1N2K1N1 w - -", true),
+            ("4k3/8/8/8/8/8/8/1N2K3 w - -", false),
+        ] {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            let mut analysis = Analysis::new(&board);
+
+            // no piece can leave B1 nor G1 and Black cannot reach them, so
+            // the white knights never left their origins
+            let squares = bitboard_of_squares(&[B1, G1]);
+            for square in squares {
+                for piece in ALL_PIECES {
+                    analysis.remove_outgoing_edges(piece, Color::White, square);
+                }
+            }
+            for origin in COLOR_ORIGINS[Color::Black.to_index()] {
+                analysis.update_reachable(origin, !squares);
+            }
+            assert_eq!(one_shot_squares(&analysis, Color::White), squares);
+
+            RouteInterferenceRule::new().apply(&mut analysis);
+            assert_eq!(analysis.result.is_none(), legal);
+        }
+    }
+}
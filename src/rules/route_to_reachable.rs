@@ -2,11 +2,13 @@
 //!
 //! This rule filters the set of reachable squares of every piece by removing
 //! the squares for which there does not exists a path from its original square.
+//! For pawns, it also filters the squares they may have reached before
+//! promoting.
 
-use chess::{get_rank, BitBoard, Board, Color, Piece, Square, ALL_COLORS, EMPTY};
+use chess::{get_rank, BitBoard, Color, Piece, Square, ALL_COLORS, EMPTY};
 
 use super::{Rule, COLOR_ORIGINS};
-use crate::analysis::Analysis;
+use crate::{analysis::Analysis, utils::initial_piece_on};
 
 #[derive(Debug)]
 pub struct RouteToReachable {
@@ -49,7 +51,7 @@ impl Rule for RouteToReachable {
 
         for color in ALL_COLORS {
             for square in COLOR_ORIGINS[color.to_index()] {
-                let piece = Board::default().piece_on(square).unwrap();
+                let piece = initial_piece_on(square);
                 let nb_allowed_captures = analysis.nb_captures_upper_bound(square);
                 let mut reachable_targets = BitBoard::from_square(square);
                 for target in analysis.reachable(square) & !analysis.steady.value {
@@ -59,6 +61,18 @@ impl Rule for RouteToReachable {
                     }
                 }
                 progress |= analysis.update_reachable(square, reachable_targets);
+
+                if piece == Piece::Pawn {
+                    let file = square.get_file();
+                    let mut reachable_as_pawn = EMPTY;
+                    for target in analysis.reachable_as_pawn(square) & analysis.reachable(square) {
+                        let n = analysis.pawn_capture_distances(color, file, target);
+                        if n <= nb_allowed_captures as u8 {
+                            reachable_as_pawn |= BitBoard::from_square(target);
+                        }
+                    }
+                    progress |= analysis.update_reachable_as_pawn(square, reachable_as_pawn);
+                }
             }
         }
         progress
@@ -104,7 +118,7 @@ pub fn distance_to_target(
 
 #[cfg(test)]
 mod tests {
-    use chess::{Color::*, Piece::*};
+    use chess::{get_file, Color::*, File, Piece::*};
 
     use super::*;
     use crate::{
@@ -140,4 +154,22 @@ mod tests {
         MobilityRule::new().apply(&mut analysis);
         assert_eq!(distance_to_target(&analysis, A2, C4, Pawn, White), 2);
     }
+
+    #[test]
+    fn test_reachable_as_pawn() {
+        let mut analysis = Analysis::new(&RetractableBoard::default());
+        OriginsRule::new().apply(&mut analysis);
+        MobilityRule::new().apply(&mut analysis);
+        analysis.update_captures_upper_bound(A2, 0);
+        RouteToReachable::new().apply(&mut analysis);
+
+        // the pawn may reach any square after promoting on A8, but as a pawn it
+        // cannot leave the A-file without capturing
+        assert_eq!(
+            analysis.reachable(A2) & bitboard_of_squares(&[C4]),
+            bitboard_of_squares(&[C4])
+        );
+        assert_eq!(analysis.reachable_as_pawn(A2) & !get_file(File::A), EMPTY);
+        assert_eq!(analysis.reachable_as_pawn(A1), EMPTY);
+    }
 }
@@ -5,7 +5,10 @@
 //! queen cannot be on the right of a king. If they do not respect a valid
 //! order, the position must be illegal.
 //!
-//! Note: We must be careful with castlings.
+//! Note: We must be careful with castlings. Unless we are under a castling
+//! hypothesis (see [`CastlingHypothesis`]), in which case we know exactly what
+//! pieces may have swapped their order, we can only check the position of the
+//! piece on the "D" file.
 //!
 //! Extra: We also have bishops into account although they are not royal pieces
 //! as they are easy to handle.
@@ -13,7 +16,7 @@
 use chess::{get_rank, BitBoard, Color, File, Square, ALL_COLORS, ALL_FILES, EMPTY};
 
 use super::{Analysis, Rule};
-use crate::Legality;
+use crate::{CastlingHypothesis, IllegalityClass};
 
 #[derive(Debug)]
 pub struct RoyaltyOn1stRankRule {
@@ -43,6 +46,18 @@ impl Rule for RoyaltyOn1stRankRule {
         for color in ALL_COLORS {
             let royalty = never_left_1st_rank(analysis, color);
 
+            if let Some(hypothesis) = analysis.options.castling[color.to_index()] {
+                if !castling_is_possible(analysis, color, hypothesis)
+                    || !royalty.windows(2).all(|w| {
+                        file_after_castling(hypothesis, w[0])
+                            < file_after_castling(hypothesis, w[1])
+                    })
+                {
+                    analysis.set_illegal(IllegalityClass::Route);
+                }
+                continue;
+            }
+
             // we could expect the royalty files to be in alphabetical order for
             // legality, however, castling spoils this nice invariant; instead,
             // we will check that the "D" file (if it exists) is in its
@@ -56,7 +71,7 @@ impl Rule for RoyaltyOn1stRankRule {
             if royalty_indices.iter().position(|&i| i == 3)
                 != sorted_royalty_indices.iter().position(|&i| i == 3)
             {
-                analysis.result = Some(Legality::Illegal);
+                analysis.set_illegal(IllegalityClass::Route);
             }
         }
 
@@ -64,6 +79,34 @@ impl Rule for RoyaltyOn1stRankRule {
     }
 }
 
+/// The file where a piece that started on the given file (of the 1st rank)
+/// would be after the castling specified by the given hypothesis.
+fn file_after_castling(hypothesis: CastlingHypothesis, file: File) -> File {
+    match (hypothesis, file) {
+        (CastlingHypothesis::CastledKingside, File::E) => File::G,
+        (CastlingHypothesis::CastledKingside, File::H) => File::F,
+        (CastlingHypothesis::CastledQueenside, File::E) => File::C,
+        (CastlingHypothesis::CastledQueenside, File::A) => File::D,
+        _ => file,
+    }
+}
+
+/// Checks that the castling specified by the given hypothesis may have
+/// happened: the king and the relevant rook must have been able to reach their
+/// squares after castling.
+fn castling_is_possible(analysis: &Analysis, color: Color, hypothesis: CastlingHypothesis) -> bool {
+    let rook_file = match hypothesis {
+        CastlingHypothesis::DidNotCastle => return true,
+        CastlingHypothesis::CastledKingside => File::H,
+        CastlingHypothesis::CastledQueenside => File::A,
+    };
+    let rank = color.to_my_backrank();
+    [File::E, rook_file].into_iter().all(|file| {
+        let target = Square::make_square(rank, file_after_castling(hypothesis, file));
+        analysis.reachable_from_origin(color, file) & BitBoard::from_square(target) != EMPTY
+    })
+}
+
 /// Returns the files of the current location of pieces of the given color that
 /// cannot have possibly have left their relative first rank.
 fn never_left_1st_rank(analysis: &Analysis, color: Color) -> Vec<File> {
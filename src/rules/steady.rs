@@ -7,7 +7,7 @@
 //! pawns on their relative 2nd rank are steady, thus a white bishop on c1 is
 //! steady if there are white pawns on b2 and d2).
 
-use chess::{get_rank, BitBoard, CastleRights, Piece, ALL_COLORS, EMPTY};
+use chess::{get_pawn_attacks, get_rank, BitBoard, CastleRights, Color, Piece, ALL_COLORS, EMPTY};
 
 use super::{Analysis, Rule, QUEEN_ORIGINS};
 use crate::{rules::COLOR_ORIGINS, utils::predecessors, RetractableBoard};
@@ -101,6 +101,19 @@ const MARRIAGE_CAGE: [BitBoard; 2] = [
     BitBoard(2610961883968045056), // C8, C7, D7, E7, F7, F8
 ];
 
+/// The squares occupied or attacked by the steady pawns of the given color.
+/// The enemy king can never have stood on any of them.
+pub fn steady_pawns_coverage(analysis: &Analysis, color: Color) -> BitBoard {
+    let pawns = analysis.steady.value
+        & analysis.board.pieces(Piece::Pawn)
+        & analysis.board.color_combined(color);
+    let mut coverage = pawns;
+    for square in pawns {
+        coverage |= get_pawn_attacks(square, color, !EMPTY);
+    }
+    coverage
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
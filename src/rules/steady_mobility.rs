@@ -3,28 +3,41 @@
 //! We refine the mobility graphs based on the information on steady pieces:
 //!  - No piece may have passed through a steady-piece square.
 //!  - No piece may have moved from a square that was checking a steady king.
+//!  - No king may have ever stood on a square that is permanently attacked by a
+//!    steady enemy piece. For sliding pieces, we only consider their attacks
+//!    through squares that no piece may have ever occupied, since any other
+//!    piece could have been blocking the attack at some point.
 
-use chess::{ALL_COLORS, ALL_PIECES};
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_rook_moves, BitBoard, Color, Piece,
+    Square, ALL_COLORS, ALL_PIECES, EMPTY,
+};
 
-use super::{Analysis, Rule};
+use super::{Analysis, Rule, ALL_ORIGINS};
 use crate::utils::checking_predecessors;
 
 #[derive(Debug)]
 pub struct SteadyMobilityRule {
     steady_counter: usize,
+    mobility_counter: usize,
 }
 
 impl Rule for SteadyMobilityRule {
     fn new() -> Self {
-        SteadyMobilityRule { steady_counter: 0 }
+        SteadyMobilityRule {
+            steady_counter: 0,
+            mobility_counter: 0,
+        }
     }
 
     fn update(&mut self, analysis: &Analysis) {
         self.steady_counter = analysis.steady.counter();
+        self.mobility_counter = analysis.mobility.counter();
     }
 
     fn is_applicable(&self, analysis: &Analysis) -> bool {
         self.steady_counter != analysis.steady.counter()
+            || self.mobility_counter != analysis.mobility.counter()
     }
 
     fn apply(&self, analysis: &mut Analysis) -> bool {
@@ -54,10 +67,73 @@ impl Rule for SteadyMobilityRule {
             }
         }
 
+        // Remove all the king nodes that are permanently attacked by a steady piece
+        for square in analysis.steady.value {
+            let piece = analysis.piece_type_on(square);
+            let color = analysis.piece_color_on(square);
+            for attacked in permanently_attacked_squares(piece, color, square) {
+                progress |= analysis.remove_incoming_edges(Piece::King, !color, attacked);
+                progress |= analysis.remove_outgoing_edges(Piece::King, !color, attacked);
+            }
+        }
+
+        // Remove all the king nodes that are attacked by a steady sliding piece
+        // through squares that have never been occupied
+        let never_occupied = never_occupied_squares(analysis);
+        for square in analysis.steady.value {
+            let piece = analysis.piece_type_on(square);
+            let color = analysis.piece_color_on(square);
+            for attacked in sliding_attacks(piece, square, !never_occupied) {
+                progress |= analysis.remove_incoming_edges(Piece::King, !color, attacked);
+                progress |= analysis.remove_outgoing_edges(Piece::King, !color, attacked);
+            }
+        }
+
         progress
     }
 }
 
+/// The squares that no piece may have ever occupied: they are empty, they are
+/// not the origin of any piece and no piece may have ever moved into them.
+fn never_occupied_squares(analysis: &Analysis) -> BitBoard {
+    let mut never_occupied = !analysis.board.combined() & !ALL_ORIGINS;
+    for square in never_occupied {
+        for color in ALL_COLORS {
+            for piece in ALL_PIECES {
+                let graph = &analysis.mobility.value[color.to_index()][piece.to_index()];
+                if graph.predecessors(square) != EMPTY {
+                    never_occupied &= !BitBoard::from_square(square);
+                }
+            }
+        }
+    }
+    never_occupied
+}
+
+/// The squares attacked by a sliding piece of the given type standing on the
+/// given square, if the given blockers are the only obstacles (the first
+/// blocker of every ray is also attacked). This is empty for other pieces.
+fn sliding_attacks(piece: Piece, square: Square, blockers: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Bishop => get_bishop_moves(square, blockers),
+        Piece::Rook => get_rook_moves(square, blockers),
+        Piece::Queen => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+        _ => EMPTY,
+    }
+}
+
+/// The squares that a piece of the given type and color standing on the given
+/// square attacks, independently of the configuration of other pieces.
+fn permanently_attacked_squares(piece: Piece, color: Color, square: Square) -> BitBoard {
+    let mut attacked = EMPTY;
+    for target in get_king_moves(square) | get_knight_moves(square) {
+        if checking_predecessors(piece, color, target) & BitBoard::from_square(square) != EMPTY {
+            attacked |= BitBoard::from_square(target);
+        }
+    }
+    attacked
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -121,4 +197,56 @@ mod tests {
         assert!(analysis.mobility.value[Black.to_index()][Knight.to_index()].exists_edge(G4, F6));
         assert!(analysis.mobility.value[Black.to_index()][Knight.to_index()].exists_edge(F6, G4));
     }
+
+    #[test]
+    fn test_steady_attackers() {
+        let mut analysis = Analysis::new(&RetractableBoard::default());
+        OriginsRule::new().apply(&mut analysis);
+
+        // learn that the white pawn on E2 and the white knight on G1 are steady
+        analysis.update_steady(bitboard_of_squares(&[E2, G1]));
+        SteadyMobilityRule::new().apply(&mut analysis);
+
+        // the black king cannot have ever stood on D3, F3 (pawn) or H3 (knight)
+        let black_king = &analysis.mobility.value[Black.to_index()][King.to_index()];
+        assert!(!black_king.exists_edge(D4, D3));
+        assert!(!black_king.exists_edge(F3, F4));
+        assert!(!black_king.exists_edge(H4, H3));
+        assert!(black_king.exists_edge(E4, E3));
+
+        // the white king is not affected
+        let white_king = &analysis.mobility.value[White.to_index()][King.to_index()];
+        assert!(white_king.exists_edge(D4, D3));
+    }
+
+    #[test]
+    fn test_steady_sliding_attackers() {
+        let board = RetractableBoard::from_fen("4k3/8/8/8/3Q4/8/8/4K3 b - -").unwrap();
+        let mut analysis = Analysis::new(&board);
+
+        // learn that the white queen is steady, its attacks may have been blocked
+        analysis.update_steady(bitboard_of_squares(&[D4]));
+        SteadyMobilityRule::new().apply(&mut analysis);
+        let black_king = &analysis.mobility.value[Black.to_index()][King.to_index()];
+        assert!(!black_king.exists_edge(C6, D5));
+        assert!(black_king.exists_edge(C7, D6));
+        assert!(black_king.exists_edge(C8, D7));
+
+        // no piece may have ever stood on D5 or D6, so the queen attacks D7
+        for square in [D5, D6] {
+            for color in ALL_COLORS {
+                for piece in ALL_PIECES {
+                    analysis.remove_incoming_edges(piece, color, square);
+                }
+            }
+        }
+        SteadyMobilityRule::new().apply(&mut analysis);
+        let black_king = &analysis.mobility.value[Black.to_index()][King.to_index()];
+        assert!(!black_king.exists_edge(C8, D7));
+        assert!(!black_king.exists_edge(E8, D7));
+
+        // but not D8, which lies behind the D7 origin square
+        assert!(black_king.exists_edge(E8, D8));
+        assert!(black_king.exists_edge(C8, D8));
+    }
 }
@@ -16,7 +16,7 @@ use chess::{
 };
 
 use super::{sum_lower_bounds_nb_captures, Analysis, Rule, ALL_ORIGINS};
-use crate::{rules::COLOR_ORIGINS, utils::origin_color, Legality};
+use crate::{rules::COLOR_ORIGINS, utils::origin_color, IllegalityClass};
 
 #[derive(Debug)]
 pub struct SurpassedPawnsRule {
@@ -94,7 +94,7 @@ impl Rule for SurpassedPawnsRule {
             || (min_nb_white_captures as u32 + nb_black_on_board + nb_black_ignored > 16)
             || (min_nb_black_captures as u32 + nb_white_on_board + nb_white_ignored > 16)
         {
-            analysis.result = Some(Legality::Illegal);
+            analysis.set_illegal(IllegalityClass::Counting);
         }
 
         false
@@ -3,18 +3,22 @@
 //! We make sure that all known capturing squares can be reached by an opponent
 //! piece to be captured. This allows us to deduce new information about e.g.
 //! the destinies of a pieces.
+//!
+//! The assignment of captured pieces to tombs is checked through a bipartite
+//! matching, which also reveals the victims that are forced on a tomb.
 
 use chess::{BitBoard, Color, Piece, Square, ALL_COLORS, ALL_FILES, ALL_RANKS, EMPTY};
 
 use super::{Analysis, Rule, COLOR_B1_AND_G1, COLOR_ORIGINS};
 use crate::{
-    utils::{find_k_group, DARK_SQUARES, LIGHT_SQUARES},
-    Legality,
+    utils::{Matching, DARK_SQUARES, LIGHT_SQUARES},
+    IllegalityClass,
 };
 
 #[derive(Debug)]
 pub struct TombsRule {
     destinies_counter: usize,
+    death_squares_counter: usize,
     missing_counter: usize,
     captures_counter: usize,
 }
@@ -23,6 +27,7 @@ impl Rule for TombsRule {
     fn new() -> Self {
         TombsRule {
             destinies_counter: 0,
+            death_squares_counter: 0,
             missing_counter: 0,
             captures_counter: 0,
         }
@@ -30,12 +35,14 @@ impl Rule for TombsRule {
 
     fn update(&mut self, analysis: &Analysis) {
         self.destinies_counter = analysis.destinies.counter();
+        self.death_squares_counter = analysis.death_squares.counter();
         self.missing_counter = analysis.missing.counter();
         self.captures_counter = analysis.captures.counter();
     }
 
     fn is_applicable(&self, analysis: &Analysis) -> bool {
         self.destinies_counter != analysis.destinies.counter()
+            || self.death_squares_counter != analysis.death_squares.counter()
             || self.missing_counter != analysis.missing.counter()
             || self.captures_counter != analysis.captures.counter()
     }
@@ -118,13 +125,14 @@ impl Rule for TombsRule {
             // if a tomb cannot be reached by a single candidate, the position is illegal
             for candidates in captured_candidates.iter().take(tombs.len()) {
                 if *candidates == EMPTY {
-                    analysis.result = Some(Legality::Illegal)
+                    analysis.set_illegal(IllegalityClass::Counting);
                 }
             }
 
             // before applying the k-groups analysis, we combine the tombs with on-the-board
             // pieces (characterized by their current square location) whose origins are
             // included in the set of candidate missing pieces
+            let nb_tombs = tombs.len();
             let mut finals = tombs;
             let mut origins_of_finals = captured_candidates;
             for square in *analysis.board.color_combined(!color) {
@@ -138,46 +146,65 @@ impl Rule for TombsRule {
                 }
             }
 
-            for k in 1..=finals.len() {
-                let mut iter = init_iter(finals.len());
-                loop {
-                    match find_k_group(k, &origins_of_finals, iter) {
-                        None => break,
-                        Some((group, remaining)) => {
-                            if group.popcnt() < k as u32 {
-                                analysis.result = Some(Legality::Illegal)
-                            }
-
-                            let group_indices = iter & !remaining;
-                            iter = remaining;
-
-                            // update the (opposite color) knight parity if it can be determined
-                            if group == COLOR_B1_AND_G1[(!color).to_index()] {
-                                let (nb_light_targets, nb_dark_targets) =
-                                    group_indices.fold((0, 0), |acc, idx| {
-                                        let targets = finals[idx.to_index()];
-                                        (
-                                            acc.0 + (targets & LIGHT_SQUARES).popcnt(),
-                                            acc.1 + (targets & DARK_SQUARES).popcnt(),
-                                        )
-                                    });
-                                if nb_light_targets == 0 || nb_dark_targets == 0 {
-                                    progress |= analysis.update_knights_parity(!color, 1);
-                                } else if nb_light_targets == 1 && nb_dark_targets == 1 {
-                                    progress |= analysis.update_knights_parity(!color, 0);
-                                }
-                            }
+            // every final requires a different piece
+            let all_finals = init_iter(finals.len());
+            let matching = Matching::new(&origins_of_finals, all_finals);
+            if !matching.is_perfect() {
+                analysis.set_illegal(IllegalityClass::Counting);
+                return true;
+            }
 
-                            // the destinies of the k-group are now clear
-                            let group_destinies =
-                                group_indices.fold(EMPTY, |acc, idx| acc | finals[idx.to_index()]);
+            // if a final is assigned the same piece in every possible assignment, such
+            // piece must have ended the game there
+            for i in all_finals {
+                let piece = match matching.element_of(i) {
+                    Some(piece) => piece,
+                    None => continue,
+                };
+                let mut reduced = origins_of_finals;
+                reduced[i.to_index()] &= !BitBoard::from_square(piece);
+                if Matching::new(&reduced, all_finals).is_perfect() {
+                    continue;
+                }
+                let final_squares = finals[i.to_index()];
+                progress |= analysis.update_destinies(piece, final_squares);
+                if i.to_index() < nb_tombs {
+                    progress |=
+                        analysis.update_certainly_missing(!color, BitBoard::from_square(piece));
+                    progress |= analysis.update_death_squares(piece, final_squares);
+                } else {
+                    progress |= analysis
+                        .update_origins(final_squares.to_square(), BitBoard::from_square(piece));
+                }
+            }
 
-                            for square in group {
-                                progress |= analysis.update_destinies(square, group_destinies)
-                            }
-                        }
+            // the smallest groups of k finals with k combined candidate pieces
+            for (group_indices, group) in matching.tight_groups() {
+                // update the (opposite color) knight parity if it can be determined
+                if group == COLOR_B1_AND_G1[(!color).to_index()] {
+                    let (nb_light_targets, nb_dark_targets) =
+                        group_indices.fold((0, 0), |acc, idx| {
+                            let targets = finals[idx.to_index()];
+                            (
+                                acc.0 + (targets & LIGHT_SQUARES).popcnt(),
+                                acc.1 + (targets & DARK_SQUARES).popcnt(),
+                            )
+                        });
+                    if nb_light_targets == 0 || nb_dark_targets == 0 {
+                        progress |= analysis.update_knights_parity(!color, 1);
+                    } else if nb_light_targets == 1 && nb_dark_targets == 1 {
+                        progress |= analysis.update_knights_parity(!color, 0);
                     }
                 }
+
+                // the destinies of the k-group are now clear
+                let group_destinies =
+                    group_indices.fold(EMPTY, |acc, idx| acc | finals[idx.to_index()]);
+
+                for square in group {
+                    progress |= analysis.update_destinies(square, group_destinies);
+                    progress |= analysis.update_death_squares(square, group_destinies);
+                }
             }
         }
 
@@ -193,10 +220,8 @@ fn missing_with_target_as_candidate_destiny(
     target: Square,
 ) -> BitBoard {
     let mut candidates = EMPTY;
-    // TODO: we could be more precise and treat the certainly missing differently
-    // from the candidate missing
     for origin in analysis.missing(color).all() {
-        if BitBoard::from_square(target) & analysis.destinies(origin) != EMPTY {
+        if BitBoard::from_square(target) & analysis.death_squares(origin) != EMPTY {
             candidates |= BitBoard::from_square(origin)
         }
     }
@@ -14,7 +14,7 @@
 use chess::{BitBoard, ALL_COLORS, EMPTY};
 
 use super::{Analysis, Rule};
-use crate::{utils::predecessors, Legality, RetractableBoard};
+use crate::{utils::predecessors, IllegalityClass, RetractableBoard};
 
 #[derive(Debug)]
 pub struct UnretractableRule {
@@ -38,7 +38,7 @@ impl Rule for UnretractableRule {
         let unretractable = unretractable_pieces(&analysis.board, &analysis.steady.value);
 
         if unretractable & !analysis.steady.value != EMPTY {
-            analysis.result = Some(Legality::Illegal);
+            analysis.set_illegal(IllegalityClass::Route);
         }
 
         false
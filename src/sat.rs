@@ -0,0 +1,323 @@
+//! Export of the analysis constraints to external SAT solvers.
+//!
+//! When the rules of the static analysis are inconclusive, the information
+//! they derived can still be combined globally: every piece on the board must
+//! be assigned a different origin among its candidates, and the captures
+//! performed by the pieces of every color (given these assignments) cannot
+//! exceed the number of missing opponents.
+//!
+//! These constraints are encoded in conjunctive normal form (CNF), which can
+//! be decided by any off-the-shelf SAT solver. An unsatisfiable formula proves
+//! the position illegal, whereas a satisfiable one is inconclusive.
+//!
+//! The encoding has one variable per piece on the board and candidate origin,
+//! meaning that the piece started the game on that origin. Every piece has
+//! exactly one origin and every origin is used at most once. The capture
+//! budget of every color is encoded with a sequential counter, where every
+//! assignment (or missing origin) is weighted by the number of captures it
+//! requires.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    iter::repeat,
+    process::{Command, Stdio},
+};
+
+use chess::ALL_COLORS;
+
+use crate::{
+    analysis::Analysis, analyze, rules::COLOR_ORIGINS, utils::initial_piece_on, Error,
+    IllegalityClass, IllegalityReason, RetractableBoard,
+};
+
+/// A propositional formula in conjunctive normal form, with clauses given as
+/// in the DIMACS format: variables are numbered from 1 and a negative number
+/// represents the negation of a variable.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cnf {
+    nb_vars: usize,
+    clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    /// The number of variables of the formula.
+    pub fn nb_vars(&self) -> usize {
+        self.nb_vars
+    }
+
+    /// The clauses of the formula.
+    pub fn clauses(&self) -> &[Vec<i32>] {
+        &self.clauses
+    }
+
+    /// The formula in the DIMACS format, the standard input format of SAT
+    /// solvers.
+    pub fn to_dimacs(&self) -> String {
+        let mut dimacs = format!("p cnf {} {}\n", self.nb_vars, self.clauses.len());
+        for clause in self.clauses.iter() {
+            for literal in clause {
+                dimacs.push_str(&format!("{} ", literal));
+            }
+            dimacs.push_str("0\n");
+        }
+        dimacs
+    }
+
+    fn new_var(&mut self) -> i32 {
+        self.nb_vars += 1;
+        self.nb_vars as i32
+    }
+
+    fn add_clause(&mut self, clause: Vec<i32>) {
+        self.clauses.push(clause);
+    }
+
+    /// Adds the (pairwise) constraints that at most one of the given literals
+    /// is true.
+    fn at_most_one(&mut self, literals: &[i32]) {
+        for (i, l1) in literals.iter().enumerate() {
+            for l2 in literals.iter().skip(i + 1) {
+                self.add_clause(vec![-l1, -l2]);
+            }
+        }
+    }
+
+    /// Adds the constraints that at most `k` of the given literals are true
+    /// (repeated literals count as many times as they appear), with the
+    /// sequential counter of Sinz, where the auxiliary variable `s[i][j]`
+    /// means that at least `j + 1` of the first `i + 1` literals are true.
+    fn at_most(&mut self, literals: &[i32], k: usize) {
+        let n = literals.len();
+        if n <= k {
+            return;
+        }
+        if k == 0 {
+            for l in literals {
+                self.add_clause(vec![-l]);
+            }
+            return;
+        }
+        let s: Vec<Vec<i32>> = (0..n - 1)
+            .map(|_| (0..k).map(|_| self.new_var()).collect())
+            .collect();
+        self.add_clause(vec![-literals[0], s[0][0]]);
+        for var in s[0].iter().take(k).skip(1) {
+            self.add_clause(vec![-var]);
+        }
+        for i in 1..n - 1 {
+            self.add_clause(vec![-literals[i], s[i][0]]);
+            self.add_clause(vec![-s[i - 1][0], s[i][0]]);
+            for j in 1..k {
+                self.add_clause(vec![-literals[i], -s[i - 1][j - 1], s[i][j]]);
+                self.add_clause(vec![-s[i - 1][j], s[i][j]]);
+            }
+            self.add_clause(vec![-literals[i], -s[i - 1][k - 1]]);
+        }
+        self.add_clause(vec![-literals[n - 1], -s[n - 2][k - 1]]);
+    }
+}
+
+/// Encodes the origins assignment and capture counting constraints derived by
+/// the given analysis as a formula, which is unsatisfiable only if the
+/// position is illegal.
+///
+/// ```
+/// use sherlock::{analyze, encode_cnf, RetractableBoard};
+///
+/// let analysis = analyze(&RetractableBoard::default());
+/// let cnf = encode_cnf(&analysis);
+/// assert!(cnf
+///     .to_dimacs()
+///     .starts_with(&format!("p cnf {} ", cnf.nb_vars())));
+/// ```
+pub fn encode_cnf(analysis: &Analysis) -> Cnf {
+    let mut cnf = Cnf::default();
+
+    // every piece comes from exactly one of its candidate origins
+    let mut assignments = vec![];
+    for square in *analysis.board.combined() {
+        let vars: Vec<i32> = analysis
+            .origins(square)
+            .into_iter()
+            .map(|origin| {
+                let var = cnf.new_var();
+                assignments.push((square, origin, var));
+                var
+            })
+            .collect();
+        cnf.at_most_one(&vars);
+        cnf.add_clause(vars);
+    }
+
+    for color in ALL_COLORS {
+        let mut weighted = vec![];
+        let mut nb_certain_captures = 0;
+        for origin in COLOR_ORIGINS[color.to_index()] {
+            let uses: Vec<_> = assignments
+                .iter()
+                .filter(|(_, o, _)| *o == origin)
+                .collect();
+            let vars: Vec<i32> = uses.iter().map(|(_, _, var)| *var).collect();
+            cnf.at_most_one(&vars);
+
+            // the captures of the piece that started on the origin
+            let lower_bound = analysis.nb_captures_lower_bound(origin).max(0) as usize;
+            for (square, _, var) in uses {
+                let mut weight = lower_bound;
+                // the piece is in its original form if it has not promoted
+                if analysis.board.piece_on(*square) == Some(initial_piece_on(origin)) {
+                    match analysis.min_captures_to_reach(origin, *square) {
+                        Some(distance) => weight = weight.max(distance as usize),
+                        None => cnf.add_clause(vec![-var]),
+                    }
+                }
+                weighted.extend(repeat(*var).take(weight));
+            }
+            if lower_bound > 0 {
+                if vars.is_empty() {
+                    nb_certain_captures += lower_bound;
+                } else {
+                    // the origin is missing iff none of its assignments holds
+                    let used = cnf.new_var();
+                    for var in vars.iter() {
+                        cnf.add_clause(vec![-var, used]);
+                    }
+                    cnf.add_clause([vec![-used], vars].concat());
+                    weighted.extend(repeat(-used).take(lower_bound));
+                }
+            }
+        }
+
+        // negative if the opponent has more than 16 pieces
+        let nb_missing_opponents = 16 - analysis.board.color_combined(!color).popcnt() as i32;
+        let nb_free_captures = nb_missing_opponents - nb_certain_captures as i32;
+        if nb_free_captures < 0 {
+            cnf.add_clause(vec![]);
+        } else {
+            cnf.at_most(&weighted, nb_free_captures as usize);
+        }
+    }
+    cnf
+}
+
+/// Decides the given formula with the given external solver, a program that
+/// reads a formula in the DIMACS format from its standard input and reports
+/// its verdict in the format of the SAT competitions (e.g. `cadical` or
+/// `kissat`). Returns whether the formula is satisfiable.
+///
+/// Returns an error if the solver cannot be run or does not report a verdict.
+pub fn run_solver(cnf: &Cnf, solver: &str) -> Result<bool, Error> {
+    let mut child = Command::new(solver)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|_| Error::SolverFailure)?;
+    child
+        .stdin
+        .take()
+        .ok_or(Error::SolverFailure)?
+        .write_all(cnf.to_dimacs().as_bytes())
+        .map_err(|_| Error::SolverFailure)?;
+    let stdout = child.stdout.take().ok_or(Error::SolverFailure)?;
+    let mut verdict = Err(Error::SolverFailure);
+    for line in BufReader::new(stdout).lines() {
+        match line.map_err(|_| Error::SolverFailure)?.trim() {
+            "s SATISFIABLE" => verdict = Ok(true),
+            "s UNSATISFIABLE" => verdict = Ok(false),
+            _ => (),
+        }
+    }
+    child.wait().map_err(|_| Error::SolverFailure)?;
+    verdict
+}
+
+/// Analyzes the given position (see [`analyze`]) and, if the analysis is
+/// inconclusive, decides the formula that encodes the derived constraints
+/// (see [`encode_cnf`]) with the given external solver (see [`run_solver`]).
+/// The position is declared illegal if the formula is unsatisfiable.
+pub fn analyze_with_solver(board: &RetractableBoard, solver: &str) -> Result<Analysis, Error> {
+    let mut analysis = analyze(board);
+    if analysis.result.is_none() && !run_solver(&encode_cnf(&analysis), solver)? {
+        analysis.set_illegal(IllegalityClass::Counting);
+        analysis.illegality_reason = Some(IllegalityReason::ExternalSolver);
+    }
+    Ok(analysis)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{
+        rules::{OriginsRule, Rule},
+        utils::*,
+    };
+
+    /// A naive DPLL procedure, only meant for small formulas. It branches on
+    /// the variables in order, so that the variables of the encoding are
+    /// decided before the auxiliary variables of the counters.
+    fn is_satisfiable(clauses: &[Vec<i32>]) -> bool {
+        if clauses.is_empty() {
+            return true;
+        }
+        if clauses.iter().any(|clause| clause.is_empty()) {
+            return false;
+        }
+        let unit = clauses.iter().find(|clause| clause.len() == 1);
+        let literal = unit.map_or_else(
+            || *clauses.iter().flatten().min_by_key(|l| l.abs()).unwrap(),
+            |clause| clause[0],
+        );
+        let assign = |literal: i32| {
+            clauses
+                .iter()
+                .filter(|clause| !clause.contains(&literal))
+                .map(|clause| clause.iter().copied().filter(|l| *l != -literal).collect())
+                .collect::<Vec<_>>()
+        };
+        is_satisfiable(&assign(literal)) || (unit.is_none() && is_satisfiable(&assign(-literal)))
+    }
+
+    #[test]
+    fn test_at_most() {
+        for n in 0..6 {
+            for k in 0..6 {
+                for nb_true in 0..=n {
+                    let mut cnf = Cnf::default();
+                    let vars: Vec<i32> = (0..n).map(|_| cnf.new_var()).collect();
+                    cnf.at_most(&vars, k);
+                    for (i, var) in vars.iter().enumerate() {
+                        cnf.add_clause(vec![if i < nb_true { *var } else { -var }]);
+                    }
+                    assert_eq!(is_satisfiable(cnf.clauses()), nb_true <= k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_cnf() {
+        let board = RetractableBoard::from_fen("4k3/8/8/8/8/1P6/8/4K3 w - -").unwrap();
+        let mut analysis = Analysis::new(&board);
+        OriginsRule::new().apply(&mut analysis);
+        assert!(is_satisfiable(encode_cnf(&analysis).clauses()));
+
+        // Black is missing 15 pieces, which must account for the captures of
+        // the pawn on B3 and of the missing pawn, whichever they are
+        analysis.update_origins(B3, bitboard_of_squares(&[A2, C2]));
+        analysis.update_captures_lower_bound(A2, 7);
+        analysis.update_captures_lower_bound(C2, 8);
+        assert!(is_satisfiable(encode_cnf(&analysis).clauses()));
+        analysis.update_captures_lower_bound(A2, 8);
+        assert!(!is_satisfiable(encode_cnf(&analysis).clauses()));
+    }
+
+    #[test]
+    fn test_encode_cnf_excess_material() {
+        // Black has more than 16 pieces, so no capture count can be satisfied
+        let board = RetractableBoard::from_fen("k7/pppppppp/pppppppp/p7/8/8/8/K7 w - -").unwrap();
+        let analysis = Analysis::new(&board);
+        assert!(!is_satisfiable(encode_cnf(&analysis).clauses()));
+    }
+}
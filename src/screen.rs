@@ -0,0 +1,86 @@
+//! Static screening of positions.
+//!
+//! Some positions are illegal for reasons that can be read directly from the
+//! diagram, without deriving anything about the history of the pieces, such as
+//! pawns on the first or last rank or checks that no single move can have
+//! delivered. Screening them first spares the analysis the computation of the
+//! fixpoint of its rules.
+
+use chess::{get_rank, Piece, Rank, EMPTY};
+
+use crate::{IllegalityClass, RetractableBoard};
+
+/// The class of the static reason that makes the given position illegal, or
+/// `None` if there is no such reason (which does not mean that the position is
+/// legal).
+///
+/// ```
+/// use sherlock::{static_screen, IllegalityClass, RetractableBoard};
+///
+/// assert_eq!(static_screen(&RetractableBoard::default()), None);
+///
+/// // no move can give check with a pawn and a knight at the same time
+/// let board = RetractableBoard::from_fen("4k3/3P4/5N2/8/8/8/8/4K3 b - -")?;
+/// assert_eq!(static_screen(&board), Some(IllegalityClass::Check));
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn static_screen(board: &RetractableBoard) -> Option<IllegalityClass> {
+    let pawns = board.pieces(Piece::Pawn);
+    if pawns & (get_rank(Rank::First) | get_rank(Rank::Eighth)) != EMPTY {
+        return Some(IllegalityClass::Route);
+    }
+
+    // a double check involves a discovered check, which only sliders give
+    let checkers = *board.checkers();
+    let sliders =
+        board.pieces(Piece::Bishop) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if checkers.popcnt() > 2 || (checkers.popcnt() == 2 && checkers & sliders == EMPTY) {
+        return Some(IllegalityClass::Check);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_static_screen() {
+        for (fen, expected) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - -", None),
+            ("4k3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ -", None),
+            // double check by a discovering rook and a pawn
+            ("4k3/3P4/8/8/8/8/8/K3R3 b - -", None),
+            (
+                "4k3/3P4/5N2/8/8/8/8/K3R3 b - -",
+                Some(IllegalityClass::Check),
+            ),
+            (
+                "4k3/3P4/5N2/8/8/8/8/4K3 b - -",
+                Some(IllegalityClass::Check),
+            ),
+        ] {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            assert_eq!(static_screen(&board), expected);
+        }
+    }
+
+    #[test]
+    fn test_static_screen_with_cache() {
+        use crate::{analyze, analyze_with_cache, AnalysisCache, IllegalityReason, Legality};
+
+        let board =
+            RetractableBoard::from_fen("4k3/3P4/5N2/8/8/8/8/4K3 b - -").expect("Valid Position");
+        let mut cache = AnalysisCache::new();
+        for analysis in [analyze(&board), analyze_with_cache(&board, &mut cache)] {
+            assert_eq!(analysis.result, Some(Legality::Illegal));
+            assert_eq!(
+                analysis.illegality_reason,
+                Some(IllegalityReason::StaticScreen(IllegalityClass::Check))
+            );
+            assert_eq!(analysis.nb_rule_applications, 0);
+        }
+    }
+}
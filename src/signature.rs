@@ -0,0 +1,137 @@
+//! Material signatures.
+//!
+//! Collections of retro problems are indexed by a few standard features of
+//! their material, which summarize what a solver should expect from the
+//! history of the position. The GBR code (named after Guy, Blandford and
+//! Roycroft) counts the pieces of every type, whereas the captures performed by
+//! the pawns and the number of promoted pieces reveal how much of the missing
+//! material is accounted for.
+
+use chess::{Board, Color, Piece, ALL_COLORS, NUM_COLORS};
+
+use crate::{rules::lower_bound_promoted, RetractableBoard};
+
+/// The material signature of a position (see [`material_signature`]).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    /// The GBR code of the position (see [`gbr_code`]).
+    pub gbr: String,
+    /// A lower bound on the number of captures performed by the pawns of each
+    /// color, indexed by `color.to_index()`.
+    pub pawn_captures: [u32; NUM_COLORS],
+    /// A lower bound on the number of promoted pieces of each color, indexed
+    /// by `color.to_index()`.
+    pub promoted: [u32; NUM_COLORS],
+}
+
+/// The GBR code of the given position. Every officer type (queens, rooks,
+/// bishops and knights, in this order) contributes with a digit, the number of
+/// white pieces plus three times the number of black pieces, or 9 if any of
+/// them has more than two. The code is completed with the number of white and
+/// black pawns, after a dot.
+///
+/// ```
+/// use chess::Board;
+/// use sherlock::gbr_code;
+///
+/// assert_eq!(gbr_code(&Board::default()), "4888.88");
+/// ```
+pub fn gbr_code(board: &Board) -> String {
+    gbr(&(*board).into())
+}
+
+fn gbr(board: &RetractableBoard) -> String {
+    let count =
+        |piece: Piece, color: Color| (board.pieces(piece) & board.color_combined(color)).popcnt();
+    let mut code = String::new();
+    for piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+        let (white, black) = (count(piece, Color::White), count(piece, Color::Black));
+        let digit = if white > 2 || black > 2 {
+            9
+        } else {
+            white + 3 * black
+        };
+        code.push_str(&digit.to_string());
+    }
+    code.push_str(&format!(
+        ".{}{}",
+        count(Piece::Pawn, Color::White),
+        count(Piece::Pawn, Color::Black)
+    ));
+    code
+}
+
+/// A lower bound on the number of captures performed by the pawns of the
+/// given color, given by the assignment of pawns to (different) origin files
+/// that minimizes the total number of changed files. Pawns in excess of 8 are
+/// ignored.
+pub(crate) fn lower_bound_pawn_captures(board: &RetractableBoard, color: Color) -> u32 {
+    let mut files: Vec<u32> = (board.pieces(Piece::Pawn) & board.color_combined(color))
+        .map(|square| square.get_file().to_index() as u32)
+        .collect();
+    files.sort();
+    files.truncate(8);
+
+    // `cost[i]` is the minimum cost of assigning the first `i` pawns to the
+    // origin files considered so far (files are considered from left to right)
+    let mut cost = vec![u32::MAX; files.len() + 1];
+    cost[0] = 0;
+    for origin in 0..8 {
+        for i in (1..=files.len()).rev() {
+            if cost[i - 1] != u32::MAX {
+                cost[i] = cost[i].min(cost[i - 1] + files[i - 1].abs_diff(origin));
+            }
+        }
+    }
+    cost[files.len()]
+}
+
+/// The material signature of the given position, derived from its material
+/// alone (see [`crate::Analysis::material_signature`] for a refined version).
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{Board, Color};
+/// use sherlock::material_signature;
+///
+/// // the tripled pawns captured twice and a white queen is promoted
+/// let board = Board::from_str("4k3/8/8/8/2P5/2P5/2P5/QQ2K3 w - -")?;
+/// let signature = material_signature(&board);
+/// assert_eq!(signature.gbr, "2000.30");
+/// assert_eq!(signature.pawn_captures[Color::White.to_index()], 2);
+/// assert_eq!(signature.promoted[Color::White.to_index()], 1);
+/// # Ok::<(), chess::Error>(())
+/// ```
+pub fn material_signature(board: &Board) -> MaterialSignature {
+    signature(&(*board).into())
+}
+
+pub(crate) fn signature(board: &RetractableBoard) -> MaterialSignature {
+    MaterialSignature {
+        gbr: gbr(board),
+        pawn_captures: ALL_COLORS.map(|color| lower_bound_pawn_captures(board, color)),
+        promoted: ALL_COLORS.map(|color| lower_bound_promoted(board, color) as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_lower_bound_pawn_captures() {
+        for (fen, white, black) in [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", 0, 0),
+            ("4k3/8/8/8/8/8/PPP5/4K3 w - -", 0, 0),
+            ("4k3/pp6/8/8/2P5/2P5/2P5/4K3 w - -", 2, 0),
+            ("4k3/8/7p/7p/8/8/P7/4K3 w - -", 0, 1),
+            ("4k3/8/8/8/PPPP4/PPPP4/8/4K3 w - -", 16, 0),
+        ] {
+            let board = RetractableBoard::from_fen(fen).expect("Valid Position");
+            assert_eq!(lower_bound_pawn_captures(&board, Color::White), white);
+            assert_eq!(lower_bound_pawn_captures(&board, Color::Black), black);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Soundness checks.
+//!
+//! The rules of the legality analysis derive facts about the history of a
+//! position. When the position comes from an actual game, we know its history,
+//! so we can check that none of the derived facts contradicts it. This is
+//! useful for catching unsound rules.
+//!
+//! The history can also be fed to the analysis ("oracle mode"), in order to
+//! validate rules against the ground truth or to annotate games.
+
+use chess::{BitBoard, Board, ChessMove, File, Piece, Square, ALL_SQUARES, EMPTY, NUM_SQUARES};
+#[cfg(feature = "random")]
+use chess::{BoardStatus, MoveGen};
+#[cfg(feature = "random")]
+use rand::Rng;
+
+use crate::{analyze, legality::saturate, rules::ALL_ORIGINS, Analysis, Fact, Legality};
+#[cfg(feature = "random")]
+use crate::{analyze_with_options, rules::RuleFactory, AnalysisOptions};
+
+/// The actual history of the pieces during a game.
+pub(crate) struct GameHistory {
+    /// The origin of the piece currently on every square (if any).
+    origin_on: [Option<Square>; NUM_SQUARES],
+    /// For every origin, the squares visited by the piece.
+    visited: [BitBoard; NUM_SQUARES],
+    /// For every origin, the squares where the piece captured.
+    captures: [BitBoard; NUM_SQUARES],
+    /// For every origin, the number of captures performed by the piece.
+    nb_captures: [u8; NUM_SQUARES],
+    /// For every origin, the square where the piece was captured (if so).
+    tomb: [Option<Square>; NUM_SQUARES],
+    /// For every origin, the number of moves performed by the piece.
+    nb_moves: [u32; NUM_SQUARES],
+}
+
+impl GameHistory {
+    pub(crate) fn new() -> Self {
+        let mut history = GameHistory {
+            origin_on: [None; NUM_SQUARES],
+            visited: [EMPTY; NUM_SQUARES],
+            captures: [EMPTY; NUM_SQUARES],
+            nb_captures: [0; NUM_SQUARES],
+            tomb: [None; NUM_SQUARES],
+            nb_moves: [0; NUM_SQUARES],
+        };
+        for origin in ALL_ORIGINS {
+            history.origin_on[origin.to_index()] = Some(origin);
+            history.visited[origin.to_index()] = BitBoard::from_square(origin);
+        }
+        history
+    }
+
+    /// Moves the piece on `source` to `target`.
+    fn move_piece(&mut self, source: Square, target: Square) {
+        let origin = self.origin_on[source.to_index()].unwrap();
+        self.origin_on[source.to_index()] = None;
+        self.origin_on[target.to_index()] = Some(origin);
+        self.visited[origin.to_index()] |= BitBoard::from_square(target);
+    }
+
+    /// The origin of the piece currently on the given square (if any).
+    pub(crate) fn origin_on(&self, square: Square) -> Option<Square> {
+        self.origin_on[square.to_index()]
+    }
+
+    /// The squares where the piece that started on `origin` has captured.
+    pub(crate) fn captures(&self, origin: Square) -> BitBoard {
+        self.captures[origin.to_index()]
+    }
+
+    /// The number of captures performed by the piece that started on
+    /// `origin`.
+    pub(crate) fn nb_captures(&self, origin: Square) -> u8 {
+        self.nb_captures[origin.to_index()]
+    }
+
+    /// The number of moves performed by the piece that started on `origin`.
+    pub(crate) fn nb_moves(&self, origin: Square) -> u32 {
+        self.nb_moves[origin.to_index()]
+    }
+
+    /// Tells whether the piece that started on `origin` has visited `square`.
+    pub(crate) fn has_visited(&self, origin: Square, square: Square) -> bool {
+        self.visited[origin.to_index()] & BitBoard::from_square(square) != EMPTY
+    }
+
+    /// Updates the history after the given (legal) move on the given board.
+    pub(crate) fn make_move(&mut self, board: &Board, m: ChessMove) {
+        let (source, target) = (m.get_source(), m.get_dest());
+        let piece = board.piece_on(source).unwrap();
+        let origin = self.origin_on[source.to_index()].unwrap();
+
+        let captured_square = if board.piece_on(target).is_some() {
+            Some(target)
+        } else if piece == Piece::Pawn && source.get_file() != target.get_file() {
+            Some(Square::make_square(source.get_rank(), target.get_file()))
+        } else {
+            None
+        };
+        if let Some(square) = captured_square {
+            let captured_origin = self.origin_on[square.to_index()].unwrap();
+            self.origin_on[square.to_index()] = None;
+            self.tomb[captured_origin.to_index()] = Some(square);
+            self.captures[origin.to_index()] |= BitBoard::from_square(target);
+            self.nb_captures[origin.to_index()] += 1;
+        }
+
+        // the rook also moves when castling
+        let rank = source.get_rank();
+        if piece == Piece::King && source.get_file() == File::E {
+            match target.get_file() {
+                File::G => self.move_piece(
+                    Square::make_square(rank, File::H),
+                    Square::make_square(rank, File::F),
+                ),
+                File::C => self.move_piece(
+                    Square::make_square(rank, File::A),
+                    Square::make_square(rank, File::D),
+                ),
+                _ => (),
+            }
+        }
+
+        self.move_piece(source, target);
+        self.nb_moves[origin.to_index()] += 1;
+    }
+
+    /// The square where the piece that started on `origin` ended the game.
+    pub(crate) fn destiny(&self, origin: Square) -> Square {
+        match self.tomb[origin.to_index()] {
+            Some(square) => square,
+            None => ALL_SQUARES
+                .into_iter()
+                .find(|s| self.origin_on[s.to_index()] == Some(origin))
+                .unwrap(),
+        }
+    }
+
+    /// Tells whether the given fact is consistent with the history.
+    fn is_consistent(&self, fact: &Fact) -> bool {
+        let contains = |bb: BitBoard, square: Square| bb & BitBoard::from_square(square) != EMPTY;
+        match *fact {
+            Fact::Steady(square) => {
+                self.origin_on[square.to_index()] == Some(square)
+                    && self.nb_moves[square.to_index()] == 0
+            }
+            Fact::OriginsSubset(square, origins) => match self.origin_on[square.to_index()] {
+                Some(origin) => contains(origins, origin),
+                None => false,
+            },
+            Fact::DestiniesSubset(origin, destinies) => contains(destinies, self.destiny(origin)),
+            Fact::DeathSquaresSubset(origin, death_squares) => match self.tomb[origin.to_index()] {
+                Some(square) => contains(death_squares, square),
+                None => true,
+            },
+            Fact::ReachableSubset(origin, reachable) => {
+                self.visited[origin.to_index()] & !reachable == EMPTY
+            }
+            Fact::CapturedOn(origin, captures) => {
+                captures & !self.captures[origin.to_index()] == EMPTY
+            }
+            Fact::CapturesAtLeast(origin, n) => self.nb_captures[origin.to_index()] >= n,
+            Fact::CapturesAtMost(origin, n) => self.nb_captures[origin.to_index()] <= n,
+            Fact::Missing(_, origins) => origins
+                .into_iter()
+                .all(|origin| self.tomb[origin.to_index()].is_some()),
+            Fact::KnightsParity(color, parity) => {
+                let rank = color.to_my_backrank();
+                let nb_moves = self.nb_moves[Square::make_square(rank, File::B).to_index()]
+                    + self.nb_moves[Square::make_square(rank, File::G).to_index()];
+                nb_moves % 2 == (parity % 2) as u32
+            }
+            Fact::Result(legality) => legality == Legality::Legal,
+        }
+    }
+}
+
+/// Plays the given sequence of (legal) moves from the starting position,
+/// analyzes the final position and returns all the derived facts that
+/// contradict the actual history of the game.
+///
+/// A non-empty output reveals an unsound derivation.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::ChessMove;
+/// use sherlock::contradicted_facts;
+///
+/// let moves: Vec<_> = ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3"]
+///     .iter()
+///     .map(|m| ChessMove::from_str(m).unwrap())
+///     .collect();
+/// assert!(contradicted_facts(&moves).is_empty());
+/// ```
+pub fn contradicted_facts(moves: &[ChessMove]) -> Vec<Fact> {
+    let mut history = GameHistory::new();
+    let mut board = Board::default();
+    for m in moves {
+        history.make_move(&board, *m);
+        board = board.make_move_new(*m);
+    }
+    analyze(&board.into())
+        .facts()
+        .filter(|fact| !history.is_consistent(fact))
+        .collect()
+}
+
+/// A game whose analysis (with some user-defined rules) derived facts that
+/// contradict its history, see [`check_rules_soundness`].
+#[cfg(feature = "random")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoundnessViolation {
+    /// The moves of the game, from the starting position.
+    pub moves: Vec<ChessMove>,
+    /// The derived facts that contradict the history of the game.
+    pub facts: Vec<Fact>,
+}
+
+/// Plays the given number of random games (of at most `max_plies` plies) from
+/// the starting position and analyzes every position reached, with the given
+/// user-defined rules on top of the built-in ones. Returns the first game
+/// whose analysis contradicts its actual history, if any, e.g. because one of
+/// its positions was declared illegal.
+///
+/// This is a test helper for user-defined rules (see [`crate::Rule`]): random
+/// games rarely exercise the corner cases of a rule, so passing the check
+/// does not prove the rule sound, but failing it certainly proves it unsound.
+///
+/// ```
+/// use rand::{rngs::SmallRng, SeedableRng};
+/// use sherlock::check_rules_soundness;
+///
+/// let mut rng = SmallRng::seed_from_u64(42);
+/// assert_eq!(check_rules_soundness(&mut rng, &[], 2, 10), None);
+/// ```
+#[cfg(feature = "random")]
+pub fn check_rules_soundness<R: Rng>(
+    rng: &mut R,
+    rules: &'static [RuleFactory],
+    nb_games: usize,
+    max_plies: usize,
+) -> Option<SoundnessViolation> {
+    let options = AnalysisOptions {
+        extra_rules: rules,
+        ..Default::default()
+    };
+    for _ in 0..nb_games {
+        let mut history = GameHistory::new();
+        let mut board = Board::default();
+        let mut moves = vec![];
+        for _ in 0..max_plies {
+            if board.status() != BoardStatus::Ongoing {
+                break;
+            }
+            let legal_moves: Vec<_> = MoveGen::new_legal(&board).collect();
+            let m = legal_moves[rng.gen_range(0, legal_moves.len())];
+            history.make_move(&board, m);
+            board = board.make_move_new(m);
+            moves.push(m);
+            let analysis = analyze_with_options(&board.into(), &options);
+            let mut facts: Vec<_> = analysis
+                .facts()
+                .filter(|fact| !history.is_consistent(fact))
+                .collect();
+            // a position reached in a real game must never be declared illegal
+            let illegal = Fact::Result(Legality::Illegal);
+            if analysis.result() == Some(Legality::Illegal) && !facts.contains(&illegal) {
+                facts.push(illegal);
+            }
+            if !facts.is_empty() {
+                return Some(SoundnessViolation { moves, facts });
+            }
+        }
+    }
+    None
+}
+
+/// Seeds the analysis with the facts known from the given history: the origin
+/// of every piece on the board, the steady pieces and the captures performed.
+fn seed_with_history(analysis: &mut Analysis, history: &GameHistory) {
+    let mut steady = EMPTY;
+    for square in *analysis.board.combined() {
+        let origin = history.origin_on(square).unwrap();
+        analysis.update_origins(square, BitBoard::from_square(origin));
+        if origin == square && history.nb_moves(origin) == 0 {
+            steady |= BitBoard::from_square(square);
+        }
+    }
+    analysis.update_steady(steady);
+    for origin in ALL_ORIGINS {
+        analysis.update_captures(origin, history.captures(origin));
+        analysis.update_captures_lower_bound(origin, history.nb_captures(origin) as i32);
+    }
+}
+
+/// Plays the given sequence of (legal) moves from the starting position and
+/// returns the analysis of the position after every ply, seeded with the facts
+/// known from the actual game so far (the origin of every piece, the steady
+/// pieces and the captures performed).
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use chess::{BitBoard, ChessMove, Square};
+/// use sherlock::analyze_game;
+///
+/// let moves: Vec<_> = ["e2e4", "d7d5", "e4d5"]
+///     .iter()
+///     .map(|m| ChessMove::from_str(m).unwrap())
+///     .collect();
+/// let analyses = analyze_game(&moves);
+/// assert_eq!(analyses.len(), 3);
+///
+/// // the pawn on D5 comes from E2 (which is known from the game)
+/// assert_eq!(
+///     analyses[2].origins(Square::D5),
+///     BitBoard::from_square(Square::E2)
+/// );
+/// ```
+pub fn analyze_game(moves: &[ChessMove]) -> Vec<Analysis> {
+    let mut history = GameHistory::new();
+    let mut board = Board::default();
+    let mut analyses = vec![];
+    for m in moves {
+        history.make_move(&board, *m);
+        board = board.make_move_new(*m);
+        let mut analysis = Analysis::new(&board.into());
+        seed_with_history(&mut analysis, &history);
+        saturate(&mut analysis);
+        analyses.push(analysis);
+    }
+    analyses
+}
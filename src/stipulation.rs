@@ -0,0 +1,125 @@
+//! Retro stipulations.
+//!
+//! A small parser for the most common retro stipulations, as found in problem
+//! databases, mapping them to the corresponding solver entry points.
+
+use std::str::FromStr;
+
+use chess::{BitBoard, Board, Color, Piece};
+
+use crate::{illegal_placements, last_moves, ChessRetraction, Error};
+
+/// A retro stipulation.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Stipulation {
+    /// "Last move?": find the last move(s) of the game.
+    LastMove,
+    /// "SPG n moves": find the shortest proof game of the given number of
+    /// plies (e.g. "SPG 9.5 moves" is a proof game of 19 plies).
+    ProofGame(u32),
+    /// "Add the wK": find the squares where the given piece can be added,
+    /// leading to a legal position.
+    AddPiece(Color, Piece),
+    /// "Release the position": find how the position can be released.
+    Release,
+}
+
+impl FromStr for Stipulation {
+    type Err = Error;
+
+    /// Parses stipulations such as "Last move?", "SPG 9.0 moves", "Add the wK"
+    /// or "Release the position".
+    ///
+    /// ```
+    /// use chess::{Color, Piece};
+    /// use sherlock::Stipulation;
+    ///
+    /// assert_eq!("Last move?".parse(), Ok(Stipulation::LastMove));
+    /// assert_eq!("SPG 9.5 moves".parse(), Ok(Stipulation::ProofGame(19)));
+    /// assert_eq!(
+    ///     "Add the wK".parse(),
+    ///     Ok(Stipulation::AddPiece(Color::White, Piece::King))
+    /// );
+    /// assert_eq!("Release the position".parse(), Ok(Stipulation::Release));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_end_matches(['?', '.', '!']).to_lowercase();
+        let words: Vec<_> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["last", "move"] | ["what", "was", "the", "last", "move"] => Ok(Stipulation::LastMove),
+            ["spg", n] | ["spg", n, "moves" | "move"] | ["proof", "game", "in", n, "moves"] => {
+                parse_nb_plies(n)
+            }
+            ["add", "the" | "a", piece] | ["add", piece] => parse_colored_piece(piece),
+            ["release", "the", "position"] | ["release"] => Ok(Stipulation::Release),
+            _ => Err(Error::InvalidStipulation),
+        }
+    }
+}
+
+/// Parses a number of moves in the usual "n.0" or "n.5" notation, returning the
+/// number of plies.
+fn parse_nb_plies(s: &str) -> Result<Stipulation, Error> {
+    let (moves, half) = s.split_once('.').unwrap_or((s, "0"));
+    let moves = moves
+        .parse::<u32>()
+        .map_err(|_| Error::InvalidStipulation)?;
+    match half {
+        "0" => Ok(Stipulation::ProofGame(2 * moves)),
+        "5" => Ok(Stipulation::ProofGame(2 * moves + 1)),
+        _ => Err(Error::InvalidStipulation),
+    }
+}
+
+/// Parses a colored piece such as "wk" or "bq".
+fn parse_colored_piece(s: &str) -> Result<Stipulation, Error> {
+    let mut chars = s.chars();
+    let color = match chars.next() {
+        Some('w') => Color::White,
+        Some('b') => Color::Black,
+        _ => return Err(Error::InvalidStipulation),
+    };
+    let piece = match chars.next() {
+        Some('k') => Piece::King,
+        Some('q') => Piece::Queen,
+        Some('r') => Piece::Rook,
+        Some('b') => Piece::Bishop,
+        Some('s' | 'n') => Piece::Knight,
+        Some('p') => Piece::Pawn,
+        _ => return Err(Error::InvalidStipulation),
+    };
+    if chars.next().is_some() {
+        return Err(Error::InvalidStipulation);
+    }
+    Ok(Stipulation::AddPiece(color, piece))
+}
+
+/// The solution to a retro stipulation.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Solution {
+    /// The candidate last moves of the game, as retractions.
+    LastMoves(Vec<ChessRetraction>),
+    /// A set of squares, e.g. where a piece can be added.
+    Squares(BitBoard),
+}
+
+/// Solves the given stipulation on the given position.
+///
+/// ```
+/// use chess::{Board, Color, Piece};
+/// use sherlock::{solve_stipulation, Error, Stipulation};
+///
+/// let board = Board::default();
+/// let release = solve_stipulation(&board, &Stipulation::Release);
+/// assert_eq!(release, Err(Error::UnsupportedStipulation));
+/// ```
+pub fn solve_stipulation(board: &Board, stipulation: &Stipulation) -> Result<Solution, Error> {
+    match *stipulation {
+        Stipulation::LastMove => Ok(Solution::LastMoves(last_moves(board))),
+        Stipulation::AddPiece(color, piece) => {
+            let illegal = illegal_placements(board, piece, color);
+            Ok(Solution::Squares(!board.combined() & !illegal))
+        }
+        Stipulation::ProofGame(_) | Stipulation::Release => Err(Error::UnsupportedStipulation),
+    }
+}
@@ -0,0 +1,96 @@
+//! Twin positions.
+//!
+//! Chess compositions often come in twins: the same diagram with a piece
+//! added, removed or relocated. Twins share most of their pawn skeleton and
+//! steady pieces, so their analyses can share the (expensive) tables derived
+//! from the mobility graphs.
+
+use chess::{Board, BoardBuilder, Color, Piece, Square};
+
+use crate::{analysis::Analysis, analyze_with_cache, AnalysisCache, Error};
+
+/// A modification of a diagram that defines a twin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// The piece on the given square is removed.
+    Remove(Square),
+    /// The given piece is added on the given (empty) square.
+    Add(Square, Piece, Color),
+    /// The piece on the first square is moved to the second (empty) square.
+    Move(Square, Square),
+    /// The pieces on the given squares exchange their places.
+    Exchange(Square, Square),
+}
+
+impl Edit {
+    /// Applies the edit on the given board builder. Returns an error if the
+    /// squares involved are not occupied (or empty) as expected.
+    fn apply(&self, builder: &mut BoardBuilder) -> Result<(), Error> {
+        match *self {
+            Edit::Remove(square) => {
+                builder[square].take().ok_or(Error::InvalidTwin)?;
+            }
+            Edit::Add(square, piece, color) => {
+                if builder[square].is_some() {
+                    return Err(Error::InvalidTwin);
+                }
+                builder[square] = Some((piece, color));
+            }
+            Edit::Move(from, to) => {
+                if builder[to].is_some() {
+                    return Err(Error::InvalidTwin);
+                }
+                builder[to] = Some(builder[from].take().ok_or(Error::InvalidTwin)?);
+            }
+            Edit::Exchange(s1, s2) => {
+                let p1 = builder[s1].ok_or(Error::InvalidTwin)?;
+                let p2 = builder[s2].ok_or(Error::InvalidTwin)?;
+                builder[s1] = Some(p2);
+                builder[s2] = Some(p1);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Analyzes the twins of the given diagram, each of them defined by a single
+/// edit on it. The output contains one analysis per twin, in the given order.
+///
+/// The base diagram is analyzed once and all the analyses share the tables
+/// derived from the mobility graphs, which are only recomputed for the graphs
+/// affected by each edit.
+///
+/// Returns an error if an edit cannot be applied on the diagram or if it leads
+/// to an invalid board (e.g. a player with two kings).
+///
+/// ```
+/// use chess::{Board, Color, Piece, Square};
+/// use sherlock::{analyze_twins, Edit, Error};
+///
+/// let twins = [Edit::Remove(Square::E2), Edit::Move(Square::G1, Square::F3)];
+/// let analyses = analyze_twins(&Board::default(), &twins)?;
+/// assert_eq!(analyses.len(), 2);
+/// assert!(analyses[0].is_steady(Square::D2));
+/// assert!(!analyses[1].is_steady(Square::F3));
+///
+/// // White cannot have two kings
+/// let twins = [Edit::Add(Square::E4, Piece::King, Color::White)];
+/// assert_eq!(
+///     analyze_twins(&Board::default(), &twins).err(),
+///     Some(Error::InvalidTwin)
+/// );
+/// # Ok::<(), sherlock::Error>(())
+/// ```
+pub fn analyze_twins(base: &Board, twins: &[Edit]) -> Result<Vec<Analysis>, Error> {
+    let mut cache = AnalysisCache::new();
+    analyze_with_cache(&(*base).into(), &mut cache);
+    twins
+        .iter()
+        .map(|edit| {
+            let mut builder = BoardBuilder::from(base);
+            edit.apply(&mut builder)?;
+            let board = Board::try_from(&builder).map_err(|_| Error::InvalidTwin)?;
+            Ok(analyze_with_cache(&board.into(), &mut cache))
+        })
+        .collect()
+}
@@ -1,14 +1,30 @@
 mod squares;
 pub use squares::*;
 
-mod k_groups;
-pub use k_groups::*;
+mod matching;
+pub use matching::*;
+
+#[cfg(feature = "rules-captures")]
+mod flow;
+#[cfg(feature = "rules-captures")]
+pub use flow::*;
 
 mod chess_utils;
 pub use chess_utils::*;
 
+mod geometry;
+pub use geometry::*;
+
 mod mobility;
 pub use mobility::*;
 
 mod uncertain_sets;
 pub use uncertain_sets::*;
+
+mod indexed;
+pub use indexed::*;
+
+#[cfg(feature = "rules-mobility")]
+mod timeline;
+#[cfg(feature = "rules-mobility")]
+pub use timeline::*;
@@ -1,12 +1,11 @@
 //! Util functions.
 
 use chess::{
-    get_bishop_moves, get_bishop_rays, get_file, get_king_moves, get_knight_moves,
-    get_pawn_attacks, get_pawn_quiets, get_rank, get_rook_moves, get_rook_rays, BitBoard, Color,
-    Piece, Rank, Square, EMPTY,
+    get_bishop_moves, get_file, get_king_moves, get_knight_moves, get_pawn_attacks, get_rank,
+    get_rook_moves, BitBoard, Color, File, Piece, Rank, Square, EMPTY,
 };
 
-use super::LIGHT_SQUARES;
+use super::{Geometry, Orthodox, LIGHT_SQUARES};
 use crate::RetractableBoard;
 
 /// An array representing all 12 different (colored) pieces, each consisting of
@@ -42,6 +41,12 @@ pub fn square_color(square: Square) -> Color {
     }
 }
 
+/// The color of the piece that starts the game on the given origin square.
+///
+/// # Panics
+///
+/// Panics if the given square is not an origin, i.e. it is not on the 1st,
+/// 2nd, 7th or 8th rank.
 pub fn origin_color(origin: Square) -> Color {
     match origin.get_rank() {
         Rank::First | Rank::Second => Color::White,
@@ -50,6 +55,61 @@ pub fn origin_color(origin: Square) -> Color {
     }
 }
 
+/// The type of the piece that starts the game on the given origin square.
+///
+/// ```
+/// use chess::{Piece, Square};
+/// use sherlock::initial_piece_on;
+///
+/// assert_eq!(initial_piece_on(Square::G8), Piece::Knight);
+/// assert_eq!(initial_piece_on(Square::E2), Piece::Pawn);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the given square is not an origin, i.e. it is not on the 1st,
+/// 2nd, 7th or 8th rank.
+pub fn initial_piece_on(origin: Square) -> Piece {
+    match origin.get_rank() {
+        Rank::Second | Rank::Seventh => Piece::Pawn,
+        Rank::First | Rank::Eighth => match origin.get_file() {
+            File::A | File::H => Piece::Rook,
+            File::B | File::G => Piece::Knight,
+            File::C | File::F => Piece::Bishop,
+            File::D => Piece::Queen,
+            File::E => Piece::King,
+        },
+        _ => panic!("Not an origin square"),
+    }
+}
+
+/// The color of the piece that starts the game on the given origin square.
+/// This is an alias of [`origin_color`], for symmetry with
+/// [`initial_piece_on`].
+///
+/// ```
+/// use chess::{Color, Square};
+/// use sherlock::initial_color_on;
+///
+/// assert_eq!(initial_color_on(Square::G8), Color::Black);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the given square is not an origin.
+#[inline]
+pub fn initial_color_on(origin: Square) -> Color {
+    origin_color(origin)
+}
+
+/// The rank where pawns of the given color may be captured en passant.
+pub fn en_passant_rank(color: Color) -> Rank {
+    match color {
+        Color::White => Rank::Fourth,
+        Color::Black => Rank::Fifth,
+    }
+}
+
 pub fn prom_index(piece: Piece) -> usize {
     match piece {
         Piece::Queen => 0,
@@ -64,14 +124,7 @@ pub fn prom_index(piece: Piece) -> usize {
 /// and `Color` can move to from the given `Square` on an empty board.
 #[inline]
 pub fn moves_on_empty_board(piece: Piece, color: Color, square: Square) -> BitBoard {
-    match piece {
-        Piece::King => get_king_moves(square),
-        Piece::Queen => get_rook_rays(square) | get_bishop_rays(square),
-        Piece::Rook => get_rook_rays(square),
-        Piece::Bishop => get_bishop_rays(square),
-        Piece::Knight => get_knight_moves(square),
-        Piece::Pawn => get_pawn_quiets(square, color, EMPTY),
-    }
+    Orthodox.moves_on_empty_board(piece, color, square)
 }
 
 /// A `BitBoard` with the squares from which a piece of the given `Piece` type
@@ -0,0 +1,201 @@
+//! Flow networks with lower and upper bounds on the flow of their edges.
+//!
+//! A circulation assigns a flow to every edge, within its bounds, such that
+//! the incoming flow of every node equals its outgoing flow. Once a feasible
+//! circulation has been found, an edge may carry more (resp. less) flow in
+//! some other feasible circulation iff its endpoints are connected by a path
+//! in the residual network that closes a cycle with the edge.
+
+use std::collections::VecDeque;
+
+/// An arc of the residual network, the arcs of the edge `e` are `2 * e`
+/// (forward) and `2 * e + 1` (backward).
+#[derive(Clone, Copy)]
+struct Arc {
+    target: usize,
+    capacity: i32,
+}
+
+/// A flow network with bounds on the flow of its edges.
+pub struct FlowNetwork {
+    nb_nodes: usize,
+    /// Whether some edge has an empty range of flows.
+    has_empty_range: bool,
+    lower_bounds: Vec<i32>,
+    arcs: Vec<Arc>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl FlowNetwork {
+    /// Creates a network with the given number of nodes and no edges.
+    pub fn new(nb_nodes: usize) -> Self {
+        FlowNetwork {
+            nb_nodes,
+            has_empty_range: false,
+            lower_bounds: vec![],
+            arcs: vec![],
+            adjacency: vec![vec![]; nb_nodes + 2],
+        }
+    }
+
+    /// Adds an edge whose flow must be in the range `[lower, upper]`, returning
+    /// its identifier.
+    pub fn add_edge(&mut self, source: usize, target: usize, lower: i32, upper: i32) -> usize {
+        self.has_empty_range |= upper < lower;
+        self.lower_bounds.push(lower);
+        self.add_arcs(source, target, (upper - lower).max(0))
+    }
+
+    fn add_arcs(&mut self, source: usize, target: usize, capacity: i32) -> usize {
+        let id = self.arcs.len() / 2;
+        self.adjacency[source].push(self.arcs.len());
+        self.arcs.push(Arc { target, capacity });
+        self.adjacency[target].push(self.arcs.len());
+        self.arcs.push(Arc {
+            target: source,
+            capacity: 0,
+        });
+        id
+    }
+
+    /// The flow of the given edge.
+    pub fn flow(&self, edge: usize) -> i32 {
+        self.lower_bounds[edge] + self.arcs[2 * edge + 1].capacity
+    }
+
+    /// Looks for a feasible circulation, returning `true` iff it exists. This
+    /// must be called (once) after all the edges have been added and before
+    /// querying the flows.
+    pub fn find_circulation(&mut self) -> bool {
+        // the lower bounds are enforced through an auxiliary source and sink
+        let (source, sink) = (self.nb_nodes, self.nb_nodes + 1);
+        let mut excess = vec![0; self.nb_nodes];
+        for (edge, lower) in self.lower_bounds.iter().enumerate() {
+            excess[self.arcs[2 * edge].target] += lower;
+            excess[self.arcs[2 * edge + 1].target] -= lower;
+        }
+        let mut demand = 0;
+        for (node, excess) in excess.into_iter().enumerate() {
+            if excess > 0 {
+                self.add_arcs(source, node, excess);
+                demand += excess;
+            } else if excess < 0 {
+                self.add_arcs(node, sink, -excess);
+            }
+        }
+        while let Some(path) = self.augmenting_path(source, sink, None) {
+            let bottleneck = path.iter().map(|a| self.arcs[*a].capacity).min().unwrap();
+            for a in path {
+                self.arcs[a].capacity -= bottleneck;
+                self.arcs[a ^ 1].capacity += bottleneck;
+            }
+            demand -= bottleneck;
+        }
+        !self.has_empty_range && demand == 0
+    }
+
+    /// Tells whether the given edge carries more flow in some feasible
+    /// circulation (after a feasible circulation has been found).
+    pub fn can_increase(&self, edge: usize) -> bool {
+        let arc = self.arcs[2 * edge];
+        let source = self.arcs[2 * edge + 1].target;
+        arc.capacity > 0
+            && self
+                .augmenting_path(arc.target, source, Some(edge))
+                .is_some()
+    }
+
+    /// Tells whether the given edge carries less flow in some feasible
+    /// circulation (after a feasible circulation has been found).
+    pub fn can_decrease(&self, edge: usize) -> bool {
+        let arc = self.arcs[2 * edge + 1];
+        let target = self.arcs[2 * edge].target;
+        arc.capacity > 0
+            && self
+                .augmenting_path(arc.target, target, Some(edge))
+                .is_some()
+    }
+
+    /// A shortest path of arcs with positive capacity from `source` to
+    /// `target`, avoiding the arcs of the excluded edge. The auxiliary nodes
+    /// are only traversed if they are the endpoints of the path.
+    fn augmenting_path(
+        &self,
+        source: usize,
+        target: usize,
+        excluded: Option<usize>,
+    ) -> Option<Vec<usize>> {
+        let mut parent_arc: Vec<Option<usize>> = vec![None; self.nb_nodes + 2];
+        let mut visited = vec![false; self.nb_nodes + 2];
+        let mut queue = VecDeque::from([source]);
+        visited[source] = true;
+        while let Some(node) = queue.pop_front() {
+            if node == target {
+                let mut path = vec![];
+                let mut current = target;
+                while let Some(a) = parent_arc[current] {
+                    path.push(a);
+                    current = self.arcs[a ^ 1].target;
+                }
+                return Some(path);
+            }
+            for a in self.adjacency[node].iter() {
+                let arc = self.arcs[*a];
+                let is_auxiliary = arc.target >= self.nb_nodes && arc.target != target;
+                if visited[arc.target]
+                    || arc.capacity == 0
+                    || is_auxiliary
+                    || Some(a / 2) == excluded
+                {
+                    continue;
+                }
+                visited[arc.target] = true;
+                parent_arc[arc.target] = Some(*a);
+                queue.push_back(arc.target);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_circulation() {
+        // 0 -> 1 -> 3 -> 0 and 0 -> 2 -> 3, where 3 -> 0 carries exactly 1 unit
+        let mut network = FlowNetwork::new(4);
+        let a = network.add_edge(0, 1, 0, 1);
+        let b = network.add_edge(1, 3, 0, 1);
+        let c = network.add_edge(0, 2, 0, 1);
+        let d = network.add_edge(2, 3, 0, 1);
+        network.add_edge(3, 0, 1, 1);
+        assert!(network.find_circulation());
+        assert_eq!(network.flow(a) + network.flow(c), 1);
+        assert_eq!(network.flow(a), network.flow(b));
+        assert_eq!(network.flow(c), network.flow(d));
+        for edge in [a, b, c, d] {
+            assert!(network.can_increase(edge) || network.can_decrease(edge));
+        }
+
+        // the branch through 2 is now mandatory
+        let mut network = FlowNetwork::new(4);
+        let a = network.add_edge(0, 1, 0, 1);
+        network.add_edge(1, 3, 0, 1);
+        let c = network.add_edge(0, 2, 1, 1);
+        network.add_edge(2, 3, 0, 1);
+        network.add_edge(3, 0, 1, 1);
+        assert!(network.find_circulation());
+        assert_eq!(network.flow(c), 1);
+        assert_eq!(network.flow(a), 0);
+        assert!(!network.can_increase(a));
+
+        // 2 units cannot go through a single edge of capacity 1
+        let mut network = FlowNetwork::new(2);
+        network.add_edge(0, 1, 0, 1);
+        network.add_edge(1, 0, 2, 2);
+        assert!(!network.find_circulation());
+    }
+}
@@ -0,0 +1,161 @@
+//! Board geometry.
+//!
+//! The movement of pieces is abstracted behind the [`Geometry`] trait, so that
+//! fairy board topologies can be plugged into the mobility graphs of an
+//! analysis.
+
+use chess::{
+    get_bishop_rays, get_king_moves, get_knight_moves, get_pawn_attacks, get_pawn_quiets,
+    get_rook_rays, BitBoard, Color, File, Piece, Rank, Square, EMPTY,
+};
+
+/// The geometry of the board, i.e., how pieces move on it.
+///
+/// Only the mobility graphs of an analysis are built through this trait, so the
+/// rules that only consume mobility graphs and origins (mobility, routes,
+/// destinies, missing, captures and tombs) work unchanged on any geometry.
+///
+/// The following rules (as well as the retractor) assume the orthodox
+/// geometry: steady, steady mobility, royalty on 1st rank, pawn on 2nd rank,
+/// pawn on 3rd rank, corner knight, surpassed pawns, unretractable and parity.
+pub trait Geometry {
+    /// A `BitBoard` with the squares that a piece of the given type and color
+    /// can move to from the given square on an empty board (excluding pawn
+    /// captures).
+    fn moves_on_empty_board(&self, piece: Piece, color: Color, square: Square) -> BitBoard;
+
+    /// A `BitBoard` with the squares that a pawn of the given color can capture
+    /// on from the given square.
+    fn pawn_attacks(&self, color: Color, square: Square) -> BitBoard;
+}
+
+/// The orthodox chess geometry.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Orthodox;
+
+impl Geometry for Orthodox {
+    #[inline]
+    fn moves_on_empty_board(&self, piece: Piece, color: Color, square: Square) -> BitBoard {
+        match piece {
+            Piece::King => get_king_moves(square),
+            Piece::Queen => get_rook_rays(square) | get_bishop_rays(square),
+            Piece::Rook => get_rook_rays(square),
+            Piece::Bishop => get_bishop_rays(square),
+            Piece::Knight => get_knight_moves(square),
+            Piece::Pawn => get_pawn_quiets(square, color, EMPTY),
+        }
+    }
+
+    #[inline]
+    fn pawn_attacks(&self, color: Color, square: Square) -> BitBoard {
+        get_pawn_attacks(square, color, !EMPTY)
+    }
+}
+
+/// A vertical cylinder board, where the A-file and the H-file are adjacent.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct VerticalCylinder;
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_JUMPS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+impl VerticalCylinder {
+    /// The square at the given offset from `square`, if it exists.
+    fn shift(square: Square, (df, dr): (i8, i8)) -> Option<Square> {
+        let file = (square.get_file().to_index() as i8 + df).rem_euclid(8);
+        let rank = square.get_rank().to_index() as i8 + dr;
+        if !(0..8).contains(&rank) {
+            return None;
+        }
+        Some(Square::make_square(
+            Rank::from_index(rank as usize),
+            File::from_index(file as usize),
+        ))
+    }
+
+    /// The squares reachable from `square` in one step in any of the given
+    /// directions (or in any number of steps if `slide` is set).
+    fn steps(square: Square, directions: &[(i8, i8)], slide: bool) -> BitBoard {
+        let mut targets = EMPTY;
+        for &direction in directions {
+            let mut current = square;
+            while let Some(next) = Self::shift(current, direction) {
+                if next == square {
+                    break;
+                }
+                targets |= BitBoard::from_square(next);
+                if !slide {
+                    break;
+                }
+                current = next;
+            }
+        }
+        targets
+    }
+}
+
+impl Geometry for VerticalCylinder {
+    fn moves_on_empty_board(&self, piece: Piece, color: Color, square: Square) -> BitBoard {
+        let queen_directions = [ROOK_DIRECTIONS, BISHOP_DIRECTIONS].concat();
+        match piece {
+            Piece::King => Self::steps(square, &queen_directions, false),
+            Piece::Queen => Self::steps(square, &queen_directions, true),
+            Piece::Rook => Self::steps(square, &ROOK_DIRECTIONS, true),
+            Piece::Bishop => Self::steps(square, &BISHOP_DIRECTIONS, true),
+            Piece::Knight => Self::steps(square, &KNIGHT_JUMPS, false),
+            // pawn pushes are vertical, thus not affected by the topology
+            Piece::Pawn => get_pawn_quiets(square, color, EMPTY),
+        }
+    }
+
+    fn pawn_attacks(&self, color: Color, square: Square) -> BitBoard {
+        let dr = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        Self::steps(square, &[(1, dr), (-1, dr)], false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_vertical_cylinder() {
+        let cylinder = VerticalCylinder;
+        assert_eq!(
+            cylinder.moves_on_empty_board(Piece::King, Color::White, A1),
+            bitboard_of_squares(&[A2, B1, B2, H1, H2])
+        );
+        assert_eq!(
+            cylinder.pawn_attacks(Color::White, H2),
+            bitboard_of_squares(&[G3, A3])
+        );
+        assert_eq!(
+            cylinder
+                .moves_on_empty_board(Piece::Rook, Color::Black, D4)
+                .popcnt(),
+            14
+        );
+        for square in [A1, C3, E4, H8] {
+            for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+                let orthodox = Orthodox.moves_on_empty_board(piece, Color::White, square);
+                let cylinder = cylinder.moves_on_empty_board(piece, Color::White, square);
+                assert_eq!(orthodox & !cylinder, EMPTY);
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Square-indexed tables.
+//!
+//! Many tables of the analysis have an entry per square, but some of them are
+//! indexed by the square where a piece started the game (its origin) and some
+//! by the square where a piece currently stands. The two kinds of tables are
+//! distinguished by their types, [`ByOrigin`] and [`BySquare`], and by the
+//! types of their keys, [`Origin`] and [`Square`] respectively, so that neither
+//! a table nor a key can be used where the other kind is expected.
+
+use std::{
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+use chess::{BitBoard, Square, ALL_SQUARES, EMPTY, NUM_SQUARES};
+
+/// The square where a piece started the game, as the key of the tables
+/// indexed by origin.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Origin(pub Square);
+
+/// The keys of square-indexed tables, each of them standing for a square.
+pub trait SquareKey: Copy {
+    /// The key that stands for the given square.
+    fn from_square(square: Square) -> Self;
+
+    /// The square the key stands for.
+    fn square(self) -> Square;
+}
+
+impl SquareKey for Square {
+    #[inline]
+    fn from_square(square: Square) -> Self {
+        square
+    }
+
+    #[inline]
+    fn square(self) -> Square {
+        self
+    }
+}
+
+impl SquareKey for Origin {
+    #[inline]
+    fn from_square(square: Square) -> Self {
+        Origin(square)
+    }
+
+    #[inline]
+    fn square(self) -> Square {
+        self.0
+    }
+}
+
+/// A table with an entry of type `T` per square, indexed by keys of type `K`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SquareIndexed<K, T> {
+    entries: [T; NUM_SQUARES],
+    marker: PhantomData<K>,
+}
+
+/// A table indexed by the square where pieces started the game.
+pub type ByOrigin<T> = SquareIndexed<Origin, T>;
+
+/// A table indexed by the square where pieces currently stand.
+pub type BySquare<T> = SquareIndexed<Square, T>;
+
+impl<K, T: Copy> SquareIndexed<K, T> {
+    /// A table where all entries are the given value.
+    pub fn new(value: T) -> Self {
+        Self::from_array([value; NUM_SQUARES])
+    }
+}
+
+impl<K: SquareKey, T> SquareIndexed<K, T> {
+    /// A table whose entry on every key is the result of the given function
+    /// on it.
+    pub fn from_fn<F: FnMut(K) -> T>(mut f: F) -> Self {
+        Self::from_array(core::array::from_fn(|i| f(K::from_square(ALL_SQUARES[i]))))
+    }
+}
+
+impl<K, T> SquareIndexed<K, T> {
+    /// A table with the given entries, where the entry of the key of square
+    /// `s` is the one at position `s.to_index()`.
+    pub fn from_array(entries: [T; NUM_SQUARES]) -> Self {
+        SquareIndexed {
+            entries,
+            marker: PhantomData,
+        }
+    }
+
+    /// The entries of the table, where the entry of the key of square `s` is
+    /// the one at position `s.to_index()`.
+    pub fn as_array(&self) -> &[T; NUM_SQUARES] {
+        &self.entries
+    }
+}
+
+impl<K: SquareKey> SquareIndexed<K, BitBoard> {
+    /// The squares of the keys, among the given ones, whose entry contains the
+    /// given square. For example, on a table of candidate origins, the squares
+    /// where the piece that started on the given origin may currently stand.
+    pub fn containing(&self, square: Square, among: BitBoard) -> BitBoard {
+        let square_bb = BitBoard::from_square(square);
+        among
+            .into_iter()
+            .filter(|s| self[K::from_square(*s)] & square_bb != EMPTY)
+            .fold(EMPTY, |acc, s| acc | BitBoard::from_square(s))
+    }
+}
+
+impl<K: SquareKey, T> Index<K> for SquareIndexed<K, T> {
+    type Output = T;
+
+    fn index(&self, key: K) -> &T {
+        &self.entries[key.square().to_index()]
+    }
+}
+
+impl<K: SquareKey, T> IndexMut<K> for SquareIndexed<K, T> {
+    fn index_mut(&mut self, key: K) -> &mut T {
+        &mut self.entries[key.square().to_index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_square_indexed() {
+        let mut origins = BySquare::new(EMPTY);
+        origins[D4] = bitboard_of_squares(&[E2, F2]);
+        origins[F3] = bitboard_of_squares(&[F2]);
+        assert_eq!(origins.as_array()[D4.to_index()], origins[D4]);
+        assert_eq!(
+            origins.containing(F2, !EMPTY),
+            bitboard_of_squares(&[D4, F3])
+        );
+        assert_eq!(
+            origins.containing(F2, bitboard_of_squares(&[D4])),
+            bitboard_of_squares(&[D4])
+        );
+        assert_eq!(origins.containing(E2, bitboard_of_squares(&[F3])), EMPTY);
+
+        let ranks = ByOrigin::from_fn(|origin: Origin| origin.0.get_rank().to_index());
+        assert_eq!(ranks[Origin(A1)], 0);
+        assert_eq!(ranks[Origin(H8)], 7);
+    }
+}
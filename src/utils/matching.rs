@@ -0,0 +1,268 @@
+//! Bipartite matchings between indices and the elements of their sets.
+//!
+//! Given an array of sets and a set of `Square`-indices, a matching assigns a
+//! distinct element of `sets[i]` to some of the indices `i`. By Hall's
+//! theorem, all the indices can be matched iff no group of `n` indices has a
+//! union of less than `n` elements. This is the basis of k-groups reasoning.
+//!
+//! We say a group of k indices is a k-group iff the union of their sets has
+//! k elements. Those elements must then be assigned to the indices of the
+//! group, so they cannot be assigned to any other index.
+
+use chess::{BitBoard, Square, EMPTY};
+
+/// A maximum matching between a set of indices and the elements of their
+/// corresponding sets.
+pub struct Matching<'a> {
+    sets: &'a [BitBoard; 64],
+    indices: BitBoard,
+    element_of: [Option<Square>; 64],
+    index_of: [Option<Square>; 64],
+}
+
+impl<'a> Matching<'a> {
+    /// Computes a maximum matching between the given indices and the elements
+    /// of their sets, with the Hopcroft–Karp algorithm.
+    pub fn new(sets: &'a [BitBoard; 64], indices: BitBoard) -> Self {
+        let mut matching = Matching {
+            sets,
+            indices,
+            element_of: [None; 64],
+            index_of: [None; 64],
+        };
+        let mut layers = [u32::MAX; 64];
+        while matching.compute_layers(&mut layers) {
+            for index in indices {
+                if matching.element_of[index.to_index()].is_none() {
+                    matching.augment(index, &mut layers);
+                }
+            }
+        }
+        matching
+    }
+
+    /// The element matched to the given index, if any.
+    pub fn element_of(&self, index: Square) -> Option<Square> {
+        self.element_of[index.to_index()]
+    }
+
+    /// Tells whether all the indices are matched.
+    pub fn is_perfect(&self) -> bool {
+        self.indices
+            .into_iter()
+            .all(|index| self.element_of[index.to_index()].is_some())
+    }
+
+    /// Returns a set of indices whose union has fewer elements than indices,
+    /// or `None` if all the indices are matched.
+    pub fn hall_violator(&self) -> Option<BitBoard> {
+        let unmatched = self
+            .indices
+            .into_iter()
+            .find(|index| self.element_of[index.to_index()].is_none())?;
+        Some(self.alternating_closure(unmatched).0)
+    }
+
+    /// Returns the smallest group of indices containing the given (matched)
+    /// index whose union has as many elements as indices, together with such
+    /// union. Returns `None` if no such group exists.
+    pub fn tight_group(&self, index: Square) -> Option<(BitBoard, BitBoard)> {
+        self.element_of[index.to_index()]?;
+        match self.alternating_closure(index) {
+            (group, union, false) => Some((group, union)),
+            _ => None,
+        }
+    }
+
+    /// Returns the different groups given by [`Matching::tight_group`] on all
+    /// the indices, smallest first. These are the minimal k-groups: a union of
+    /// smaller k-groups is not reported as a k-group of its own, since it
+    /// carries no information beyond its parts.
+    pub fn tight_groups(&self) -> Vec<(BitBoard, BitBoard)> {
+        let mut groups: Vec<_> = self
+            .indices
+            .into_iter()
+            .filter_map(|index| self.tight_group(index))
+            .collect();
+        groups.sort_by_key(|(group, _)| (group.popcnt(), group.0));
+        groups.dedup();
+        groups
+    }
+
+    /// The indices and elements reachable from the given index through
+    /// alternating paths (from an index to any element of its set, and from an
+    /// element to the index it is matched to). The boolean flag indicates
+    /// whether an unmatched element was reached.
+    fn alternating_closure(&self, index: Square) -> (BitBoard, BitBoard, bool) {
+        let mut group = BitBoard::from_square(index);
+        let mut union = EMPTY;
+        let mut reaches_unmatched = false;
+        let mut queue = vec![index];
+        while let Some(i) = queue.pop() {
+            for element in self.sets[i.to_index()] & !union {
+                union |= BitBoard::from_square(element);
+                match self.index_of[element.to_index()] {
+                    None => reaches_unmatched = true,
+                    Some(j) => {
+                        if group & BitBoard::from_square(j) == EMPTY {
+                            group |= BitBoard::from_square(j);
+                            queue.push(j);
+                        }
+                    }
+                }
+            }
+        }
+        (group, union, reaches_unmatched)
+    }
+
+    /// Computes the BFS layers of matched indices, starting from the unmatched
+    /// ones. Returns `true` iff an augmenting path exists.
+    fn compute_layers(&self, layers: &mut [u32; 64]) -> bool {
+        let mut queue = vec![];
+        for index in self.indices {
+            if self.element_of[index.to_index()].is_none() {
+                layers[index.to_index()] = 0;
+                queue.push(index);
+            } else {
+                layers[index.to_index()] = u32::MAX;
+            }
+        }
+        let mut found = false;
+        let mut head = 0;
+        while head < queue.len() {
+            let i = queue[head];
+            head += 1;
+            for element in self.sets[i.to_index()] {
+                match self.index_of[element.to_index()] {
+                    None => found = true,
+                    Some(j) => {
+                        if layers[j.to_index()] == u32::MAX {
+                            layers[j.to_index()] = layers[i.to_index()] + 1;
+                            queue.push(j);
+                        }
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Searches for an augmenting path from the given index along the layers,
+    /// flipping it if found.
+    fn augment(&mut self, index: Square, layers: &mut [u32; 64]) -> bool {
+        for element in self.sets[index.to_index()] {
+            let can_take = match self.index_of[element.to_index()] {
+                None => true,
+                Some(j) => {
+                    layers[j.to_index()] == layers[index.to_index()] + 1 && self.augment(j, layers)
+                }
+            };
+            if can_take {
+                self.element_of[index.to_index()] = Some(element);
+                self.index_of[element.to_index()] = Some(index);
+                return true;
+            }
+        }
+        layers[index.to_index()] = u32::MAX;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_matching() {
+        let mut sets = [!EMPTY; 64];
+        sets[0] = bitboard_of_squares(&[A1, A2]);
+        sets[1] = bitboard_of_squares(&[A3]);
+        sets[2] = bitboard_of_squares(&[A1, A2, A4]);
+        sets[3] = bitboard_of_squares(&[A2]);
+
+        let matching = Matching::new(&sets, BitBoard(15));
+        assert!(matching.is_perfect());
+        assert_eq!(matching.element_of(A1), Some(A1));
+        assert_eq!(matching.element_of(B1), Some(A3));
+        assert_eq!(matching.element_of(C1), Some(A4));
+        assert_eq!(matching.element_of(D1), Some(A2));
+        assert_eq!(matching.hall_violator(), None);
+
+        // the smallest groups of sets whose union is as large as the group
+        assert_eq!(matching.tight_group(B1), Some((BitBoard(2), sets[1])));
+        assert_eq!(matching.tight_group(D1), Some((BitBoard(8), sets[3])));
+        assert_eq!(matching.tight_group(A1), Some((BitBoard(9), sets[0])));
+        assert_eq!(
+            matching.tight_group(C1),
+            Some((BitBoard(13), bitboard_of_squares(&[A1, A2, A4])))
+        );
+        assert_eq!(
+            matching.tight_groups(),
+            vec![
+                (BitBoard(2), sets[1]),
+                (BitBoard(8), sets[3]),
+                (BitBoard(9), sets[0]),
+                (BitBoard(13), bitboard_of_squares(&[A1, A2, A4])),
+            ]
+        );
+
+        // the sets of indices 2 and 3 are both {A2}
+        sets[2] = bitboard_of_squares(&[A2]);
+        assert_eq!(
+            Matching::new(&sets, BitBoard(15)).hall_violator(),
+            Some(BitBoard(12))
+        );
+        assert_eq!(Matching::new(&sets, BitBoard(7)).hall_violator(), None);
+    }
+    #[test]
+    fn test_tight_groups() {
+        let mut sets = [!EMPTY; 64];
+        sets[0] = bitboard_of_squares(&[A1, A2]);
+        sets[1] = bitboard_of_squares(&[A3]);
+        sets[2] = bitboard_of_squares(&[A1, A2]);
+        sets[3] = bitboard_of_squares(&[A2]);
+        sets[4] = bitboard_of_squares(&[A1, A3, A4]);
+
+        // the sets of indices 0, 2 and 3 are all included in {A1, A2}
+        assert!(Matching::new(&sets, BitBoard(31)).hall_violator().is_some());
+
+        // the union of the 1-group {1} and the 2-group {0, 2} is not reported
+        assert_eq!(
+            Matching::new(&sets, BitBoard(23)).tight_groups(),
+            vec![
+                (BitBoard(2), sets[1]),
+                (BitBoard(5), sets[0] | sets[2]),
+                (BitBoard(23), sets[0] | sets[1] | sets[4]),
+            ]
+        );
+
+        sets[0] = bitboard_of_squares(&[B1, B2, B3]);
+        sets[1] = bitboard_of_squares(&[B2, B3, B4]);
+        sets[2] = bitboard_of_squares(&[B2, B3, B4]);
+        sets[3] = bitboard_of_squares(&[B1, H8]);
+        sets[4] = bitboard_of_squares(&[B1, B2, B4]);
+
+        assert_eq!(
+            Matching::new(&sets, BitBoard(23)).tight_groups(),
+            vec![(BitBoard(23), sets[0] | sets[1] | sets[2] | sets[4])]
+        );
+        assert_eq!(
+            Matching::new(&sets, BitBoard(31)).tight_groups(),
+            vec![
+                (BitBoard(23), sets[0] | sets[1] | sets[2] | sets[4]),
+                (
+                    BitBoard(31),
+                    sets[0] | sets[1] | sets[2] | sets[3] | sets[4]
+                ),
+            ]
+        );
+
+        // index 3 is no longer part of a k-group
+        sets[3] = bitboard_of_squares(&[B1, H8, G8]);
+        assert_eq!(
+            Matching::new(&sets, BitBoard(31)).tight_groups(),
+            vec![(BitBoard(23), sets[0] | sets[1] | sets[2] | sets[4])]
+        );
+    }
+}
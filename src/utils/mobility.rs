@@ -1,121 +1,168 @@
 //! Graph functions.
 
-use chess::{
-    get_pawn_attacks, get_rank, BitBoard, Color, Piece, Square, ALL_SQUARES, EMPTY, NUM_SQUARES,
-};
-use petgraph::{
-    algo::{astar, dijkstra},
-    graph::{DiGraph, EdgeIndex, EdgeReference, NodeIndex},
-    visit::EdgeRef,
-    Direction::{Incoming, Outgoing},
-};
-
-use super::moves_on_empty_board;
+use chess::{get_rank, BitBoard, Color, Piece, Square, ALL_SQUARES, EMPTY, NUM_SQUARES};
 
+use super::Geometry;
+
+/// A directed graph over the squares of the board, where an edge from a
+/// square to another indicates that a piece can move between them. Edges are
+/// either regular (of weight 0) or capturing (of weight 1), see
+/// [`MobilityGraph::mark_capturing_edge`].
+#[derive(Clone)]
 pub struct MobilityGraph {
-    graph: DiGraph<(), u32>,
-    square_indices: [NodeIndex; NUM_SQUARES],
+    /// The targets of the edges from every square.
+    successors: [BitBoard; NUM_SQUARES],
+    /// The targets of the capturing edges from every square, a subset of the
+    /// corresponding `successors`.
+    capturing: [BitBoard; NUM_SQUARES],
+    /// If enabled, a record of all the removed edges, encoded as
+    /// `64 * source + target`, together with the tag that was active at the
+    /// moment of the removal.
+    journal: Option<Vec<(u16, u8)>>,
+    journal_tag: u8,
 }
 
 impl MobilityGraph {
     fn new() -> Self {
-        let mut graph = DiGraph::<(), u32>::new();
-        let square_indices = core::array::from_fn(|_| graph.add_node(()));
         Self {
-            graph,
-            square_indices,
+            successors: [EMPTY; NUM_SQUARES],
+            capturing: [EMPTY; NUM_SQUARES],
+            journal: None,
+            journal_tag: 0,
+        }
+    }
+
+    /// Starts recording the removed edges.
+    pub fn enable_journal(&mut self) {
+        self.journal.get_or_insert_with(Vec::new);
+    }
+
+    /// Sets the tag attached to the edges removed from now on.
+    pub fn set_journal_tag(&mut self, tag: u8) {
+        self.journal_tag = tag;
+    }
+
+    /// The recorded removed edges (if the journal is enabled) with their tags,
+    /// in order of removal.
+    pub fn journal(&self) -> impl Iterator<Item = (Square, Square, u8)> + '_ {
+        self.journal.iter().flatten().map(|(edge, tag)| {
+            let source = ALL_SQUARES[(edge / 64) as usize];
+            let target = ALL_SQUARES[(edge % 64) as usize];
+            (source, target, *tag)
+        })
+    }
+
+    /// Records the removal of the given edge in the journal (if enabled).
+    fn record(&mut self, source: Square, target: Square) {
+        if let Some(journal) = self.journal.as_mut() {
+            let encoded = 64 * source.to_index() as u16 + target.to_index() as u16;
+            journal.push((encoded, self.journal_tag));
         }
     }
 
+    #[cfg(test)]
+    /// Initializes the mobility graph of the given piece type and color, on
+    /// the orthodox board.
     pub fn init(piece: Piece, color: Color) -> Self {
+        Self::init_with_geometry(piece, color, &super::Orthodox)
+    }
+
+    /// Initializes the mobility graph of the given piece type and color, on
+    /// a board with the given geometry.
+    pub fn init_with_geometry<G: Geometry>(piece: Piece, color: Color, geometry: &G) -> Self {
         let mut graph = Self::new();
         for source in ALL_SQUARES {
             if piece == Piece::Pawn {
                 if BitBoard::from_square(source) & get_rank(color.to_my_backrank()) != EMPTY {
                     continue;
                 }
-                for target in get_pawn_attacks(source, color, !EMPTY) {
-                    graph.add_edge(source, target, 1);
-                }
-            }
-            for target in moves_on_empty_board(piece, color, source) {
-                graph.add_edge(source, target, 0)
+                let attacks = geometry.pawn_attacks(color, source);
+                graph.successors[source.to_index()] |= attacks;
+                graph.capturing[source.to_index()] |= attacks;
             }
+            let moves = geometry.moves_on_empty_board(piece, color, source);
+            graph.successors[source.to_index()] |= moves;
+            // a move that can be performed without capturing is not capturing
+            graph.capturing[source.to_index()] &= !moves;
         }
         graph
     }
 
-    fn node(&self, square: Square) -> NodeIndex {
-        self.square_indices[square.to_index()]
+    /// The weight of the edge between the given squares, which must exist.
+    fn weight(&self, source: Square, target: Square) -> u32 {
+        (self.capturing[source.to_index()] & BitBoard::from_square(target) != EMPTY) as u32
     }
 
-    fn edge(&self, source: Square, target: Square) -> Option<EdgeIndex> {
-        self.graph.find_edge(self.node(source), self.node(target))
-    }
-
-    fn add_edge(&mut self, source: Square, target: Square, weight: u32) {
-        self.graph
-            .add_edge(self.node(source), self.node(target), weight);
+    #[cfg(test)]
+    /// The number of edges of the graph.
+    pub fn edge_count(&self) -> u32 {
+        self.successors.iter().map(|targets| targets.popcnt()).sum()
     }
 
-    #[cfg(test)]
     /// Tells whether there exists an edge between the two given squares.
     pub fn exists_edge(&self, source: Square, target: Square) -> bool {
-        self.edge(source, target).is_some()
+        self.successors[source.to_index()] & BitBoard::from_square(target) != EMPTY
     }
 
     /// Makes sure the edge between the given squares disappears from the graph.
     /// Returns `true` iff this operation modifies the graph.
     pub fn remove_edge(&mut self, source: Square, target: Square) -> bool {
-        match self.edge(source, target) {
-            None => false,
-            Some(edge) => {
-                self.graph.remove_edge(edge);
-                true
-            }
+        if !self.exists_edge(source, target) {
+            return false;
         }
+        self.record(source, target);
+        self.successors[source.to_index()] &= !BitBoard::from_square(target);
+        self.capturing[source.to_index()] &= !BitBoard::from_square(target);
+        true
     }
 
-    /// Removes all the given edges.
-    fn remove_edges(&mut self, edges: &[EdgeIndex]) {
-        for edge in edges.iter() {
-            self.graph.remove_edge(*edge);
+    /// Marks the edge between the given squares as a capturing edge, i.e. the
+    /// piece cannot traverse it without capturing an enemy piece on its target.
+    /// Returns `true` iff this operation modifies the graph.
+    pub fn mark_capturing_edge(&mut self, source: Square, target: Square) -> bool {
+        if !self.exists_edge(source, target) || self.weight(source, target) == 1 {
+            return false;
         }
+        self.capturing[source.to_index()] |= BitBoard::from_square(target);
+        true
     }
 
     /// Makes sure the graph does not have outgoing edges from the given node.
     /// Returns `true` iff this operation modifies the graph.
     pub fn remove_outgoing_edges(&mut self, source: Square) -> bool {
-        let outgoing_edges: Vec<_> = self
-            .graph
-            .edges_directed(self.node(source), Outgoing)
-            .map(|edge_ref| edge_ref.id())
-            .collect();
-        self.remove_edges(&outgoing_edges);
-        !outgoing_edges.is_empty()
+        let targets = self.successors[source.to_index()];
+        for target in targets {
+            self.remove_edge(source, target);
+        }
+        targets != EMPTY
     }
 
     /// Makes sure the graph does not have incoming edges to the given node.
     /// Returns `true` iff this operation modifies the graph.
     pub fn remove_incoming_edges(&mut self, target: Square) -> bool {
-        let incoming_edges: Vec<_> = self
-            .graph
-            .edges_directed(self.node(target), Incoming)
-            .map(|edge_ref| edge_ref.id())
-            .collect();
-        self.remove_edges(&incoming_edges);
-        !incoming_edges.is_empty()
+        let sources = self.predecessors(target);
+        for source in sources {
+            self.remove_edge(source, target);
+        }
+        sources != EMPTY
     }
 
     /// The squares for which there exists an edge to the given `target`.
     pub fn predecessors(&self, target: Square) -> BitBoard {
         let mut neighbors = EMPTY;
-        for node in self.graph.neighbors_directed(self.node(target), Incoming) {
-            neighbors |= BitBoard::from_square(ALL_SQUARES[node.index()]);
+        for source in ALL_SQUARES {
+            if self.exists_edge(source, target) {
+                neighbors |= BitBoard::from_square(source);
+            }
         }
         neighbors
     }
 
+    /// The squares for which there exists an edge from the given `source`.
+    pub fn successors(&self, source: Square) -> BitBoard {
+        self.successors[source.to_index()]
+    }
+
     /// Makes sure the given node is disconnected from the rest of the graph.
     /// Returns `true` iff this operation modifies the graph.
     #[allow(dead_code)]
@@ -123,28 +170,149 @@ impl MobilityGraph {
         self.remove_outgoing_edges(node) || self.remove_incoming_edges(node)
     }
 
+    /// All the edges of the graph, sorted.
+    pub fn edges(&self) -> Vec<(Square, Square)> {
+        ALL_SQUARES
+            .iter()
+            .flat_map(|source| {
+                self.successors(*source)
+                    .map(move |target| (*source, target))
+            })
+            .collect()
+    }
+
+    /// All the capturing edges of the graph (see
+    /// [`MobilityGraph::mark_capturing_edge`]), sorted.
+    pub fn capturing_edges(&self) -> Vec<(Square, Square)> {
+        ALL_SQUARES
+            .iter()
+            .flat_map(|source| {
+                self.capturing[source.to_index()].map(move |target| (*source, target))
+            })
+            .collect()
+    }
+
+    /// The minimum weight of the routes from `source` to every square in this
+    /// mobility graph (`u32::MAX` if the square is unreachable), where edges
+    /// are weighted by the given function, together with the predecessor of
+    /// every square in one such route. Edges of weight `u32::MAX` are ignored.
+    fn shortest_paths<F>(
+        &self,
+        source: Square,
+        weight: F,
+    ) -> ([u32; NUM_SQUARES], [u8; NUM_SQUARES])
+    where
+        F: Fn(Square, Square) -> u32,
+    {
+        dijkstra(source, |square| self.successors(square), weight)
+    }
+
     #[cfg(test)]
     pub fn distance(&self, source: Square, target: Square) -> Option<u32> {
-        let node_map = dijkstra(&self.graph, self.node(source), None, |e| *e.weight());
-        node_map.get(&self.node(target)).copied()
+        let (distances, _) = self.shortest_paths(source, |s, t| self.weight(s, t));
+        Some(distances[target.to_index()]).filter(|d| *d != u32::MAX)
     }
 
     pub fn reachable_from_source(&self, source: Square) -> BitBoard {
-        let node_map = dijkstra(&self.graph, self.node(source), None, |e| *e.weight());
+        let (distances, _) = self.shortest_paths(source, |s, t| self.weight(s, t));
         let mut reachable = EMPTY;
-        for key in node_map.keys() {
-            reachable |= BitBoard::from_square(ALL_SQUARES[key.index()]);
+        for square in ALL_SQUARES {
+            if distances[square.to_index()] != u32::MAX {
+                reachable |= BitBoard::from_square(square);
+            }
         }
         reachable
     }
 
     pub fn distances_from_source(&self, source: Square) -> [u8; NUM_SQUARES] {
-        let node_map = dijkstra(&self.graph, self.node(source), None, |e| *e.weight());
-        let mut distances = [16; NUM_SQUARES];
-        for (key, bound) in node_map.iter() {
-            distances[ALL_SQUARES[key.index()].to_index()] = *bound as u8;
+        let (distances, _) = self.shortest_paths(source, |s, t| self.weight(s, t));
+        distances.map(|d| if d == u32::MAX { 16 } else { d as u8 })
+    }
+
+    /// The minimum number of moves (regardless of captures) necessary to go
+    /// from `source` to every square in this mobility graph, `None` if the
+    /// square is unreachable.
+    pub fn move_distances_from_source(&self, source: Square) -> [Option<u32>; NUM_SQUARES] {
+        let (distances, _) = self.shortest_paths(source, |_, _| 1);
+        distances.map(|d| Some(d).filter(|d| *d != u32::MAX))
+    }
+
+    /// The distinct sequences of capturing squares (the targets of the
+    /// capturing edges) of the routes from `source` to `target` in this
+    /// mobility graph with at most `max_captures` captures.
+    pub fn capture_skeletons(
+        &self,
+        source: Square,
+        target: Square,
+        max_captures: u8,
+    ) -> Vec<Vec<Square>> {
+        // the routes are explored backwards, from `target`
+        let (to_target, _) = dijkstra(
+            target,
+            |square| self.predecessors(square),
+            |next, previous| self.weight(previous, next),
+        );
+        let mut skeletons = vec![];
+        self.extend_skeletons(
+            source,
+            target,
+            max_captures as u32,
+            &to_target,
+            &mut vec![],
+            &mut skeletons,
+        );
+        skeletons
+    }
+
+    /// Adds to `skeletons` the extensions of the given (partial) skeleton with
+    /// the routes from `node` to `target` with at most `nb_allowed` captures,
+    /// where `to_target` contains the minimum number of captures from every
+    /// node to `target` (`u32::MAX` if `target` is unreachable).
+    fn extend_skeletons(
+        &self,
+        node: Square,
+        target: Square,
+        nb_allowed: u32,
+        to_target: &[u32; NUM_SQUARES],
+        skeleton: &mut Vec<Square>,
+        skeletons: &mut Vec<Vec<Square>>,
+    ) {
+        // the squares reachable from `node` without capturing
+        let mut closure = BitBoard::from_square(node);
+        let mut frontier = vec![node];
+        while let Some(square) = frontier.pop() {
+            let next = self.successors(square) & !self.capturing[square.to_index()] & !closure;
+            closure |= next;
+            frontier.extend(next);
+        }
+        if closure & BitBoard::from_square(target) != EMPTY {
+            skeletons.push(skeleton.clone());
+        }
+        if nb_allowed == 0 {
+            return;
+        }
+
+        // the squares where the next capture may take place
+        let mut next_captures = EMPTY;
+        for square in closure {
+            for next in self.capturing[square.to_index()] {
+                if to_target[next.to_index()] < nb_allowed {
+                    next_captures |= BitBoard::from_square(next);
+                }
+            }
+        }
+        for square in next_captures {
+            skeleton.push(square);
+            self.extend_skeletons(
+                square,
+                target,
+                nb_allowed - 1,
+                to_target,
+                skeleton,
+                skeletons,
+            );
+            skeleton.pop();
         }
-        distances
     }
 
     /// Returns a `BitBoard` with all the squares where a capture must have
@@ -158,40 +326,98 @@ impl MobilityGraph {
         target: Square,
         allowed_nb_captures: u8,
     ) -> BitBoard {
-        let source = self.node(source);
-        let finish = |n| n == self.node(target);
-        match astar(&self.graph, source, finish, |e| *e.weight(), |_| 0) {
-            None => EMPTY,
-            Some((distance, path)) => {
-                debug_assert!(distance <= allowed_nb_captures as u32);
-                let mut forced = EMPTY;
-                for node in path.iter().skip(1) {
-                    // If after significantly increasing the weight of capturing edges that arrive
-                    // to `node`, the distance from source to target surpasses the allowed number of
-                    // captures, it must be the case that `node` is an essential (capturing) square.
-
-                    const DELTA: u32 = 1000;
-                    let new_weights = |e: EdgeReference<u32, u32>| {
-                        let mut weight = *e.weight();
-                        if weight == 1 && e.target() == *node {
-                            weight += DELTA;
-                        }
-                        weight
-                    };
-                    let node_map =
-                        dijkstra(&self.graph, source, Some(self.node(target)), new_weights);
-                    if let Some(new_distance) = node_map.get(&self.node(target)).copied() {
-                        if new_distance > allowed_nb_captures as u32 {
-                            forced |= BitBoard::from_square(ALL_SQUARES[node.index()])
-                        }
-                    }
+        // A capture on a square is forced iff the capturing edges into it cut all
+        // the routes from `source` to `target` with at most `allowed` captures.
+        // These cuts are found at once, like dominators, on the graph whose nodes
+        // are the pairs of a square and a number of captures: `must[c][s]` is the
+        // set of squares entered with a capture by every route from `source` that
+        // reaches `s` after `c` captures. A route never needs to visit a square
+        // twice, so at most 64 captures are considered.
+        let allowed = allowed_nb_captures.min(NUM_SQUARES as u8) as usize;
+        let mut must = vec![[!EMPTY; NUM_SQUARES]; allowed + 1];
+        let mut reached = vec![EMPTY; allowed + 1];
+        must[0][source.to_index()] = EMPTY;
+        reached[0] = BitBoard::from_square(source);
+        let mut worklist = vec![(source, 0)];
+        while let Some((square, nb_captures)) = worklist.pop() {
+            let known = must[nb_captures][square.to_index()];
+            for next in self.successors(square) {
+                let (nb_captures, entry) = match self.weight(square, next) {
+                    0 => (nb_captures, EMPTY),
+                    _ => (nb_captures + 1, BitBoard::from_square(next)),
+                };
+                if nb_captures > allowed {
+                    continue;
+                }
+                let refined = must[nb_captures][next.to_index()] & (known | entry);
+                if reached[nb_captures] & BitBoard::from_square(next) == EMPTY
+                    || refined != must[nb_captures][next.to_index()]
+                {
+                    must[nb_captures][next.to_index()] = refined;
+                    reached[nb_captures] |= BitBoard::from_square(next);
+                    worklist.push((next, nb_captures));
                 }
-                forced
             }
         }
+
+        let mut forced = !EMPTY;
+        let mut is_reachable = false;
+        for (nb_captures, reached) in reached.iter().enumerate() {
+            if reached & BitBoard::from_square(target) != EMPTY {
+                forced &= must[nb_captures][target.to_index()];
+                is_reachable = true;
+            }
+        }
+        if is_reachable {
+            forced
+        } else {
+            EMPTY
+        }
     }
 }
 
+/// Dijkstra's algorithm on scratch arrays with an entry per square, which does
+/// not allocate. Returns the minimum weight of the routes from `source` to
+/// every square (`u32::MAX` if the square is unreachable), where the squares
+/// that follow a square are given by `next` and the step between two squares
+/// is weighted by `weight`, together with the predecessor of every square in
+/// one such route. Steps of weight `u32::MAX` are ignored.
+fn dijkstra<N, W>(source: Square, next: N, weight: W) -> ([u32; NUM_SQUARES], [u8; NUM_SQUARES])
+where
+    N: Fn(Square) -> BitBoard,
+    W: Fn(Square, Square) -> u32,
+{
+    let mut distances = [u32::MAX; NUM_SQUARES];
+    let mut parents = [source.to_index() as u8; NUM_SQUARES];
+    let mut settled = 0u64;
+    distances[source.to_index()] = 0;
+    loop {
+        // the closest square that has not been settled yet
+        let mut closest = None;
+        for i in 0..NUM_SQUARES {
+            if settled & (1 << i) == 0
+                && distances[i] != u32::MAX
+                && closest.map_or(true, |c| distances[i] < distances[c])
+            {
+                closest = Some(i);
+            }
+        }
+        let node = match closest {
+            None => break,
+            Some(node) => node,
+        };
+        settled |= 1 << node;
+        for square in next(ALL_SQUARES[node]) {
+            let distance = distances[node].saturating_add(weight(ALL_SQUARES[node], square));
+            if distance < distances[square.to_index()] {
+                distances[square.to_index()] = distance;
+                parents[square.to_index()] = node as u8;
+            }
+        }
+    }
+    (distances, parents)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -204,25 +430,25 @@ mod tests {
     #[test]
     fn test_init() {
         let king_mobility = MobilityGraph::init(King, White);
-        assert_eq!(king_mobility.graph.edge_count(), 420);
+        assert_eq!(king_mobility.edge_count(), 420);
 
         let queen_mobility = MobilityGraph::init(Queen, White);
-        assert_eq!(queen_mobility.graph.edge_count(), 896 + 560);
+        assert_eq!(queen_mobility.edge_count(), 896 + 560);
 
         let rook_mobility = MobilityGraph::init(Rook, Black);
-        assert_eq!(rook_mobility.graph.edge_count(), 896);
+        assert_eq!(rook_mobility.edge_count(), 896);
 
         let bishop_mobility = MobilityGraph::init(Bishop, Black);
-        assert_eq!(bishop_mobility.graph.edge_count(), 560);
+        assert_eq!(bishop_mobility.edge_count(), 560);
 
         let knight_mobility = MobilityGraph::init(Knight, White);
-        assert_eq!(knight_mobility.graph.edge_count(), 336);
+        assert_eq!(knight_mobility.edge_count(), 336);
 
         let white_pawn_mobility = MobilityGraph::init(Pawn, White);
-        assert_eq!(white_pawn_mobility.graph.edge_count(), 140);
+        assert_eq!(white_pawn_mobility.edge_count(), 140);
 
         let black_pawn_mobility = MobilityGraph::init(Pawn, Black);
-        assert_eq!(black_pawn_mobility.graph.edge_count(), 140);
+        assert_eq!(black_pawn_mobility.edge_count(), 140);
 
         assert_eq!(white_pawn_mobility.distance(E2, C4), Some(2));
         assert_eq!(white_pawn_mobility.distance(E2, E4), Some(0));
@@ -230,4 +456,108 @@ mod tests {
         assert_eq!(white_pawn_mobility.distance(E2, H4), None);
         assert_eq!(white_pawn_mobility.distance(E2, H5), Some(3));
     }
+
+    #[test]
+    fn test_move_distances_from_source() {
+        let distances = MobilityGraph::init(Knight, White).move_distances_from_source(B1);
+        assert_eq!(distances[B1.to_index()], Some(0));
+        assert_eq!(distances[C3.to_index()], Some(1));
+        assert_eq!(distances[H8.to_index()], Some(5));
+
+        let mut graph = MobilityGraph::init(Pawn, White);
+        assert_eq!(graph.move_distances_from_source(E2)[E4.to_index()], Some(1));
+        graph.remove_edge(E2, E4);
+        assert_eq!(graph.move_distances_from_source(E2)[E4.to_index()], Some(2));
+        assert_eq!(graph.move_distances_from_source(E2)[E1.to_index()], None);
+    }
+
+    #[test]
+    fn test_mark_capturing_edge() {
+        let mut graph = MobilityGraph::init(Rook, White);
+        assert_eq!(graph.distance(A1, A8), Some(0));
+        assert!(graph.capturing_edges().is_empty());
+
+        // the rook must capture to enter A5, but it can go around it
+        for source in graph.predecessors(A5) {
+            assert!(graph.mark_capturing_edge(source, A5));
+        }
+        assert!(!graph.mark_capturing_edge(A1, A5));
+        assert_eq!(graph.capturing_edges().len(), 14);
+        assert_eq!(graph.distance(A1, A8), Some(0));
+        assert_eq!(graph.distance(A1, A5), Some(1));
+    }
+
+    #[test]
+    fn test_journal() {
+        let mut graph = MobilityGraph::init(Rook, White);
+        graph.enable_journal();
+        graph.set_journal_tag(3);
+        assert!(graph.remove_outgoing_edges(A1));
+        let removed: Vec<_> = graph.journal().collect();
+        assert_eq!(removed.len(), 14);
+        assert!(removed.contains(&(A1, A8, 3)));
+        assert!(removed.contains(&(A1, H1, 3)));
+        assert_eq!(graph.edge_count(), 896 - 14);
+        assert!(graph.exists_edge(A2, A1));
+        assert!(!graph.exists_edge(A1, A2));
+    }
+
+    #[test]
+    fn test_capture_skeletons() {
+        let white_pawn_mobility = MobilityGraph::init(Pawn, White);
+        assert_eq!(
+            white_pawn_mobility.capture_skeletons(E2, D5, 1),
+            vec![vec![D3], vec![D4], vec![D5]]
+        );
+        assert_eq!(
+            white_pawn_mobility.capture_skeletons(E2, E4, 2),
+            vec![vec![], vec![D3, E4], vec![F3, E4]]
+        );
+        assert_eq!(
+            white_pawn_mobility.capture_skeletons(E2, E4, 0),
+            vec![vec![]]
+        );
+        assert!(white_pawn_mobility.capture_skeletons(E2, H5, 2).is_empty());
+
+        // the captures of officers are not revealed by their routes
+        let knight_mobility = MobilityGraph::init(Knight, Black);
+        assert_eq!(knight_mobility.capture_skeletons(G8, A1, 3), vec![vec![]]);
+    }
+
+    #[test]
+    fn test_forced_captures() {
+        let white_pawn_mobility = MobilityGraph::init(Pawn, White);
+        assert_eq!(
+            white_pawn_mobility.forced_captures(E2, C4, 2),
+            bitboard_of_squares(&[D3, C4])
+        );
+        assert_eq!(white_pawn_mobility.forced_captures(E2, D5, 1), EMPTY);
+        assert_eq!(white_pawn_mobility.forced_captures(E2, E4, 0), EMPTY);
+        assert_eq!(white_pawn_mobility.forced_captures(E2, H4, 2), EMPTY);
+        assert_eq!(
+            white_pawn_mobility.forced_captures(E2, H5, 3),
+            bitboard_of_squares(&[F3, G4, H5])
+        );
+        assert_eq!(white_pawn_mobility.forced_captures(E2, H5, 2), EMPTY);
+
+        // the rook must capture to enter A5, but not to go past it
+        let mut rook_mobility = MobilityGraph::init(Rook, White);
+        for source in rook_mobility.predecessors(A5) {
+            rook_mobility.mark_capturing_edge(source, A5);
+        }
+        assert_eq!(
+            rook_mobility.forced_captures(A1, A5, 1),
+            bitboard_of_squares(&[A5])
+        );
+        assert_eq!(rook_mobility.forced_captures(A1, A8, 1), EMPTY);
+
+        // A5 is entered with the first or the second capture, B1 may be avoided
+        for source in rook_mobility.predecessors(B1) {
+            rook_mobility.mark_capturing_edge(source, B1);
+        }
+        assert_eq!(
+            rook_mobility.forced_captures(A1, A5, 2),
+            bitboard_of_squares(&[A5])
+        );
+    }
 }
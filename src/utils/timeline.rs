@@ -0,0 +1,136 @@
+//! Square-occupancy timelines.
+//!
+//! A timeline partitions the game into consecutive phases, delimited by
+//! provable events (e.g. "the C2-pawn left C2"), and records the squares that
+//! are occupied during the entirety of every phase. A piece cannot pass
+//! through (nor stop on) a square while it is occupied, so the edges of its
+//! mobility graph are restricted differently in every phase.
+
+use chess::{between, BitBoard, EMPTY, NUM_SQUARES};
+
+use super::MobilityGraph;
+
+/// A sequence of phases of the game, with the squares that are occupied during
+/// the entirety of every phase.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timeline {
+    phases: Vec<BitBoard>,
+}
+
+impl Timeline {
+    /// A timeline with a single phase (the whole game), where no square is
+    /// known to be occupied.
+    pub fn new() -> Self {
+        Timeline {
+            phases: vec![EMPTY],
+        }
+    }
+
+    /// Starts a new phase, after all the existing ones, where no square is
+    /// known to be occupied (yet). Returns its index.
+    pub fn split(&mut self) -> usize {
+        self.phases.push(EMPTY);
+        self.phases.len() - 1
+    }
+
+    /// Records that the given squares are occupied during the entirety of the
+    /// given phase.
+    pub fn occupy(&mut self, phase: usize, squares: BitBoard) {
+        self.phases[phase] |= squares;
+    }
+
+    /// The squares that may have been reached by a piece that moves according
+    /// to the given mobility graph and stands on one of the given squares at
+    /// the beginning of the first phase.
+    ///
+    /// A piece that stands on a square that becomes occupied when a phase
+    /// starts (e.g. it was captured there by the piece that occupies it) does
+    /// not move from then on.
+    pub fn reachable(&self, graph: &MobilityGraph, sources: BitBoard) -> BitBoard {
+        let mut reached = sources;
+        for occupied in self.phases.iter() {
+            let successors = successors_avoiding(graph, *occupied);
+            reached = closure(&successors, reached & !occupied) | (reached & occupied);
+        }
+        reached
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The successors of every square in the given mobility graph, through moves
+/// that do not pass through (nor start or end on) the given blocked squares.
+fn successors_avoiding(graph: &MobilityGraph, blocked: BitBoard) -> [BitBoard; NUM_SQUARES] {
+    let mut successors = [EMPTY; NUM_SQUARES];
+    for (source, target) in graph.edges() {
+        let segment =
+            between(source, target) | BitBoard::from_square(source) | BitBoard::from_square(target);
+        if segment & blocked == EMPTY {
+            successors[source.to_index()] |= BitBoard::from_square(target);
+        }
+    }
+    successors
+}
+
+/// The squares reachable from the given ones (including them) according to
+/// the given successors.
+fn closure(successors: &[BitBoard; NUM_SQUARES], sources: BitBoard) -> BitBoard {
+    let mut reached = sources;
+    let mut frontier = sources;
+    while frontier != EMPTY {
+        let mut next = EMPTY;
+        for square in frontier {
+            next |= successors[square.to_index()];
+        }
+        frontier = next & !reached;
+        reached |= next;
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chess::{get_rank, Color, Piece, Rank};
+
+    use super::*;
+    use crate::utils::*;
+
+    #[test]
+    fn test_timeline() {
+        let graph = MobilityGraph::init(Piece::King, Color::White);
+        let first_rank = get_rank(Rank::First);
+        let second_rank = get_rank(Rank::Second);
+
+        // the king is enclosed in the 1st rank during the whole game
+        let mut timeline = Timeline::new();
+        timeline.occupy(0, second_rank);
+        assert_eq!(
+            timeline.reachable(&graph, bitboard_of_squares(&[A1])),
+            first_rank
+        );
+
+        // until the D2-pawn goes to D3
+        let blocked = (second_rank & !bitboard_of_squares(&[D2])) | bitboard_of_squares(&[D3]);
+        let phase = timeline.split();
+        timeline.occupy(phase, blocked);
+        assert_eq!(
+            timeline.reachable(&graph, bitboard_of_squares(&[A1])),
+            !blocked
+        );
+
+        // a piece on a square that becomes occupied cannot move anymore
+        let mut timeline = Timeline::new();
+        timeline.occupy(0, !bitboard_of_squares(&[E2]));
+        let phase = timeline.split();
+        timeline.occupy(phase, bitboard_of_squares(&[E2]));
+        assert_eq!(
+            timeline.reachable(&graph, bitboard_of_squares(&[E2])),
+            bitboard_of_squares(&[E2])
+        );
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt;
 
 use chess::{BitBoard, Square, EMPTY};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct UncertainSet {
     /// Size of the set.
     size: u32,
@@ -27,7 +27,6 @@ impl UncertainSet {
         self.size
     }
 
-    #[allow(dead_code)]
     pub fn certainly_in_the_set(&self) -> BitBoard {
         self.certain
     }
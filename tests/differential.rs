@@ -0,0 +1,44 @@
+#![cfg(feature = "random")]
+
+use chess::Board;
+use rand::{rngs::SmallRng, SeedableRng};
+use sherlock::{
+    differential::{cross_check, ExternalOracle},
+    generate_position, PositionConstraints,
+};
+
+fn random_boards(seed: u64, nb_boards: usize, max_plies: usize) -> Vec<Board> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let constraints = PositionConstraints {
+        max_plies,
+        ..Default::default()
+    };
+    (0..nb_boards)
+        .filter_map(|_| generate_position(&mut rng, &constraints))
+        .collect()
+}
+
+// the proof games are orthodox, which duplex chess does not accept
+#[cfg(not(feature = "duplex"))]
+#[test]
+fn test_differential_proof_games() {
+    use sherlock::differential::ProofGameOracle;
+
+    let mut oracle = ProofGameOracle::new(3);
+    let disagreements = cross_check(random_boards(42, 20, 3), &mut oracle);
+    assert!(disagreements.is_empty(), "{:?}", disagreements);
+}
+
+/// Cross-checks random positions against the external retro engine given by
+/// the `SHERLOCK_ORACLE` environment variable, if set.
+#[test]
+fn test_differential_external_oracle() {
+    let program = match std::env::var("SHERLOCK_ORACLE") {
+        Ok(program) => program,
+        Err(_) => return,
+    };
+    let mut oracle = ExternalOracle::spawn(&program, &[]).expect("Oracle could not be spawned");
+    let disagreements = cross_check(random_boards(42, 100, 200), &mut oracle);
+    let unsound: Vec<_> = disagreements.iter().filter(|d| d.is_unsound()).collect();
+    assert!(unsound.is_empty(), "{:?}", unsound);
+}
@@ -103,6 +103,22 @@ fn test_legality_misc() {
     test_legality(&positions)
 }
 
+#[test]
+fn test_legality_en_passant() {
+    use crate::Legality::*;
+    #[rustfmt::skip]
+    let positions = [
+        // the last move was d7-d5
+        ("4k3/8/8/3pP3/8/5K2/8/8 w - d6", Legal),
+        // but d7-d5 cannot have been played with the white king in check
+        ("b3k3/8/8/3pP3/8/5K2/8/8 w - d6", Illegal),
+        ("b3k3/8/8/3pP3/8/5K2/8/8 w - -", Legal),
+        // nor with the D7-square occupied
+        ("4k3/3n4/8/3pP3/8/5K2/8/8 w - d6", Illegal),
+    ];
+    test_legality(&positions)
+}
+
 #[test]
 #[ignore]
 fn test_legality_slow() {
@@ -117,3 +133,191 @@ fn test_legality_slow() {
     ];
     test_legality(&positions)
 }
+
+#[test]
+fn test_release_order() {
+    [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+            Some(0),
+        ),
+        ("4k3/8/8/1B6/8/8/8/4K3 b - -", Some(1)),
+        ("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -", None),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq -", None),
+    ]
+    .iter()
+    .for_each(|(fen, expected)| {
+        let board = Board::from_str(fen).expect("Valid Position");
+        let order = sherlock::release_order(&board);
+        assert_eq!(
+            order.as_ref().map(|order| order.len()),
+            *expected,
+            "{}",
+            fen
+        );
+        let budgeted = match order.clone() {
+            Some(order) => sherlock::BudgetedRelease::Released(order),
+            None => sherlock::BudgetedRelease::Unreleasable,
+        };
+        assert_eq!(
+            sherlock::release_order_with_budget(&board, usize::MAX),
+            budgeted,
+            "{}",
+            fen
+        );
+        if let Some(order) = order {
+            let mut released: sherlock::RetractableBoard = board.into();
+            for r in order {
+                released = released.make_retraction_new(r);
+            }
+            assert!(!sherlock::RetractionGen::is_limited_in_retractions(
+                &released
+            ));
+        }
+    })
+}
+
+#[test]
+fn test_retro_conventions() {
+    use sherlock::{is_legal_under_convention, RetroConvention::*};
+
+    [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+            true,
+            true,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq -",
+            true,
+            true,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3",
+            false,
+            true,
+        ),
+        ("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - -", false, false),
+    ]
+    .iter()
+    .for_each(|(fen, codex, permissive)| {
+        let board = Board::from_str(fen).expect("Valid Position");
+        assert_eq!(is_legal_under_convention(&board, Codex), *codex, "{}", fen);
+        assert_eq!(
+            is_legal_under_convention(&board, Permissive),
+            *permissive,
+            "{}",
+            fen
+        );
+    })
+}
+
+#[test]
+fn test_endgame_legality() {
+    use sherlock::{endgame_legality, Legality as L};
+
+    [
+        ("k7/8/8/8/8/8/8/7K w - -", Some(L::Legal)),
+        ("k7/8/1K6/8/8/8/8/8 b - -", Some(L::Legal)),
+        ("k7/8/8/8/8/6n1/5n2/7K w - -", Some(L::Illegal)),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", None),
+    ]
+    .iter()
+    .for_each(|(fen, expected)| {
+        let board = Board::from_str(fen).expect("Valid Position");
+        assert_eq!(endgame_legality(&board, 6), *expected, "{}", fen);
+    })
+}
+
+#[test]
+fn test_max_promotions() {
+    use sherlock::{is_legal_with_options, AnalysisOptions};
+
+    [
+        ("4k3/8/8/8/8/8/1PPPPPPP/QNBQKBNR w - -", [None, None], true),
+        (
+            "4k3/8/8/8/8/8/1PPPPPPP/QNBQKBNR w - -",
+            [Some(1), Some(0)],
+            true,
+        ),
+        (
+            "4k3/8/8/8/8/8/1PPPPPPP/QNBQKBNR w - -",
+            [Some(0), None],
+            false,
+        ),
+        (
+            "4k3/8/8/8/8/8/2PPPPPP/QNBQKBQR w - -",
+            [Some(1), None],
+            false,
+        ),
+    ]
+    .iter()
+    .for_each(|(fen, max_promotions, expected)| {
+        let board = Board::from_str(fen).expect("Valid Position");
+        let options = AnalysisOptions {
+            max_promotions: *max_promotions,
+            ..Default::default()
+        };
+        assert_eq!(
+            is_legal_with_options(&board, &options),
+            *expected,
+            "{}",
+            fen
+        );
+    })
+}
+
+#[test]
+fn test_legality_both_turns() {
+    use chess::{BoardBuilder, Color, ALL_COLORS};
+    use sherlock::{is_legal, legality_both_turns, Legality};
+
+    [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+        "4k3/8/8/8/8/8/8/r3K3 w - -",
+        "4k3/3P4/5N2/8/8/8/8/K3R3 b - -",
+        "r1bqkbnr/pppppppp/2n5/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq -",
+        "2nR3K/pk1Rp1p1/p2p4/P1p5/1Pp5/2PP2P1/4P2P/n7 b - -",
+    ]
+    .iter()
+    .for_each(|fen| {
+        let board = Board::from_str(fen).expect("Valid Position");
+        let expected = ALL_COLORS.map(|side_to_move| {
+            let mut builder = BoardBuilder::from(&board);
+            builder.side_to_move(side_to_move);
+            match Board::try_from(&builder) {
+                Ok(board) if is_legal(&board) => Legality::Legal,
+                _ => Legality::Illegal,
+            }
+        });
+        let (white, black) = legality_both_turns(&board);
+        assert_eq!(white, expected[Color::White.to_index()], "{}", fen);
+        assert_eq!(black, expected[Color::Black.to_index()], "{}", fen);
+    })
+}
+
+#[test]
+fn test_legality_parallel() {
+    use sherlock::{is_legal, is_legal_parallel};
+
+    [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq -",
+        "2nb3K/pkPRp1p1/p2p4/P1p5/1Pp4Q/2PP2P1/4P2P/n7 w - -",
+        "2nb3K/pkPRp1p1/p2p4/P1p5/1Pp4B/2PP2P1/4P2P/n7 w - -",
+        "4k3/8/8/3pP3/8/5K2/8/8 w - d6",
+        "b3k3/8/8/3pP3/8/5K2/8/8 w - d6",
+    ]
+    .iter()
+    .for_each(|fen| {
+        let board = Board::from_str(fen).expect("Valid Position");
+        for nb_threads in [1, 4] {
+            assert_eq!(
+                is_legal_parallel(&board, nb_threads),
+                is_legal(&board),
+                "{}",
+                fen
+            );
+        }
+    })
+}
@@ -0,0 +1,82 @@
+use chess::{Board, BoardStatus, ChessMove, MoveGen};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+fn random_game(rng: &mut SmallRng, nb_plies: usize) -> Vec<ChessMove> {
+    let mut board = Board::default();
+    let mut moves = vec![];
+    while moves.len() < nb_plies && board.status() == BoardStatus::Ongoing {
+        let legal_moves: Vec<_> = MoveGen::new_legal(&board).collect();
+        let m = legal_moves[rng.gen_range(0, legal_moves.len())];
+        board = board.make_move_new(m);
+        moves.push(m);
+    }
+    moves
+}
+
+#[test]
+fn test_soundness_on_random_games() {
+    let mut rng = SmallRng::seed_from_u64(0x5eed_cafe_f00d_d00d);
+    for _ in 0..10 {
+        let game = random_game(&mut rng, 80);
+        for n in (0..=game.len()).step_by(10) {
+            let contradicted = sherlock::contradicted_facts(&game[..n]);
+            assert!(
+                contradicted.is_empty(),
+                "{:?} {:?}",
+                &game[..n],
+                contradicted
+            );
+        }
+    }
+}
+
+/// An unsound rule, which declares every position illegal (by a route
+/// argument).
+#[derive(Debug)]
+struct EverythingIllegalRule;
+
+impl sherlock::Rule for EverythingIllegalRule {
+    fn new() -> Self {
+        EverythingIllegalRule
+    }
+
+    fn update(&mut self, _analysis: &sherlock::Analysis) {}
+
+    fn is_applicable(&self, analysis: &sherlock::Analysis) -> bool {
+        analysis.result().is_none()
+    }
+
+    fn apply(&self, analysis: &mut sherlock::Analysis) -> bool {
+        analysis.set_illegal(sherlock::IllegalityClass::Route);
+        true
+    }
+}
+
+#[cfg(feature = "random")]
+#[test]
+fn test_check_rules_soundness_flags_illegal_verdicts() {
+    static RULES: [sherlock::RuleFactory; 1] = [|| Box::new(EverythingIllegalRule)];
+    let mut rng = SmallRng::seed_from_u64(42);
+    let violation = sherlock::check_rules_soundness(&mut rng, &RULES, 1, 4).unwrap();
+    assert_eq!(violation.moves.len(), 1);
+    assert!(violation
+        .facts
+        .contains(&sherlock::Fact::Result(sherlock::Legality::Illegal)));
+}
+
+#[test]
+fn test_set_illegal_tags_the_verdict() {
+    static RULES: [sherlock::RuleFactory; 1] = [|| Box::new(EverythingIllegalRule)];
+    let options = sherlock::AnalysisOptions {
+        extra_rules: &RULES,
+        ..Default::default()
+    };
+    let analysis = sherlock::analyze_with_options(&sherlock::RetractableBoard::default(), &options);
+    assert_eq!(
+        analysis.illegality_reason(),
+        Some(&sherlock::IllegalityReason::Rule(
+            "EverythingIllegalRule".to_string(),
+            sherlock::IllegalityClass::Route
+        ))
+    );
+}